@@ -0,0 +1,13 @@
+#version 410
+
+layout(location=0) in vec3 VertexPosition;
+
+layout(location=0) out vec3 LocalPosition;
+
+uniform mat4 Projection;
+uniform mat4 View;
+
+void main() {
+    LocalPosition = VertexPosition;
+    gl_Position = Projection * View * vec4(VertexPosition, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 410
+
+layout(location=0) in vec2 TexCoord;
+layout(location=0) out vec4 FragColor;
+
+uniform sampler2D GAlbedo;
+uniform sampler2D GDepth;
+uniform vec3 Ambient;
+uniform vec3 Background;
+
+void main() {
+    if (texture(GDepth, TexCoord).r >= 1.0) {
+        FragColor = vec4(Background, 1.0);
+        return;
+    }
+    FragColor = vec4(Ambient * texture(GAlbedo, TexCoord).rgb, 1.0);
+}
@@ -0,0 +1,79 @@
+#version 410
+
+#define DIRECTIONAL 0
+#define POINT 1
+#define SPOT 2
+
+layout(location=0) out vec4 FragColor;
+
+// One entry of the Lighting block in phong.fs.glsl.
+struct Light {
+    vec4 PositionType;
+    vec4 DirectionRange;
+    vec4 ColorIntensity;
+    vec4 Falloff;
+};
+
+uniform Light CurrentLight;
+
+uniform sampler2D GAlbedo;
+uniform sampler2D GNormal;
+uniform sampler2D GMaterial;
+uniform sampler2D GDepth;
+uniform mat4 InverseViewProjection;
+uniform vec3 CameraPosition;
+uniform vec2 ScreenSize;
+
+// Matches MAX_SHININESS in gbuffer.fs.glsl.
+const float MAX_SHININESS = 256.0;
+
+// Matches Attenuation::factor on the CPU side.
+float attenuation(Light light, float distance) {
+    float range = light.DirectionRange.w;
+    float falloff = 1.0 / (1.0 + light.Falloff.x * distance + light.Falloff.y * distance * distance);
+    float window = clamp(1.0 - pow(distance / range, 4.0), 0.0, 1.0);
+    return falloff * window * window;
+}
+
+// Blinn-Phong, like shade() in phong.fs.glsl without shadows. Drawn with a full-screen
+// triangle for directional lights and a sphere around the range for the others.
+void main() {
+    vec2 uv = gl_FragCoord.xy / ScreenSize;
+    float depth = texture(GDepth, uv).r;
+    if (depth >= 1.0) {
+        discard;
+    }
+    vec4 world = InverseViewProjection * vec4(vec3(uv, depth) * 2.0 - 1.0, 1.0);
+    vec3 position = world.xyz / world.w;
+    vec3 n = normalize(texture(GNormal, uv).xyz);
+    vec3 v = normalize(CameraPosition - position);
+
+    Light light = CurrentLight;
+    int kind = int(light.PositionType.w);
+    vec3 l;
+    float amount = light.ColorIntensity.a;
+    if (kind == DIRECTIONAL) {
+        l = -normalize(light.DirectionRange.xyz);
+    } else {
+        vec3 to_light = light.PositionType.xyz - position;
+        float distance = length(to_light);
+        if (distance >= light.DirectionRange.w) {
+            discard;
+        }
+        l = to_light / distance;
+        amount *= attenuation(light, distance);
+        if (kind == SPOT) {
+            float cos_angle = dot(-l, normalize(light.DirectionRange.xyz));
+            amount *= smoothstep(light.Falloff.w, light.Falloff.z, cos_angle);
+        }
+    }
+    float lambert = max(dot(n, l), 0.0);
+    if (lambert <= 0.0 || amount <= 0.0) {
+        discard;
+    }
+    vec4 material = texture(GMaterial, uv);
+    vec3 h = normalize(l + v);
+    float specular = pow(max(dot(n, h), 0.0), material.a * MAX_SHININESS);
+    vec3 diffuse = texture(GAlbedo, uv).rgb;
+    FragColor = vec4((diffuse * lambert + material.rgb * specular) * light.ColorIntensity.rgb * amount, 1.0);
+}
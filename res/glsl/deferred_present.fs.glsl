@@ -0,0 +1,46 @@
+#version 410
+
+#define LIT 0
+#define ALBEDO 1
+#define NORMAL 2
+#define SPECULAR 3
+#define SHININESS 4
+#define DEPTH 5
+
+layout(location=0) in vec2 TexCoord;
+layout(location=0) out vec4 FragColor;
+
+uniform sampler2D Lit;
+uniform sampler2D GAlbedo;
+uniform sampler2D GNormal;
+uniform sampler2D GMaterial;
+uniform sampler2D GDepth;
+uniform mat4 InverseProjection;
+// Index into GBufferView::ALL.
+uniform int View;
+
+float view_depth(float depth) {
+    vec4 p = InverseProjection * vec4(0.0, 0.0, depth * 2.0 - 1.0, 1.0);
+    return -p.z / p.w;
+}
+
+void main() {
+    vec3 color;
+    if (View == ALBEDO) {
+        color = texture(GAlbedo, TexCoord).rgb;
+    } else if (View == NORMAL) {
+        color = texture(GNormal, TexCoord).xyz * 0.5 + 0.5;
+    } else if (View == SPECULAR) {
+        color = texture(GMaterial, TexCoord).rgb;
+    } else if (View == SHININESS) {
+        color = vec3(texture(GMaterial, TexCoord).a);
+    } else if (View == DEPTH) {
+        // Linear between the near and far planes.
+        float near = view_depth(0.0);
+        float far = view_depth(1.0);
+        color = vec3((view_depth(texture(GDepth, TexCoord).r) - near) / (far - near));
+    } else {
+        color = texture(Lit, TexCoord).rgb;
+    }
+    FragColor = vec4(color, 1.0);
+}
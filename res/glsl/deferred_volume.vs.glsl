@@ -0,0 +1,9 @@
+#version 410
+
+layout(location=0) in vec3 VertexPosition;
+
+uniform mat4 MVP;
+
+void main() {
+    gl_Position = MVP * vec4(VertexPosition, 1.0);
+}
@@ -0,0 +1,19 @@
+#version 410
+
+layout(location=0) in vec3 LocalPosition;
+layout(location=0) out vec4 FragColor;
+
+uniform sampler2D EquirectMap;
+
+const vec2 INV_ATAN = vec2(0.1591549, 0.3183099);
+
+// Longitude and latitude of a direction, mapped to [0, 1] with +Y at the top.
+vec2 equirect_uv(vec3 v) {
+    vec2 uv = vec2(atan(v.z, v.x), asin(clamp(v.y, -1.0, 1.0)));
+    return uv * INV_ATAN + 0.5;
+}
+
+void main() {
+    vec3 color = texture(EquirectMap, equirect_uv(normalize(LocalPosition))).rgb;
+    FragColor = vec4(color, 1.0);
+}
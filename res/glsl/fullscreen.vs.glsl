@@ -0,0 +1,10 @@
+#version 410
+
+layout(location=0) out vec2 TexCoord;
+
+// One triangle covering the viewport, drawn with three vertices and no buffers.
+void main() {
+    vec2 p = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    TexCoord = p;
+    gl_Position = vec4(p * 2.0 - 1.0, 0.0, 1.0);
+}
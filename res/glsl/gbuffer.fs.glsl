@@ -0,0 +1,22 @@
+#version 410
+
+layout(location=0) in vec3 WorldPosition;
+layout(location=1) in vec3 Normal;
+
+// Attachments of core::deferred::GBuffer.
+layout(location=0) out vec4 Albedo;
+layout(location=1) out vec4 WorldNormal;
+layout(location=2) out vec4 Material;
+
+uniform vec4 Diffuse;
+uniform vec3 Specular;
+uniform float Shininess;
+
+// Matches MAX_SHININESS in deferred_light.fs.glsl.
+const float MAX_SHININESS = 256.0;
+
+void main() {
+    Albedo = vec4(Diffuse.rgb, 1.0);
+    WorldNormal = vec4(normalize(Normal), 0.0);
+    Material = vec4(Specular, clamp(Shininess / MAX_SHININESS, 0.0, 1.0));
+}
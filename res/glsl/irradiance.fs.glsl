@@ -0,0 +1,29 @@
+#version 410
+
+layout(location=0) in vec3 LocalPosition;
+layout(location=0) out vec4 FragColor;
+
+uniform samplerCube EnvironmentMap;
+
+const float PI = 3.14159265359;
+const float SAMPLE_DELTA = 0.025;
+
+// Cosine weighted integral of the incoming radiance over the hemisphere around the normal.
+void main() {
+    vec3 n = normalize(LocalPosition);
+    vec3 up = abs(n.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0);
+    vec3 right = normalize(cross(up, n));
+    up = cross(n, right);
+
+    vec3 irradiance = vec3(0.0);
+    float samples = 0.0;
+    for (float phi = 0.0; phi < 2.0 * PI; phi += SAMPLE_DELTA) {
+        for (float theta = 0.0; theta < 0.5 * PI; theta += SAMPLE_DELTA) {
+            vec3 tangent = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 v = tangent.x * right + tangent.y * up + tangent.z * n;
+            irradiance += texture(EnvironmentMap, v).rgb * cos(theta) * sin(theta);
+            samples += 1.0;
+        }
+    }
+    FragColor = vec4(PI * irradiance / samples, 1.0);
+}
@@ -0,0 +1,10 @@
+#version 410
+
+// Written to the R32UI attachment of core::picking::IdBuffer; 0 means no object.
+layout(location=0) out uint ObjectId;
+
+uniform int Id;
+
+void main() {
+    ObjectId = uint(Id);
+}
@@ -0,0 +1,177 @@
+#version 410
+
+#define MAX_LIGHTS 16
+#define DIRECTIONAL 0
+#define POINT 1
+#define SPOT 2
+
+layout(location=0) in vec3 WorldPosition;
+layout(location=1) in vec3 Normal;
+layout(location=2) in vec2 TexCoord;
+layout(location=3) in vec4 Tangent;
+layout(location=0) out vec4 FragColor;
+
+struct Light {
+    vec4 PositionType;
+    vec4 DirectionRange;
+    vec4 ColorIntensity;
+    vec4 Falloff;
+};
+
+layout(std140) uniform Lighting {
+    vec4 Ambient;
+    vec4 CameraPosition;
+    ivec4 LightCount;
+    Light Lights[MAX_LIGHTS];
+};
+
+// Metallic-roughness material; factors multiply the maps like in glTF.
+uniform sampler2D AlbedoMap;
+uniform sampler2D NormalMap;
+uniform sampler2D MetallicRoughnessMap;
+uniform sampler2D OcclusionMap;
+uniform sampler2D EmissiveMap;
+uniform vec4 BaseColor;
+uniform float Metallic;
+uniform float Roughness;
+uniform vec3 Emissive;
+uniform float NormalScale;
+uniform float OcclusionStrength;
+uniform float Exposure;
+
+// Image-based lighting, see ibl::Environment.
+uniform samplerCube IrradianceMap;
+uniform samplerCube PrefilterMap;
+uniform sampler2D BrdfLut;
+uniform float PrefilterLevels;
+
+const float PI = 3.14159265359;
+
+vec3 srgb_to_linear(vec3 c) {
+    return pow(c, vec3(2.2));
+}
+
+// Narkowicz's fit of the ACES filmic curve.
+vec3 tonemap(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+vec3 surface_normal() {
+    vec3 n = normalize(Normal);
+    vec3 t = Tangent.xyz;
+    float sign = Tangent.w < 0.0 ? -1.0 : 1.0;
+    if (dot(t, t) < 1e-8) {
+        // Cotangent frame from screen-space derivatives.
+        vec3 dp1 = dFdx(WorldPosition);
+        vec3 dp2 = dFdy(WorldPosition);
+        vec2 duv1 = dFdx(TexCoord);
+        vec2 duv2 = dFdy(TexCoord);
+        t = dp1 * duv2.y - dp2 * duv1.y;
+        sign = 1.0;
+        if (dot(t, t) < 1e-12) {
+            return n;
+        }
+    }
+    t = normalize(t - n * dot(n, t));
+    vec3 b = cross(n, t) * sign;
+    vec3 m = texture(NormalMap, TexCoord).xyz * 2.0 - 1.0;
+    m.xy *= NormalScale;
+    return normalize(mat3(t, b, n) * m);
+}
+
+float attenuation(Light light, float distance) {
+    float range = light.DirectionRange.w;
+    float falloff = 1.0 / (1.0 + light.Falloff.x * distance + light.Falloff.y * distance * distance);
+    float window = clamp(1.0 - pow(distance / range, 4.0), 0.0, 1.0);
+    return falloff * window * window;
+}
+
+float distribution_ggx(float n_dot_h, float roughness) {
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+float geometry_smith(float n_dot_v, float n_dot_l, float roughness) {
+    float r = roughness + 1.0;
+    float k = r * r / 8.0;
+    float gv = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    float gl = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return gv * gl;
+}
+
+vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+vec3 fresnel_schlick_roughness(float cos_theta, vec3 f0, float roughness) {
+    return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Cook-Torrance reflectance of one light, times its radiance.
+vec3 shade(Light light, vec3 n, vec3 v, vec3 albedo, float metallic, float roughness, vec3 f0) {
+    int kind = int(light.PositionType.w);
+    vec3 l;
+    float amount = light.ColorIntensity.a;
+    if (kind == DIRECTIONAL) {
+        l = -normalize(light.DirectionRange.xyz);
+    } else {
+        vec3 to_light = light.PositionType.xyz - WorldPosition;
+        float distance = length(to_light);
+        l = to_light / distance;
+        amount *= attenuation(light, distance);
+        if (kind == SPOT) {
+            float cos_angle = dot(-l, normalize(light.DirectionRange.xyz));
+            amount *= smoothstep(light.Falloff.w, light.Falloff.z, cos_angle);
+        }
+    }
+    float n_dot_l = max(dot(n, l), 0.0);
+    if (n_dot_l <= 0.0 || amount <= 0.0) {
+        return vec3(0.0);
+    }
+    vec3 h = normalize(l + v);
+    float n_dot_v = max(dot(n, v), 1e-4);
+    vec3 f = fresnel_schlick(max(dot(h, v), 0.0), f0);
+    float d = distribution_ggx(max(dot(n, h), 0.0), roughness);
+    float g = geometry_smith(n_dot_v, n_dot_l, roughness);
+    vec3 specular = d * g * f / (4.0 * n_dot_v * n_dot_l + 1e-4);
+    vec3 kd = (1.0 - f) * (1.0 - metallic);
+    return (kd * albedo / PI + specular) * light.ColorIntensity.rgb * amount * n_dot_l;
+}
+
+void main() {
+    vec4 base = texture(AlbedoMap, TexCoord);
+    vec3 albedo = srgb_to_linear(base.rgb) * BaseColor.rgb;
+    float alpha = base.a * BaseColor.a;
+    // glTF packs roughness in green and metalness in blue.
+    vec4 mr = texture(MetallicRoughnessMap, TexCoord);
+    float roughness = clamp(mr.g * Roughness, 0.04, 1.0);
+    float metallic = clamp(mr.b * Metallic, 0.0, 1.0);
+    float occlusion = mix(1.0, texture(OcclusionMap, TexCoord).r, OcclusionStrength);
+    vec3 emissive = srgb_to_linear(texture(EmissiveMap, TexCoord).rgb) * Emissive;
+
+    vec3 n = surface_normal();
+    vec3 v = normalize(CameraPosition.xyz - WorldPosition);
+    vec3 f0 = mix(vec3(0.04), albedo, metallic);
+
+    vec3 color = vec3(0.0);
+    for (int i = 0; i < min(LightCount.x, MAX_LIGHTS); ++i) {
+        color += shade(Lights[i], n, v, albedo, metallic, roughness, f0);
+    }
+
+    // Split-sum image-based lighting.
+    float n_dot_v = max(dot(n, v), 0.0);
+    vec3 f = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+    vec3 kd = (1.0 - f) * (1.0 - metallic);
+    vec3 diffuse = texture(IrradianceMap, n).rgb * albedo;
+    vec3 r = reflect(-v, n);
+    vec3 prefiltered = textureLod(PrefilterMap, r, roughness * (PrefilterLevels - 1.0)).rgb;
+    vec2 brdf = texture(BrdfLut, vec2(n_dot_v, roughness)).rg;
+    vec3 specular = prefiltered * (f * brdf.x + brdf.y);
+    color += (kd * diffuse + specular + Ambient.rgb * albedo) * occlusion;
+    color += emissive;
+
+    color = tonemap(color * Exposure);
+    FragColor = vec4(pow(color, vec3(1.0 / 2.2)), alpha);
+}
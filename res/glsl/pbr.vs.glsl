@@ -0,0 +1,23 @@
+#version 410
+
+layout(location=0) in vec3 VertexPosition;
+layout(location=1) in vec3 VertexNormal;
+layout(location=2) in vec2 VertexTexCoord;
+layout(location=4) in vec4 VertexTangent;
+
+layout(location=0) out vec3 WorldPosition;
+layout(location=1) out vec3 Normal;
+layout(location=2) out vec2 TexCoord;
+layout(location=3) out vec4 Tangent;
+
+uniform mat4 MVP;
+uniform mat4 Model;
+
+void main() {
+    gl_Position = MVP * vec4(VertexPosition, 1.0);
+    WorldPosition = vec3(Model * vec4(VertexPosition, 1.0));
+    Normal = mat3(transpose(inverse(Model))) * VertexNormal;
+    // Meshes without tangents read (0, 0, 0, 1); the fragment shader falls back to derivatives.
+    Tangent = vec4(mat3(Model) * VertexTangent.xyz, VertexTangent.w);
+    TexCoord = VertexTexCoord;
+}
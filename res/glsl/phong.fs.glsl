@@ -0,0 +1,157 @@
+#version 410
+
+#define MAX_LIGHTS 16
+#define DIRECTIONAL 0
+#define POINT 1
+#define SPOT 2
+#define MAX_CASCADES 4
+
+layout(location=0) in vec3 WorldPosition;
+layout(location=1) in vec3 Normal;
+layout(location=0) out vec4 FragColor;
+
+struct Light {
+    vec4 PositionType;
+    vec4 DirectionRange;
+    vec4 ColorIntensity;
+    vec4 Falloff;
+};
+
+layout(std140) uniform Lighting {
+    vec4 Ambient;
+    vec4 CameraPosition;
+    ivec4 LightCount;
+    Light Lights[MAX_LIGHTS];
+};
+
+uniform vec4 Diffuse;
+uniform vec3 Specular;
+uniform float Shininess;
+
+// Cascaded shadow map of the directional light at CascadeLight; off while CascadeCount is 0.
+uniform sampler2DShadow ShadowMap;
+uniform mat4 ShadowMatrices[MAX_CASCADES];
+uniform vec4 CascadeSplits;
+uniform vec4 CascadeTexels;
+uniform int CascadeCount;
+uniform int CascadeLight;
+uniform mat4 CameraView;
+
+// Cube shadow map of the point light at PointShadowLight; off while PointShadowFar is 0.
+uniform samplerCube PointShadowMap;
+uniform vec3 PointShadowPosition;
+uniform float PointShadowFar;
+uniform int PointShadowLight;
+
+const vec3 POINT_SAMPLES[20] = vec3[](
+    vec3(1, 1, 1), vec3(1, -1, 1), vec3(-1, -1, 1), vec3(-1, 1, 1),
+    vec3(1, 1, -1), vec3(1, -1, -1), vec3(-1, -1, -1), vec3(-1, 1, -1),
+    vec3(1, 1, 0), vec3(1, -1, 0), vec3(-1, -1, 0), vec3(-1, 1, 0),
+    vec3(1, 0, 1), vec3(-1, 0, 1), vec3(1, 0, -1), vec3(-1, 0, -1),
+    vec3(0, 1, 1), vec3(0, -1, 1), vec3(0, -1, -1), vec3(0, 1, -1)
+);
+
+// Matches Attenuation::factor on the CPU side.
+float attenuation(Light light, float distance) {
+    float range = light.DirectionRange.w;
+    float falloff = 1.0 / (1.0 + light.Falloff.x * distance + light.Falloff.y * distance * distance);
+    float window = clamp(1.0 - pow(distance / range, 4.0), 0.0, 1.0);
+    return falloff * window * window;
+}
+
+// 3x3 PCF in the cascade covering the fragment; each tap already blends four comparisons.
+float cascade_shadow(vec3 n, vec3 l) {
+    float depth = -(CameraView * vec4(WorldPosition, 1.0)).z;
+    int cascade = -1;
+    for (int i = 0; i < min(CascadeCount, MAX_CASCADES); ++i) {
+        if (depth < CascadeSplits[i]) {
+            cascade = i;
+            break;
+        }
+    }
+    if (cascade < 0) {
+        return 1.0;
+    }
+    // Pushing the lookup out along the normal, more so at grazing angles, keeps surfaces
+    // from shadowing themselves.
+    float slope = 1.0 - max(dot(n, l), 0.0);
+    vec3 p = WorldPosition + n * CascadeTexels[cascade] * (1.0 + 2.0 * slope);
+    vec3 coord = (ShadowMatrices[cascade] * vec4(p, 1.0)).xyz;
+    if (coord.z >= 1.0) {
+        return 1.0;
+    }
+    vec2 texel = 1.0 / vec2(textureSize(ShadowMap, 0));
+    float tile = 1.0 / float(CascadeCount);
+    vec2 low = vec2(float(cascade) * tile, 0.0) + texel * 1.5;
+    vec2 high = vec2(float(cascade + 1) * tile, 1.0) - texel * 1.5;
+    float lit = 0.0;
+    for (int y = -1; y <= 1; ++y) {
+        for (int x = -1; x <= 1; ++x) {
+            vec2 uv = clamp(coord.xy + vec2(x, y) * texel, low, high);
+            lit += textureLod(ShadowMap, vec3(uv, coord.z - 0.0005), 0.0);
+        }
+    }
+    return lit / 9.0;
+}
+
+// Compares against the nearest distances in a small disk of directions around the fragment.
+float point_shadow(vec3 n) {
+    vec3 from_light = WorldPosition + n * 0.02 - PointShadowPosition;
+    float current = length(from_light);
+    if (current >= PointShadowFar) {
+        return 1.0;
+    }
+    float radius = 0.01 + 0.03 * current / PointShadowFar;
+    float lit = 0.0;
+    for (int i = 0; i < 20; ++i) {
+        float closest = textureLod(PointShadowMap, from_light + POINT_SAMPLES[i] * radius, 0.0).r;
+        lit += current - 0.03 > closest * PointShadowFar ? 0.0 : 1.0;
+    }
+    return lit / 20.0;
+}
+
+float shadow(int index, vec3 n, vec3 l) {
+    if (index == CascadeLight && CascadeCount > 0) {
+        return cascade_shadow(n, l);
+    }
+    if (index == PointShadowLight && PointShadowFar > 0.0) {
+        return point_shadow(n);
+    }
+    return 1.0;
+}
+
+vec3 shade(int index, Light light, vec3 n, vec3 v) {
+    int kind = int(light.PositionType.w);
+    vec3 l;
+    float amount = light.ColorIntensity.a;
+    if (kind == DIRECTIONAL) {
+        l = -normalize(light.DirectionRange.xyz);
+    } else {
+        vec3 to_light = light.PositionType.xyz - WorldPosition;
+        float distance = length(to_light);
+        l = to_light / distance;
+        amount *= attenuation(light, distance);
+        if (kind == SPOT) {
+            float cos_angle = dot(-l, normalize(light.DirectionRange.xyz));
+            amount *= smoothstep(light.Falloff.w, light.Falloff.z, cos_angle);
+        }
+    }
+    float lambert = max(dot(n, l), 0.0);
+    if (lambert <= 0.0 || amount <= 0.0) {
+        return vec3(0.0);
+    }
+    amount *= shadow(index, n, l);
+    vec3 h = normalize(l + v);
+    float specular = pow(max(dot(n, h), 0.0), Shininess);
+    return (Diffuse.rgb * lambert + Specular * specular) * light.ColorIntensity.rgb * amount;
+}
+
+void main() {
+    vec3 n = normalize(Normal);
+    vec3 v = normalize(CameraPosition.xyz - WorldPosition);
+    vec3 color = Ambient.rgb * Diffuse.rgb;
+    for (int i = 0; i < min(LightCount.x, MAX_LIGHTS); ++i) {
+        color += shade(i, Lights[i], n, v);
+    }
+    FragColor = vec4(color, Diffuse.a);
+}
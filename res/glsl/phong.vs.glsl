@@ -0,0 +1,16 @@
+#version 410
+
+layout(location=0) in vec3 VertexPosition;
+layout(location=1) in vec3 VertexNormal;
+
+layout(location=0) out vec3 WorldPosition;
+layout(location=1) out vec3 Normal;
+
+uniform mat4 MVP;
+uniform mat4 Model;
+
+void main() {
+    gl_Position = MVP * vec4(VertexPosition, 1.0);
+    WorldPosition = vec3(Model * vec4(VertexPosition, 1.0));
+    Normal = mat3(transpose(inverse(Model))) * VertexNormal;
+}
@@ -0,0 +1,11 @@
+#version 410
+
+layout(location=0) in vec3 WorldPosition;
+
+uniform vec3 LightPosition;
+uniform float FarPlane;
+
+// Linear distance to the light, so every face compares in the same units.
+void main() {
+    gl_FragDepth = length(WorldPosition - LightPosition) / FarPlane;
+}
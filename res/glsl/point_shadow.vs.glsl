@@ -0,0 +1,13 @@
+#version 410
+
+layout(location=0) in vec3 VertexPosition;
+
+layout(location=0) out vec3 WorldPosition;
+
+uniform mat4 MVP;
+uniform mat4 Model;
+
+void main() {
+    gl_Position = MVP * vec4(VertexPosition, 1.0);
+    WorldPosition = vec3(Model * vec4(VertexPosition, 1.0));
+}
@@ -0,0 +1,13 @@
+#version 410
+
+layout(location=0) in vec2 TexCoord;
+layout(location=0) out vec4 FragColor;
+
+uniform sampler2D Input;
+uniform sampler2D Bloom;
+uniform float Intensity;
+
+void main() {
+    vec4 c = texture(Input, TexCoord);
+    FragColor = vec4(c.rgb + texture(Bloom, TexCoord).rgb * Intensity, c.a);
+}
@@ -0,0 +1,19 @@
+#version 410
+
+layout(location=0) in vec2 TexCoord;
+layout(location=0) out vec4 FragColor;
+
+uniform sampler2D Input;
+uniform float Threshold;
+uniform float Knee;
+
+// Keeps the light above Threshold, easing in over a quadratic knee to avoid hard edges.
+void main() {
+    vec3 c = texture(Input, TexCoord).rgb;
+    float brightness = max(c.r, max(c.g, c.b));
+    float knee = Threshold * Knee + 1e-5;
+    float soft = clamp(brightness - Threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+    float contribution = max(soft, brightness - Threshold) / max(brightness, 1e-5);
+    FragColor = vec4(c * contribution, 1.0);
+}
@@ -0,0 +1,20 @@
+#version 410
+
+layout(location=0) in vec2 TexCoord;
+layout(location=0) out vec4 FragColor;
+
+uniform sampler2D Input;
+// One texel along the blur axis.
+uniform vec2 Direction;
+
+const float WEIGHTS[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+// Nine tap Gaussian along Direction.
+void main() {
+    vec3 sum = texture(Input, TexCoord).rgb * WEIGHTS[0];
+    for (int i = 1; i < 5; ++i) {
+        sum += texture(Input, TexCoord + Direction * float(i)).rgb * WEIGHTS[i];
+        sum += texture(Input, TexCoord - Direction * float(i)).rgb * WEIGHTS[i];
+    }
+    FragColor = vec4(sum, 1.0);
+}
@@ -0,0 +1,10 @@
+#version 410
+
+layout(location=0) in vec2 TexCoord;
+layout(location=0) out vec4 FragColor;
+
+uniform sampler2D Input;
+
+void main() {
+    FragColor = texture(Input, TexCoord);
+}
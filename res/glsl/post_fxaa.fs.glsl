@@ -0,0 +1,45 @@
+#version 410
+
+layout(location=0) in vec2 TexCoord;
+layout(location=0) out vec4 FragColor;
+
+uniform sampler2D Input;
+uniform vec2 TexelSize;
+uniform float SpanMax;
+uniform float ReduceMul;
+uniform float ReduceMin;
+
+const vec3 LUMA = vec3(0.299, 0.587, 0.114);
+
+// FXAA 2: blurs along the edge direction estimated from the luma of the corners.
+void main() {
+    vec3 nw = texture(Input, TexCoord + vec2(-1.0, -1.0) * TexelSize).rgb;
+    vec3 ne = texture(Input, TexCoord + vec2(1.0, -1.0) * TexelSize).rgb;
+    vec3 sw = texture(Input, TexCoord + vec2(-1.0, 1.0) * TexelSize).rgb;
+    vec3 se = texture(Input, TexCoord + vec2(1.0, 1.0) * TexelSize).rgb;
+    vec4 m = texture(Input, TexCoord);
+
+    float luma_nw = dot(nw, LUMA);
+    float luma_ne = dot(ne, LUMA);
+    float luma_sw = dot(sw, LUMA);
+    float luma_se = dot(se, LUMA);
+    float luma_m = dot(m.rgb, LUMA);
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    vec2 dir = vec2(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se));
+    float reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * ReduceMul, ReduceMin);
+    float scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+    dir = clamp(dir * scale, vec2(-SpanMax), vec2(SpanMax)) * TexelSize;
+
+    vec3 a = 0.5 * (
+        texture(Input, TexCoord + dir * (1.0 / 3.0 - 0.5)).rgb +
+        texture(Input, TexCoord + dir * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 b = a * 0.5 + 0.25 * (
+        texture(Input, TexCoord + dir * -0.5).rgb +
+        texture(Input, TexCoord + dir * 0.5).rgb);
+    float luma_b = dot(b, LUMA);
+    FragColor = vec4(luma_b < luma_min || luma_b > luma_max ? a : b, m.a);
+}
@@ -0,0 +1,12 @@
+#version 410
+
+layout(location=0) in vec2 TexCoord;
+layout(location=0) out vec4 FragColor;
+
+uniform sampler2D Input;
+uniform float Gamma;
+
+void main() {
+    vec4 c = texture(Input, TexCoord);
+    FragColor = vec4(pow(max(c.rgb, 0.0), vec3(1.0 / Gamma)), c.a);
+}
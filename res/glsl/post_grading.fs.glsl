@@ -0,0 +1,27 @@
+#version 410
+
+layout(location=0) in vec2 TexCoord;
+layout(location=0) out vec4 FragColor;
+
+uniform sampler2D Input;
+// Slices of constant blue side by side, red across each slice and green down the rows.
+uniform sampler2D Lut;
+uniform float Strength;
+
+// Sample of slice `b` at red and green in [0, 1], staying half a texel inside the slice
+// so linear filtering never blends in its neighbour.
+vec3 lut_slice(float b, vec2 rg, float size) {
+    vec2 texel = rg * (size - 1.0) + 0.5;
+    return texture(Lut, vec2((b * size + texel.x) / (size * size), texel.y / size)).rgb;
+}
+
+void main() {
+    vec4 c = texture(Input, TexCoord);
+    vec3 rgb = clamp(c.rgb, 0.0, 1.0);
+    float size = float(textureSize(Lut, 0).y);
+    float b = rgb.b * (size - 1.0);
+    float b0 = floor(b);
+    float b1 = min(b0 + 1.0, size - 1.0);
+    vec3 graded = mix(lut_slice(b0, rgb.rg, size), lut_slice(b1, rgb.rg, size), b - b0);
+    FragColor = vec4(mix(c.rgb, graded, Strength), c.a);
+}
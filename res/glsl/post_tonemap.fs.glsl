@@ -0,0 +1,47 @@
+#version 410
+
+layout(location=0) in vec2 TexCoord;
+layout(location=0) out vec4 FragColor;
+
+uniform sampler2D Input;
+uniform int Operator;
+uniform float Exposure;
+
+vec3 reinhard(vec3 c) {
+    return c / (1.0 + c);
+}
+
+// Narkowicz's fit of the ACES filmic curve.
+vec3 aces(vec3 c) {
+    return clamp((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14), 0.0, 1.0);
+}
+
+vec3 uncharted2_curve(vec3 x) {
+    const float A = 0.15;
+    const float B = 0.50;
+    const float C = 0.10;
+    const float D = 0.20;
+    const float E = 0.02;
+    const float F = 0.30;
+    return ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+}
+
+vec3 uncharted2(vec3 c) {
+    const float white = 11.2;
+    return uncharted2_curve(2.0 * c) / uncharted2_curve(vec3(white));
+}
+
+void main() {
+    vec4 hdr = texture(Input, TexCoord);
+    vec3 c = hdr.rgb * Exposure;
+    if (Operator == 1) {
+        c = reinhard(c);
+    } else if (Operator == 2) {
+        c = aces(c);
+    } else if (Operator == 3) {
+        c = uncharted2(c);
+    } else {
+        c = clamp(c, 0.0, 1.0);
+    }
+    FragColor = vec4(c, hdr.a);
+}
@@ -0,0 +1,24 @@
+#version 410
+
+layout(location=0) in vec2 TexCoord;
+layout(location=0) out vec4 FragColor;
+
+uniform sampler2D Input;
+uniform float Strength;
+uniform float Radius;
+uniform float Softness;
+uniform vec3 Color;
+uniform int Round;
+
+void main() {
+    vec4 c = texture(Input, TexCoord);
+    vec2 offset = TexCoord - 0.5;
+    if (Round != 0) {
+        // Measure in pixels, scaled so the long side still spans 0 to 1.
+        vec2 size = vec2(textureSize(Input, 0));
+        offset *= size / max(size.x, size.y);
+    }
+    float d = length(offset) * 1.41421356;
+    float v = 1.0 - smoothstep(Radius - Softness, Radius, d);
+    FragColor = vec4(mix(Color, c.rgb, mix(1.0, v, Strength)), c.a);
+}
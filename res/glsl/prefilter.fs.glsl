@@ -0,0 +1,72 @@
+#version 410
+
+layout(location=0) in vec3 LocalPosition;
+layout(location=0) out vec4 FragColor;
+
+uniform samplerCube EnvironmentMap;
+uniform float Roughness;
+// Face size of the environment's top level, to pick the mip that matches a sample's footprint.
+uniform float EnvironmentSize;
+uniform int SampleCount;
+
+const float PI = 3.14159265359;
+
+float radical_inverse(uint bits) {
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return float(bits) * 2.3283064365386963e-10;
+}
+
+vec2 hammersley(uint i, uint n) {
+    return vec2(float(i) / float(n), radical_inverse(i));
+}
+
+vec3 importance_sample_ggx(vec2 xi, vec3 n, float roughness) {
+    float a = roughness * roughness;
+    float phi = 2.0 * PI * xi.x;
+    float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    vec3 h = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+    vec3 up = abs(n.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, n));
+    vec3 bitangent = cross(n, tangent);
+    return normalize(tangent * h.x + bitangent * h.y + n * h.z);
+}
+
+float distribution_ggx(float n_dot_h, float roughness) {
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+// GGX filtered radiance, assuming the view direction equals the normal.
+void main() {
+    vec3 n = normalize(LocalPosition);
+    vec3 v = n;
+
+    uint count = uint(SampleCount);
+    vec3 color = vec3(0.0);
+    float weight = 0.0;
+    for (uint i = 0u; i < count; ++i) {
+        vec3 h = importance_sample_ggx(hammersley(i, count), n, Roughness);
+        vec3 l = normalize(2.0 * dot(v, h) * h - v);
+        float n_dot_l = dot(n, l);
+        if (n_dot_l > 0.0) {
+            // Sample a blurrier mip where samples are sparse to avoid bright dots.
+            float n_dot_h = max(dot(n, h), 0.0);
+            float pdf = distribution_ggx(n_dot_h, Roughness) * 0.25 + 0.0001;
+            float sa_texel = 4.0 * PI / (6.0 * EnvironmentSize * EnvironmentSize);
+            float sa_sample = 1.0 / (float(count) * pdf + 0.0001);
+            float mip = Roughness == 0.0 ? 0.0 : 0.5 * log2(sa_sample / sa_texel);
+
+            color += textureLod(EnvironmentMap, l, mip).rgb * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+    FragColor = vec4(color / max(weight, 0.0001), 1.0);
+}
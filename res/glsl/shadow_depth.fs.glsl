@@ -0,0 +1,5 @@
+#version 410
+
+// Depth only; the rasterizer writes gl_FragCoord.z.
+void main() {
+}
@@ -0,0 +1,18 @@
+#version 410
+
+layout(location=0) in vec3 LocalPosition;
+layout(location=0) out vec4 FragColor;
+
+uniform samplerCube EnvironmentMap;
+uniform float Exposure;
+
+// Narkowicz's fit of the ACES filmic curve; matches pbr.fs.glsl.
+vec3 tonemap(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+    vec3 color = textureLod(EnvironmentMap, LocalPosition, 0.0).rgb;
+    color = tonemap(color * Exposure);
+    FragColor = vec4(pow(color, vec3(1.0 / 2.2)), 1.0);
+}
@@ -0,0 +1,15 @@
+#version 410
+
+layout(location=0) in vec3 VertexPosition;
+
+layout(location=0) out vec3 LocalPosition;
+
+uniform mat4 Projection;
+uniform mat4 View;
+
+void main() {
+    LocalPosition = VertexPosition;
+    // Rotation only, and depth pinned to the far plane.
+    vec4 position = Projection * mat4(mat3(View)) * vec4(VertexPosition, 1.0);
+    gl_Position = position.xyww;
+}
@@ -0,0 +1,16 @@
+#version 410
+
+layout(location=0) in vec2 VertexPosition;
+layout(location=1) in vec2 VertexTexCoord;
+layout(location=2) in vec4 VertexColor;
+
+layout(location=0) out vec2 TexCoord;
+layout(location=1) out vec4 Color;
+
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * vec4(VertexPosition, 0.0, 1.0);
+    TexCoord = VertexTexCoord;
+    Color = VertexColor;
+}
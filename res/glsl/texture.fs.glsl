@@ -4,7 +4,8 @@ layout(location=0) in vec2 TexCoord;
 layout(location=0) out vec4 FragColor;
 
 uniform sampler2D Tex0;
+uniform vec4 Tint;
 
 void main() {
-    FragColor = texture(Tex0, TexCoord);
+    FragColor = texture(Tex0, TexCoord) * Tint;
 }
@@ -1,4 +1,8 @@
+pub mod deferred;
 pub mod hello_triangle;
+pub mod lighting;
 pub mod minimal;
+pub mod pbr;
+pub mod sprites;
 pub mod texture_mapping;
 pub mod imgui;
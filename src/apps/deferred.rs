@@ -0,0 +1,226 @@
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+use imgui_glfw_rs::imgui::{self, im_str, ImString};
+use nalgebra_glm as glm;
+
+use crate::core::app::App;
+use crate::core::camera::{Camera, ControllerInput, OrbitController};
+use crate::core::deferred::{DeferredRenderer, GBufferView};
+use crate::core::framework::Context;
+use crate::core::geometry;
+use crate::core::lighting::Light;
+use crate::core::material::{Material, MaterialInstance};
+use crate::core::mesh::Mesh;
+use crate::core::scene::{NodeId, Scene, Transform};
+use crate::core::state::RenderState;
+
+const GRID: i32 = 5;
+const SPACING: f32 = 2.5;
+const LAMPS: usize = 64;
+const LAMP_RANGE: f32 = 3.0;
+
+/// A field of boxes lit by a dim sun and dozens of wandering point lights through the
+/// deferred renderer, with a few glass boxes drawn forward on top. The overlay switches
+/// between the lit frame and the G-buffer channels. Right drag orbits the camera, the
+/// wheel zooms.
+pub struct DeferredApp {
+    scene: Scene,
+    lamps: Vec<NodeId>,
+    renderer: DeferredRenderer,
+    camera: Camera,
+    orbit: OrbitController,
+}
+
+impl DeferredApp {
+    fn add_box(
+        &mut self,
+        mesh: &Rc<Mesh>,
+        material: &Rc<Material>,
+        transform: Transform,
+        diffuse: glm::Vec4,
+    ) {
+        let id = self.scene.add("box");
+        self.scene.set_transform(id, transform);
+        let mut instance = MaterialInstance::new(material.clone());
+        instance.set_param("Diffuse", diffuse);
+        let node = self.scene.node_mut(id).unwrap();
+        node.mesh = Some(mesh.clone());
+        node.material = Some(instance);
+    }
+
+    // Lamps wander on circles of their own, hues spread around the color wheel.
+    fn lamp_transform(index: usize, time: f32) -> Transform {
+        let t = index as f32 / LAMPS as f32;
+        let angle = time * (0.2 + 0.3 * t) + t * 2.0 * PI * 7.0;
+        let radius = 1.0 + 6.0 * ((index * 37) % LAMPS) as f32 / LAMPS as f32;
+        Transform::from_translation(glm::vec3(
+            radius * angle.cos(),
+            0.4 + 0.6 * (time * 0.7 + t * 11.0).sin().abs(),
+            radius * angle.sin(),
+        ))
+    }
+
+    fn lamp_color(index: usize) -> glm::Vec3 {
+        let hue = index as f32 / LAMPS as f32 * 6.0;
+        let channel = |offset: f32| (((hue + offset) % 6.0 - 3.0).abs() - 1.0).clamp(0.0, 1.0);
+        glm::vec3(channel(0.0), channel(4.0), channel(2.0))
+    }
+}
+
+impl App for DeferredApp {
+    fn new(ctx: &Context) -> DeferredApp {
+        ctx.set_viewport();
+        let mut renderer = DeferredRenderer::new(ctx.width(), ctx.height()).unwrap();
+        renderer.set_ambient(glm::vec3(0.02, 0.02, 0.025));
+        renderer.set_background(glm::vec3(0.01, 0.01, 0.015));
+
+        let mut camera = Camera::perspective(60.0, 0.1, 100.0, ctx.width(), ctx.height());
+        let mut orbit = OrbitController::new(glm::vec3(0.0, 0.5, 0.0), 14.0);
+        orbit.pitch = -0.6;
+        orbit.min_distance = 2.0;
+        orbit.update(&mut camera, &ControllerInput::default());
+
+        let mut app = DeferredApp {
+            scene: Scene::new(),
+            lamps: Vec::new(),
+            renderer,
+            camera,
+            orbit,
+        };
+
+        let opaque = Rc::new(Material::load("res/materials/gbuffer.toml").unwrap());
+        let glass = Rc::new(Material::load("res/materials/phong_transparent.toml").unwrap());
+        app.renderer.attach_forward(glass.shader()).unwrap();
+        let cube = Rc::new(geometry::cube(1.0, 1).build(RenderState::opaque()).unwrap());
+
+        let ground = app.scene.add("ground");
+        let node = app.scene.node_mut(ground).unwrap();
+        node.mesh = Some(Rc::new(
+            geometry::plane(20.0).build(RenderState::opaque()).unwrap(),
+        ));
+        node.material = Some(MaterialInstance::new(opaque.clone()));
+
+        for x in -GRID / 2..=GRID / 2 {
+            for z in -GRID / 2..=GRID / 2 {
+                let size = 0.5 + 0.15 * ((x * 3 + z * 5).rem_euclid(4)) as f32;
+                let position = glm::vec3(x as f32 * SPACING, size * 0.5, z as f32 * SPACING);
+                let transform = Transform::from_translation(position)
+                    .with_rotation(glm::quat_angle_axis(
+                        (x + 2 * z) as f32 * 0.3,
+                        &glm::Vec3::y(),
+                    ))
+                    .with_scale(glm::vec3(size, size, size));
+                let shade = 0.5 + 0.1 * ((x - z).rem_euclid(4)) as f32;
+                app.add_box(
+                    &cube,
+                    &opaque,
+                    transform,
+                    glm::vec4(shade, shade, shade, 1.0),
+                );
+            }
+        }
+        let panes = [
+            (glm::vec3(-1.25, 0.75, 1.25), glm::vec4(0.3, 0.6, 1.0, 0.35)),
+            (glm::vec3(1.25, 0.75, -1.25), glm::vec4(1.0, 0.4, 0.3, 0.35)),
+            (glm::vec3(3.75, 0.75, 1.25), glm::vec4(0.4, 1.0, 0.5, 0.35)),
+        ];
+        for (position, color) in panes.iter() {
+            let transform =
+                Transform::from_translation(*position).with_scale(glm::vec3(1.5, 1.5, 0.2));
+            app.add_box(&cube, &glass, transform, *color);
+        }
+
+        let sun = app.scene.add("sun");
+        app.scene.set_transform(
+            sun,
+            Transform::default().with_rotation(
+                glm::quat_angle_axis(0.6, &glm::Vec3::y())
+                    * glm::quat_angle_axis(-0.9, &glm::Vec3::x()),
+            ),
+        );
+        app.scene.node_mut(sun).unwrap().light = Some(Light::Directional {
+            color: glm::vec3(0.8, 0.85, 1.0),
+            intensity: 0.1,
+        });
+        for i in 0..LAMPS {
+            let lamp = app.scene.add(&format!("lamp {}", i));
+            app.scene
+                .set_transform(lamp, DeferredApp::lamp_transform(i, 0.0));
+            app.scene.node_mut(lamp).unwrap().light = Some(Light::Point {
+                color: DeferredApp::lamp_color(i),
+                intensity: 1.5,
+                range: LAMP_RANGE,
+            });
+            app.lamps.push(lamp);
+        }
+        app.scene.update_transforms();
+        app
+    }
+
+    fn update(&mut self, ctx: &Context) -> Result<(), String> {
+        let input = ControllerInput::from_input(
+            ctx.input(),
+            ctx.width(),
+            ctx.height(),
+            ctx.time().delta_secs(),
+        );
+        self.orbit.update(&mut self.camera, &input);
+
+        let time = ctx.time().elapsed_secs();
+        for (i, &lamp) in self.lamps.iter().enumerate() {
+            self.scene
+                .set_transform(lamp, DeferredApp::lamp_transform(i, time));
+        }
+        self.scene.update_transforms();
+        Ok(())
+    }
+
+    fn render(&self, ctx: &Context, _alpha: f32) -> Result<(), String> {
+        self.renderer.render(ctx, &self.scene, &self.camera);
+        Ok(())
+    }
+
+    fn ui_overlay<'a>(&self, ui: imgui::Ui<'a>) -> imgui::Ui<'a> {
+        let names: Vec<ImString> = GBufferView::ALL
+            .iter()
+            .map(|view| ImString::new(view.name()))
+            .collect();
+        let names: Vec<&ImString> = names.iter().collect();
+        let mut current = self.renderer.view().index() as i32;
+        ui.window(im_str!("Deferred shading"))
+            .always_auto_resize(true)
+            .build(|| {
+                ui.text(format!("{} lights", self.scene.lights().len()));
+                if ui.combo(im_str!("View"), &mut current, &names, -1) {
+                    self.renderer.set_view(GBufferView::ALL[current as usize]);
+                }
+            });
+        ui
+    }
+
+    fn resized(&mut self, ctx: &Context, width: u32, height: u32) -> Result<(), String> {
+        ctx.set_viewport();
+        self.camera.resize(width, height);
+        self.renderer.resize(width, height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::framework::FrameworkBuilder;
+    use crate::core::headless::Headless;
+
+    #[test]
+    #[ignore = "needs an OpenGL 4.1 capable display"]
+    fn renders_headless() {
+        FrameworkBuilder::new()
+            .window("Deferred", 640, 360)
+            .headless(Headless::new(10))
+            .build()
+            .unwrap()
+            .run::<DeferredApp>()
+            .unwrap();
+    }
+}
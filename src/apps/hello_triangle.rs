@@ -2,6 +2,7 @@ use nalgebra_glm as glm;
 
 use crate::core::app::App;
 use crate::core::buffer::Buffer;
+use crate::core::camera::Camera;
 use crate::core::framework::Context;
 use crate::core::shader::Shader;
 use crate::core::vertex::VertexArray;
@@ -9,7 +10,8 @@ use crate::core::vertex::VertexArray;
 pub struct HelloTriangleApp {
     vao: VertexArray,
     shader: Shader,
-    mvp: glm::Mat4,
+    camera: Camera,
+    model: glm::Mat4,
 }
 
 #[allow(dead_code)]
@@ -23,33 +25,41 @@ impl App for HelloTriangleApp {
         let vertices = [
             -1.0f32, -1.0f32, 0.0f32, 1.0f32, -1.0f32, 0.0f32, 0.0f32, 1.0f32, 0.0f32,
         ];
-        let vao = VertexArray::new();
-        let vbo = Buffer::new(gl::ARRAY_BUFFER, &vertices, gl::STATIC_DRAW);
+        let vao = VertexArray::new().unwrap();
+        let vbo = Buffer::new(gl::ARRAY_BUFFER, &vertices, gl::STATIC_DRAW).unwrap();
         vao.binding(|| {
             vbo.vertex_input_attrib(0, 3, 0, 0);
         });
 
-        let model = glm::Mat4::identity();
-        let eye = glm::vec3(0.0, 0.0, -2.5);
-        let target = glm::vec3(0.0, 0.0, 0.0);
-        let view = glm::look_at_rh(&eye, &target, &glm::Vec3::y());
-        let fov = glm::radians(&glm::vec3(60.0, 60.0, 60.0));
-        let proj = glm::perspective(ctx.aspect(), fov.y, 0.1, 100.0);
+        let mut camera = Camera::perspective(60.0, 0.1, 100.0, ctx.width(), ctx.height());
+        camera.look_at(
+            &glm::vec3(0.0, 0.0, -2.5),
+            &glm::vec3(0.0, 0.0, 0.0),
+            &glm::Vec3::y(),
+        );
 
         HelloTriangleApp {
             vao: vao,
             shader: shader,
-            mvp: proj * view * model,
+            camera,
+            model: glm::Mat4::identity(),
         }
     }
 
-    fn render(&self, ctx: &Context) -> Result<(), String> {
+    fn render(&self, ctx: &Context, _alpha: f32) -> Result<(), String> {
         ctx.clear_buffer(gl::COLOR_BUFFER_BIT);
 
         self.shader.use_program();
-        self.shader.set_mat4("MVP", &self.mvp);
+        let mvp = self.camera.view_projection() * self.model;
+        self.shader.set_mat4("MVP", &mvp);
         self.vao.draw_arrays(gl::TRIANGLES, 0, 3);
 
         Ok(())
     }
+
+    fn resized(&mut self, ctx: &Context, width: u32, height: u32) -> Result<(), String> {
+        ctx.set_viewport();
+        self.camera.resize(width, height);
+        Ok(())
+    }
 }
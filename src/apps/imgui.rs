@@ -13,7 +13,7 @@ impl App for ImGuiApp {
         ImGuiApp {}
     }
 
-    fn render(&self, ctx: &Context) -> Result<(), String> {
+    fn render(&self, ctx: &Context, _alpha: f32) -> Result<(), String> {
         ctx.clear_buffer(gl::COLOR_BUFFER_BIT);
         Ok(())
     }
@@ -0,0 +1,301 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::rc::Rc;
+
+use nalgebra_glm as glm;
+
+use crate::core::app::App;
+use crate::core::camera::{Camera, ControllerInput, OrbitController};
+use crate::core::framework::Context;
+use crate::core::geometry;
+use crate::core::lighting::{Light, LightBlock, LightingBuffer, MAX_LIGHTS};
+use crate::core::material::{Material, MaterialInstance};
+use crate::core::mesh::Mesh;
+use crate::core::picking::Picker;
+use crate::core::scene::{NodeId, Scene, Transform};
+use crate::core::shadow::{CascadeSettings, CascadedShadowMap, PointShadowMap};
+use crate::core::state::RenderState;
+
+const AMBIENT: [f32; 3] = [0.04, 0.04, 0.05];
+const LAMP_RANGE: f32 = 8.0;
+
+/// Blinn-Phong shaded boxes on a ground plane, lit by a sun, two orbiting point lights
+/// and a spot light. The sun and the first lamp cast shadows. Right drag orbits the
+/// camera, the wheel zooms and a left click logs the object under the cursor.
+pub struct LightingApp {
+    scene: Scene,
+    sun: NodeId,
+    lamps: Vec<NodeId>,
+    lighting: LightingBuffer,
+    sun_shadow: CascadedShadowMap,
+    lamp_shadow: PointShadowMap,
+    material: Rc<Material>,
+    camera: Camera,
+    orbit: OrbitController,
+    picker: Picker,
+}
+
+impl LightingApp {
+    fn add_object(
+        &mut self,
+        name: &str,
+        mesh: &Rc<Mesh>,
+        transform: Transform,
+        diffuse: glm::Vec4,
+        shininess: f32,
+    ) {
+        let id = self.scene.add(name);
+        self.scene.set_transform(id, transform);
+        let mut material = MaterialInstance::new(self.material.clone());
+        material.set_param("Diffuse", diffuse);
+        material.set_param("Shininess", shininess);
+        let node = self.scene.node_mut(id).unwrap();
+        node.mesh = Some(mesh.clone());
+        node.material = Some(material);
+    }
+
+    fn add_light(&mut self, name: &str, transform: Transform, light: Light) -> NodeId {
+        let id = self.scene.add(name);
+        self.scene.set_transform(id, transform);
+        self.scene.node_mut(id).unwrap().light = Some(light);
+        id
+    }
+
+    // Index of `light` in the Lighting block, which lists lights in scene order, or -1 when
+    // it is hidden or beyond the `MAX_LIGHTS` the block holds.
+    fn light_index(&self, light: NodeId) -> i32 {
+        self.scene
+            .visible_nodes()
+            .into_iter()
+            .filter(|&id| self.scene.node(id).is_some_and(|n| n.light.is_some()))
+            .take(MAX_LIGHTS)
+            .position(|id| id == light)
+            .map_or(-1, |i| i as i32)
+    }
+
+    // Call after `Scene::update_transforms` and camera changes.
+    fn fit_shadows(&mut self) {
+        let sun = self.scene.node(self.sun).unwrap().world();
+        let direction = -sun.column(2).xyz();
+        self.sun_shadow.fit(&self.camera, &direction);
+        let lamp = self.scene.node(self.lamps[0]).unwrap().world();
+        self.lamp_shadow.set_position(lamp.column(3).xyz());
+    }
+
+    // Point lights circle the scene at different heights and speeds.
+    fn lamp_transform(index: usize, time: f32) -> Transform {
+        let angle = time * (0.6 + 0.3 * index as f32) + index as f32 * PI;
+        let radius = 3.0 + index as f32;
+        Transform::from_translation(glm::vec3(
+            radius * angle.cos(),
+            1.0 + 0.5 * index as f32,
+            radius * angle.sin(),
+        ))
+    }
+}
+
+impl App for LightingApp {
+    fn new(ctx: &Context) -> LightingApp {
+        ctx.set_viewport();
+        ctx.set_clear_color(0.02, 0.02, 0.03, 1.0);
+
+        let material = Rc::new(Material::load("res/materials/phong.toml").unwrap());
+        let lighting = LightingBuffer::new().unwrap();
+        lighting.attach(material.shader()).unwrap();
+        let sun_shadow = CascadedShadowMap::new(CascadeSettings::default()).unwrap();
+        let lamp_shadow = PointShadowMap::new(512, LAMP_RANGE).unwrap();
+        sun_shadow.attach(material.shader());
+        lamp_shadow.attach(material.shader());
+
+        let mut camera = Camera::perspective(60.0, 0.1, 100.0, ctx.width(), ctx.height());
+        let mut orbit = OrbitController::new(glm::vec3(0.0, 0.5, 0.0), 9.0);
+        orbit.pitch = -0.45;
+        orbit.min_distance = 2.0;
+        orbit.update(&mut camera, &ControllerInput::default());
+
+        // The shadow casting sun is added first so its id is known up front.
+        let mut scene = Scene::new();
+        let sun = scene.add("sun");
+        let mut app = LightingApp {
+            scene,
+            sun,
+            lamps: Vec::new(),
+            lighting,
+            sun_shadow,
+            lamp_shadow,
+            material,
+            camera,
+            orbit,
+            picker: Picker::new(),
+        };
+
+        let cube = Rc::new(geometry::cube(1.0, 1).build(RenderState::opaque()).unwrap());
+        let plane = Rc::new(geometry::plane(16.0).build(RenderState::opaque()).unwrap());
+        app.add_object(
+            "ground",
+            &plane,
+            Transform::default(),
+            glm::vec4(0.6, 0.6, 0.6, 1.0),
+            8.0,
+        );
+        let boxes = [
+            (
+                glm::vec3(0.0, 0.75, 0.0),
+                1.5,
+                glm::vec4(0.8, 0.2, 0.15, 1.0),
+                64.0,
+            ),
+            (
+                glm::vec3(-2.5, 0.5, 1.0),
+                1.0,
+                glm::vec4(0.2, 0.6, 0.9, 1.0),
+                16.0,
+            ),
+            (
+                glm::vec3(2.0, 0.4, -1.5),
+                0.8,
+                glm::vec4(0.9, 0.8, 0.3, 1.0),
+                128.0,
+            ),
+            (
+                glm::vec3(1.5, 0.3, 2.0),
+                0.6,
+                glm::vec4(0.3, 0.8, 0.35, 1.0),
+                32.0,
+            ),
+        ];
+        for (i, (position, size, diffuse, shininess)) in boxes.iter().enumerate() {
+            let transform = Transform::from_translation(*position)
+                .with_rotation(glm::quat_angle_axis(i as f32 * 0.4, &glm::Vec3::y()))
+                .with_scale(glm::vec3(*size, *size, *size));
+            app.add_object(
+                &format!("box {}", i),
+                &cube,
+                transform,
+                *diffuse,
+                *shininess,
+            );
+        }
+
+        let sun = Transform::default().with_rotation(
+            glm::quat_angle_axis(0.6, &glm::Vec3::y())
+                * glm::quat_angle_axis(-0.9, &glm::Vec3::x()),
+        );
+        app.scene.set_transform(app.sun, sun);
+        app.scene.node_mut(app.sun).unwrap().light = Some(Light::Directional {
+            color: glm::vec3(1.0, 0.95, 0.85),
+            intensity: 0.35,
+        });
+        let colors = [glm::vec3(1.0, 0.45, 0.2), glm::vec3(0.3, 0.5, 1.0)];
+        for (i, color) in colors.iter().enumerate() {
+            let lamp = app.add_light(
+                &format!("lamp {}", i),
+                LightingApp::lamp_transform(i, 0.0),
+                Light::Point {
+                    color: *color,
+                    intensity: 2.0,
+                    range: LAMP_RANGE,
+                },
+            );
+            app.lamps.push(lamp);
+        }
+        app.add_light(
+            "spot",
+            Transform::from_translation(glm::vec3(0.0, 5.0, 0.0))
+                .with_rotation(glm::quat_angle_axis(-FRAC_PI_2, &glm::Vec3::x())),
+            Light::Spot {
+                color: glm::vec3(1.0, 1.0, 1.0),
+                intensity: 3.0,
+                range: 12.0,
+                inner_angle: 0.25,
+                outer_angle: 0.4,
+            },
+        );
+        app.scene.update_transforms();
+        app.fit_shadows();
+        app
+    }
+
+    fn update(&mut self, ctx: &Context) -> Result<(), String> {
+        let input = ControllerInput::from_input(
+            ctx.input(),
+            ctx.width(),
+            ctx.height(),
+            ctx.time().delta_secs(),
+        );
+        self.orbit.update(&mut self.camera, &input);
+
+        let time = ctx.time().elapsed_secs();
+        for (i, &lamp) in self.lamps.iter().enumerate() {
+            self.scene
+                .set_transform(lamp, LightingApp::lamp_transform(i, time));
+        }
+        self.scene.update_transforms();
+        self.fit_shadows();
+
+        let viewport = glm::vec2(ctx.width() as f32, ctx.height() as f32);
+        if let Some(pick) = self
+            .picker
+            .update(ctx.input(), &self.camera, &viewport, &self.scene)
+        {
+            let name = self.scene.node(pick.node).map_or("", |n| n.name());
+            log::info!(
+                "Picked \"{}\" at {:?}, {:.2} units away",
+                name,
+                pick.point,
+                pick.distance
+            );
+        }
+        Ok(())
+    }
+
+    fn render(&self, ctx: &Context, _alpha: f32) -> Result<(), String> {
+        // Both passes leave the window framebuffer bound with depth writes on.
+        self.sun_shadow.render(ctx, &self.scene);
+        self.lamp_shadow.render(ctx, &self.scene);
+        ctx.clear_buffer(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+
+        let mut block = LightBlock::from_scene(&self.scene, glm::make_vec3(&AMBIENT));
+        block.set_camera_position(self.camera.position());
+        self.lighting.upload(&block);
+
+        let shader = self.material.shader();
+        self.sun_shadow
+            .upload(shader, self.camera.view(), self.light_index(self.sun));
+        self.lamp_shadow
+            .upload(shader, self.light_index(self.lamps[0]));
+        self.sun_shadow.bind();
+        self.lamp_shadow.bind();
+
+        self.scene
+            .render_with(ctx, self.material.shader(), &self.camera.view_projection());
+        Ok(())
+    }
+
+    fn resized(&mut self, ctx: &Context, width: u32, height: u32) -> Result<(), String> {
+        ctx.set_viewport();
+        self.camera.resize(width, height);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::framework::FrameworkBuilder;
+    use crate::core::headless::Headless;
+
+    // GLFW initializes once per process, so run it on its own on a machine with an OpenGL
+    // 4.1 display: `cargo test apps::lighting::tests::matches_golden_image -- --ignored`. Set
+    // UPDATE_GOLDEN=1 to rewrite res/golden/lighting.png after an intended change.
+    #[test]
+    #[ignore = "needs an OpenGL 4.1 capable display"]
+    fn matches_golden_image() {
+        FrameworkBuilder::new()
+            .window("Lighting", 640, 360)
+            .headless(Headless::new(30).golden("res/golden/lighting.png"))
+            .build()
+            .unwrap()
+            .run::<LightingApp>()
+            .unwrap();
+    }
+}
@@ -15,7 +15,7 @@ impl App for MinimalApp {
         ctx.set_clear_color(1.0, 1.0, 1.0, 1.0);
 
         let shader = Shader::new("res/glsl/triangle.vs.glsl", "res/glsl/triangle.fs.glsl").unwrap();
-        let vao = VertexArray::new();
+        let vao = VertexArray::new().unwrap();
 
         MinimalApp {
             vao: vao,
@@ -23,7 +23,7 @@ impl App for MinimalApp {
         }
     }
 
-    fn render(&self, ctx: &Context) -> Result<(), String> {
+    fn render(&self, ctx: &Context, _alpha: f32) -> Result<(), String> {
         ctx.clear_buffer(gl::COLOR_BUFFER_BIT);
 
         self.shader.use_program();
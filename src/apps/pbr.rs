@@ -0,0 +1,156 @@
+use std::rc::Rc;
+
+use nalgebra_glm as glm;
+
+use crate::core::app::App;
+use crate::core::camera::{Camera, ControllerInput, OrbitController};
+use crate::core::framework::Context;
+use crate::core::geometry;
+use crate::core::ibl::{Environment, IblSettings, Skybox};
+use crate::core::lighting::{Light, LightBlock, LightingBuffer};
+use crate::core::material::{Material, MaterialInstance};
+use crate::core::pbr;
+use crate::core::scene::{Scene, Transform};
+use crate::core::state::RenderState;
+
+const ENVIRONMENT: &str = "res/textures/sky.hdr";
+const GRID: usize = 7;
+const SPACING: f32 = 1.25;
+const EXPOSURE: f32 = 1.0;
+
+/// Spheres lit by an HDR sky and two point lights, metalness increasing upwards and
+/// roughness to the right. Right drag orbits the camera, the wheel zooms.
+pub struct PbrApp {
+    scene: Scene,
+    lighting: LightingBuffer,
+    material: Rc<Material>,
+    environment: Environment,
+    skybox: Skybox,
+    camera: Camera,
+    orbit: OrbitController,
+}
+
+impl App for PbrApp {
+    fn new(ctx: &Context) -> PbrApp {
+        ctx.set_viewport();
+        ctx.set_clear_color(0.0, 0.0, 0.0, 1.0);
+
+        let environment = Environment::load(ctx, ENVIRONMENT, &IblSettings::default()).unwrap();
+        let material = Rc::new(pbr::load_material(pbr::DEFAULT_MATERIAL).unwrap());
+        let lighting = LightingBuffer::new().unwrap();
+        lighting.attach(material.shader()).unwrap();
+        environment.attach(material.shader());
+
+        let mut camera = Camera::perspective(45.0, 0.1, 100.0, ctx.width(), ctx.height());
+        let mut orbit = OrbitController::new(glm::vec3(0.0, 0.0, 0.0), 13.0);
+        orbit.min_distance = 3.0;
+        orbit.update(&mut camera, &ControllerInput::default());
+
+        let mut scene = Scene::new();
+        let sphere = Rc::new(
+            geometry::uv_sphere(0.5, 48, 24)
+                .build(RenderState::opaque())
+                .unwrap(),
+        );
+        let offset = (GRID - 1) as f32 * SPACING * 0.5;
+        for row in 0..GRID {
+            for col in 0..GRID {
+                let id = scene.add(&format!("sphere {} {}", row, col));
+                let position = glm::vec3(
+                    col as f32 * SPACING - offset,
+                    row as f32 * SPACING - offset,
+                    0.0,
+                );
+                scene.set_transform(id, Transform::from_translation(position));
+                let mut instance = MaterialInstance::new(material.clone());
+                instance.set_param("BaseColor", glm::vec4(0.9, 0.1, 0.1, 1.0));
+                instance.set_param("Metallic", row as f32 / (GRID - 1) as f32);
+                instance.set_param("Roughness", (col as f32 / (GRID - 1) as f32).max(0.05));
+                let node = scene.node_mut(id).unwrap();
+                node.mesh = Some(sphere.clone());
+                node.material = Some(instance);
+            }
+        }
+        for (i, x) in [-6.0, 6.0].iter().enumerate() {
+            let id = scene.add(&format!("lamp {}", i));
+            scene.set_transform(id, Transform::from_translation(glm::vec3(*x, 4.0, 6.0)));
+            scene.node_mut(id).unwrap().light = Some(Light::Point {
+                color: glm::vec3(1.0, 1.0, 1.0),
+                intensity: 40.0,
+                range: 20.0,
+            });
+        }
+        scene.update_transforms();
+
+        PbrApp {
+            scene,
+            lighting,
+            material,
+            environment,
+            skybox: Skybox::new().unwrap(),
+            camera,
+            orbit,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context) -> Result<(), String> {
+        let input = ControllerInput::from_input(
+            ctx.input(),
+            ctx.width(),
+            ctx.height(),
+            ctx.time().delta_secs(),
+        );
+        self.orbit.update(&mut self.camera, &input);
+        Ok(())
+    }
+
+    fn render(&self, ctx: &Context, _alpha: f32) -> Result<(), String> {
+        // The skybox leaves depth writes off, and clears honor the depth mask.
+        ctx.apply_state(&RenderState::opaque());
+        ctx.clear_buffer(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+
+        let mut block = LightBlock::from_scene(&self.scene, glm::vec3(0.0, 0.0, 0.0));
+        block.set_camera_position(self.camera.position());
+        self.lighting.upload(&block);
+        self.environment.bind();
+
+        self.scene
+            .render_with(ctx, self.material.shader(), &self.camera.view_projection());
+        self.skybox.draw(
+            ctx,
+            self.environment.environment(),
+            self.camera.view(),
+            self.camera.projection(),
+            EXPOSURE,
+        );
+        Ok(())
+    }
+
+    fn resized(&mut self, ctx: &Context, width: u32, height: u32) -> Result<(), String> {
+        ctx.set_viewport();
+        self.camera.resize(width, height);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::framework::FrameworkBuilder;
+    use crate::core::headless::Headless;
+
+    // GLFW initializes once per process, so run it on its own on a machine with an OpenGL
+    // 4.1 display: `cargo test apps::pbr::tests::matches_golden_image -- --ignored`. Set
+    // UPDATE_GOLDEN=1 to rewrite res/golden/pbr.png after an intended change.
+    #[test]
+    #[ignore = "needs an OpenGL 4.1 capable display"]
+    fn matches_golden_image() {
+        FrameworkBuilder::new()
+            .window("PBR", 640, 360)
+            .headless(Headless::new(2).golden("res/golden/pbr.png"))
+            .build()
+            .unwrap()
+            .run::<PbrApp>()
+            .unwrap();
+    }
+}
@@ -0,0 +1,83 @@
+use std::cell::RefCell;
+
+use image::GenericImageView;
+use nalgebra_glm as glm;
+
+use crate::core::app::App;
+use crate::core::framework::Context;
+use crate::utils::atlas::{Atlas, AtlasBuilder};
+use crate::utils::sprite::Sprite;
+use crate::utils::sprite_batch::SpriteBatch;
+
+const TILES: u32 = 4;
+const SPRITES: usize = 4096;
+
+pub struct SpritesApp {
+    atlas: Atlas,
+    batch: RefCell<SpriteBatch>,
+    sprites: Vec<Sprite>,
+}
+
+#[allow(dead_code)]
+impl App for SpritesApp {
+    fn new(ctx: &Context) -> SpritesApp {
+        ctx.set_viewport();
+        ctx.set_clear_color(0.1, 0.1, 0.1, 1.0);
+
+        // Cut the sample picture into tiles and pack them back into an atlas.
+        let img = image::open("res/textures/sailboat-on-lake.png").expect("failed to load image");
+        let (tw, th) = (img.width() / TILES, img.height() / TILES);
+        let mut builder = AtlasBuilder::new(4096, 4096);
+        for i in 0..TILES * TILES {
+            let tile = img.crop_imm((i % TILES) * tw, (i / TILES) * th, tw, th);
+            builder = builder.image(format!("tile{}", i), tile);
+        }
+        let atlas = Atlas::from_packed(builder.build().unwrap()).unwrap();
+
+        let cols = (SPRITES as f32).sqrt() as usize;
+        let sprites = (0..SPRITES)
+            .map(|i| {
+                let mut sprite = Sprite::new(
+                    &atlas.layout,
+                    &format!("tile{}", i as u32 % (TILES * TILES)),
+                )
+                .unwrap();
+                let (x, y) = ((i % cols) as f32, (i / cols) as f32);
+                sprite.position = glm::vec2(
+                    (x + 0.5) * ctx.width() as f32 / cols as f32,
+                    (y + 0.5) * ctx.height() as f32 / cols as f32,
+                );
+                sprite.scale = glm::vec2(16.0 / tw as f32, 16.0 / th as f32);
+                sprite.rotation = i as f32 * 0.1;
+                sprite.tint = glm::vec4(1.0, 1.0 - x / cols as f32, 1.0 - y / cols as f32, 1.0);
+                sprite
+            })
+            .collect();
+
+        SpritesApp {
+            atlas,
+            batch: RefCell::new(SpriteBatch::new(1024).unwrap()),
+            sprites,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context) -> Result<(), String> {
+        let dt = ctx.time().delta_secs();
+        for sprite in self.sprites.iter_mut() {
+            sprite.rotation += 1.2 * dt;
+        }
+        Ok(())
+    }
+
+    fn render(&self, ctx: &Context, _alpha: f32) -> Result<(), String> {
+        ctx.clear_buffer(gl::COLOR_BUFFER_BIT);
+
+        let mut batch = self.batch.borrow_mut();
+        batch.begin(ctx);
+        for sprite in self.sprites.iter() {
+            batch.draw(&self.atlas.texture, sprite, 0);
+        }
+        batch.end(ctx);
+        Ok(())
+    }
+}
@@ -2,17 +2,17 @@ use nalgebra_glm as glm;
 
 use crate::core::app::App;
 use crate::core::buffer::Buffer;
+use crate::core::camera::{Camera, ControllerInput, OrbitController};
 use crate::core::framework::Context;
-use crate::core::shader::Shader;
-use crate::core::texture::Texture2D;
-use crate::core::texture::TextureBuilder;
+use crate::core::material::Material;
 use crate::core::vertex::VertexArray;
 
 pub struct TextureMappingApp {
-    shader: Shader,
+    material: Material,
     vao: VertexArray,
-    tex: Texture2D,
-    mvp: glm::Mat4
+    camera: Camera,
+    orbit: OrbitController,
+    model: glm::Mat4,
 }
 
 #[allow(dead_code)]
@@ -21,7 +21,7 @@ impl App for TextureMappingApp {
         ctx.set_viewport();
         ctx.set_clear_color(1.0, 1.0, 1.0, 1.0);
 
-        let shader = Shader::new("res/glsl/texture.vs.glsl", "res/glsl/texture.fs.glsl").unwrap();
+        let material = Material::load("res/materials/textured.toml").unwrap();
 
         let vertices: [f32; 20] = [
             -1.0,  1.0, 0.0, 0.0, 0.0,
@@ -34,9 +34,9 @@ impl App for TextureMappingApp {
             3, 2, 1, // second triangle
         ];
 
-        let vao = VertexArray::new();
-        let vbo = Buffer::new(gl::ARRAY_BUFFER, &vertices, gl::STATIC_DRAW);
-        let ibo = Buffer::new(gl::ELEMENT_ARRAY_BUFFER, &indices, gl::STATIC_DRAW);
+        let vao = VertexArray::new().unwrap();
+        let vbo = Buffer::new(gl::ARRAY_BUFFER, &vertices, gl::STATIC_DRAW).unwrap();
+        let ibo = Buffer::new(gl::ELEMENT_ARRAY_BUFFER, &indices, gl::STATIC_DRAW).unwrap();
 
         vao.binding(|| {
             vbo.vertex_input_attrib(0, 3, 5, 0);
@@ -44,37 +44,51 @@ impl App for TextureMappingApp {
             ibo.bind();
         });
 
-        let tex = TextureBuilder::new()
-            .filter(gl::LINEAR)
-            .wrap(gl::REPEAT)
-            .build2d("res/textures/sailboat-on-lake.png")
-            .unwrap();
-
-        let model = glm::Mat4::identity();
-        let eye = glm::vec3(0.0, 0.0, -2.5);
-        let target = glm::vec3(0.0, 0.0, 0.0);
-        let view = glm::look_at_rh(&eye, &target, &glm::Vec3::y());
-        let fov = glm::radians(&glm::vec3(60.0, 60.0, 60.0));
-        let proj = glm::perspective(ctx.aspect(), fov.y, 0.1, 100.0);
+        let mut camera = Camera::perspective(60.0, 0.1, 100.0, ctx.width(), ctx.height());
+        camera.look_at(
+            &glm::vec3(0.0, 0.0, -2.5),
+            &glm::vec3(0.0, 0.0, 0.0),
+            &glm::Vec3::y(),
+        );
+        // Right drag orbits around the quad, the wheel zooms.
+        let mut orbit = OrbitController::new(glm::vec3(0.0, 0.0, 0.0), 2.5);
+        orbit.yaw = std::f32::consts::PI;
 
         TextureMappingApp {
+            material,
             vao: vao,
-            shader: shader,
-            tex: tex,
-            mvp: proj * view * model
+            camera,
+            orbit,
+            model: glm::Mat4::identity(),
         }
     }
 
-    fn render(&self, ctx: &Context) -> Result<(), String> {
+    fn update(&mut self, ctx: &Context) -> Result<(), String> {
+        let input = ControllerInput::from_input(
+            ctx.input(),
+            ctx.width(),
+            ctx.height(),
+            ctx.time().delta_secs(),
+        );
+        self.orbit.update(&mut self.camera, &input);
+        Ok(())
+    }
+
+    fn render(&self, ctx: &Context, _alpha: f32) -> Result<(), String> {
         ctx.clear_buffer(gl::COLOR_BUFFER_BIT);
 
-        self.shader.use_program();
-        self.shader.set_mat4("MVP", &self.mvp);
-        self.tex.binding(|| {
-            self.vao
-                .draw_elements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, 0);
-        });
+        self.material.apply(ctx);
+        let mvp = self.camera.view_projection() * self.model;
+        self.material.shader().set_mat4("MVP", &mvp);
+        self.vao
+            .draw_elements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, 0);
 
         Ok(())
     }
+
+    fn resized(&mut self, ctx: &Context, width: u32, height: u32) -> Result<(), String> {
+        ctx.set_viewport();
+        self.camera.resize(width, height);
+        Ok(())
+    }
 }
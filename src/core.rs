@@ -1,7 +1,32 @@
+pub mod action;
 pub mod app;
+pub mod bounds;
 pub mod buffer;
+pub mod bvh;
+pub mod camera;
+pub mod debug;
+pub mod deferred;
+pub mod device;
+pub mod framebuffer;
 pub mod framework;
+pub mod frustum;
+pub mod geometry;
+pub mod headless;
+pub mod ibl;
+pub mod input;
+pub mod lighting;
+pub mod material;
 pub mod mesh;
+pub mod meshops;
+pub mod pbr;
+pub mod picking;
+pub mod postprocess;
+pub mod resource;
+pub mod scene;
 pub mod shader;
+pub mod shadow;
+pub mod state;
 pub mod texture;
+pub mod time;
 pub mod vertex;
+pub mod window;
@@ -0,0 +1,828 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use imgui_glfw_rs::glfw;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use super::input::Input;
+
+/// Gamepad axis value treated as "pressed" when an axis is bound to a digital action.
+const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+macro_rules! name_table {
+    ($name:ident: $ty:ty { $($key:ident => $variant:path),* $(,)? }) => {
+        const $name: &[(&str, $ty)] = &[$((stringify!($key), $variant)),*];
+    };
+}
+
+name_table!(KEYS: glfw::Key {
+    Space => glfw::Key::Space, Apostrophe => glfw::Key::Apostrophe, Comma => glfw::Key::Comma,
+    Minus => glfw::Key::Minus, Period => glfw::Key::Period, Slash => glfw::Key::Slash,
+    Num0 => glfw::Key::Num0, Num1 => glfw::Key::Num1, Num2 => glfw::Key::Num2,
+    Num3 => glfw::Key::Num3, Num4 => glfw::Key::Num4, Num5 => glfw::Key::Num5,
+    Num6 => glfw::Key::Num6, Num7 => glfw::Key::Num7, Num8 => glfw::Key::Num8,
+    Num9 => glfw::Key::Num9, Semicolon => glfw::Key::Semicolon, Equal => glfw::Key::Equal,
+    A => glfw::Key::A, B => glfw::Key::B, C => glfw::Key::C, D => glfw::Key::D,
+    E => glfw::Key::E, F => glfw::Key::F, G => glfw::Key::G, H => glfw::Key::H,
+    I => glfw::Key::I, J => glfw::Key::J, K => glfw::Key::K, L => glfw::Key::L,
+    M => glfw::Key::M, N => glfw::Key::N, O => glfw::Key::O, P => glfw::Key::P,
+    Q => glfw::Key::Q, R => glfw::Key::R, S => glfw::Key::S, T => glfw::Key::T,
+    U => glfw::Key::U, V => glfw::Key::V, W => glfw::Key::W, X => glfw::Key::X,
+    Y => glfw::Key::Y, Z => glfw::Key::Z,
+    LeftBracket => glfw::Key::LeftBracket, Backslash => glfw::Key::Backslash,
+    RightBracket => glfw::Key::RightBracket, GraveAccent => glfw::Key::GraveAccent,
+    Escape => glfw::Key::Escape, Enter => glfw::Key::Enter, Tab => glfw::Key::Tab,
+    Backspace => glfw::Key::Backspace, Insert => glfw::Key::Insert, Delete => glfw::Key::Delete,
+    Right => glfw::Key::Right, Left => glfw::Key::Left, Down => glfw::Key::Down,
+    Up => glfw::Key::Up, PageUp => glfw::Key::PageUp, PageDown => glfw::Key::PageDown,
+    Home => glfw::Key::Home, End => glfw::Key::End, CapsLock => glfw::Key::CapsLock,
+    ScrollLock => glfw::Key::ScrollLock, NumLock => glfw::Key::NumLock,
+    PrintScreen => glfw::Key::PrintScreen, Pause => glfw::Key::Pause,
+    F1 => glfw::Key::F1, F2 => glfw::Key::F2, F3 => glfw::Key::F3, F4 => glfw::Key::F4,
+    F5 => glfw::Key::F5, F6 => glfw::Key::F6, F7 => glfw::Key::F7, F8 => glfw::Key::F8,
+    F9 => glfw::Key::F9, F10 => glfw::Key::F10, F11 => glfw::Key::F11, F12 => glfw::Key::F12,
+    Kp0 => glfw::Key::Kp0, Kp1 => glfw::Key::Kp1, Kp2 => glfw::Key::Kp2, Kp3 => glfw::Key::Kp3,
+    Kp4 => glfw::Key::Kp4, Kp5 => glfw::Key::Kp5, Kp6 => glfw::Key::Kp6, Kp7 => glfw::Key::Kp7,
+    Kp8 => glfw::Key::Kp8, Kp9 => glfw::Key::Kp9, KpDecimal => glfw::Key::KpDecimal,
+    KpDivide => glfw::Key::KpDivide, KpMultiply => glfw::Key::KpMultiply,
+    KpSubtract => glfw::Key::KpSubtract, KpAdd => glfw::Key::KpAdd,
+    KpEnter => glfw::Key::KpEnter, KpEqual => glfw::Key::KpEqual,
+    LeftShift => glfw::Key::LeftShift, LeftControl => glfw::Key::LeftControl,
+    LeftAlt => glfw::Key::LeftAlt, LeftSuper => glfw::Key::LeftSuper,
+    RightShift => glfw::Key::RightShift, RightControl => glfw::Key::RightControl,
+    RightAlt => glfw::Key::RightAlt, RightSuper => glfw::Key::RightSuper, Menu => glfw::Key::Menu,
+});
+
+name_table!(MOUSE_BUTTONS: glfw::MouseButton {
+    Left => glfw::MouseButton::Button1, Right => glfw::MouseButton::Button2,
+    Middle => glfw::MouseButton::Button3, Button4 => glfw::MouseButton::Button4,
+    Button5 => glfw::MouseButton::Button5, Button6 => glfw::MouseButton::Button6,
+    Button7 => glfw::MouseButton::Button7, Button8 => glfw::MouseButton::Button8,
+});
+
+name_table!(GAMEPAD_BUTTONS: glfw::GamepadButton {
+    A => glfw::GamepadButton::ButtonA, B => glfw::GamepadButton::ButtonB,
+    X => glfw::GamepadButton::ButtonX, Y => glfw::GamepadButton::ButtonY,
+    LeftBumper => glfw::GamepadButton::ButtonLeftBumper,
+    RightBumper => glfw::GamepadButton::ButtonRightBumper,
+    Back => glfw::GamepadButton::ButtonBack, Start => glfw::GamepadButton::ButtonStart,
+    Guide => glfw::GamepadButton::ButtonGuide, LeftThumb => glfw::GamepadButton::ButtonLeftThumb,
+    RightThumb => glfw::GamepadButton::ButtonRightThumb,
+    DpadUp => glfw::GamepadButton::ButtonDpadUp, DpadRight => glfw::GamepadButton::ButtonDpadRight,
+    DpadDown => glfw::GamepadButton::ButtonDpadDown, DpadLeft => glfw::GamepadButton::ButtonDpadLeft,
+});
+
+name_table!(GAMEPAD_AXES: glfw::GamepadAxis {
+    LeftX => glfw::GamepadAxis::AxisLeftX, LeftY => glfw::GamepadAxis::AxisLeftY,
+    RightX => glfw::GamepadAxis::AxisRightX, RightY => glfw::GamepadAxis::AxisRightY,
+    LeftTrigger => glfw::GamepadAxis::AxisLeftTrigger,
+    RightTrigger => glfw::GamepadAxis::AxisRightTrigger,
+});
+
+fn lookup<T: Copy>(table: &[(&str, T)], name: &str) -> Option<T> {
+    table
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|&(_, v)| v)
+}
+
+fn name_of<T: Copy + PartialEq>(table: &[(&'static str, T)], value: T) -> &'static str {
+    table
+        .iter()
+        .find(|&&(_, v)| v == value)
+        .map(|&(n, _)| n)
+        .unwrap_or("Unknown")
+}
+
+/// A digital input. Text form: `Ctrl+Shift+S`, `Mouse:Left`, `Gamepad:A`, `GamepadAxis:+LeftTrigger`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Binding {
+    Key(glfw::Key, glfw::Modifiers),
+    Mouse(glfw::MouseButton, glfw::Modifiers),
+    GamepadButton(glfw::GamepadButton),
+    /// Axis pushed past the threshold; `true` for the positive direction.
+    GamepadAxis(glfw::GamepadAxis, bool),
+}
+
+impl Binding {
+    pub fn key(key: glfw::Key) -> Binding {
+        Binding::Key(key, glfw::Modifiers::empty())
+    }
+
+    fn down(&self, input: &Input) -> bool {
+        match *self {
+            Binding::Key(key, mods) => input.key_down(key) && mods_down(input, mods),
+            Binding::Mouse(button, mods) => input.mouse_down(button) && mods_down(input, mods),
+            Binding::GamepadButton(button) => {
+                input.gamepads().iter().any(|g| g.button_down(button))
+            }
+            Binding::GamepadAxis(axis, positive) => input.gamepads().iter().any(|g| {
+                let v = g.axis(axis);
+                if positive {
+                    v > AXIS_PRESS_THRESHOLD
+                } else {
+                    v < -AXIS_PRESS_THRESHOLD
+                }
+            }),
+        }
+    }
+}
+
+fn mods_down(input: &Input, mods: glfw::Modifiers) -> bool {
+    let held = |l, r| input.key_down(l) || input.key_down(r);
+    (!mods.contains(glfw::Modifiers::Control)
+        || held(glfw::Key::LeftControl, glfw::Key::RightControl))
+        && (!mods.contains(glfw::Modifiers::Shift)
+            || held(glfw::Key::LeftShift, glfw::Key::RightShift))
+        && (!mods.contains(glfw::Modifiers::Alt) || held(glfw::Key::LeftAlt, glfw::Key::RightAlt))
+        && (!mods.contains(glfw::Modifiers::Super)
+            || held(glfw::Key::LeftSuper, glfw::Key::RightSuper))
+}
+
+const MODIFIERS: [(&str, glfw::Modifiers); 4] = [
+    ("Ctrl", glfw::Modifiers::Control),
+    ("Shift", glfw::Modifiers::Shift),
+    ("Alt", glfw::Modifiers::Alt),
+    ("Super", glfw::Modifiers::Super),
+];
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let write_mods = |f: &mut fmt::Formatter, mods: glfw::Modifiers| -> fmt::Result {
+            for &(name, m) in MODIFIERS.iter() {
+                if mods.contains(m) {
+                    write!(f, "{}+", name)?;
+                }
+            }
+            Ok(())
+        };
+        match *self {
+            Binding::Key(key, mods) => {
+                write_mods(f, mods)?;
+                write!(f, "{}", name_of(KEYS, key))
+            }
+            Binding::Mouse(button, mods) => {
+                write_mods(f, mods)?;
+                write!(f, "Mouse:{}", name_of(MOUSE_BUTTONS, button))
+            }
+            Binding::GamepadButton(button) => {
+                write!(f, "Gamepad:{}", name_of(GAMEPAD_BUTTONS, button))
+            }
+            Binding::GamepadAxis(axis, positive) => write!(
+                f,
+                "GamepadAxis:{}{}",
+                if positive { "+" } else { "-" },
+                name_of(GAMEPAD_AXES, axis)
+            ),
+        }
+    }
+}
+
+impl FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Binding, String> {
+        let invalid = || format!("Invalid binding \"{}\"", s);
+        // Axis bindings carry their sign after the prefix and take no modifiers.
+        if let Some(name) = strip_prefix_ci(s.trim(), "GamepadAxis:") {
+            let (positive, name) = match name.chars().next() {
+                Some('+') => (true, &name[1..]),
+                Some('-') => (false, &name[1..]),
+                _ => (true, name),
+            };
+            let axis = lookup(GAMEPAD_AXES, name).ok_or_else(invalid)?;
+            return Ok(Binding::GamepadAxis(axis, positive));
+        }
+
+        let parts: Vec<&str> = s.split('+').map(|p| p.trim()).collect();
+        let (input, mods) = parts.split_last().ok_or_else(invalid)?;
+
+        let mut modifiers = glfw::Modifiers::empty();
+        for m in mods {
+            let (_, flag) = MODIFIERS
+                .iter()
+                .find(|(name, _)| {
+                    name.eq_ignore_ascii_case(m)
+                        || (*name == "Ctrl" && m.eq_ignore_ascii_case("Control"))
+                })
+                .ok_or_else(invalid)?;
+            modifiers |= *flag;
+        }
+
+        if let Some(name) = strip_prefix_ci(input, "Mouse:") {
+            let button = lookup(MOUSE_BUTTONS, name).ok_or_else(invalid)?;
+            return Ok(Binding::Mouse(button, modifiers));
+        }
+        if let Some(name) = strip_prefix_ci(input, "Gamepad:") {
+            if !modifiers.is_empty() {
+                return Err(invalid());
+            }
+            let button = lookup(GAMEPAD_BUTTONS, name).ok_or_else(invalid)?;
+            return Ok(Binding::GamepadButton(button));
+        }
+        let key = lookup(KEYS, input).ok_or_else(invalid)?;
+        Ok(Binding::Key(key, modifiers))
+    }
+}
+
+fn strip_prefix_ci<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    if s.len() >= prefix.len() && s[..prefix.len()].eq_ignore_ascii_case(prefix) {
+        Some(&s[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PointerAxis {
+    X,
+    Y,
+}
+
+/// An analog input contributing to a named axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AxisBinding {
+    /// -1 while `negative` is down, +1 while `positive` is down.
+    Composite {
+        negative: Binding,
+        positive: Binding,
+    },
+    Gamepad {
+        axis: glfw::GamepadAxis,
+        deadzone: f32,
+        invert: bool,
+    },
+    /// Cursor movement in pixels per frame, scaled.
+    Mouse { axis: PointerAxis, scale: f32 },
+    /// Scroll offset per frame, scaled.
+    Scroll { axis: PointerAxis, scale: f32 },
+}
+
+impl AxisBinding {
+    fn value(&self, input: &Input) -> f32 {
+        match *self {
+            AxisBinding::Composite { negative, positive } => {
+                (positive.down(input) as i32 - negative.down(input) as i32) as f32
+            }
+            AxisBinding::Gamepad {
+                axis,
+                deadzone,
+                invert,
+            } => {
+                let v = input
+                    .gamepads()
+                    .iter()
+                    .map(|g| g.axis(axis))
+                    .fold(0.0f32, |acc, v| if v.abs() > acc.abs() { v } else { acc });
+                if v.abs() <= deadzone {
+                    return 0.0;
+                }
+                // Rescale so the output starts at 0 on the deadzone edge.
+                let v = v.signum() * (v.abs() - deadzone) / (1.0 - deadzone).max(f32::EPSILON);
+                if invert {
+                    -v
+                } else {
+                    v
+                }
+            }
+            AxisBinding::Mouse { axis, scale } => {
+                let d = input.cursor_delta();
+                scale
+                    * match axis {
+                        PointerAxis::X => d.x,
+                        PointerAxis::Y => d.y,
+                    }
+            }
+            AxisBinding::Scroll { axis, scale } => {
+                let d = input.scroll();
+                scale
+                    * match axis {
+                        PointerAxis::X => d.x,
+                        PointerAxis::Y => d.y,
+                    }
+            }
+        }
+    }
+
+    fn is_pointer(&self) -> bool {
+        matches!(
+            *self,
+            AxisBinding::Mouse { .. } | AxisBinding::Scroll { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct ActionState {
+    down: bool,
+    pressed: bool,
+    released: bool,
+}
+
+/// Named actions and axes resolved from `Input` once per frame.
+#[derive(Debug, Clone, Default)]
+pub struct ActionMap {
+    actions: IndexMap<String, Vec<Binding>>,
+    axes: IndexMap<String, Vec<AxisBinding>>,
+    states: HashMap<String, ActionState>,
+    values: HashMap<String, f32>,
+}
+
+#[allow(dead_code)]
+impl ActionMap {
+    pub fn new() -> ActionMap {
+        Default::default()
+    }
+
+    /// The bindings used when no file is given: Escape quits.
+    pub fn with_defaults() -> ActionMap {
+        let mut map = ActionMap::new();
+        map.bind("quit", Binding::key(glfw::Key::Escape));
+        map
+    }
+
+    pub fn bind<S>(&mut self, action: S, binding: Binding)
+    where
+        S: Into<String>,
+    {
+        self.actions.entry(action.into()).or_default().push(binding);
+    }
+
+    pub fn bind_axis<S>(&mut self, axis: S, binding: AxisBinding)
+    where
+        S: Into<String>,
+    {
+        self.axes.entry(axis.into()).or_default().push(binding);
+    }
+
+    /// Removes every binding of an action or axis.
+    pub fn unbind(&mut self, name: &str) {
+        self.actions.shift_remove(name);
+        self.axes.shift_remove(name);
+        self.states.remove(name);
+        self.values.remove(name);
+    }
+
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.actions
+            .get(action)
+            .map(|b| b.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn axis_bindings(&self, axis: &str) -> &[AxisBinding] {
+        self.axes.get(axis).map(|b| b.as_slice()).unwrap_or(&[])
+    }
+
+    /// Resolves every action and axis against the current input state.
+    pub fn update(&mut self, input: &Input) {
+        // A key binding is shadowed by a satisfied binding on the same key with more
+        // modifiers, so Ctrl+S does not also trigger S.
+        let satisfied: Vec<(glfw::Key, glfw::Modifiers)> = self
+            .actions
+            .values()
+            .flatten()
+            .filter_map(|b| match *b {
+                Binding::Key(key, mods) if b.down(input) => Some((key, mods)),
+                _ => None,
+            })
+            .collect();
+        let shadowed = |key: glfw::Key, mods: glfw::Modifiers| {
+            satisfied
+                .iter()
+                .any(|&(k, m)| k == key && m != mods && m.contains(mods))
+        };
+
+        for (name, bindings) in self.actions.iter() {
+            let down = bindings.iter().any(|b| match *b {
+                Binding::Key(key, mods) => b.down(input) && !shadowed(key, mods),
+                _ => b.down(input),
+            });
+            let state = self.states.entry(name.clone()).or_default();
+            *state = ActionState {
+                down,
+                pressed: down && !state.down,
+                released: !down && state.down,
+            };
+        }
+
+        for (name, bindings) in self.axes.iter() {
+            let digital: f32 = bindings
+                .iter()
+                .filter(|b| !b.is_pointer())
+                .map(|b| b.value(input))
+                .sum();
+            let pointer: f32 = bindings
+                .iter()
+                .filter(|b| b.is_pointer())
+                .map(|b| b.value(input))
+                .sum();
+            self.values
+                .insert(name.clone(), digital.clamp(-1.0, 1.0) + pointer);
+        }
+    }
+
+    pub fn down(&self, action: &str) -> bool {
+        self.states.get(action).map(|s| s.down).unwrap_or(false)
+    }
+
+    pub fn pressed(&self, action: &str) -> bool {
+        self.states.get(action).map(|s| s.pressed).unwrap_or(false)
+    }
+
+    pub fn released(&self, action: &str) -> bool {
+        self.states.get(action).map(|s| s.released).unwrap_or(false)
+    }
+
+    pub fn axis(&self, axis: &str) -> f32 {
+        self.values.get(axis).cloned().unwrap_or(0.0)
+    }
+
+    pub fn from_toml(text: &str) -> Result<ActionMap, String> {
+        let file: BindingsFile =
+            toml::from_str(text).map_err(|e| format!("Could not parse bindings: {}", e))?;
+
+        let mut map = ActionMap::new();
+        for (action, bindings) in file.actions {
+            for b in bindings {
+                map.bind(action.clone(), b.parse()?);
+            }
+        }
+        for (axis, bindings) in file.axes {
+            for b in bindings {
+                map.bind_axis(axis.clone(), b.into_binding()?);
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn to_toml(&self) -> Result<String, String> {
+        let file = BindingsFile {
+            actions: self
+                .actions
+                .iter()
+                .map(|(name, b)| (name.clone(), b.iter().map(|b| b.to_string()).collect()))
+                .collect(),
+            axes: self
+                .axes
+                .iter()
+                .map(|(name, b)| (name.clone(), b.iter().map(AxisDesc::from).collect()))
+                .collect(),
+        };
+        toml::to_string_pretty(&file).map_err(|e| format!("Could not write bindings: {}", e))
+    }
+
+    pub fn load<P>(path: P) -> Result<ActionMap, String>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("failed to open file: {}: {}", path.display(), e))?;
+        ActionMap::from_toml(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn save<P>(&self, path: P) -> Result<(), String>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        fs::write(path, self.to_toml()?)
+            .map_err(|e| format!("failed to write file: {}: {}", path.display(), e))
+    }
+}
+
+// Bindings file layout:
+//
+// [actions]
+// quit = ["Escape"]
+// save = ["Ctrl+S"]
+//
+// [[axes.move_forward]]
+// negative = "S"
+// positive = "W"
+//
+// [[axes.move_forward]]
+// gamepad = "LeftY"
+// invert = true
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BindingsFile {
+    #[serde(default)]
+    actions: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    axes: BTreeMap<String, Vec<AxisDesc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum AxisDesc {
+    Composite {
+        negative: String,
+        positive: String,
+    },
+    Gamepad {
+        gamepad: String,
+        #[serde(default = "AxisDesc::default_deadzone")]
+        deadzone: f32,
+        #[serde(default)]
+        invert: bool,
+    },
+    Mouse {
+        mouse: PointerAxis,
+        #[serde(default = "AxisDesc::default_scale")]
+        scale: f32,
+    },
+    Scroll {
+        scroll: PointerAxis,
+        #[serde(default = "AxisDesc::default_scale")]
+        scale: f32,
+    },
+}
+
+impl AxisDesc {
+    fn default_deadzone() -> f32 {
+        0.15
+    }
+
+    fn default_scale() -> f32 {
+        1.0
+    }
+
+    fn into_binding(self) -> Result<AxisBinding, String> {
+        Ok(match self {
+            AxisDesc::Composite { negative, positive } => AxisBinding::Composite {
+                negative: negative.parse()?,
+                positive: positive.parse()?,
+            },
+            AxisDesc::Gamepad {
+                gamepad,
+                deadzone,
+                invert,
+            } => AxisBinding::Gamepad {
+                axis: lookup(GAMEPAD_AXES, &gamepad)
+                    .ok_or_else(|| format!("Invalid gamepad axis \"{}\"", gamepad))?,
+                deadzone,
+                invert,
+            },
+            AxisDesc::Mouse { mouse, scale } => AxisBinding::Mouse { axis: mouse, scale },
+            AxisDesc::Scroll { scroll, scale } => AxisBinding::Scroll {
+                axis: scroll,
+                scale,
+            },
+        })
+    }
+}
+
+impl From<&AxisBinding> for AxisDesc {
+    fn from(binding: &AxisBinding) -> AxisDesc {
+        match *binding {
+            AxisBinding::Composite { negative, positive } => AxisDesc::Composite {
+                negative: negative.to_string(),
+                positive: positive.to_string(),
+            },
+            AxisBinding::Gamepad {
+                axis,
+                deadzone,
+                invert,
+            } => AxisDesc::Gamepad {
+                gamepad: name_of(GAMEPAD_AXES, axis).to_string(),
+                deadzone,
+                invert,
+            },
+            AxisBinding::Mouse { axis, scale } => AxisDesc::Mouse { mouse: axis, scale },
+            AxisBinding::Scroll { axis, scale } => AxisDesc::Scroll {
+                scroll: axis,
+                scale,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::input::Gamepad;
+
+    fn key(input: &mut Input, key: glfw::Key, action: glfw::Action) {
+        input.handle_event(&glfw::WindowEvent::Key(
+            key,
+            0,
+            action,
+            glfw::Modifiers::empty(),
+        ));
+    }
+
+    #[test]
+    fn parses_and_prints_bindings() {
+        for text in &[
+            "W",
+            "Ctrl+S",
+            "Ctrl+Shift+Alt+Super+F5",
+            "Mouse:Left",
+            "Shift+Mouse:Right",
+            "Gamepad:A",
+            "GamepadAxis:+RightTrigger",
+            "GamepadAxis:-LeftY",
+        ] {
+            let binding: Binding = text.parse().unwrap();
+            assert_eq!(&binding.to_string(), text);
+        }
+        assert_eq!(
+            "control+escape".parse::<Binding>(),
+            Ok(Binding::Key(glfw::Key::Escape, glfw::Modifiers::Control))
+        );
+        assert!("Hyper+W".parse::<Binding>().is_err());
+        assert!("Mouse:Nose".parse::<Binding>().is_err());
+        assert!("Ctrl+Gamepad:A".parse::<Binding>().is_err());
+    }
+
+    #[test]
+    fn resolves_action_edges() {
+        let mut map = ActionMap::with_defaults();
+        map.bind("jump", Binding::key(glfw::Key::Space));
+        map.bind(
+            "jump",
+            Binding::Mouse(glfw::MouseButton::Button1, glfw::Modifiers::empty()),
+        );
+
+        let mut input = Input::new();
+        input.begin_frame();
+        key(&mut input, glfw::Key::Space, glfw::Action::Press);
+        map.update(&input);
+        assert!(map.pressed("jump") && map.down("jump"));
+        assert!(!map.down("quit"));
+
+        // Mouse keeps the action down after the key is released.
+        input.begin_frame();
+        input.handle_event(&glfw::WindowEvent::MouseButton(
+            glfw::MouseButton::Button1,
+            glfw::Action::Press,
+            glfw::Modifiers::empty(),
+        ));
+        key(&mut input, glfw::Key::Space, glfw::Action::Release);
+        map.update(&input);
+        assert!(map.down("jump") && !map.pressed("jump") && !map.released("jump"));
+
+        input.begin_frame();
+        input.handle_event(&glfw::WindowEvent::MouseButton(
+            glfw::MouseButton::Button1,
+            glfw::Action::Release,
+            glfw::Modifiers::empty(),
+        ));
+        map.update(&input);
+        assert!(map.released("jump") && !map.down("jump"));
+        assert!(!map.pressed("unknown"));
+    }
+
+    #[test]
+    fn modifiers_shadow_plain_bindings() {
+        let mut map = ActionMap::new();
+        map.bind("move_back", "S".parse().unwrap());
+        map.bind("save", "Ctrl+S".parse().unwrap());
+
+        let mut input = Input::new();
+        input.begin_frame();
+        key(&mut input, glfw::Key::S, glfw::Action::Press);
+        map.update(&input);
+        assert!(map.down("move_back") && !map.down("save"));
+
+        input.begin_frame();
+        key(&mut input, glfw::Key::RightControl, glfw::Action::Press);
+        map.update(&input);
+        assert!(map.pressed("save") && !map.down("move_back"));
+        assert!(map.released("move_back"));
+    }
+
+    #[test]
+    fn combines_axis_sources() {
+        let mut map = ActionMap::new();
+        map.bind_axis(
+            "move_forward",
+            AxisBinding::Composite {
+                negative: "S".parse().unwrap(),
+                positive: "W".parse().unwrap(),
+            },
+        );
+        map.bind_axis(
+            "move_forward",
+            AxisBinding::Gamepad {
+                axis: glfw::GamepadAxis::AxisLeftY,
+                deadzone: 0.2,
+                invert: true,
+            },
+        );
+        map.bind_axis(
+            "zoom",
+            AxisBinding::Scroll {
+                axis: PointerAxis::Y,
+                scale: 0.5,
+            },
+        );
+
+        let mut input = Input::new();
+        input.begin_frame();
+        key(&mut input, glfw::Key::W, glfw::Action::Press);
+        input.handle_event(&glfw::WindowEvent::Scroll(0.0, 4.0));
+        map.update(&input);
+        assert_eq!(map.axis("move_forward"), 1.0);
+        assert_eq!(map.axis("zoom"), 2.0);
+
+        // Stick pushed forward (negative Y) plus W is clamped to 1; back inside the deadzone is 0.
+        let mut axes = vec![0.0; 6];
+        axes[glfw::GamepadAxis::AxisLeftY as usize] = -0.6;
+        input.set_gamepads(vec![Gamepad::mapped(
+            glfw::JoystickId::Joystick1,
+            "pad",
+            axes,
+            vec![false; 15],
+        )]);
+        map.update(&input);
+        assert_eq!(map.axis("move_forward"), 1.0);
+
+        input.begin_frame();
+        key(&mut input, glfw::Key::W, glfw::Action::Release);
+        map.update(&input);
+        assert!((map.axis("move_forward") - 0.5).abs() < 1e-6);
+        assert_eq!(map.axis("zoom"), 0.0);
+
+        let mut axes = vec![0.0; 6];
+        axes[glfw::GamepadAxis::AxisLeftY as usize] = 0.1;
+        input.set_gamepads(vec![Gamepad::mapped(
+            glfw::JoystickId::Joystick1,
+            "pad",
+            axes,
+            vec![false; 15],
+        )]);
+        map.update(&input);
+        assert_eq!(map.axis("move_forward"), 0.0);
+    }
+
+    #[test]
+    fn gamepad_buttons_trigger_actions() {
+        let mut map = ActionMap::new();
+        map.bind("jump", "Gamepad:A".parse().unwrap());
+        map.bind("fire", "GamepadAxis:+RightTrigger".parse().unwrap());
+
+        let mut buttons = vec![false; 15];
+        buttons[glfw::GamepadButton::ButtonA as usize] = true;
+        let mut axes = vec![0.0; 6];
+        axes[glfw::GamepadAxis::AxisRightTrigger as usize] = 0.9;
+
+        let mut input = Input::new();
+        input.set_gamepads(vec![Gamepad::mapped(
+            glfw::JoystickId::Joystick2,
+            "pad",
+            axes,
+            buttons,
+        )]);
+        map.update(&input);
+        assert!(map.pressed("jump"));
+        assert!(map.pressed("fire"));
+    }
+
+    #[test]
+    fn round_trips_toml() {
+        let text = r#"
+            [actions]
+            quit = ["Escape"]
+            screenshot = ["F12", "Ctrl+P"]
+            jump = ["Space", "Gamepad:A"]
+
+            [[axes.move_forward]]
+            negative = "S"
+            positive = "W"
+
+            [[axes.move_forward]]
+            gamepad = "LeftY"
+            invert = true
+
+            [[axes.look_x]]
+            mouse = "X"
+            scale = 0.1
+        "#;
+        let map = ActionMap::from_toml(text).unwrap();
+        assert_eq!(map.bindings("screenshot").len(), 2);
+        assert_eq!(
+            map.axis_bindings("move_forward")[1],
+            AxisBinding::Gamepad {
+                axis: glfw::GamepadAxis::AxisLeftY,
+                deadzone: 0.15,
+                invert: true
+            }
+        );
+
+        let again = ActionMap::from_toml(&map.to_toml().unwrap()).unwrap();
+        for name in &["quit", "screenshot", "jump"] {
+            assert_eq!(again.bindings(name), map.bindings(name));
+        }
+        for name in &["move_forward", "look_x"] {
+            assert_eq!(again.axis_bindings(name), map.axis_bindings(name));
+        }
+        assert!(ActionMap::from_toml("[actions]\nquit = [\"Nope\"]").is_err());
+    }
+}
@@ -1,15 +1,23 @@
 use super::framework::Context;
+use imgui_glfw_rs::glfw;
 use imgui_glfw_rs::imgui;
 
 #[allow(unused_variables)]
 pub trait App {
     fn new(ctx: &Context) -> Self;
 
+    /// Called `FrameworkBuilder::fixed_rate` times per second with the fixed step in seconds.
+    fn fixed_update(&mut self, ctx: &Context, dt: f32) -> Result<(), String> {
+        Ok(())
+    }
+
     fn update(&mut self, ctx: &Context) -> Result<(), String> {
         Ok(())
     }
 
-    fn render(&self, ctx: &Context) -> Result<(), String> {
+    /// Draws the frame. `alpha` is how far it lies between the last and the next fixed
+    /// update, in [0, 1), to blend the states of the last two steps; 1 without a fixed rate.
+    fn render(&self, ctx: &Context, alpha: f32) -> Result<(), String> {
         Ok(())
     }
 
@@ -21,6 +29,11 @@ pub trait App {
         Ok(())
     }
 
+    /// Called for every window event after `Context::input` has been updated.
+    fn event(&mut self, ctx: &Context, event: &glfw::WindowEvent) -> Result<(), String> {
+        Ok(())
+    }
+
     fn resized(&mut self, ctx: &Context, width: u32, height: u32) -> Result<(), String> {
         ctx.set_viewport();
         Ok(())
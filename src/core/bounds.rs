@@ -0,0 +1,371 @@
+use nalgebra_glm as glm;
+
+/// Axis-aligned bounding box. The empty box has `min` above `max` and contains nothing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: glm::Vec3,
+    pub max: glm::Vec3,
+}
+
+impl Default for Aabb {
+    fn default() -> Aabb {
+        Aabb::empty()
+    }
+}
+
+#[allow(dead_code)]
+impl Aabb {
+    pub fn new(min: glm::Vec3, max: glm::Vec3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: glm::vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: glm::vec3(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    /// Box around tightly packed xyz positions, e.g. `MeshData::positions`.
+    pub fn from_positions(positions: &[f32]) -> Aabb {
+        positions
+            .chunks_exact(3)
+            .fold(Aabb::empty(), |aabb, p| aabb.with_point(&glm::make_vec3(p)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn with_point(mut self, point: &glm::Vec3) -> Aabb {
+        self.min = glm::min2(&self.min, point);
+        self.max = glm::max2(&self.max, point);
+        self
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: glm::min2(&self.min, &other.min),
+            max: glm::max2(&self.max, &other.max),
+        }
+    }
+
+    pub fn center(&self) -> glm::Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> glm::Vec3 {
+        self.max - self.min
+    }
+
+    pub fn half_extents(&self) -> glm::Vec3 {
+        self.size() * 0.5
+    }
+
+    /// Half the sum of the face areas, the cost measure of surface area heuristics.
+    pub fn half_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let size = self.size();
+        size.x * size.y + size.y * size.z + size.z * size.x
+    }
+
+    pub fn contains(&self, point: &glm::Vec3) -> bool {
+        (0..3).all(|i| point[i] >= self.min[i] && point[i] <= self.max[i])
+    }
+
+    pub fn corners(&self) -> [glm::Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            glm::vec3(a.x, a.y, a.z),
+            glm::vec3(b.x, a.y, a.z),
+            glm::vec3(a.x, b.y, a.z),
+            glm::vec3(b.x, b.y, a.z),
+            glm::vec3(a.x, a.y, b.z),
+            glm::vec3(b.x, a.y, b.z),
+            glm::vec3(a.x, b.y, b.z),
+            glm::vec3(b.x, b.y, b.z),
+        ]
+    }
+
+    /// Smallest axis-aligned box around this box moved by `transform`.
+    pub fn transformed(&self, transform: &glm::Mat4) -> Aabb {
+        if self.is_empty() {
+            return *self;
+        }
+        // Each output axis spans the extents projected onto the matrix row (Arvo).
+        let center = transform_point(transform, &self.center());
+        let half = self.half_extents();
+        let mut extent = glm::Vec3::zeros();
+        for row in 0..3 {
+            for col in 0..3 {
+                extent[row] += transform[(row, col)].abs() * half[col];
+            }
+        }
+        Aabb {
+            min: center - extent,
+            max: center + extent,
+        }
+    }
+}
+
+/// `point` moved by the affine `transform`.
+pub fn transform_point(transform: &glm::Mat4, point: &glm::Vec3) -> glm::Vec3 {
+    (transform * glm::vec4(point.x, point.y, point.z, 1.0)).xyz()
+}
+
+/// Sphere around a set of points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: glm::Vec3,
+    pub radius: f32,
+}
+
+#[allow(dead_code)]
+impl BoundingSphere {
+    pub fn new(center: glm::Vec3, radius: f32) -> BoundingSphere {
+        BoundingSphere { center, radius }
+    }
+
+    /// Ritter's sphere around tightly packed xyz positions: at most a few percent larger
+    /// than the smallest one. Empty input gives a sphere of radius 0 at the origin.
+    pub fn from_positions(positions: &[f32]) -> BoundingSphere {
+        let points: Vec<glm::Vec3> = positions.chunks_exact(3).map(glm::make_vec3).collect();
+        let first = match points.first() {
+            Some(p) => *p,
+            None => return BoundingSphere::new(glm::Vec3::zeros(), 0.0),
+        };
+        let farthest = |from: &glm::Vec3| {
+            *points
+                .iter()
+                .max_by(|a, b| {
+                    glm::distance2(from, a)
+                        .partial_cmp(&glm::distance2(from, b))
+                        .unwrap()
+                })
+                .unwrap()
+        };
+        let a = farthest(&first);
+        let b = farthest(&a);
+        let mut sphere = BoundingSphere::new((a + b) * 0.5, glm::distance(&a, &b) * 0.5);
+        for p in points.iter() {
+            let distance = glm::distance(&sphere.center, p);
+            if distance > sphere.radius {
+                // Grow just enough to touch `p`, keeping the far side where it was.
+                let radius = (sphere.radius + distance) * 0.5;
+                sphere.center += (p - sphere.center) * ((radius - sphere.radius) / distance);
+                sphere.radius = radius;
+            }
+        }
+        sphere
+    }
+
+    pub fn from_aabb(aabb: &Aabb) -> BoundingSphere {
+        BoundingSphere::new(aabb.center(), aabb.half_extents().norm())
+    }
+
+    pub fn contains(&self, point: &glm::Vec3) -> bool {
+        glm::distance(&self.center, point) <= self.radius
+    }
+
+    /// Sphere around this sphere moved by `transform`, scaled by its largest axis scale.
+    pub fn transformed(&self, transform: &glm::Mat4) -> BoundingSphere {
+        let scale = (0..3)
+            .map(|col| transform.column(col).xyz().norm())
+            .fold(0.0, f32::max);
+        BoundingSphere {
+            center: transform_point(transform, &self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+/// Half-line from `origin` along `direction`. Hits are reported as the `t` of
+/// `origin + direction * t`, so they are distances only for a unit `direction`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: glm::Vec3,
+    pub direction: glm::Vec3,
+}
+
+#[allow(dead_code)]
+impl Ray {
+    pub fn new(origin: glm::Vec3, direction: glm::Vec3) -> Ray {
+        Ray { origin, direction }
+    }
+
+    pub fn at(&self, t: f32) -> glm::Vec3 {
+        self.origin + self.direction * t
+    }
+
+    /// The same ray in the space `transform` maps to. `direction` is not renormalized, so
+    /// `t` of hits in that space still matches `t` along this ray.
+    pub fn transformed(&self, transform: &glm::Mat4) -> Ray {
+        let direction =
+            transform * glm::vec4(self.direction.x, self.direction.y, self.direction.z, 0.0);
+        Ray::new(transform_point(transform, &self.origin), direction.xyz())
+    }
+
+    /// `t` where the ray enters `aabb`, 0 when it starts inside.
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let (mut near, mut far) = (0.0f32, f32::INFINITY);
+        for i in 0..3 {
+            // Dividing by a zero direction gives infinities that keep the slab test exact.
+            let inverse = 1.0 / self.direction[i];
+            let t0 = (aabb.min[i] - self.origin[i]) * inverse;
+            let t1 = (aabb.max[i] - self.origin[i]) * inverse;
+            let (t0, t1) = if t0 <= t1 { (t0, t1) } else { (t1, t0) };
+            // NaN, from a zero direction on the slab's boundary, leaves the range alone.
+            near = near.max(t0);
+            far = far.min(t1);
+            if near > far {
+                return None;
+            }
+        }
+        Some(near)
+    }
+
+    /// `t` where the ray enters `sphere`, 0 when it starts inside.
+    pub fn intersect_sphere(&self, sphere: &BoundingSphere) -> Option<f32> {
+        let offset = self.origin - sphere.center;
+        let a = self.direction.norm_squared();
+        let b = offset.dot(&self.direction);
+        let c = offset.norm_squared() - sphere.radius * sphere.radius;
+        if c <= 0.0 {
+            return Some(0.0);
+        }
+        let discriminant = b * b - a * c;
+        if a == 0.0 || b > 0.0 || discriminant < 0.0 {
+            return None;
+        }
+        Some((-b - discriminant.sqrt()) / a)
+    }
+
+    /// `t` where the ray hits the triangle `p`, from either side (Möller-Trumbore).
+    pub fn intersect_triangle(&self, p: &[glm::Vec3; 3]) -> Option<f32> {
+        let (e1, e2) = (p[1] - p[0], p[2] - p[0]);
+        let h = self.direction.cross(&e2);
+        let det = e1.dot(&h);
+        if det.abs() < f32::EPSILON * e1.norm() * e2.norm() * self.direction.norm() {
+            return None;
+        }
+        let s = self.origin - p[0];
+        let u = s.dot(&h) / det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(&e1);
+        let v = self.direction.dot(&q) / det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = e2.dot(&q) / det;
+        if t >= 0.0 {
+            Some(t)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINTS: [f32; 12] = [
+        1.0, 2.0, 3.0, //
+        -1.0, 0.5, 0.0, //
+        0.0, -2.0, 1.0, //
+        2.0, 0.0, -4.0,
+    ];
+
+    #[test]
+    fn boxes_points_and_merges() {
+        let aabb = Aabb::from_positions(&POINTS);
+        assert_eq!(aabb.min, glm::vec3(-1.0, -2.0, -4.0));
+        assert_eq!(aabb.max, glm::vec3(2.0, 2.0, 3.0));
+        assert_eq!(aabb.center(), glm::vec3(0.5, 0.0, -0.5));
+        assert!(aabb.contains(&glm::vec3(0.0, 0.0, 0.0)));
+        assert!(!aabb.contains(&glm::vec3(0.0, 3.0, 0.0)));
+
+        let empty = Aabb::from_positions(&[]);
+        assert!(empty.is_empty());
+        assert_eq!(empty.half_area(), 0.0);
+        assert_eq!(empty.union(&aabb), aabb);
+        assert_eq!(aabb.half_area(), 3.0 * 4.0 + 4.0 * 7.0 + 7.0 * 3.0);
+    }
+
+    #[test]
+    fn transformed_box_holds_the_transformed_corners() {
+        let aabb = Aabb::new(glm::vec3(-1.0, -2.0, -0.5), glm::vec3(1.0, 2.0, 0.5));
+        let transform = glm::translation(&glm::vec3(3.0, 0.0, -1.0))
+            * glm::rotation(0.7, &glm::vec3(0.3, 1.0, 0.2).normalize())
+            * glm::scaling(&glm::vec3(2.0, 1.0, 1.5));
+        let moved = aabb.transformed(&transform);
+
+        let mut exact = Aabb::empty();
+        for corner in aabb.corners().iter() {
+            exact = exact.with_point(&transform_point(&transform, corner));
+        }
+        assert!((moved.min - exact.min).norm() < 1e-5);
+        assert!((moved.max - exact.max).norm() < 1e-5);
+    }
+
+    #[test]
+    fn sphere_contains_every_point() {
+        let sphere = BoundingSphere::from_positions(&POINTS);
+        for p in POINTS.chunks_exact(3) {
+            assert!(glm::distance(&sphere.center, &glm::make_vec3(p)) <= sphere.radius + 1e-5);
+        }
+        // Never looser than the sphere around the box.
+        let boxed = BoundingSphere::from_aabb(&Aabb::from_positions(&POINTS));
+        assert!(sphere.radius <= boxed.radius);
+
+        let moved = sphere.transformed(
+            &(glm::translation(&glm::vec3(1.0, 0.0, 0.0))
+                * glm::scaling(&glm::vec3(1.0, 3.0, 2.0))),
+        );
+        assert!((moved.radius - sphere.radius * 3.0).abs() < 1e-5);
+        assert_eq!(BoundingSphere::from_positions(&[]).radius, 0.0);
+    }
+
+    #[test]
+    fn rays_hit_boxes_spheres_and_triangles() {
+        let aabb = Aabb::new(glm::vec3(-1.0, -1.0, -1.0), glm::vec3(1.0, 1.0, 1.0));
+        let ray = Ray::new(glm::vec3(-5.0, 0.5, 0.0), glm::vec3(1.0, 0.0, 0.0));
+        assert_eq!(ray.intersect_aabb(&aabb), Some(4.0));
+        assert_eq!(ray.at(4.0), glm::vec3(-1.0, 0.5, 0.0));
+        let inside = Ray::new(glm::vec3(0.0, 0.0, 0.0), glm::vec3(0.0, 0.0, -1.0));
+        assert_eq!(inside.intersect_aabb(&aabb), Some(0.0));
+        let above = Ray::new(glm::vec3(-5.0, 2.0, 0.0), glm::vec3(1.0, 0.0, 0.0));
+        assert_eq!(above.intersect_aabb(&aabb), None);
+        let away = Ray::new(glm::vec3(-5.0, 0.0, 0.0), glm::vec3(-1.0, 0.0, 0.0));
+        assert_eq!(away.intersect_aabb(&aabb), None);
+
+        let sphere = BoundingSphere::new(glm::vec3(0.0, 0.0, -10.0), 2.0);
+        let forward = Ray::new(glm::vec3(0.0, 0.0, 0.0), glm::vec3(0.0, 0.0, -2.0));
+        assert_eq!(forward.intersect_sphere(&sphere), Some(4.0));
+        assert_eq!(inside.intersect_sphere(&sphere), Some(8.0));
+        assert_eq!(ray.intersect_sphere(&sphere), None);
+
+        let triangle = [
+            glm::vec3(0.0, 0.0, -3.0),
+            glm::vec3(1.0, 0.0, -3.0),
+            glm::vec3(0.0, 1.0, -3.0),
+        ];
+        let ray = Ray::new(glm::vec3(0.25, 0.25, 0.0), glm::vec3(0.0, 0.0, -1.0));
+        assert!((ray.intersect_triangle(&triangle).unwrap() - 3.0).abs() < 1e-6);
+        let miss = Ray::new(glm::vec3(0.75, 0.75, 0.0), glm::vec3(0.0, 0.0, -1.0));
+        assert_eq!(miss.intersect_triangle(&triangle), None);
+        let behind = Ray::new(glm::vec3(0.25, 0.25, -5.0), glm::vec3(0.0, 0.0, -1.0));
+        assert_eq!(behind.intersect_triangle(&triangle), None);
+
+        // Hits keep their t in the space the ray is moved to.
+        let world =
+            glm::translation(&glm::vec3(0.0, 0.0, -3.0)) * glm::scaling(&glm::vec3(2.0, 2.0, 2.0));
+        let local = forward.transformed(&glm::inverse(&world));
+        let unit = BoundingSphere::new(glm::vec3(0.0, 0.0, -3.5), 1.0);
+        assert!((local.intersect_sphere(&unit).unwrap() - 4.0).abs() < 1e-5);
+    }
+}
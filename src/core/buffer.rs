@@ -1,68 +1,102 @@
-use std::os::raw::c_void;
-
 use gl::types::*;
 
+use super::resource::{self, ContextHandle, ResourceKind};
+
 pub struct Buffer {
     id: u32,
     target: GLenum,
+    ctx: ContextHandle,
 }
 
 impl Drop for Buffer {
     // Delete the buffer
     fn drop(&mut self) {
-        unsafe {
-            gl::DeleteBuffers(1, &self.id);
-        }
-        println!("Delete Buffer: {}", self.id);
+        self.ctx.release(ResourceKind::Buffer, self.id);
     }
 }
 
+/// Views a slice of plain vertex or index data as bytes.
+fn as_bytes<T>(data: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data)) }
+}
+
 #[allow(dead_code)]
 impl Buffer {
-    pub fn new<T>(target: GLenum, data: &[T], usage: GLenum) -> Self {
-        let mut buf = 0;
-        unsafe {
-            gl::GenBuffers(1, &mut buf);
-            gl::BindBuffer(target, buf);
-            gl::BufferData(
-                target,
-                (data.len() * std::mem::size_of::<T>()) as gl::types::GLsizeiptr,
-                std::mem::transmute(data.as_ptr()),
-                usage,
-            );
-            gl::BindBuffer(target, 0);
-        }
-        Buffer {
+    pub fn new<T>(target: GLenum, data: &[T], usage: GLenum) -> Result<Buffer, String> {
+        let ctx = ContextHandle::current()?;
+        let bytes = as_bytes(data);
+        let device = ctx.device();
+        let buf = device.gen_buffer();
+        device.bind_buffer(target, buf);
+        device.buffer_data(target, bytes.len(), Some(bytes), usage);
+        device.bind_buffer(target, 0);
+        ctx.created(ResourceKind::Buffer, buf);
+        Ok(Buffer {
             id: buf,
-            target: target,
-        }
+            target,
+            ctx,
+        })
+    }
+
+    /// Names the object in debug messages and graphics debuggers.
+    pub fn set_label(&self, label: &str) {
+        self.ctx.device().object_label(gl::BUFFER, self.id, label);
+        resource::track_label(ResourceKind::Buffer, self.id, label);
+    }
+
+    /// Allocates `size` bytes without initializing them, e.g. for streaming with `sub_data`.
+    pub fn with_size(target: GLenum, size: usize, usage: GLenum) -> Result<Buffer, String> {
+        let ctx = ContextHandle::current()?;
+        let device = ctx.device();
+        let buf = device.gen_buffer();
+        device.bind_buffer(target, buf);
+        device.buffer_data(target, size, None, usage);
+        device.bind_buffer(target, 0);
+        ctx.created(ResourceKind::Buffer, buf);
+        Ok(Buffer {
+            id: buf,
+            target,
+            ctx,
+        })
+    }
+
+    /// Replaces part of the buffer, starting at `offset` bytes.
+    pub fn sub_data<T>(&self, offset: usize, data: &[T]) {
+        let device = self.ctx.device();
+        device.bind_buffer(self.target, self.id);
+        device.buffer_sub_data(self.target, offset, as_bytes(data));
+        device.bind_buffer(self.target, 0);
+    }
+
+    /// Reallocates the storage so the driver does not have to wait for pending draws.
+    pub fn orphan(&self, size: usize, usage: GLenum) {
+        let device = self.ctx.device();
+        device.bind_buffer(self.target, self.id);
+        device.buffer_data(self.target, size, None, usage);
+        device.bind_buffer(self.target, 0);
     }
 
     pub fn bind(&self) {
-        unsafe {
-            gl::BindBuffer(self.target, self.id);
-        }
+        self.ctx.device().bind_buffer(self.target, self.id);
+    }
+
+    /// Binds the whole buffer to binding point `index`, e.g. for a uniform block.
+    pub fn bind_base(&self, index: u32) {
+        self.ctx
+            .device()
+            .bind_buffer_base(self.target, index, self.id);
     }
 
     pub fn unbind(&self) {
-        unsafe {
-            gl::BindBuffer(self.target, 0);
-        }
+        self.ctx.device().bind_buffer(self.target, 0);
     }
 
+    /// Sources attribute `index` from `size` floats, with stride and offset counted in floats.
     pub fn vertex_input_attrib(&self, index: GLuint, size: GLint, stride: usize, offset: usize) {
-        unsafe {
-            gl::BindBuffer(self.target, self.id);
-            gl::EnableVertexAttribArray(index);
-            gl::VertexAttribPointer(
-                index,
-                size,
-                gl::FLOAT,
-                gl::FALSE,
-                (stride * std::mem::size_of::<GLfloat>()) as GLsizei,
-                (offset * std::mem::size_of::<GLfloat>()) as *const c_void,
-            );
-            gl::BindBuffer(self.target, 0);
-        }
+        let float = std::mem::size_of::<GLfloat>();
+        let device = self.ctx.device();
+        device.bind_buffer(self.target, self.id);
+        device.vertex_attrib(index, size, stride * float, offset * float);
+        device.bind_buffer(self.target, 0);
     }
 }
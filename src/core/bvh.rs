@@ -0,0 +1,357 @@
+use std::ops::Range;
+
+use super::bounds::{Aabb, Ray};
+use super::frustum::{Frustum, Intersection};
+
+/// Most items a leaf holds.
+pub const LEAF_SIZE: usize = 2;
+
+enum Children {
+    Leaf,
+    Inner(usize, usize),
+}
+
+struct BvhNode {
+    bounds: Aabb,
+    /// Items of the whole subtree, which are stored contiguously.
+    items: Range<usize>,
+    children: Children,
+}
+
+/// Bounding volume hierarchy over items with a box each, split by the surface area
+/// heuristic. It does not track the items: rebuild it when they move.
+pub struct Bvh<T> {
+    nodes: Vec<BvhNode>,
+    items: Vec<(T, Aabb)>,
+}
+
+#[allow(dead_code)]
+impl<T: Copy> Bvh<T> {
+    /// Items with empty boxes are left out.
+    pub fn new(items: Vec<(T, Aabb)>) -> Bvh<T> {
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            items: items.into_iter().filter(|(_, b)| !b.is_empty()).collect(),
+        };
+        if !bvh.items.is_empty() {
+            bvh.build(0..bvh.items.len());
+        }
+        bvh
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Box around every item.
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |n| n.bounds)
+    }
+
+    /// Depth of the tree; 0 when empty, 1 for a single leaf.
+    pub fn depth(&self) -> usize {
+        if self.nodes.is_empty() {
+            return 0;
+        }
+        let mut depth = 0;
+        let mut stack = vec![(0, 1)];
+        while let Some((node, level)) = stack.pop() {
+            depth = depth.max(level);
+            if let Children::Inner(left, right) = self.nodes[node].children {
+                stack.push((left, level + 1));
+                stack.push((right, level + 1));
+            }
+        }
+        depth
+    }
+
+    /// Items whose box `frustum` may see, as `Frustum::test_aabb` decides. Subtrees fully
+    /// inside are taken without testing their items.
+    pub fn cull(&self, frustum: &Frustum) -> Vec<T> {
+        let mut visible = Vec::new();
+        let mut stack = if self.nodes.is_empty() {
+            Vec::new()
+        } else {
+            vec![0]
+        };
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let items = self.items[node.items.clone()].iter();
+            match frustum.test_aabb(&node.bounds) {
+                Intersection::Outside => {}
+                Intersection::Inside => visible.extend(items.map(|(item, _)| *item)),
+                Intersection::Intersecting => match node.children {
+                    Children::Leaf => visible.extend(
+                        items
+                            .filter(|(_, b)| frustum.intersects_aabb(b))
+                            .map(|(item, _)| *item),
+                    ),
+                    Children::Inner(left, right) => {
+                        stack.push(right);
+                        stack.push(left);
+                    }
+                },
+            }
+        }
+        visible
+    }
+
+    /// Items whose box `ray` hits, nearest entry first, with the `t` of the entry.
+    pub fn hits(&self, ray: &Ray) -> Vec<(T, f32)> {
+        let mut hits = Vec::new();
+        let mut stack = if self.nodes.is_empty() {
+            Vec::new()
+        } else {
+            vec![0]
+        };
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if ray.intersect_aabb(&node.bounds).is_none() {
+                continue;
+            }
+            match node.children {
+                Children::Leaf => {
+                    for (item, bounds) in self.items[node.items.clone()].iter() {
+                        if let Some(t) = ray.intersect_aabb(bounds) {
+                            hits.push((*item, t));
+                        }
+                    }
+                }
+                Children::Inner(left, right) => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+        hits.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        hits
+    }
+
+    /// Nearest item `hit` reports a hit for, with the `t` it returned. `hit` only sees items
+    /// whose box the ray enters before the nearest hit so far, so it can run the exact test,
+    /// e.g. against triangles.
+    pub fn raycast<F>(&self, ray: &Ray, mut hit: F) -> Option<(T, f32)>
+    where
+        F: FnMut(T) -> Option<f32>,
+    {
+        let mut nearest: Option<(T, f32)> = None;
+        let mut stack: Vec<(usize, f32)> = Vec::new();
+        if let Some(t) = self
+            .nodes
+            .first()
+            .and_then(|n| ray.intersect_aabb(&n.bounds))
+        {
+            stack.push((0, t));
+        }
+        while let Some((index, entry)) = stack.pop() {
+            if nearest.is_some_and(|(_, best)| entry >= best) {
+                continue;
+            }
+            let node = &self.nodes[index];
+            match node.children {
+                Children::Leaf => {
+                    for (item, bounds) in self.items[node.items.clone()].iter() {
+                        let closer = |t: f32| nearest.map_or(true, |(_, best)| t < best);
+                        if !ray.intersect_aabb(bounds).is_some_and(closer) {
+                            continue;
+                        }
+                        if let Some(t) = hit(*item).filter(|&t| closer(t)) {
+                            nearest = Some((*item, t));
+                        }
+                    }
+                }
+                Children::Inner(left, right) => {
+                    let mut children: Vec<(usize, f32)> = [left, right]
+                        .iter()
+                        .filter_map(|&c| {
+                            let t = ray.intersect_aabb(&self.nodes[c].bounds)?;
+                            Some((c, t))
+                        })
+                        .collect();
+                    // The nearer child goes on top of the stack.
+                    children.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+                    stack.extend(children);
+                }
+            }
+        }
+        nearest
+    }
+
+    // Adds the subtree over `range` and returns its node index; its items end up in
+    // `range` in an order that keeps every child's items contiguous.
+    fn build(&mut self, range: Range<usize>) -> usize {
+        let bounds = self.items[range.clone()]
+            .iter()
+            .fold(Aabb::empty(), |b, (_, item)| b.union(item));
+        let index = self.nodes.len();
+        self.nodes.push(BvhNode {
+            bounds,
+            items: range.clone(),
+            children: Children::Leaf,
+        });
+        if range.len() <= LEAF_SIZE {
+            return index;
+        }
+
+        // Sort along the axis the centers spread most on; coincident centers stay a leaf.
+        let centers = self.items[range.clone()]
+            .iter()
+            .fold(Aabb::empty(), |b, (_, item)| b.with_point(&item.center()));
+        let spread = centers.size();
+        let axis = if spread.x >= spread.y && spread.x >= spread.z {
+            0
+        } else if spread.y >= spread.z {
+            1
+        } else {
+            2
+        };
+        if spread[axis] == 0.0 {
+            return index;
+        }
+        self.items[range.clone()]
+            .sort_by(|(_, a), (_, b)| a.center()[axis].partial_cmp(&b.center()[axis]).unwrap());
+
+        // Split where the boxes on both sides, weighted by their item counts, have the
+        // least surface area.
+        let items = &self.items[range.clone()];
+        let mut right_areas = vec![0.0; items.len()];
+        let mut right = Aabb::empty();
+        for i in (1..items.len()).rev() {
+            right = right.union(&items[i].1);
+            right_areas[i] = right.half_area();
+        }
+        let mut left = Aabb::empty();
+        let mut split = 1;
+        let mut best = f32::INFINITY;
+        for (i, right_area) in right_areas.iter().enumerate().skip(1) {
+            left = left.union(&items[i - 1].1);
+            let cost = left.half_area() * i as f32 + right_area * (items.len() - i) as f32;
+            if cost < best {
+                best = cost;
+                split = i;
+            }
+        }
+
+        let middle = range.start + split;
+        let left = self.build(range.start..middle);
+        let right = self.build(middle..range.end);
+        self.nodes[index].children = Children::Inner(left, right);
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra_glm as glm;
+
+    // Deterministic scatter of `count` boxes of assorted sizes in a 40 unit cube.
+    fn boxes(count: usize) -> Vec<(usize, Aabb)> {
+        let mut seed = 12345u32;
+        let mut next = move || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 8) as f32 / (1 << 24) as f32
+        };
+        (0..count)
+            .map(|i| {
+                let center = glm::vec3(next(), next(), next()) * 40.0 - glm::vec3(20.0, 20.0, 20.0);
+                let half = glm::vec3(next(), next(), next()) * 1.5 + glm::vec3(0.1, 0.1, 0.1);
+                (i, Aabb::new(center - half, center + half))
+            })
+            .collect()
+    }
+
+    fn sorted(mut items: Vec<usize>) -> Vec<usize> {
+        items.sort();
+        items
+    }
+
+    #[test]
+    fn builds_a_balanced_tree_around_every_item() {
+        let items = boxes(500);
+        let bvh = Bvh::new(items.clone());
+        assert_eq!(bvh.len(), 500);
+        let all = items
+            .iter()
+            .fold(Aabb::empty(), |b, (_, item)| b.union(item));
+        assert_eq!(bvh.bounds(), all);
+        // A perfectly balanced tree with two items per leaf has 9 levels.
+        assert!(bvh.depth() <= 20, "{}", bvh.depth());
+
+        let empty: Bvh<usize> = Bvh::new(vec![(0, Aabb::empty())]);
+        assert!(empty.is_empty());
+        assert_eq!(empty.depth(), 0);
+        assert!(empty
+            .hits(&Ray::new(glm::Vec3::zeros(), glm::Vec3::x()))
+            .is_empty());
+    }
+
+    #[test]
+    fn culls_like_testing_every_box() {
+        let items = boxes(500);
+        let bvh = Bvh::new(items.clone());
+        let view = glm::look_at_rh(
+            &glm::vec3(-30.0, 5.0, 10.0),
+            &glm::vec3(0.0, 0.0, 0.0),
+            &glm::Vec3::y(),
+        );
+        let frustum = Frustum::from_matrix(&(glm::perspective(1.5, 0.8, 1.0, 45.0) * view));
+
+        let expected: Vec<usize> = items
+            .iter()
+            .filter(|(_, b)| frustum.intersects_aabb(b))
+            .map(|(i, _)| *i)
+            .collect();
+        assert!(!expected.is_empty() && expected.len() < items.len());
+        assert_eq!(sorted(bvh.cull(&frustum)), expected);
+    }
+
+    #[test]
+    fn raycasts_find_the_nearest_hit() {
+        let items = boxes(500);
+        let bvh = Bvh::new(items.clone());
+        for k in 0..20 {
+            let angle = k as f32 * 0.3;
+            let ray = Ray::new(
+                glm::vec3(
+                    angle.cos() * 30.0,
+                    (k as f32 - 10.0) * 1.5,
+                    angle.sin() * 30.0,
+                ),
+                glm::vec3(-angle.cos(), 0.05, -angle.sin()),
+            );
+            let expected: Vec<(usize, f32)> = {
+                let mut hits: Vec<(usize, f32)> = items
+                    .iter()
+                    .filter_map(|(i, b)| ray.intersect_aabb(b).map(|t| (*i, t)))
+                    .collect();
+                hits.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+                hits
+            };
+            let hits = bvh.hits(&ray);
+            assert_eq!(
+                sorted(hits.iter().map(|h| h.0).collect()),
+                sorted(expected.iter().map(|h| h.0).collect())
+            );
+            assert!(hits.windows(2).all(|w| w[0].1 <= w[1].1));
+
+            // Odd items count as misses, as an exact test rejecting them would.
+            let nearest_even = expected.iter().find(|(i, _)| i % 2 == 0).cloned();
+            let mut tested = 0;
+            let nearest = bvh.raycast(&ray, |i| {
+                tested += 1;
+                if i % 2 == 0 {
+                    ray.intersect_aabb(&items[i].1)
+                } else {
+                    None
+                }
+            });
+            assert_eq!(nearest.map(|n| n.1), nearest_even.map(|n| n.1));
+            assert!(tested <= expected.len());
+        }
+    }
+}
@@ -0,0 +1,621 @@
+use imgui_glfw_rs::glfw;
+use nalgebra_glm as glm;
+
+use super::bounds::Ray;
+use super::input::Input;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Vertical field of view in radians.
+    Perspective { fovy: f32, near: f32, far: f32 },
+    /// Visible height in world units; the width follows the aspect ratio.
+    Orthographic { height: f32, near: f32, far: f32 },
+}
+
+/// Position, orientation and projection of a viewer.
+/// The camera looks down its local -Z axis with +Y up.
+#[derive(Debug, Clone)]
+pub struct Camera {
+    position: glm::Vec3,
+    orientation: glm::Quat,
+    projection: Projection,
+    aspect: f32,
+    view: glm::Mat4,
+    proj: glm::Mat4,
+}
+
+#[allow(dead_code)]
+impl Camera {
+    pub fn new(projection: Projection, width: u32, height: u32) -> Camera {
+        let mut camera = Camera {
+            position: glm::vec3(0.0, 0.0, 0.0),
+            orientation: glm::quat_identity(),
+            projection,
+            aspect: 1.0,
+            view: glm::Mat4::identity(),
+            proj: glm::Mat4::identity(),
+        };
+        camera.resize(width, height);
+        camera.update_view();
+        camera
+    }
+
+    /// Perspective camera with `fovy` in degrees.
+    pub fn perspective(fovy: f32, near: f32, far: f32, width: u32, height: u32) -> Camera {
+        Camera::new(
+            Projection::Perspective {
+                fovy: fovy.to_radians(),
+                near,
+                far,
+            },
+            width,
+            height,
+        )
+    }
+
+    pub fn orthographic(view_height: f32, near: f32, far: f32, width: u32, height: u32) -> Camera {
+        Camera::new(
+            Projection::Orthographic {
+                height: view_height,
+                near,
+                far,
+            },
+            width,
+            height,
+        )
+    }
+
+    /// Rebuilds the projection for a new framebuffer size. Call from `App::resized`.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            // Minimized window; keep the last projection.
+            return;
+        }
+        self.aspect = width as f32 / height as f32;
+        self.update_projection();
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+        self.update_projection();
+    }
+
+    pub fn projection_params(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+
+    pub fn position(&self) -> glm::Vec3 {
+        self.position
+    }
+
+    pub fn orientation(&self) -> glm::Quat {
+        self.orientation
+    }
+
+    pub fn set_position(&mut self, position: glm::Vec3) {
+        self.position = position;
+        self.update_view();
+    }
+
+    pub fn set_orientation(&mut self, orientation: glm::Quat) {
+        self.orientation = glm::quat_normalize(&orientation);
+        self.update_view();
+    }
+
+    pub fn look_at(&mut self, eye: &glm::Vec3, target: &glm::Vec3, up: &glm::Vec3) {
+        self.position = *eye;
+        // quat_look_at_rh is the view rotation; the camera orientation is its inverse.
+        let view = glm::quat_look_at_rh(&(target - eye), up);
+        self.orientation = glm::quat_normalize(&glm::quat_inverse(&view));
+        self.update_view();
+    }
+
+    pub fn forward(&self) -> glm::Vec3 {
+        glm::quat_rotate_vec3(&self.orientation, &glm::vec3(0.0, 0.0, -1.0))
+    }
+
+    pub fn right(&self) -> glm::Vec3 {
+        glm::quat_rotate_vec3(&self.orientation, &glm::vec3(1.0, 0.0, 0.0))
+    }
+
+    pub fn up(&self) -> glm::Vec3 {
+        glm::quat_rotate_vec3(&self.orientation, &glm::vec3(0.0, 1.0, 0.0))
+    }
+
+    pub fn view(&self) -> &glm::Mat4 {
+        &self.view
+    }
+
+    pub fn projection(&self) -> &glm::Mat4 {
+        &self.proj
+    }
+
+    pub fn view_projection(&self) -> glm::Mat4 {
+        self.proj * self.view
+    }
+
+    /// World space ray through `cursor`, in pixels with the origin at the top-left of a
+    /// `viewport` sized framebuffer. It starts on the near plane and has a unit direction.
+    pub fn ray(&self, cursor: &glm::Vec2, viewport: &glm::Vec2) -> Ray {
+        let x = 2.0 * cursor.x / viewport.x.max(1.0) - 1.0;
+        let y = 1.0 - 2.0 * cursor.y / viewport.y.max(1.0);
+        let inverse = glm::inverse(&self.view_projection());
+        let unproject = |z: f32| {
+            let p = inverse * glm::vec4(x, y, z, 1.0);
+            p.xyz() / p.w
+        };
+        let (near, far) = (unproject(-1.0), unproject(1.0));
+        Ray::new(near, (far - near).normalize())
+    }
+
+    fn update_view(&mut self) {
+        let center = self.position + self.forward();
+        self.view = glm::look_at_rh(&self.position, &center, &self.up());
+    }
+
+    fn update_projection(&mut self) {
+        self.proj = match self.projection {
+            Projection::Perspective { fovy, near, far } => {
+                glm::perspective(self.aspect, fovy, near, far)
+            }
+            Projection::Orthographic { height, near, far } => {
+                let (hw, hh) = (height * self.aspect * 0.5, height * 0.5);
+                glm::ortho(-hw, hw, -hh, hh, near, far)
+            }
+        };
+    }
+}
+
+/// Per-frame input consumed by the camera controllers, decoupled from the window system.
+#[derive(Debug, Clone, Copy)]
+pub struct ControllerInput {
+    /// Movement intent along the camera's right, up and forward axes, each in [-1, 1].
+    pub movement: glm::Vec3,
+    /// Cursor movement in pixels while looking/rotating.
+    pub look: glm::Vec2,
+    /// Cursor movement in pixels while panning.
+    pub pan: glm::Vec2,
+    /// Scroll amount; positive zooms in.
+    pub zoom: f32,
+    /// Current cursor position in pixels, origin at the top-left.
+    pub cursor: glm::Vec2,
+    /// Framebuffer size in pixels.
+    pub viewport: glm::Vec2,
+    /// Seconds since the last update.
+    pub dt: f32,
+}
+
+impl Default for ControllerInput {
+    fn default() -> Self {
+        ControllerInput {
+            movement: glm::vec3(0.0, 0.0, 0.0),
+            look: glm::vec2(0.0, 0.0),
+            pan: glm::vec2(0.0, 0.0),
+            zoom: 0.0,
+            cursor: glm::vec2(0.0, 0.0),
+            viewport: glm::vec2(1.0, 1.0),
+            dt: 0.0,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl ControllerInput {
+    /// Default bindings: WASD to move, Q/E down/up, right mouse to look/rotate,
+    /// middle mouse to pan and the wheel to zoom.
+    pub fn from_input(input: &Input, width: u32, height: u32, dt: f32) -> ControllerInput {
+        let axis = |neg: glfw::Key, pos: glfw::Key| {
+            (input.key_down(pos) as i32 - input.key_down(neg) as i32) as f32
+        };
+        let delta = input.cursor_delta();
+        let zero = glm::vec2(0.0, 0.0);
+        ControllerInput {
+            movement: glm::vec3(
+                axis(glfw::Key::A, glfw::Key::D),
+                axis(glfw::Key::Q, glfw::Key::E),
+                axis(glfw::Key::S, glfw::Key::W),
+            ),
+            look: if input.mouse_down(glfw::MouseButton::Button2) {
+                delta
+            } else {
+                zero
+            },
+            pan: if input.mouse_down(glfw::MouseButton::Button3) {
+                delta
+            } else {
+                zero
+            },
+            zoom: input.scroll().y,
+            cursor: input.cursor(),
+            viewport: glm::vec2(width as f32, height as f32),
+            dt,
+        }
+    }
+}
+
+/// First-person fly-through: WASD-style movement and mouse look.
+#[derive(Debug, Clone)]
+pub struct FlyController {
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Units per second.
+    pub speed: f32,
+    /// Radians per pixel.
+    pub sensitivity: f32,
+}
+
+#[allow(dead_code)]
+impl FlyController {
+    const PITCH_LIMIT: f32 = 89.0 * std::f32::consts::PI / 180.0;
+
+    pub fn new(speed: f32, sensitivity: f32) -> FlyController {
+        FlyController {
+            yaw: 0.0,
+            pitch: 0.0,
+            speed,
+            sensitivity,
+        }
+    }
+
+    /// Picks up yaw and pitch from the camera's current orientation.
+    pub fn from_camera(camera: &Camera, speed: f32, sensitivity: f32) -> FlyController {
+        let f = camera.forward();
+        FlyController {
+            yaw: (-f.x).atan2(-f.z),
+            pitch: f.y.clamp(-1.0, 1.0).asin(),
+            speed,
+            sensitivity,
+        }
+    }
+
+    pub fn update(&mut self, camera: &mut Camera, input: &ControllerInput) {
+        self.yaw -= input.look.x * self.sensitivity;
+        self.pitch = (self.pitch - input.look.y * self.sensitivity)
+            .clamp(-Self::PITCH_LIMIT, Self::PITCH_LIMIT);
+
+        let orientation = yaw_pitch(self.yaw, self.pitch);
+        camera.set_orientation(orientation);
+
+        let m = input.movement;
+        if m != glm::Vec3::zeros() {
+            let up = glm::Vec3::y();
+            let delta = camera.right() * m.x + up * m.y + camera.forward() * m.z;
+            let len = glm::length(&delta);
+            if len > 1.0 {
+                camera.set_position(camera.position() + delta / len * self.speed * input.dt);
+            } else {
+                camera.set_position(camera.position() + delta * self.speed * input.dt);
+            }
+        }
+    }
+}
+
+/// Turntable orbit around a target: drag rotates, scroll zooms, pan moves the target.
+#[derive(Debug, Clone)]
+pub struct OrbitController {
+    pub target: glm::Vec3,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Radians per pixel.
+    pub sensitivity: f32,
+    /// Fraction of the distance per scroll step.
+    pub zoom_speed: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+}
+
+#[allow(dead_code)]
+impl OrbitController {
+    const PITCH_LIMIT: f32 = 89.0 * std::f32::consts::PI / 180.0;
+
+    pub fn new(target: glm::Vec3, distance: f32) -> OrbitController {
+        OrbitController {
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            sensitivity: 0.005,
+            zoom_speed: 0.1,
+            min_distance: 0.01,
+            max_distance: f32::MAX,
+        }
+    }
+
+    pub fn update(&mut self, camera: &mut Camera, input: &ControllerInput) {
+        self.yaw -= input.look.x * self.sensitivity;
+        self.pitch = (self.pitch - input.look.y * self.sensitivity)
+            .clamp(-Self::PITCH_LIMIT, Self::PITCH_LIMIT);
+        self.distance = (self.distance * (1.0 - self.zoom_speed).powf(input.zoom))
+            .clamp(self.min_distance, self.max_distance);
+
+        let orientation = yaw_pitch(self.yaw, self.pitch);
+        if input.pan != glm::Vec2::zeros() {
+            // Move the target so the point under the cursor follows it.
+            let scale = self.distance / input.viewport.y.max(1.0);
+            let right = glm::quat_rotate_vec3(&orientation, &glm::Vec3::x());
+            let up = glm::quat_rotate_vec3(&orientation, &glm::Vec3::y());
+            self.target += (-right * input.pan.x + up * input.pan.y) * scale;
+        }
+
+        let back = glm::quat_rotate_vec3(&orientation, &glm::Vec3::z());
+        camera.set_orientation(orientation);
+        camera.set_position(self.target + back * self.distance);
+    }
+}
+
+/// Arcball rotation: the cursor drags a virtual sphere around the target.
+#[derive(Debug, Clone)]
+pub struct ArcballController {
+    pub target: glm::Vec3,
+    pub distance: f32,
+    pub rotation: glm::Quat,
+    pub zoom_speed: f32,
+}
+
+#[allow(dead_code)]
+impl ArcballController {
+    pub fn new(target: glm::Vec3, distance: f32) -> ArcballController {
+        ArcballController {
+            target,
+            distance,
+            rotation: glm::quat_identity(),
+            zoom_speed: 0.1,
+        }
+    }
+
+    /// Maps a cursor position onto the unit arcball (Shoemake/Holroyd).
+    pub fn project(cursor: &glm::Vec2, viewport: &glm::Vec2) -> glm::Vec3 {
+        let size = viewport.x.min(viewport.y).max(1.0);
+        let x = (2.0 * cursor.x - viewport.x) / size;
+        let y = (viewport.y - 2.0 * cursor.y) / size;
+        let d2 = x * x + y * y;
+        if d2 <= 0.5 {
+            glm::vec3(x, y, (1.0 - d2).sqrt())
+        } else {
+            glm::normalize(&glm::vec3(x, y, 0.5 / d2.sqrt()))
+        }
+    }
+
+    pub fn update(&mut self, camera: &mut Camera, input: &ControllerInput) {
+        if input.look != glm::Vec2::zeros() {
+            let prev = ArcballController::project(&(input.cursor - input.look), &input.viewport);
+            let curr = ArcballController::project(&input.cursor, &input.viewport);
+            // Dragging rotates the object; the camera rotates the opposite way.
+            let drag = glm::quat_rotation(&curr, &prev);
+            self.rotation = glm::quat_normalize(&(self.rotation * drag));
+        }
+        self.distance *= (1.0 - self.zoom_speed).powf(input.zoom);
+
+        let back = glm::quat_rotate_vec3(&self.rotation, &glm::Vec3::z());
+        camera.set_orientation(self.rotation);
+        camera.set_position(self.target + back * self.distance);
+    }
+}
+
+/// 2D pan and zoom for orthographic cameras. Zoom keeps the point under the cursor fixed.
+#[derive(Debug, Clone)]
+pub struct PanZoomController {
+    pub zoom_speed: f32,
+    pub min_height: f32,
+    pub max_height: f32,
+}
+
+#[allow(dead_code)]
+impl PanZoomController {
+    pub fn new() -> PanZoomController {
+        PanZoomController {
+            zoom_speed: 0.1,
+            min_height: 0.01,
+            max_height: f32::MAX,
+        }
+    }
+
+    pub fn update(&self, camera: &mut Camera, input: &ControllerInput) {
+        let (height, near, far) = match camera.projection_params() {
+            Projection::Orthographic { height, near, far } => (height, near, far),
+            Projection::Perspective { .. } => return,
+        };
+        let viewport = input.viewport;
+        let units_per_pixel = height / viewport.y.max(1.0);
+
+        let mut position = camera.position();
+        position += (-camera.right() * input.pan.x + camera.up() * input.pan.y) * units_per_pixel;
+
+        let new_height = (height * (1.0 - self.zoom_speed).powf(input.zoom))
+            .clamp(self.min_height, self.max_height);
+        if new_height != height {
+            // Offset of the cursor from the view center, in pixels with y up.
+            let offset = glm::vec2(
+                input.cursor.x - viewport.x * 0.5,
+                viewport.y * 0.5 - input.cursor.y,
+            );
+            let shift = offset * (units_per_pixel - new_height / viewport.y.max(1.0));
+            position += camera.right() * shift.x + camera.up() * shift.y;
+            camera.set_projection(Projection::Orthographic {
+                height: new_height,
+                near,
+                far,
+            });
+        }
+        camera.set_position(position);
+    }
+}
+
+fn yaw_pitch(yaw: f32, pitch: f32) -> glm::Quat {
+    let q = glm::quat_angle_axis(yaw, &glm::Vec3::y());
+    glm::quat_normalize(&glm::quat_rotate(&q, pitch, &glm::Vec3::x()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: &glm::Vec3, b: &glm::Vec3) {
+        assert!(glm::distance(a, b) < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    fn input() -> ControllerInput {
+        ControllerInput {
+            viewport: glm::vec2(800.0, 600.0),
+            cursor: glm::vec2(400.0, 300.0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn look_at_matches_glm() {
+        let mut camera = Camera::perspective(60.0, 0.1, 100.0, 1280, 720);
+        let eye = glm::vec3(0.0, 0.0, -2.5);
+        camera.look_at(&eye, &glm::vec3(0.0, 0.0, 0.0), &glm::Vec3::y());
+
+        let expected = glm::look_at_rh(&eye, &glm::vec3(0.0, 0.0, 0.0), &glm::Vec3::y());
+        assert!((camera.view() - expected).abs().max() < 1e-5);
+        assert_near(&camera.forward(), &glm::vec3(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn rays_start_on_the_near_plane_under_the_cursor() {
+        let mut camera = Camera::perspective(90.0, 0.5, 100.0, 800, 600);
+        camera.look_at(
+            &glm::vec3(1.0, 2.0, 3.0),
+            &glm::vec3(1.0, 2.0, -3.0),
+            &glm::Vec3::y(),
+        );
+        let viewport = glm::vec2(800.0, 600.0);
+        let center = camera.ray(&glm::vec2(400.0, 300.0), &viewport);
+        assert_near(&center.origin, &glm::vec3(1.0, 2.0, 2.5));
+        assert_near(&center.direction, &camera.forward());
+
+        // The top edge is 45 degrees up; the left edge further out by the aspect ratio.
+        let top = camera.ray(&glm::vec2(400.0, 0.0), &viewport);
+        assert_near(&top.direction, &glm::vec3(0.0, 1.0, -1.0).normalize());
+        let left = camera.ray(&glm::vec2(0.0, 300.0), &viewport);
+        assert_near(
+            &left.direction,
+            &glm::vec3(-4.0 / 3.0, 0.0, -1.0).normalize(),
+        );
+
+        let ortho = Camera::orthographic(4.0, -1.0, 1.0, 400, 400);
+        let corner = ortho.ray(&glm::vec2(400.0, 400.0), &glm::vec2(400.0, 400.0));
+        assert_near(&corner.origin, &glm::vec3(2.0, -2.0, 1.0));
+        assert_near(&corner.direction, &glm::vec3(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn resize_rebuilds_projection() {
+        let mut camera = Camera::perspective(60.0, 0.1, 100.0, 800, 600);
+        camera.resize(1920, 1080);
+        let expected = glm::perspective(1920.0 / 1080.0, 60f32.to_radians(), 0.1, 100.0);
+        assert!((camera.projection() - expected).abs().max() < 1e-5);
+
+        camera.resize(0, 0);
+        assert!((camera.projection() - expected).abs().max() < 1e-5);
+    }
+
+    #[test]
+    fn fly_moves_along_view_axes() {
+        let mut camera = Camera::perspective(60.0, 0.1, 100.0, 800, 600);
+        let mut fly = FlyController::new(2.0, 0.01);
+
+        let mut forward = input();
+        forward.movement = glm::vec3(0.0, 0.0, 1.0);
+        forward.dt = 0.5;
+        fly.update(&mut camera, &forward);
+        assert_near(&camera.position(), &glm::vec3(0.0, 0.0, -1.0));
+
+        // Turn left by 90 degrees, then strafe right.
+        let mut turn = input();
+        turn.look = glm::vec2(-std::f32::consts::FRAC_PI_2 / 0.01, 0.0);
+        fly.update(&mut camera, &turn);
+        assert_near(&camera.forward(), &glm::vec3(-1.0, 0.0, 0.0));
+
+        let mut strafe = input();
+        strafe.movement = glm::vec3(1.0, 0.0, 0.0);
+        strafe.dt = 1.0;
+        fly.update(&mut camera, &strafe);
+        assert_near(&camera.position(), &glm::vec3(0.0, 0.0, -3.0));
+    }
+
+    #[test]
+    fn fly_clamps_pitch() {
+        let mut camera = Camera::perspective(60.0, 0.1, 100.0, 800, 600);
+        let mut fly = FlyController::new(1.0, 0.01);
+        let mut look = input();
+        look.look = glm::vec2(0.0, -10000.0);
+        fly.update(&mut camera, &look);
+        assert!(fly.pitch <= FlyController::PITCH_LIMIT);
+        assert!(camera.forward().y > 0.99);
+    }
+
+    #[test]
+    fn orbit_keeps_distance_and_faces_target() {
+        let mut camera = Camera::perspective(60.0, 0.1, 100.0, 800, 600);
+        let target = glm::vec3(1.0, 2.0, 3.0);
+        let mut orbit = OrbitController::new(target, 5.0);
+
+        let mut drag = input();
+        drag.look = glm::vec2(120.0, -45.0);
+        orbit.update(&mut camera, &drag);
+        assert!((glm::distance(&camera.position(), &target) - 5.0).abs() < 1e-4);
+        let to_target = glm::normalize(&(target - camera.position()));
+        assert_near(&camera.forward(), &to_target);
+
+        let mut zoom = input();
+        zoom.zoom = 1.0;
+        orbit.update(&mut camera, &zoom);
+        assert!((orbit.distance - 4.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn arcball_center_drag_rotates_about_up() {
+        let mut camera = Camera::perspective(60.0, 0.1, 100.0, 800, 600);
+        let mut arcball = ArcballController::new(glm::vec3(0.0, 0.0, 0.0), 3.0);
+        arcball.update(&mut camera, &input());
+        assert_near(&camera.position(), &glm::vec3(0.0, 0.0, 3.0));
+
+        // Dragging right spins the object right, so the camera swings to the left.
+        let mut drag = input();
+        drag.cursor = glm::vec2(500.0, 300.0);
+        drag.look = glm::vec2(100.0, 0.0);
+        arcball.update(&mut camera, &drag);
+        let p = camera.position();
+        assert!(p.x < -0.1 && p.y.abs() < 1e-4);
+        assert!((glm::length(&p) - 3.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn pan_zoom_keeps_cursor_point() {
+        let mut camera = Camera::orthographic(10.0, -1.0, 1.0, 800, 600);
+        let controller = PanZoomController::new();
+
+        // World point under the cursor before zooming.
+        let cursor = glm::vec2(600.0, 150.0);
+        let world = |camera: &Camera| {
+            let h = match camera.projection_params() {
+                Projection::Orthographic { height, .. } => height,
+                _ => unreachable!(),
+            };
+            let upp = h / 600.0;
+            camera.position().xy() + glm::vec2((cursor.x - 400.0) * upp, (300.0 - cursor.y) * upp)
+        };
+        let before = world(&camera);
+
+        let mut zoom = input();
+        zoom.cursor = cursor;
+        zoom.zoom = 3.0;
+        controller.update(&mut camera, &zoom);
+        let after = world(&camera);
+        assert!(glm::distance(&before, &after) < 1e-4);
+
+        let mut pan = input();
+        pan.pan = glm::vec2(60.0, 0.0);
+        let x = camera.position().x;
+        controller.update(&mut camera, &pan);
+        assert!(camera.position().x < x);
+    }
+}
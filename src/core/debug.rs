@@ -0,0 +1,363 @@
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+
+use gl::types::*;
+
+use super::device::GraphicsDevice;
+use super::resource::ContextHandle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Source {
+    Api,
+    WindowSystem,
+    ShaderCompiler,
+    ThirdParty,
+    Application,
+    Other,
+}
+
+impl Source {
+    pub fn from_gl(source: GLenum) -> Source {
+        match source {
+            gl::DEBUG_SOURCE_API => Source::Api,
+            gl::DEBUG_SOURCE_WINDOW_SYSTEM => Source::WindowSystem,
+            gl::DEBUG_SOURCE_SHADER_COMPILER => Source::ShaderCompiler,
+            gl::DEBUG_SOURCE_THIRD_PARTY => Source::ThirdParty,
+            gl::DEBUG_SOURCE_APPLICATION => Source::Application,
+            _ => Source::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Type {
+    Error,
+    DeprecatedBehavior,
+    UndefinedBehavior,
+    Portability,
+    Performance,
+    Marker,
+    PushGroup,
+    PopGroup,
+    Other,
+}
+
+impl Type {
+    pub fn from_gl(ty: GLenum) -> Type {
+        match ty {
+            gl::DEBUG_TYPE_ERROR => Type::Error,
+            gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => Type::DeprecatedBehavior,
+            gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => Type::UndefinedBehavior,
+            gl::DEBUG_TYPE_PORTABILITY => Type::Portability,
+            gl::DEBUG_TYPE_PERFORMANCE => Type::Performance,
+            gl::DEBUG_TYPE_MARKER => Type::Marker,
+            gl::DEBUG_TYPE_PUSH_GROUP => Type::PushGroup,
+            gl::DEBUG_TYPE_POP_GROUP => Type::PopGroup,
+            _ => Type::Other,
+        }
+    }
+}
+
+/// Ordered from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Notification,
+    Low,
+    Medium,
+    High,
+}
+
+impl Severity {
+    pub fn from_gl(severity: GLenum) -> Severity {
+        match severity {
+            gl::DEBUG_SEVERITY_HIGH => Severity::High,
+            gl::DEBUG_SEVERITY_MEDIUM => Severity::Medium,
+            gl::DEBUG_SEVERITY_LOW => Severity::Low,
+            _ => Severity::Notification,
+        }
+    }
+}
+
+/// Decides which driver messages reach the log.
+#[derive(Debug, Clone)]
+pub struct DebugFilter {
+    pub min_severity: Severity,
+    pub sources: Vec<Source>,
+    pub types: Vec<Type>,
+    /// Message ids that are never logged, e.g. known driver chatter.
+    pub ignored_ids: Vec<u32>,
+}
+
+impl Default for DebugFilter {
+    fn default() -> Self {
+        DebugFilter {
+            min_severity: Severity::Low,
+            sources: vec![
+                Source::Api,
+                Source::WindowSystem,
+                Source::ShaderCompiler,
+                Source::ThirdParty,
+                Source::Application,
+                Source::Other,
+            ],
+            types: vec![
+                Type::Error,
+                Type::DeprecatedBehavior,
+                Type::UndefinedBehavior,
+                Type::Portability,
+                Type::Performance,
+                Type::Other,
+            ],
+            ignored_ids: Vec::new(),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl DebugFilter {
+    pub fn allows(&self, source: Source, ty: Type, severity: Severity, id: u32) -> bool {
+        // Errors are always reported, whatever their severity.
+        (ty == Type::Error || severity >= self.min_severity)
+            && self.sources.contains(&source)
+            && self.types.contains(&ty)
+            && !self.ignored_ids.contains(&id)
+    }
+
+    /// Log level a message is reported at.
+    pub fn level(ty: Type, severity: Severity) -> log::Level {
+        match (ty, severity) {
+            (Type::Error, _) | (_, Severity::High) => log::Level::Error,
+            (_, Severity::Medium) => log::Level::Warn,
+            (_, Severity::Low) => log::Level::Info,
+            (_, Severity::Notification) => log::Level::Debug,
+        }
+    }
+}
+
+extern "system" fn debug_callback(
+    source: GLenum,
+    ty: GLenum,
+    id: GLuint,
+    severity: GLenum,
+    _length: GLsizei,
+    message: *const GLchar,
+    user_param: *mut c_void,
+) {
+    // Safety: `user_param` points to the filter boxed in `Debugger`, which
+    // unregisters the callback before dropping it.
+    let filter = unsafe { &*(user_param as *const DebugFilter) };
+    let (source, ty, severity) = (
+        Source::from_gl(source),
+        Type::from_gl(ty),
+        Severity::from_gl(severity),
+    );
+    if !filter.allows(source, ty, severity, id) {
+        return;
+    }
+    let message = unsafe { CStr::from_ptr(message as *const c_char) }.to_string_lossy();
+    log::log!(
+        target: "gl",
+        DebugFilter::level(ty, severity),
+        "[{:?}/{:?}/{:?} #{}] {}",
+        source,
+        ty,
+        severity,
+        id,
+        message.trim_end()
+    );
+}
+
+/// Driver message reporting for a context: KHR_debug when available, `glGetError` otherwise.
+pub struct Debugger {
+    ctx: ContextHandle,
+    filter: Option<Box<DebugFilter>>,
+}
+
+impl Drop for Debugger {
+    fn drop(&mut self) {
+        if self.filter.is_some() && self.ctx.is_alive() {
+            self.ctx
+                .device()
+                .debug_message_callback(None, std::ptr::null());
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Debugger {
+    /// Installs the debug callback if `ctx` supports KHR_debug.
+    pub fn new(ctx: &ContextHandle, filter: DebugFilter) -> Debugger {
+        let ctx = ctx.clone();
+        if !ctx.device().has_debug_output() {
+            log::info!(target: "gl", "KHR_debug unavailable, falling back to glGetError");
+            return Debugger { ctx, filter: None };
+        }
+        let filter = Box::new(filter);
+        ctx.device().debug_message_callback(
+            Some(debug_callback),
+            &*filter as *const DebugFilter as *const c_void,
+        );
+        Debugger {
+            ctx,
+            filter: Some(filter),
+        }
+    }
+
+    pub fn uses_callback(&self) -> bool {
+        self.filter.is_some()
+    }
+
+    /// Logs pending `glGetError` codes when no debug callback is installed.
+    pub fn check(&self, context: &str) {
+        if self.filter.is_none() {
+            for err in drain_errors(self.ctx.device()) {
+                log::error!(target: "gl", "{} after {}", error_name(err), context);
+            }
+        }
+    }
+}
+
+/// Pops every pending error off the GL error queue.
+pub fn drain_errors(device: &dyn GraphicsDevice) -> Vec<GLenum> {
+    let mut errors = Vec::new();
+    loop {
+        let err = device.get_error();
+        // Without a context GetError keeps returning the same code.
+        if err == gl::NO_ERROR || errors.len() >= 16 {
+            break;
+        }
+        errors.push(err);
+    }
+    errors
+}
+
+pub fn error_name(err: GLenum) -> &'static str {
+    match err {
+        gl::INVALID_ENUM => "GL_INVALID_ENUM",
+        gl::INVALID_VALUE => "GL_INVALID_VALUE",
+        gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+        gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+        gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+        gl::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
+        gl::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
+        _ => "unknown GL error",
+    }
+}
+
+/// Debug group that stays open until dropped.
+pub struct DebugGroup {
+    ctx: ContextHandle,
+}
+
+impl Drop for DebugGroup {
+    fn drop(&mut self) {
+        if self.ctx.is_alive() {
+            self.ctx.device().pop_debug_group();
+        }
+    }
+}
+
+/// Opens a named debug group in `ctx`, shown as a scope in tools like RenderDoc.
+pub fn group(ctx: &ContextHandle, name: &str) -> DebugGroup {
+    ctx.device().push_debug_group(name);
+    DebugGroup { ctx: ctx.clone() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::device::RecordingDevice;
+    use crate::core::resource::ContextOwner;
+
+    #[test]
+    fn filters_by_severity_source_and_type() {
+        let mut filter = DebugFilter::default();
+        assert!(filter.allows(Source::Api, Type::Performance, Severity::Low, 1));
+        assert!(!filter.allows(Source::Api, Type::Other, Severity::Notification, 1));
+        assert!(!filter.allows(Source::Application, Type::PushGroup, Severity::High, 1));
+        // Errors bypass the severity threshold.
+        filter.min_severity = Severity::High;
+        assert!(filter.allows(Source::Api, Type::Error, Severity::Low, 1));
+        assert!(!filter.allows(Source::Api, Type::Portability, Severity::Medium, 1));
+
+        filter.sources = vec![Source::ShaderCompiler];
+        assert!(!filter.allows(Source::Api, Type::Error, Severity::High, 1));
+        filter.ignored_ids.push(7);
+        assert!(!filter.allows(Source::ShaderCompiler, Type::Error, Severity::High, 7));
+    }
+
+    #[test]
+    fn installs_and_removes_the_callback() {
+        let device = RecordingDevice::new();
+        let owner = ContextOwner::with_device(Box::new(device.clone()));
+        let debugger = Debugger::new(owner.handle(), DebugFilter::default());
+        assert!(!debugger.uses_callback());
+        drop(debugger);
+        assert!(device.calls_to("DebugMessageCallback").is_empty());
+
+        device.set_debug_output(true);
+        let debugger = Debugger::new(owner.handle(), DebugFilter::default());
+        assert!(debugger.uses_callback());
+        drop(debugger);
+        assert_eq!(
+            device.calls_to("DebugMessageCallback"),
+            vec![
+                "DebugMessageCallback(callback)",
+                "DebugMessageCallback(null)"
+            ]
+        );
+    }
+
+    #[test]
+    fn drains_errors_and_nests_groups() {
+        let device = RecordingDevice::new();
+        let owner = ContextOwner::with_device(Box::new(device.clone()));
+        device.push_error(gl::INVALID_ENUM);
+        device.push_error(gl::OUT_OF_MEMORY);
+        assert_eq!(
+            drain_errors(&device),
+            vec![gl::INVALID_ENUM, gl::OUT_OF_MEMORY]
+        );
+        assert!(drain_errors(&device).is_empty());
+
+        {
+            let _outer = group(owner.handle(), "frame");
+            let _inner = group(owner.handle(), "pass");
+        }
+        assert_eq!(
+            device.take(),
+            vec![
+                "PushDebugGroup(\"frame\")",
+                "PushDebugGroup(\"pass\")",
+                "PopDebugGroup()",
+                "PopDebugGroup()"
+            ]
+        );
+    }
+
+    #[test]
+    fn maps_gl_enums_and_levels() {
+        assert_eq!(
+            Source::from_gl(gl::DEBUG_SOURCE_SHADER_COMPILER),
+            Source::ShaderCompiler
+        );
+        assert_eq!(Type::from_gl(gl::DEBUG_TYPE_POP_GROUP), Type::PopGroup);
+        assert_eq!(
+            Severity::from_gl(gl::DEBUG_SEVERITY_NOTIFICATION),
+            Severity::Notification
+        );
+        assert!(Severity::High > Severity::Medium);
+        assert_eq!(
+            DebugFilter::level(Type::Error, Severity::Low),
+            log::Level::Error
+        );
+        assert_eq!(
+            DebugFilter::level(Type::Performance, Severity::Medium),
+            log::Level::Warn
+        );
+        assert_eq!(
+            DebugFilter::level(Type::Marker, Severity::Notification),
+            log::Level::Debug
+        );
+    }
+}
@@ -0,0 +1,582 @@
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::f32::consts::PI;
+
+use gl::types::GLenum;
+use nalgebra_glm as glm;
+
+use super::camera::Camera;
+use super::framebuffer::Framebuffer;
+use super::framework::Context;
+use super::frustum::Frustum;
+use super::geometry;
+use super::lighting::{Light, LightBlock, LightingBuffer, MAX_LIGHTS};
+use super::mesh::Mesh;
+use super::scene::{NodeId, Scene};
+use super::shader::Shader;
+use super::state::{Blend, Face, RenderState};
+use super::texture::Texture2D;
+use super::vertex::VertexArray;
+
+const FULLSCREEN_VS: &str = "res/glsl/fullscreen.vs.glsl";
+
+// Light volume spheres are latitude-longitude grids this fine.
+const VOLUME_SEGMENTS: u32 = 16;
+const VOLUME_RINGS: u32 = 8;
+
+/// What `DeferredRenderer::present` shows: the lit frame or one G-buffer channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GBufferView {
+    Lit,
+    Albedo,
+    Normal,
+    Specular,
+    Shininess,
+    Depth,
+}
+
+#[allow(dead_code)]
+impl GBufferView {
+    /// Every view, in `View` uniform order.
+    pub const ALL: [GBufferView; 6] = [
+        GBufferView::Lit,
+        GBufferView::Albedo,
+        GBufferView::Normal,
+        GBufferView::Specular,
+        GBufferView::Shininess,
+        GBufferView::Depth,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GBufferView::Lit => "Lit",
+            GBufferView::Albedo => "Albedo",
+            GBufferView::Normal => "Normal",
+            GBufferView::Specular => "Specular",
+            GBufferView::Shininess => "Shininess",
+            GBufferView::Depth => "Depth",
+        }
+    }
+
+    pub fn index(self) -> usize {
+        GBufferView::ALL.iter().position(|&v| v == self).unwrap()
+    }
+}
+
+/// Surface attributes of the visible opaque geometry, one texel per pixel:
+///
+/// | attachment          | format       | contents                                  |
+/// |---------------------|--------------|-------------------------------------------|
+/// | `COLOR_ATTACHMENT0` | `RGBA8`      | diffuse color                             |
+/// | `COLOR_ATTACHMENT1` | `RGBA16F`    | world space normal                        |
+/// | `COLOR_ATTACHMENT2` | `RGBA8`      | specular color, shininess / 256           |
+/// | `DEPTH_ATTACHMENT`  | `DEPTH24`    | depth, for world positions                |
+pub struct GBuffer {
+    // Declared first so the framebuffer is deleted before its attachments.
+    fbo: Framebuffer,
+    albedo: Texture2D,
+    normal: Texture2D,
+    material: Texture2D,
+    depth: Texture2D,
+}
+
+#[allow(dead_code)]
+impl GBuffer {
+    /// Color attachments in `layout(location)` order of `gbuffer.fs.glsl`.
+    pub const ATTACHMENTS: [GLenum; 3] = [
+        gl::COLOR_ATTACHMENT0,
+        gl::COLOR_ATTACHMENT1,
+        gl::COLOR_ATTACHMENT2,
+    ];
+
+    pub fn new(width: u32, height: u32) -> Result<GBuffer, String> {
+        let texture = |format| {
+            Texture2D::from_float(width, height, format, None, gl::NEAREST, gl::CLAMP_TO_EDGE)
+        };
+        let albedo = texture(gl::RGBA8)?;
+        let normal = texture(gl::RGBA16F)?;
+        let material = texture(gl::RGBA8)?;
+        let depth = texture(gl::DEPTH_COMPONENT24)?;
+        albedo.set_label("G-buffer albedo");
+        normal.set_label("G-buffer normal");
+        material.set_label("G-buffer material");
+        depth.set_label("G-buffer depth");
+
+        let fbo = Framebuffer::new(width, height)?;
+        fbo.set_label("G-buffer");
+        for (attachment, texture) in GBuffer::ATTACHMENTS
+            .iter()
+            .zip(&[&albedo, &normal, &material])
+        {
+            fbo.attach_texture(*attachment, texture, 0);
+        }
+        fbo.attach_texture(gl::DEPTH_ATTACHMENT, &depth, 0);
+        fbo.draw_buffers(&GBuffer::ATTACHMENTS);
+        fbo.check()?;
+        Ok(GBuffer {
+            fbo,
+            albedo,
+            normal,
+            material,
+            depth,
+        })
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.fbo
+    }
+
+    pub fn width(&self) -> u32 {
+        self.fbo.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.fbo.height()
+    }
+
+    pub fn albedo(&self) -> &Texture2D {
+        &self.albedo
+    }
+
+    pub fn normal(&self) -> &Texture2D {
+        &self.normal
+    }
+
+    pub fn material(&self) -> &Texture2D {
+        &self.material
+    }
+
+    pub fn depth(&self) -> &Texture2D {
+        &self.depth
+    }
+
+    pub fn bind(&self) {
+        self.fbo.bind();
+    }
+
+    /// Binds the channels to units 0 to 3 and points the `GAlbedo`, `GNormal`, `GMaterial`
+    /// and `GDepth` samplers the program uses at them.
+    pub fn bind_textures(&self, shader: &Shader) {
+        let channels = [
+            ("GAlbedo", &self.albedo),
+            ("GNormal", &self.normal),
+            ("GMaterial", &self.material),
+            ("GDepth", &self.depth),
+        ];
+        for (unit, (name, texture)) in channels.iter().enumerate() {
+            texture.bind(unit as u32);
+            if shader.has_uniform(name) {
+                shader.set_texture(name, unit as i32);
+            }
+        }
+    }
+}
+
+/// Model matrix of the sphere that bounds a point or spot light, or `None` for lights
+/// that reach everywhere. The sphere mesh is a polyhedron inside the unit sphere, so it is
+/// scaled up until its faces clear the light's range.
+pub fn light_volume(light: &Light, world: &glm::Mat4) -> Option<glm::Mat4> {
+    let range = light.range();
+    if !range.is_finite() {
+        return None;
+    }
+    let inset = (PI / VOLUME_SEGMENTS as f32).cos() * (PI / (2.0 * VOLUME_RINGS as f32)).cos();
+    let radius = range / inset;
+    let position = world.column(3).xyz();
+    Some(glm::scale(
+        &glm::translation(&position),
+        &glm::vec3(radius, radius, radius),
+    ))
+}
+
+// Uploads the `CurrentLight` struct of `deferred_light.fs.glsl`.
+fn set_light(shader: &Shader, light: &Light, world: &glm::Mat4) {
+    let fields = [
+        "PositionType",
+        "DirectionRange",
+        "ColorIntensity",
+        "Falloff",
+    ];
+    for (field, value) in fields.iter().zip(light.std140(world).iter()) {
+        shader.set_vec4(&format!("CurrentLight.{}", field), value);
+    }
+}
+
+// Unit sphere drawn around point and spot lights: inside faces only, so it lights pixels
+// whether or not the camera is inside the volume, adding up overlapping lights.
+fn volume_mesh() -> Result<Mesh, String> {
+    geometry::uv_sphere(1.0, VOLUME_SEGMENTS, VOLUME_RINGS).build(
+        RenderState::default()
+            .with_blend(Some(Blend::ADDITIVE))
+            .with_depth(None, false)
+            .with_cull(Some(Face::Front)),
+    )
+}
+
+/// Splits the visible nodes with a mesh into opaque nodes, in scene order, and nodes whose
+/// material blends, sorted back to front from `eye`.
+pub fn partition_nodes(scene: &Scene, eye: &glm::Vec3) -> (Vec<NodeId>, Vec<NodeId>) {
+    let (mut opaque, mut transparent) = (Vec::new(), Vec::new());
+    for id in scene.visible_nodes() {
+        let node = scene.node(id).unwrap();
+        if node.mesh.is_none() {
+            continue;
+        }
+        let blended = node
+            .material
+            .as_ref()
+            .is_some_and(|m| m.material().render_state().blend.is_some());
+        if blended {
+            transparent.push((id, glm::distance2(eye, &node.world_position())));
+        } else {
+            opaque.push(id);
+        }
+    }
+    transparent.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    (opaque, transparent.into_iter().map(|(id, _)| id).collect())
+}
+
+/// Deferred Blinn-Phong renderer. Opaque nodes write their surfaces into a `GBuffer`, then
+/// each light shades only the pixels it covers: directional lights with a full-screen
+/// triangle, point and spot lights with a sphere around their range, so the cost grows
+/// with the pixels lights touch rather than with lights times objects. Nodes whose
+/// material blends are drawn forward afterwards, depth tested against the opaque ones.
+///
+/// Opaque nodes need a material writing the G-buffer like `res/materials/gbuffer.toml`;
+/// transparent ones a forward material attached with `attach_forward`, such as
+/// `res/materials/phong_transparent.toml`, which sees at most `MAX_LIGHTS` lights.
+pub struct DeferredRenderer {
+    gbuffer: GBuffer,
+    // Both draw into `lit`; the forward one also tests against the G-buffer depth.
+    light_fbo: Framebuffer,
+    forward_fbo: Framebuffer,
+    lit: Texture2D,
+    ambient: Shader,
+    directional: Shader,
+    volume: Shader,
+    present: Shader,
+    fallback: Shader,
+    sphere: Mesh,
+    triangle: VertexArray,
+    lighting: LightingBuffer,
+    ambient_color: glm::Vec3,
+    background: glm::Vec3,
+    view: Cell<GBufferView>,
+}
+
+#[allow(dead_code)]
+impl DeferredRenderer {
+    pub fn new(width: u32, height: u32) -> Result<DeferredRenderer, String> {
+        let (gbuffer, lit, light_fbo, forward_fbo) = DeferredRenderer::targets(width, height)?;
+        let additive = RenderState::default()
+            .with_blend(Some(Blend::ADDITIVE))
+            .with_depth(None, false);
+        let mut directional = Shader::new(FULLSCREEN_VS, "res/glsl/deferred_light.fs.glsl")?;
+        directional.set_render_state(additive);
+        let mut fallback = Shader::new("res/glsl/phong.vs.glsl", "res/glsl/gbuffer.fs.glsl")?;
+        fallback.set_render_state(RenderState::opaque());
+        fallback.use_program();
+        fallback.set_vec4("Diffuse", &glm::vec4(0.8, 0.8, 0.8, 1.0));
+        fallback.set_vec3("Specular", &glm::vec3(0.5, 0.5, 0.5));
+        fallback.set_float("Shininess", 32.0);
+        let triangle = VertexArray::new()?;
+        triangle.set_label("deferred triangle");
+        Ok(DeferredRenderer {
+            gbuffer,
+            light_fbo,
+            forward_fbo,
+            lit,
+            ambient: Shader::new(FULLSCREEN_VS, "res/glsl/deferred_ambient.fs.glsl")?,
+            directional,
+            volume: Shader::new(
+                "res/glsl/deferred_volume.vs.glsl",
+                "res/glsl/deferred_light.fs.glsl",
+            )?,
+            present: Shader::new(FULLSCREEN_VS, "res/glsl/deferred_present.fs.glsl")?,
+            fallback,
+            sphere: volume_mesh()?,
+            triangle,
+            lighting: LightingBuffer::new()?,
+            ambient_color: glm::vec3(0.03, 0.03, 0.03),
+            background: glm::vec3(0.0, 0.0, 0.0),
+            view: Cell::new(GBufferView::Lit),
+        })
+    }
+
+    fn targets(
+        width: u32,
+        height: u32,
+    ) -> Result<(GBuffer, Texture2D, Framebuffer, Framebuffer), String> {
+        let gbuffer = GBuffer::new(width, height)?;
+        let lit = Texture2D::from_float(
+            width,
+            height,
+            gl::RGBA16F,
+            None,
+            gl::LINEAR,
+            gl::CLAMP_TO_EDGE,
+        )?;
+        lit.set_label("deferred lighting");
+        let light_fbo = Framebuffer::new(width, height)?;
+        light_fbo.set_label("deferred lighting");
+        light_fbo.attach_texture(gl::COLOR_ATTACHMENT0, &lit, 0);
+        light_fbo.check()?;
+        let forward_fbo = Framebuffer::new(width, height)?;
+        forward_fbo.set_label("deferred forward");
+        forward_fbo.attach_texture(gl::COLOR_ATTACHMENT0, &lit, 0);
+        forward_fbo.attach_texture(gl::DEPTH_ATTACHMENT, gbuffer.depth(), 0);
+        forward_fbo.check()?;
+        Ok((gbuffer, lit, light_fbo, forward_fbo))
+    }
+
+    /// Recreates the targets for a new frame size. Ignores a minimized window.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        let (gbuffer, lit, light_fbo, forward_fbo) = DeferredRenderer::targets(width, height)?;
+        // Framebuffers first, before the textures they reference.
+        self.light_fbo = light_fbo;
+        self.forward_fbo = forward_fbo;
+        self.gbuffer = gbuffer;
+        self.lit = lit;
+        Ok(())
+    }
+
+    pub fn gbuffer(&self) -> &GBuffer {
+        &self.gbuffer
+    }
+
+    /// HDR result of the lighting and forward passes.
+    pub fn lit(&self) -> &Texture2D {
+        &self.lit
+    }
+
+    /// Points a forward shader's `Lighting` block at the lights of the forward pass.
+    pub fn attach_forward(&self, shader: &Shader) -> Result<(), String> {
+        self.lighting.attach(shader)
+    }
+
+    pub fn ambient(&self) -> &glm::Vec3 {
+        &self.ambient_color
+    }
+
+    pub fn set_ambient(&mut self, ambient: glm::Vec3) {
+        self.ambient_color = ambient;
+    }
+
+    /// Color of pixels no opaque geometry covers.
+    pub fn set_background(&mut self, background: glm::Vec3) {
+        self.background = background;
+    }
+
+    pub fn view(&self) -> GBufferView {
+        self.view.get()
+    }
+
+    /// Takes `&self` so an overlay can switch views while the app is borrowed for drawing.
+    pub fn set_view(&self, view: GBufferView) {
+        self.view.set(view);
+    }
+
+    /// Runs every pass for the scene's current transforms, leaving the result in the window,
+    /// or the post-processing input while a frame renders into it. Nodes outside the
+    /// camera's frustum are skipped.
+    pub fn render(&self, ctx: &Context, scene: &Scene, camera: &Camera) {
+        let frustum = Frustum::from_matrix(&camera.view_projection());
+        let in_view: HashSet<NodeId> = scene.visible_in(&frustum).into_iter().collect();
+        let (mut opaque, mut transparent) = partition_nodes(scene, &camera.position());
+        opaque.retain(|id| in_view.contains(id));
+        transparent.retain(|id| in_view.contains(id));
+        self.geometry_pass(ctx, scene, camera, &opaque);
+        self.lighting_pass(ctx, scene, camera);
+        self.forward_pass(ctx, scene, camera, &transparent);
+        self.present(ctx, camera);
+    }
+
+    /// Draws `nodes` into the G-buffer. Returns the number of draws.
+    pub fn geometry_pass(
+        &self,
+        ctx: &Context,
+        scene: &Scene,
+        camera: &Camera,
+        nodes: &[NodeId],
+    ) -> usize {
+        self.gbuffer.bind();
+        ctx.set_viewport_size(self.gbuffer.width(), self.gbuffer.height());
+        // Clears skip the depth buffer unless depth writes are on.
+        ctx.apply_state(&RenderState::opaque());
+        ctx.clear_buffer(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        scene.render_nodes(ctx, &self.fallback, &camera.view_projection(), nodes)
+    }
+
+    /// Shades the G-buffer with ambient light and every visible scene light. Returns the
+    /// number of lights drawn.
+    pub fn lighting_pass(&self, ctx: &Context, scene: &Scene, camera: &Camera) -> usize {
+        self.light_fbo.bind();
+        ctx.set_viewport_size(self.gbuffer.width(), self.gbuffer.height());
+        let view_projection = camera.view_projection();
+        let inverse = glm::inverse(&view_projection);
+
+        // Ambient light, and the background where there is no geometry; covers every pixel.
+        self.ambient.bind(ctx);
+        self.gbuffer.bind_textures(&self.ambient);
+        self.ambient.set_vec3("Ambient", &self.ambient_color);
+        self.ambient.set_vec3("Background", &self.background);
+        self.triangle.draw_arrays(gl::TRIANGLES, 0, 3);
+
+        let lights = scene.lights();
+        for shader in [&self.directional, &self.volume].iter() {
+            shader.bind(ctx);
+            self.gbuffer.bind_textures(shader);
+            shader.set_mat4("InverseViewProjection", &inverse);
+            shader.set_vec3("CameraPosition", &camera.position());
+            shader.set_vec2(
+                "ScreenSize",
+                &glm::vec2(self.gbuffer.width() as f32, self.gbuffer.height() as f32),
+            );
+        }
+        for (light, world) in lights.iter() {
+            match light_volume(light, world) {
+                Some(model) => {
+                    // The sphere applies its own state when drawn.
+                    self.volume.use_program();
+                    self.volume.set_mat4("MVP", &(view_projection * model));
+                    set_light(&self.volume, light, world);
+                    self.sphere.draw(ctx);
+                }
+                None => {
+                    self.directional.bind(ctx);
+                    set_light(&self.directional, light, world);
+                    self.triangle.draw_arrays(gl::TRIANGLES, 0, 3);
+                }
+            }
+        }
+        lights.len()
+    }
+
+    /// Draws `nodes` over the lit frame, lit by the first `MAX_LIGHTS` scene lights.
+    /// Returns the number of draws.
+    pub fn forward_pass(
+        &self,
+        ctx: &Context,
+        scene: &Scene,
+        camera: &Camera,
+        nodes: &[NodeId],
+    ) -> usize {
+        if nodes.is_empty() {
+            return 0;
+        }
+        self.forward_fbo.bind();
+        ctx.set_viewport_size(self.gbuffer.width(), self.gbuffer.height());
+        let mut block = LightBlock::new(self.ambient_color);
+        block.set_camera_position(camera.position());
+        for (light, world) in scene.lights().iter().take(MAX_LIGHTS) {
+            block.push(light, world);
+        }
+        self.lighting.upload(&block);
+        scene.render_nodes(ctx, &self.fallback, &camera.view_projection(), nodes)
+    }
+
+    /// Draws the lit frame, or the G-buffer channel `set_view` picked, to the window.
+    pub fn present(&self, ctx: &Context, camera: &Camera) {
+        self.light_fbo.unbind();
+        ctx.set_viewport();
+        self.present.bind(ctx);
+        self.gbuffer.bind_textures(&self.present);
+        self.lit.bind(4);
+        self.present.set_texture("Lit", 4);
+        self.present.set_int("View", self.view().index() as i32);
+        self.present
+            .set_mat4("InverseProjection", &glm::inverse(camera.projection()));
+        self.triangle.draw_arrays(gl::TRIANGLES, 0, 3);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::device::RecordingDevice;
+    use crate::core::material::{Material, MaterialInstance};
+    use crate::core::resource::ContextOwner;
+    use crate::core::scene::Transform;
+    use std::rc::Rc;
+
+    #[test]
+    fn gbuffer_writes_three_color_targets() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let gbuffer = GBuffer::new(64, 32).unwrap();
+        assert_eq!(
+            device.calls_to("TexImageFloat"),
+            vec![
+                "TexImageFloat(TEXTURE_2D, 0, RGBA8, 64x32, null)",
+                "TexImageFloat(TEXTURE_2D, 0, RGBA16F, 64x32, null)",
+                "TexImageFloat(TEXTURE_2D, 0, RGBA8, 64x32, null)",
+                "TexImageFloat(TEXTURE_2D, 0, DEPTH_COMPONENT24, 64x32, null)",
+            ]
+        );
+        assert_eq!(
+            device.calls_to("DrawBuffers"),
+            vec!["DrawBuffers(COLOR_ATTACHMENT0, COLOR_ATTACHMENT1, COLOR_ATTACHMENT2)"]
+        );
+        assert_eq!(gbuffer.width(), 64);
+    }
+
+    #[test]
+    fn light_volumes_enclose_the_range() {
+        let world = glm::translation(&glm::vec3(1.0, 2.0, 3.0));
+        let sun = Light::Directional {
+            color: glm::vec3(1.0, 1.0, 1.0),
+            intensity: 1.0,
+        };
+        assert_eq!(light_volume(&sun, &world), None);
+
+        let lamp = Light::Point {
+            color: glm::vec3(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            range: 4.0,
+        };
+        let model = light_volume(&lamp, &world).unwrap();
+        assert_eq!(model.column(3).xyz(), glm::vec3(1.0, 2.0, 3.0));
+        let radius = model[(0, 0)];
+        assert!(radius > 4.0 && radius < 4.4, "{}", radius);
+        // The nearest face of the scaled mesh, between two rings and two segments, still
+        // lies outside the range.
+        let inset = (PI / VOLUME_SEGMENTS as f32).cos() * (PI / (2.0 * VOLUME_RINGS as f32)).cos();
+        assert!(radius * inset >= 4.0 - 1e-4);
+    }
+
+    #[test]
+    fn transparent_nodes_are_drawn_back_to_front() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let shader =
+            Rc::new(Shader::new("res/glsl/phong.vs.glsl", "res/glsl/gbuffer.fs.glsl").unwrap());
+        let opaque = Rc::new(Material::new(shader.clone()));
+        let mut glass = Material::new(shader);
+        glass.set_render_state(RenderState::transparent());
+        let glass = Rc::new(glass);
+        let mesh = Rc::new(volume_mesh().unwrap());
+
+        let mut scene = Scene::new();
+        let mut add = |name: &str, z: f32, material: &Rc<Material>| {
+            let id = scene.add(name);
+            scene.set_transform(id, Transform::from_translation(glm::vec3(0.0, 0.0, z)));
+            let node = scene.node_mut(id).unwrap();
+            node.mesh = Some(mesh.clone());
+            node.material = Some(MaterialInstance::new(material.clone()));
+            id
+        };
+        let wall = add("wall", -5.0, &opaque);
+        let near = add("near glass", -1.0, &glass);
+        let far = add("far glass", -3.0, &glass);
+        let floor = add("floor", 0.0, &opaque);
+        scene.add("empty");
+        scene.update_transforms();
+
+        let (opaque, transparent) = partition_nodes(&scene, &glm::vec3(0.0, 0.0, 0.0));
+        assert_eq!(opaque, vec![wall, floor]);
+        assert_eq!(transparent, vec![far, near]);
+    }
+}
@@ -0,0 +1,1353 @@
+use std::collections::{HashMap, VecDeque};
+use std::ffi::CString;
+use std::os::raw::c_void;
+use std::sync::{Arc, Mutex};
+
+use gl::types::*;
+
+use super::resource::ResourceKind;
+use super::state::{
+    Blend, Capability, CompareFunc, Face, FrontFace, PolygonMode, Rect, StateBackend, StencilOp,
+};
+
+/// Every GL call made by `Buffer`, `VertexArray`, `Shader`, the texture types, `Framebuffer`,
+/// `Context` and its debug output.
+///
+/// Sizes and offsets are in bytes. Implementations are shared with the threads a
+/// resource may be dropped on, but are only called on the context's own thread.
+pub trait GraphicsDevice: StateBackend + Send + Sync {
+    fn gen_buffer(&self) -> u32;
+    fn bind_buffer(&self, target: GLenum, id: u32);
+    /// Allocates `size` bytes, initialized from `data` when given.
+    fn buffer_data(&self, target: GLenum, size: usize, data: Option<&[u8]>, usage: GLenum);
+    fn buffer_sub_data(&self, target: GLenum, offset: usize, data: &[u8]);
+    /// Binds a uniform or storage buffer to an indexed binding point.
+    fn bind_buffer_base(&self, target: GLenum, index: u32, id: u32);
+    /// Enables attribute `index` and sources `size` floats from the bound array buffer.
+    fn vertex_attrib(&self, index: u32, size: i32, stride: usize, offset: usize);
+
+    fn gen_vertex_array(&self) -> u32;
+    fn bind_vertex_array(&self, id: u32);
+    fn draw_arrays(&self, mode: GLenum, first: i32, count: i32);
+    fn draw_elements(&self, mode: GLenum, count: i32, index_type: GLenum, offset: usize);
+
+    /// Compiles one stage, returning the info log on failure.
+    fn compile_shader(&self, stage: GLenum, source: &str) -> Result<u32, String>;
+    fn delete_shader(&self, shader: u32);
+    fn create_program(&self) -> u32;
+    fn attach_shader(&self, program: u32, shader: u32);
+    /// Links the attached stages, returning the info log on failure.
+    fn link_program(&self, program: u32) -> Result<(), String>;
+    fn use_program(&self, program: u32);
+    fn uniform_location(&self, program: u32, name: &str) -> Option<i32>;
+    /// GL type of the active uniform `name`, e.g. `FLOAT` or `SAMPLER_2D`.
+    fn uniform_type(&self, program: u32, name: &str) -> Option<GLenum>;
+    /// Points the uniform block `name` at a binding point; false if the program has no such block.
+    fn uniform_block_binding(&self, program: u32, name: &str, binding: u32) -> bool;
+    fn uniform_int(&self, location: i32, value: i32);
+    fn uniform_float(&self, location: i32, value: f32);
+    fn uniform_vec2(&self, location: i32, value: &[f32; 2]);
+    fn uniform_vec3(&self, location: i32, value: &[f32; 3]);
+    fn uniform_vec4(&self, location: i32, value: &[f32; 4]);
+    fn uniform_mat4(&self, location: i32, value: &[f32; 16]);
+
+    fn gen_texture(&self) -> u32;
+    /// Selects texture unit `unit`, counted from `TEXTURE0`.
+    fn active_texture(&self, unit: u32);
+    fn bind_texture(&self, target: GLenum, id: u32);
+    fn tex_parameter(&self, target: GLenum, name: GLenum, value: GLenum);
+    /// Uploads tightly packed unsigned bytes; `format` is also the internal format.
+    fn tex_image_2d(&self, target: GLenum, format: GLenum, width: u32, height: u32, data: &[u8]);
+    /// Allocates mip `level` of a float texture such as `RGB16F`, initialized from tightly
+    /// packed floats when given. `target` may be a cube map face.
+    fn tex_image_float(
+        &self,
+        target: GLenum,
+        level: u32,
+        internal_format: GLenum,
+        width: u32,
+        height: u32,
+        data: Option<&[f32]>,
+    );
+    fn generate_mipmap(&self, target: GLenum);
+
+    fn gen_framebuffer(&self) -> u32;
+    /// Binds `id` for drawing and reading; 0 is the window's framebuffer.
+    fn bind_framebuffer(&self, id: u32);
+    /// Attaches mip `level` of a texture, or of the cube map face `target`, to the bound framebuffer.
+    fn framebuffer_texture(&self, attachment: GLenum, target: GLenum, texture: u32, level: u32);
+    fn gen_renderbuffer(&self) -> u32;
+    fn renderbuffer_storage(&self, id: u32, internal_format: GLenum, width: u32, height: u32);
+    /// Like `renderbuffer_storage`, with `samples` samples per pixel.
+    fn renderbuffer_storage_multisample(
+        &self,
+        id: u32,
+        samples: u32,
+        internal_format: GLenum,
+        width: u32,
+        height: u32,
+    );
+    /// Allocates the bound `TEXTURE_2D_MULTISAMPLE` with fixed sample locations.
+    fn tex_image_multisample(&self, samples: u32, internal_format: GLenum, width: u32, height: u32);
+    /// `GL_MAX_SAMPLES`: the most samples a multisampled attachment may have.
+    fn max_samples(&self) -> u32;
+    fn framebuffer_renderbuffer(&self, attachment: GLenum, renderbuffer: u32);
+    /// Color attachments of the bound framebuffer that draws write to; none for depth-only
+    /// targets, which then neither draw nor read color.
+    fn draw_buffers(&self, attachments: &[GLenum]);
+    /// `FRAMEBUFFER_COMPLETE` when the bound framebuffer can be rendered to.
+    fn check_framebuffer_status(&self) -> GLenum;
+    /// Copies the `mask` buffers of `src` in framebuffer `read` to `dst` in `draw`, resolving
+    /// samples when `read` is multisampled. Leaves `read` and `draw` bound.
+    fn blit_framebuffer(
+        &self,
+        read: u32,
+        draw: u32,
+        src: Rect,
+        dst: Rect,
+        mask: GLenum,
+        filter: GLenum,
+    );
+
+    fn clear(&self, mask: GLenum);
+    /// Clears the unsigned integer color attachment at `draw_buffer` to `value`.
+    fn clear_color_uint(&self, draw_buffer: u32, value: u32);
+    /// Reads RGBA8 pixels of the bound read framebuffer, bottom row first.
+    fn read_pixels(&self, x: i32, y: i32, width: u32, height: u32) -> Vec<u8>;
+    /// Reads the red channel of one pixel of an unsigned integer attachment.
+    fn read_pixel_uint(&self, x: i32, y: i32) -> u32;
+
+    /// Whether the context was created with the debug flag and can report KHR_debug messages.
+    fn has_debug_output(&self) -> bool;
+    /// Routes debug messages to `callback`, called synchronously with `user_param`; `None`
+    /// removes it.
+    fn debug_message_callback(&self, callback: GLDEBUGPROC, user_param: *const c_void);
+    /// Pops the oldest error off the error queue, `NO_ERROR` when it is empty.
+    fn get_error(&self) -> GLenum;
+    /// Names a GL object for debuggers and debug messages; a no-op without KHR_debug.
+    fn object_label(&self, identifier: GLenum, id: u32, label: &str);
+    /// Opens a debug group, shown as a scope in tools like RenderDoc; a no-op without KHR_debug.
+    fn push_debug_group(&self, name: &str);
+    fn pop_debug_group(&self);
+    fn delete(&self, kind: ResourceKind, id: u32);
+}
+
+/// `GraphicsDevice` for the OpenGL context current on this thread.
+pub struct GlDevice;
+
+impl StateBackend for GlDevice {
+    fn set_enabled(&self, cap: Capability, enabled: bool) {
+        unsafe {
+            if enabled {
+                gl::Enable(cap.to_gl());
+            } else {
+                gl::Disable(cap.to_gl());
+            }
+        }
+    }
+
+    fn blend_func(&self, blend: &Blend) {
+        unsafe {
+            gl::BlendFuncSeparate(
+                blend.src_color.to_gl(),
+                blend.dst_color.to_gl(),
+                blend.src_alpha.to_gl(),
+                blend.dst_alpha.to_gl(),
+            );
+            gl::BlendEquationSeparate(blend.color_op.to_gl(), blend.alpha_op.to_gl());
+        }
+    }
+
+    fn depth_func(&self, func: CompareFunc) {
+        unsafe { gl::DepthFunc(func.to_gl()) }
+    }
+
+    fn depth_mask(&self, write: bool) {
+        unsafe { gl::DepthMask(write as GLboolean) }
+    }
+
+    fn cull_face(&self, face: Face) {
+        unsafe { gl::CullFace(face.to_gl()) }
+    }
+
+    fn front_face(&self, front_face: FrontFace) {
+        unsafe { gl::FrontFace(front_face.to_gl()) }
+    }
+
+    fn scissor(&self, rect: Rect) {
+        unsafe { gl::Scissor(rect.x, rect.y, rect.width, rect.height) }
+    }
+
+    fn color_mask(&self, mask: [bool; 4]) {
+        unsafe {
+            gl::ColorMask(
+                mask[0] as GLboolean,
+                mask[1] as GLboolean,
+                mask[2] as GLboolean,
+                mask[3] as GLboolean,
+            )
+        }
+    }
+
+    fn stencil_func(&self, func: CompareFunc, reference: i32, mask: u32) {
+        unsafe { gl::StencilFunc(func.to_gl(), reference, mask) }
+    }
+
+    fn stencil_op(&self, fail: StencilOp, depth_fail: StencilOp, pass: StencilOp) {
+        unsafe { gl::StencilOp(fail.to_gl(), depth_fail.to_gl(), pass.to_gl()) }
+    }
+
+    fn stencil_mask(&self, mask: u32) {
+        unsafe { gl::StencilMask(mask) }
+    }
+
+    fn polygon_mode(&self, mode: PolygonMode) {
+        unsafe { gl::PolygonMode(gl::FRONT_AND_BACK, mode.to_gl()) }
+    }
+
+    fn clear_color(&self, color: [f32; 4]) {
+        unsafe { gl::ClearColor(color[0], color[1], color[2], color[3]) }
+    }
+
+    fn viewport(&self, rect: Rect) {
+        unsafe { gl::Viewport(rect.x, rect.y, rect.width, rect.height) }
+    }
+}
+
+impl GraphicsDevice for GlDevice {
+    fn gen_buffer(&self) -> u32 {
+        let mut id = 0;
+        unsafe { gl::GenBuffers(1, &mut id) };
+        id
+    }
+
+    fn bind_buffer(&self, target: GLenum, id: u32) {
+        unsafe { gl::BindBuffer(target, id) }
+    }
+
+    fn buffer_data(&self, target: GLenum, size: usize, data: Option<&[u8]>, usage: GLenum) {
+        let ptr = data.map_or(std::ptr::null(), |d| d.as_ptr() as *const c_void);
+        unsafe { gl::BufferData(target, size as GLsizeiptr, ptr, usage) }
+    }
+
+    fn buffer_sub_data(&self, target: GLenum, offset: usize, data: &[u8]) {
+        unsafe {
+            gl::BufferSubData(
+                target,
+                offset as GLintptr,
+                data.len() as GLsizeiptr,
+                data.as_ptr() as *const c_void,
+            )
+        }
+    }
+
+    fn bind_buffer_base(&self, target: GLenum, index: u32, id: u32) {
+        unsafe { gl::BindBufferBase(target, index, id) }
+    }
+
+    fn vertex_attrib(&self, index: u32, size: i32, stride: usize, offset: usize) {
+        unsafe {
+            gl::EnableVertexAttribArray(index);
+            gl::VertexAttribPointer(
+                index,
+                size,
+                gl::FLOAT,
+                gl::FALSE,
+                stride as GLsizei,
+                offset as *const c_void,
+            );
+        }
+    }
+
+    fn gen_vertex_array(&self) -> u32 {
+        let mut id = 0;
+        unsafe { gl::GenVertexArrays(1, &mut id) };
+        id
+    }
+
+    fn bind_vertex_array(&self, id: u32) {
+        unsafe { gl::BindVertexArray(id) }
+    }
+
+    fn draw_arrays(&self, mode: GLenum, first: i32, count: i32) {
+        unsafe { gl::DrawArrays(mode, first, count) }
+    }
+
+    fn draw_elements(&self, mode: GLenum, count: i32, index_type: GLenum, offset: usize) {
+        unsafe { gl::DrawElements(mode, count, index_type, offset as *const c_void) }
+    }
+
+    fn compile_shader(&self, stage: GLenum, source: &str) -> Result<u32, String> {
+        unsafe {
+            let id = gl::CreateShader(stage);
+            let ptr = source.as_ptr() as *const GLchar;
+            let len = source.len() as GLint;
+            gl::ShaderSource(id, 1, &ptr, &len);
+            gl::CompileShader(id);
+
+            let mut status = 0;
+            gl::GetShaderiv(id, gl::COMPILE_STATUS, &mut status);
+            if status != 0 {
+                return Ok(id);
+            }
+            let log = info_log(id, gl::GetShaderiv, gl::GetShaderInfoLog);
+            gl::DeleteShader(id);
+            Err(log)
+        }
+    }
+
+    fn delete_shader(&self, shader: u32) {
+        unsafe { gl::DeleteShader(shader) }
+    }
+
+    fn create_program(&self) -> u32 {
+        unsafe { gl::CreateProgram() }
+    }
+
+    fn attach_shader(&self, program: u32, shader: u32) {
+        unsafe { gl::AttachShader(program, shader) }
+    }
+
+    fn link_program(&self, program: u32) -> Result<(), String> {
+        unsafe {
+            gl::LinkProgram(program);
+            let mut status = 0;
+            gl::GetProgramiv(program, gl::LINK_STATUS, &mut status);
+            if status != 0 {
+                Ok(())
+            } else {
+                Err(info_log(program, gl::GetProgramiv, gl::GetProgramInfoLog))
+            }
+        }
+    }
+
+    fn use_program(&self, program: u32) {
+        unsafe { gl::UseProgram(program) }
+    }
+
+    fn uniform_location(&self, program: u32, name: &str) -> Option<i32> {
+        let c_name = CString::new(name).ok()?;
+        match unsafe { gl::GetUniformLocation(program, c_name.as_ptr()) } {
+            -1 => None,
+            loc => Some(loc),
+        }
+    }
+
+    fn uniform_type(&self, program: u32, name: &str) -> Option<GLenum> {
+        let c_name = CString::new(name).ok()?;
+        let names = [c_name.as_ptr()];
+        let mut index = gl::INVALID_INDEX;
+        let mut ty: GLint = 0;
+        unsafe {
+            gl::GetUniformIndices(program, 1, names.as_ptr(), &mut index);
+            if index == gl::INVALID_INDEX {
+                return None;
+            }
+            gl::GetActiveUniformsiv(program, 1, &index, gl::UNIFORM_TYPE, &mut ty);
+        }
+        Some(ty as GLenum)
+    }
+
+    fn uniform_block_binding(&self, program: u32, name: &str, binding: u32) -> bool {
+        let c_name = match CString::new(name) {
+            Ok(c_name) => c_name,
+            Err(_) => return false,
+        };
+        unsafe {
+            let index = gl::GetUniformBlockIndex(program, c_name.as_ptr());
+            if index == gl::INVALID_INDEX {
+                return false;
+            }
+            gl::UniformBlockBinding(program, index, binding);
+        }
+        true
+    }
+
+    fn uniform_int(&self, location: i32, value: i32) {
+        unsafe { gl::Uniform1i(location, value) }
+    }
+
+    fn uniform_float(&self, location: i32, value: f32) {
+        unsafe { gl::Uniform1f(location, value) }
+    }
+
+    fn uniform_vec2(&self, location: i32, value: &[f32; 2]) {
+        unsafe { gl::Uniform2fv(location, 1, value.as_ptr()) }
+    }
+
+    fn uniform_vec3(&self, location: i32, value: &[f32; 3]) {
+        unsafe { gl::Uniform3fv(location, 1, value.as_ptr()) }
+    }
+
+    fn uniform_vec4(&self, location: i32, value: &[f32; 4]) {
+        unsafe { gl::Uniform4fv(location, 1, value.as_ptr()) }
+    }
+
+    fn uniform_mat4(&self, location: i32, value: &[f32; 16]) {
+        unsafe { gl::UniformMatrix4fv(location, 1, gl::FALSE, value.as_ptr()) }
+    }
+
+    fn gen_texture(&self) -> u32 {
+        let mut id = 0;
+        unsafe { gl::GenTextures(1, &mut id) };
+        id
+    }
+
+    fn active_texture(&self, unit: u32) {
+        unsafe { gl::ActiveTexture(gl::TEXTURE0 + unit) }
+    }
+
+    fn bind_texture(&self, target: GLenum, id: u32) {
+        unsafe { gl::BindTexture(target, id) }
+    }
+
+    fn tex_parameter(&self, target: GLenum, name: GLenum, value: GLenum) {
+        unsafe { gl::TexParameteri(target, name, value as GLint) }
+    }
+
+    fn tex_image_2d(&self, target: GLenum, format: GLenum, width: u32, height: u32, data: &[u8]) {
+        unsafe {
+            gl::TexImage2D(
+                target,
+                0,
+                format as GLint,
+                width as GLsizei,
+                height as GLsizei,
+                0,
+                format,
+                gl::UNSIGNED_BYTE,
+                data.as_ptr() as *const c_void,
+            );
+        }
+    }
+
+    fn tex_image_float(
+        &self,
+        target: GLenum,
+        level: u32,
+        internal_format: GLenum,
+        width: u32,
+        height: u32,
+        data: Option<&[f32]>,
+    ) {
+        let ptr = data.map_or(std::ptr::null(), |d| d.as_ptr() as *const c_void);
+        unsafe {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage2D(
+                target,
+                level as GLint,
+                internal_format as GLint,
+                width as GLsizei,
+                height as GLsizei,
+                0,
+                float_format(internal_format),
+                gl::FLOAT,
+                ptr,
+            );
+        }
+    }
+
+    fn generate_mipmap(&self, target: GLenum) {
+        unsafe { gl::GenerateMipmap(target) }
+    }
+
+    fn gen_framebuffer(&self) -> u32 {
+        let mut id = 0;
+        unsafe { gl::GenFramebuffers(1, &mut id) };
+        id
+    }
+
+    fn bind_framebuffer(&self, id: u32) {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, id) }
+    }
+
+    fn framebuffer_texture(&self, attachment: GLenum, target: GLenum, texture: u32, level: u32) {
+        unsafe {
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment, target, texture, level as GLint)
+        }
+    }
+
+    fn gen_renderbuffer(&self) -> u32 {
+        let mut id = 0;
+        unsafe { gl::GenRenderbuffers(1, &mut id) };
+        id
+    }
+
+    fn renderbuffer_storage(&self, id: u32, internal_format: GLenum, width: u32, height: u32) {
+        unsafe {
+            gl::BindRenderbuffer(gl::RENDERBUFFER, id);
+            gl::RenderbufferStorage(
+                gl::RENDERBUFFER,
+                internal_format,
+                width as GLsizei,
+                height as GLsizei,
+            );
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+        }
+    }
+
+    fn renderbuffer_storage_multisample(
+        &self,
+        id: u32,
+        samples: u32,
+        internal_format: GLenum,
+        width: u32,
+        height: u32,
+    ) {
+        unsafe {
+            gl::BindRenderbuffer(gl::RENDERBUFFER, id);
+            gl::RenderbufferStorageMultisample(
+                gl::RENDERBUFFER,
+                samples as GLsizei,
+                internal_format,
+                width as GLsizei,
+                height as GLsizei,
+            );
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+        }
+    }
+
+    fn tex_image_multisample(
+        &self,
+        samples: u32,
+        internal_format: GLenum,
+        width: u32,
+        height: u32,
+    ) {
+        unsafe {
+            gl::TexImage2DMultisample(
+                gl::TEXTURE_2D_MULTISAMPLE,
+                samples as GLsizei,
+                internal_format,
+                width as GLsizei,
+                height as GLsizei,
+                gl::TRUE,
+            );
+        }
+    }
+
+    fn max_samples(&self) -> u32 {
+        let mut samples = 0;
+        unsafe { gl::GetIntegerv(gl::MAX_SAMPLES, &mut samples) };
+        samples.max(0) as u32
+    }
+
+    fn framebuffer_renderbuffer(&self, attachment: GLenum, renderbuffer: u32) {
+        unsafe {
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment, gl::RENDERBUFFER, renderbuffer)
+        }
+    }
+
+    fn draw_buffers(&self, attachments: &[GLenum]) {
+        unsafe {
+            if attachments.is_empty() {
+                gl::DrawBuffer(gl::NONE);
+                gl::ReadBuffer(gl::NONE);
+            } else {
+                gl::DrawBuffers(attachments.len() as GLsizei, attachments.as_ptr());
+            }
+        }
+    }
+
+    fn check_framebuffer_status(&self) -> GLenum {
+        unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) }
+    }
+
+    fn blit_framebuffer(
+        &self,
+        read: u32,
+        draw: u32,
+        src: Rect,
+        dst: Rect,
+        mask: GLenum,
+        filter: GLenum,
+    ) {
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, read);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, draw);
+            gl::BlitFramebuffer(
+                src.x,
+                src.y,
+                src.x + src.width,
+                src.y + src.height,
+                dst.x,
+                dst.y,
+                dst.x + dst.width,
+                dst.y + dst.height,
+                mask,
+                filter,
+            );
+        }
+    }
+
+    fn clear(&self, mask: GLenum) {
+        unsafe { gl::Clear(mask) }
+    }
+
+    fn clear_color_uint(&self, draw_buffer: u32, value: u32) {
+        let color = [value, 0, 0, 0];
+        unsafe { gl::ClearBufferuiv(gl::COLOR, draw_buffer as GLint, color.as_ptr()) }
+    }
+
+    fn read_pixels(&self, x: i32, y: i32, width: u32, height: u32) -> Vec<u8> {
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                x,
+                y,
+                width as GLsizei,
+                height as GLsizei,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut c_void,
+            );
+        }
+        pixels
+    }
+
+    fn read_pixel_uint(&self, x: i32, y: i32) -> u32 {
+        let mut value = 0u32;
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                x,
+                y,
+                1,
+                1,
+                gl::RED_INTEGER,
+                gl::UNSIGNED_INT,
+                &mut value as *mut u32 as *mut c_void,
+            );
+        }
+        value
+    }
+
+    fn has_debug_output(&self) -> bool {
+        if !gl::DebugMessageCallback::is_loaded() {
+            return false;
+        }
+        let mut flags = 0;
+        unsafe {
+            gl::GetIntegerv(gl::CONTEXT_FLAGS, &mut flags);
+        }
+        flags as GLenum & gl::CONTEXT_FLAG_DEBUG_BIT != 0
+    }
+
+    fn debug_message_callback(&self, callback: GLDEBUGPROC, user_param: *const c_void) {
+        unsafe {
+            if callback.is_some() {
+                gl::Enable(gl::DEBUG_OUTPUT);
+                gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+            }
+            gl::DebugMessageCallback(callback, user_param);
+        }
+    }
+
+    fn get_error(&self) -> GLenum {
+        unsafe { gl::GetError() }
+    }
+
+    fn object_label(&self, identifier: GLenum, id: u32, label: &str) {
+        if gl::ObjectLabel::is_loaded() {
+            unsafe {
+                gl::ObjectLabel(
+                    identifier,
+                    id,
+                    label.len() as GLsizei,
+                    label.as_ptr() as *const GLchar,
+                );
+            }
+        }
+    }
+
+    fn push_debug_group(&self, name: &str) {
+        if gl::PushDebugGroup::is_loaded() {
+            unsafe {
+                gl::PushDebugGroup(
+                    gl::DEBUG_SOURCE_APPLICATION,
+                    0,
+                    name.len() as GLsizei,
+                    name.as_ptr() as *const GLchar,
+                );
+            }
+        }
+    }
+
+    fn pop_debug_group(&self) {
+        if gl::PopDebugGroup::is_loaded() {
+            unsafe {
+                gl::PopDebugGroup();
+            }
+        }
+    }
+
+    fn delete(&self, kind: ResourceKind, id: u32) {
+        unsafe {
+            match kind {
+                ResourceKind::Buffer => gl::DeleteBuffers(1, &id),
+                ResourceKind::Shader => gl::DeleteProgram(id),
+                ResourceKind::Texture => gl::DeleteTextures(1, &id),
+                ResourceKind::VertexArray => gl::DeleteVertexArrays(1, &id),
+                ResourceKind::Framebuffer => gl::DeleteFramebuffers(1, &id),
+                ResourceKind::Renderbuffer => gl::DeleteRenderbuffers(1, &id),
+            }
+        }
+    }
+}
+
+/// Client-side format matching a float or depth internal format.
+fn float_format(internal_format: GLenum) -> GLenum {
+    match internal_format {
+        gl::DEPTH_COMPONENT16 | gl::DEPTH_COMPONENT24 | gl::DEPTH_COMPONENT32F => {
+            gl::DEPTH_COMPONENT
+        }
+        gl::R16F | gl::R32F => gl::RED,
+        gl::RG16F | gl::RG32F => gl::RG,
+        gl::RGB16F | gl::RGB32F | gl::R11F_G11F_B10F => gl::RGB,
+        _ => gl::RGBA,
+    }
+}
+
+type GetIv = unsafe fn(GLuint, GLenum, *mut GLint);
+type GetInfoLog = unsafe fn(GLuint, GLsizei, *mut GLsizei, *mut GLchar);
+
+unsafe fn info_log(id: u32, get_iv: GetIv, get_log: GetInfoLog) -> String {
+    let mut len = 0;
+    get_iv(id, gl::INFO_LOG_LENGTH, &mut len);
+    let mut buf = vec![0u8; len.max(1) as usize];
+    let mut written = 0;
+    get_log(id, len, &mut written, buf.as_mut_ptr() as *mut GLchar);
+    buf.truncate(written.max(0) as usize);
+    String::from_utf8_lossy(&buf).into_owned()
+}
+
+/// Names of the enums the crate passes to the device, for readable recordings.
+fn enum_name(e: GLenum) -> String {
+    let name = match e {
+        gl::ARRAY_BUFFER => "ARRAY_BUFFER",
+        gl::ELEMENT_ARRAY_BUFFER => "ELEMENT_ARRAY_BUFFER",
+        gl::UNIFORM_BUFFER => "UNIFORM_BUFFER",
+        gl::STATIC_DRAW => "STATIC_DRAW",
+        gl::DYNAMIC_DRAW => "DYNAMIC_DRAW",
+        gl::STREAM_DRAW => "STREAM_DRAW",
+        gl::TRIANGLES => "TRIANGLES",
+        gl::LINES => "LINES",
+        gl::POINTS => "POINTS",
+        gl::UNSIGNED_INT => "UNSIGNED_INT",
+        gl::UNSIGNED_SHORT => "UNSIGNED_SHORT",
+        gl::VERTEX_SHADER => "VERTEX_SHADER",
+        gl::FRAGMENT_SHADER => "FRAGMENT_SHADER",
+        gl::TEXTURE_2D => "TEXTURE_2D",
+        gl::TEXTURE_2D_MULTISAMPLE => "TEXTURE_2D_MULTISAMPLE",
+        gl::TEXTURE_MIN_FILTER => "TEXTURE_MIN_FILTER",
+        gl::TEXTURE_MAG_FILTER => "TEXTURE_MAG_FILTER",
+        gl::TEXTURE_WRAP_S => "TEXTURE_WRAP_S",
+        gl::TEXTURE_WRAP_T => "TEXTURE_WRAP_T",
+        gl::TEXTURE_WRAP_R => "TEXTURE_WRAP_R",
+        gl::TEXTURE_MAX_LEVEL => "TEXTURE_MAX_LEVEL",
+        gl::TEXTURE_CUBE_MAP => "TEXTURE_CUBE_MAP",
+        gl::TEXTURE_CUBE_MAP_POSITIVE_X => "TEXTURE_CUBE_MAP_POSITIVE_X",
+        gl::TEXTURE_CUBE_MAP_NEGATIVE_X => "TEXTURE_CUBE_MAP_NEGATIVE_X",
+        gl::TEXTURE_CUBE_MAP_POSITIVE_Y => "TEXTURE_CUBE_MAP_POSITIVE_Y",
+        gl::TEXTURE_CUBE_MAP_NEGATIVE_Y => "TEXTURE_CUBE_MAP_NEGATIVE_Y",
+        gl::TEXTURE_CUBE_MAP_POSITIVE_Z => "TEXTURE_CUBE_MAP_POSITIVE_Z",
+        gl::TEXTURE_CUBE_MAP_NEGATIVE_Z => "TEXTURE_CUBE_MAP_NEGATIVE_Z",
+        gl::NEAREST => "NEAREST",
+        gl::LINEAR => "LINEAR",
+        gl::LINEAR_MIPMAP_LINEAR => "LINEAR_MIPMAP_LINEAR",
+        gl::REPEAT => "REPEAT",
+        gl::CLAMP_TO_EDGE => "CLAMP_TO_EDGE",
+        gl::MIRRORED_REPEAT => "MIRRORED_REPEAT",
+        gl::RED => "RED",
+        gl::RG => "RG",
+        gl::RGB => "RGB",
+        gl::RGBA => "RGBA",
+        gl::RGBA8 => "RGBA8",
+        gl::RG16F => "RG16F",
+        gl::RGB16F => "RGB16F",
+        gl::RGBA16F => "RGBA16F",
+        gl::RGB32F => "RGB32F",
+        gl::R32UI => "R32UI",
+        gl::RED_INTEGER => "RED_INTEGER",
+        gl::DEPTH_COMPONENT24 => "DEPTH_COMPONENT24",
+        gl::DEPTH_COMPONENT32F => "DEPTH_COMPONENT32F",
+        gl::DEPTH24_STENCIL8 => "DEPTH24_STENCIL8",
+        gl::TEXTURE_COMPARE_MODE => "TEXTURE_COMPARE_MODE",
+        gl::TEXTURE_COMPARE_FUNC => "TEXTURE_COMPARE_FUNC",
+        gl::COMPARE_REF_TO_TEXTURE => "COMPARE_REF_TO_TEXTURE",
+        gl::LEQUAL => "LEQUAL",
+        gl::COLOR_ATTACHMENT0 => "COLOR_ATTACHMENT0",
+        gl::COLOR_ATTACHMENT1 => "COLOR_ATTACHMENT1",
+        gl::COLOR_ATTACHMENT2 => "COLOR_ATTACHMENT2",
+        gl::DEPTH_ATTACHMENT => "DEPTH_ATTACHMENT",
+        gl::DEPTH_STENCIL_ATTACHMENT => "DEPTH_STENCIL_ATTACHMENT",
+        gl::BUFFER => "BUFFER",
+        gl::PROGRAM => "PROGRAM",
+        gl::TEXTURE => "TEXTURE",
+        gl::VERTEX_ARRAY => "VERTEX_ARRAY",
+        gl::FRAMEBUFFER => "FRAMEBUFFER",
+        gl::RENDERBUFFER => "RENDERBUFFER",
+        _ => return format!("{:#x}", e),
+    };
+    name.to_string()
+}
+
+#[derive(Default)]
+struct Recording {
+    calls: Vec<String>,
+    last_id: u32,
+    pixel_uint: u32,
+    uniform_types: HashMap<String, GLenum>,
+    debug_output: bool,
+    errors: VecDeque<GLenum>,
+}
+
+/// `GraphicsDevice` that needs no GL: it hands out ids and records every call as text,
+/// e.g. `BindBuffer(ARRAY_BUFFER, 1)`. Clones share the same recording.
+#[derive(Clone, Default)]
+pub struct RecordingDevice {
+    recording: Arc<Mutex<Recording>>,
+}
+
+#[allow(dead_code)]
+impl RecordingDevice {
+    /// What `max_samples` reports, a common desktop limit.
+    pub const MAX_SAMPLES: u32 = 8;
+
+    pub fn new() -> RecordingDevice {
+        Default::default()
+    }
+
+    /// Calls recorded so far.
+    pub fn calls(&self) -> Vec<String> {
+        self.lock().calls.clone()
+    }
+
+    /// Returns and clears the calls recorded so far.
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut self.lock().calls)
+    }
+
+    /// Sets what `read_pixel_uint` returns.
+    pub fn set_pixel_uint(&self, value: u32) {
+        self.lock().pixel_uint = value;
+    }
+
+    /// Sets what `uniform_type` reports for `name`; unset uniforms report `None`.
+    pub fn set_uniform_type(&self, name: &str, ty: GLenum) {
+        self.lock().uniform_types.insert(name.to_string(), ty);
+    }
+
+    /// Sets what `has_debug_output` reports; off by default.
+    pub fn set_debug_output(&self, enabled: bool) {
+        self.lock().debug_output = enabled;
+    }
+
+    /// Queues an error for `get_error` to return.
+    pub fn push_error(&self, error: GLenum) {
+        self.lock().errors.push_back(error);
+    }
+
+    /// Recorded calls whose name is `name`, e.g. `"TexParameter"`.
+    pub fn calls_to(&self, name: &str) -> Vec<String> {
+        self.lock()
+            .calls
+            .iter()
+            .filter(|c| c.split('(').next() == Some(name))
+            .cloned()
+            .collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Recording> {
+        self.recording.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn record(&self, call: String) {
+        self.lock().calls.push(call);
+    }
+
+    fn next_id(&self, call: &str) -> u32 {
+        let mut recording = self.lock();
+        recording.last_id += 1;
+        let id = recording.last_id;
+        recording.calls.push(format!("{} -> {}", call, id));
+        id
+    }
+}
+
+impl StateBackend for RecordingDevice {
+    fn set_enabled(&self, cap: Capability, enabled: bool) {
+        let verb = if enabled { "Enable" } else { "Disable" };
+        self.record(format!("{}({:?})", verb, cap));
+    }
+
+    fn blend_func(&self, blend: &Blend) {
+        self.record(format!(
+            "BlendFunc({:?}, {:?})",
+            blend.src_color, blend.dst_color
+        ));
+    }
+
+    fn depth_func(&self, func: CompareFunc) {
+        self.record(format!("DepthFunc({:?})", func));
+    }
+
+    fn depth_mask(&self, write: bool) {
+        self.record(format!("DepthMask({})", write));
+    }
+
+    fn cull_face(&self, face: Face) {
+        self.record(format!("CullFace({:?})", face));
+    }
+
+    fn front_face(&self, front_face: FrontFace) {
+        self.record(format!("FrontFace({:?})", front_face));
+    }
+
+    fn scissor(&self, rect: Rect) {
+        self.record(format!("Scissor({}, {})", rect.width, rect.height));
+    }
+
+    fn color_mask(&self, mask: [bool; 4]) {
+        self.record(format!("ColorMask({:?})", mask));
+    }
+
+    fn stencil_func(&self, func: CompareFunc, reference: i32, _mask: u32) {
+        self.record(format!("StencilFunc({:?}, {})", func, reference));
+    }
+
+    fn stencil_op(&self, fail: StencilOp, depth_fail: StencilOp, pass: StencilOp) {
+        self.record(format!(
+            "StencilOp({:?}, {:?}, {:?})",
+            fail, depth_fail, pass
+        ));
+    }
+
+    fn stencil_mask(&self, mask: u32) {
+        self.record(format!("StencilMask({:#x})", mask));
+    }
+
+    fn polygon_mode(&self, mode: PolygonMode) {
+        self.record(format!("PolygonMode({:?})", mode));
+    }
+
+    fn clear_color(&self, color: [f32; 4]) {
+        self.record(format!("ClearColor({:?})", color));
+    }
+
+    fn viewport(&self, rect: Rect) {
+        self.record(format!("Viewport({}, {})", rect.width, rect.height));
+    }
+}
+
+impl GraphicsDevice for RecordingDevice {
+    fn gen_buffer(&self) -> u32 {
+        self.next_id("GenBuffer")
+    }
+
+    fn bind_buffer(&self, target: GLenum, id: u32) {
+        self.record(format!("BindBuffer({}, {})", enum_name(target), id));
+    }
+
+    fn buffer_data(&self, target: GLenum, size: usize, data: Option<&[u8]>, usage: GLenum) {
+        let init = if data.is_some() { "data" } else { "null" };
+        self.record(format!(
+            "BufferData({}, {}, {}, {})",
+            enum_name(target),
+            size,
+            init,
+            enum_name(usage)
+        ));
+    }
+
+    fn buffer_sub_data(&self, target: GLenum, offset: usize, data: &[u8]) {
+        self.record(format!(
+            "BufferSubData({}, {}, {})",
+            enum_name(target),
+            offset,
+            data.len()
+        ));
+    }
+
+    fn bind_buffer_base(&self, target: GLenum, index: u32, id: u32) {
+        self.record(format!(
+            "BindBufferBase({}, {}, {})",
+            enum_name(target),
+            index,
+            id
+        ));
+    }
+
+    fn vertex_attrib(&self, index: u32, size: i32, stride: usize, offset: usize) {
+        self.record(format!(
+            "VertexAttrib({}, {}, {}, {})",
+            index, size, stride, offset
+        ));
+    }
+
+    fn gen_vertex_array(&self) -> u32 {
+        self.next_id("GenVertexArray")
+    }
+
+    fn bind_vertex_array(&self, id: u32) {
+        self.record(format!("BindVertexArray({})", id));
+    }
+
+    fn draw_arrays(&self, mode: GLenum, first: i32, count: i32) {
+        self.record(format!(
+            "DrawArrays({}, {}, {})",
+            enum_name(mode),
+            first,
+            count
+        ));
+    }
+
+    fn draw_elements(&self, mode: GLenum, count: i32, index_type: GLenum, offset: usize) {
+        self.record(format!(
+            "DrawElements({}, {}, {}, {})",
+            enum_name(mode),
+            count,
+            enum_name(index_type),
+            offset
+        ));
+    }
+
+    fn compile_shader(&self, stage: GLenum, _source: &str) -> Result<u32, String> {
+        Ok(self.next_id(&format!("CompileShader({})", enum_name(stage))))
+    }
+
+    fn delete_shader(&self, shader: u32) {
+        self.record(format!("DeleteShader({})", shader));
+    }
+
+    fn create_program(&self) -> u32 {
+        self.next_id("CreateProgram")
+    }
+
+    fn attach_shader(&self, program: u32, shader: u32) {
+        self.record(format!("AttachShader({}, {})", program, shader));
+    }
+
+    fn link_program(&self, program: u32) -> Result<(), String> {
+        self.record(format!("LinkProgram({})", program));
+        Ok(())
+    }
+
+    fn use_program(&self, program: u32) {
+        self.record(format!("UseProgram({})", program));
+    }
+
+    fn uniform_location(&self, program: u32, name: &str) -> Option<i32> {
+        self.record(format!("GetUniformLocation({}, {})", program, name));
+        Some(0)
+    }
+
+    fn uniform_type(&self, program: u32, name: &str) -> Option<GLenum> {
+        self.record(format!("GetUniformType({}, {})", program, name));
+        self.lock().uniform_types.get(name).copied()
+    }
+
+    fn uniform_block_binding(&self, program: u32, name: &str, binding: u32) -> bool {
+        self.record(format!(
+            "UniformBlockBinding({}, {}, {})",
+            program, name, binding
+        ));
+        true
+    }
+
+    fn uniform_int(&self, location: i32, value: i32) {
+        self.record(format!("Uniform1i({}, {})", location, value));
+    }
+
+    fn uniform_float(&self, location: i32, value: f32) {
+        self.record(format!("Uniform1f({}, {:?})", location, value));
+    }
+
+    fn uniform_vec2(&self, location: i32, value: &[f32; 2]) {
+        self.record(format!("Uniform2fv({}, {:?})", location, value));
+    }
+
+    fn uniform_vec3(&self, location: i32, value: &[f32; 3]) {
+        self.record(format!("Uniform3fv({}, {:?})", location, value));
+    }
+
+    fn uniform_vec4(&self, location: i32, value: &[f32; 4]) {
+        self.record(format!("Uniform4fv({}, {:?})", location, value));
+    }
+
+    fn uniform_mat4(&self, location: i32, _value: &[f32; 16]) {
+        self.record(format!("UniformMatrix4fv({})", location));
+    }
+
+    fn gen_texture(&self) -> u32 {
+        self.next_id("GenTexture")
+    }
+
+    fn active_texture(&self, unit: u32) {
+        self.record(format!("ActiveTexture({})", unit));
+    }
+
+    fn bind_texture(&self, target: GLenum, id: u32) {
+        self.record(format!("BindTexture({}, {})", enum_name(target), id));
+    }
+
+    fn tex_parameter(&self, target: GLenum, name: GLenum, value: GLenum) {
+        let value = match name {
+            gl::TEXTURE_MAX_LEVEL => value.to_string(),
+            _ => enum_name(value),
+        };
+        self.record(format!(
+            "TexParameter({}, {}, {})",
+            enum_name(target),
+            enum_name(name),
+            value
+        ));
+    }
+
+    fn tex_image_2d(&self, target: GLenum, format: GLenum, width: u32, height: u32, data: &[u8]) {
+        self.record(format!(
+            "TexImage2D({}, {}, {}x{}, {} bytes)",
+            enum_name(target),
+            enum_name(format),
+            width,
+            height,
+            data.len()
+        ));
+    }
+
+    fn tex_image_float(
+        &self,
+        target: GLenum,
+        level: u32,
+        internal_format: GLenum,
+        width: u32,
+        height: u32,
+        data: Option<&[f32]>,
+    ) {
+        let init = if data.is_some() { "data" } else { "null" };
+        self.record(format!(
+            "TexImageFloat({}, {}, {}, {}x{}, {})",
+            enum_name(target),
+            level,
+            enum_name(internal_format),
+            width,
+            height,
+            init
+        ));
+    }
+
+    fn generate_mipmap(&self, target: GLenum) {
+        self.record(format!("GenerateMipmap({})", enum_name(target)));
+    }
+
+    fn gen_framebuffer(&self) -> u32 {
+        self.next_id("GenFramebuffer")
+    }
+
+    fn bind_framebuffer(&self, id: u32) {
+        self.record(format!("BindFramebuffer({})", id));
+    }
+
+    fn framebuffer_texture(&self, attachment: GLenum, target: GLenum, texture: u32, level: u32) {
+        self.record(format!(
+            "FramebufferTexture({}, {}, {}, {})",
+            enum_name(attachment),
+            enum_name(target),
+            texture,
+            level
+        ));
+    }
+
+    fn gen_renderbuffer(&self) -> u32 {
+        self.next_id("GenRenderbuffer")
+    }
+
+    fn renderbuffer_storage(&self, id: u32, internal_format: GLenum, width: u32, height: u32) {
+        self.record(format!(
+            "RenderbufferStorage({}, {}, {}x{})",
+            id,
+            enum_name(internal_format),
+            width,
+            height
+        ));
+    }
+
+    fn renderbuffer_storage_multisample(
+        &self,
+        id: u32,
+        samples: u32,
+        internal_format: GLenum,
+        width: u32,
+        height: u32,
+    ) {
+        self.record(format!(
+            "RenderbufferStorageMultisample({}, {}, {}, {}x{})",
+            id,
+            samples,
+            enum_name(internal_format),
+            width,
+            height
+        ));
+    }
+
+    fn tex_image_multisample(
+        &self,
+        samples: u32,
+        internal_format: GLenum,
+        width: u32,
+        height: u32,
+    ) {
+        self.record(format!(
+            "TexImageMultisample({}, {}, {}x{})",
+            samples,
+            enum_name(internal_format),
+            width,
+            height
+        ));
+    }
+
+    fn max_samples(&self) -> u32 {
+        RecordingDevice::MAX_SAMPLES
+    }
+
+    fn framebuffer_renderbuffer(&self, attachment: GLenum, renderbuffer: u32) {
+        self.record(format!(
+            "FramebufferRenderbuffer({}, {})",
+            enum_name(attachment),
+            renderbuffer
+        ));
+    }
+
+    fn draw_buffers(&self, attachments: &[GLenum]) {
+        let names: Vec<String> = attachments.iter().map(|&a| enum_name(a)).collect();
+        if names.is_empty() {
+            self.record("DrawBuffers(NONE)".to_string());
+        } else {
+            self.record(format!("DrawBuffers({})", names.join(", ")));
+        }
+    }
+
+    fn check_framebuffer_status(&self) -> GLenum {
+        gl::FRAMEBUFFER_COMPLETE
+    }
+
+    fn blit_framebuffer(
+        &self,
+        read: u32,
+        draw: u32,
+        src: Rect,
+        dst: Rect,
+        mask: GLenum,
+        filter: GLenum,
+    ) {
+        self.record(format!(
+            "BlitFramebuffer({} -> {}, {}x{} -> {}x{}, {:#x}, {})",
+            read,
+            draw,
+            src.width,
+            src.height,
+            dst.width,
+            dst.height,
+            mask,
+            enum_name(filter)
+        ));
+    }
+
+    fn clear(&self, mask: GLenum) {
+        self.record(format!("Clear({:#x})", mask));
+    }
+
+    fn clear_color_uint(&self, draw_buffer: u32, value: u32) {
+        self.record(format!("ClearColorUint({}, {})", draw_buffer, value));
+    }
+
+    fn read_pixels(&self, x: i32, y: i32, width: u32, height: u32) -> Vec<u8> {
+        self.record(format!("ReadPixels({}, {}, {}x{})", x, y, width, height));
+        vec![0; width as usize * height as usize * 4]
+    }
+
+    fn read_pixel_uint(&self, x: i32, y: i32) -> u32 {
+        self.record(format!("ReadPixelUint({}, {})", x, y));
+        self.lock().pixel_uint
+    }
+
+    fn has_debug_output(&self) -> bool {
+        self.lock().debug_output
+    }
+
+    fn debug_message_callback(&self, callback: GLDEBUGPROC, _user_param: *const c_void) {
+        let callback = if callback.is_some() {
+            "callback"
+        } else {
+            "null"
+        };
+        self.record(format!("DebugMessageCallback({})", callback));
+    }
+
+    fn get_error(&self) -> GLenum {
+        self.lock().errors.pop_front().unwrap_or(gl::NO_ERROR)
+    }
+
+    fn object_label(&self, identifier: GLenum, id: u32, label: &str) {
+        self.record(format!(
+            "ObjectLabel({}, {}, {:?})",
+            enum_name(identifier),
+            id,
+            label
+        ));
+    }
+
+    fn push_debug_group(&self, name: &str) {
+        self.record(format!("PushDebugGroup({:?})", name));
+    }
+
+    fn pop_debug_group(&self) {
+        self.record("PopDebugGroup()".to_string());
+    }
+
+    fn delete(&self, kind: ResourceKind, id: u32) {
+        self.record(format!("Delete({:?}, {})", kind, id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_calls_and_hands_out_ids() {
+        let device = RecordingDevice::new();
+        let shared = device.clone();
+        let buf = device.gen_buffer();
+        let vao = device.gen_vertex_array();
+        device.bind_buffer(gl::ARRAY_BUFFER, buf);
+        device.tex_parameter(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT);
+        device.clear(gl::COLOR_BUFFER_BIT);
+
+        assert_eq!((buf, vao), (1, 2));
+        assert_eq!(
+            shared.take(),
+            vec![
+                "GenBuffer -> 1",
+                "GenVertexArray -> 2",
+                "BindBuffer(ARRAY_BUFFER, 1)",
+                "TexParameter(TEXTURE_2D, TEXTURE_WRAP_S, REPEAT)",
+                "Clear(0x4000)",
+            ]
+        );
+        assert!(device.calls().is_empty());
+    }
+
+    #[test]
+    fn filters_calls_by_name() {
+        let device = RecordingDevice::new();
+        device.bind_texture(gl::TEXTURE_2D, 3);
+        device.active_texture(1);
+        device.bind_texture(gl::TEXTURE_2D, 0);
+        assert_eq!(
+            device.calls_to("BindTexture"),
+            vec!["BindTexture(TEXTURE_2D, 3)", "BindTexture(TEXTURE_2D, 0)"]
+        );
+    }
+}
@@ -0,0 +1,527 @@
+use gl::types::*;
+
+use super::device::GraphicsDevice;
+use super::resource::{self, ContextHandle, ResourceKind};
+use super::state::Rect;
+use super::texture::{MultisampleTexture, Texture2D, TextureCube};
+
+/// Fails when `samples` exceeds what `device` supports for multisampled attachments.
+pub fn check_samples(device: &dyn GraphicsDevice, samples: u32) -> Result<(), String> {
+    let max = device.max_samples();
+    if samples > max {
+        return Err(format!(
+            "{} MSAA samples exceed GL_MAX_SAMPLES of {}",
+            samples, max
+        ));
+    }
+    Ok(())
+}
+
+/// Storage for an attachment that is rendered to but never sampled, e.g. a depth buffer.
+pub struct Renderbuffer {
+    id: u32,
+    ctx: ContextHandle,
+}
+
+impl Drop for Renderbuffer {
+    fn drop(&mut self) {
+        self.ctx.release(ResourceKind::Renderbuffer, self.id);
+    }
+}
+
+#[allow(dead_code)]
+impl Renderbuffer {
+    pub fn new(internal_format: GLenum, width: u32, height: u32) -> Result<Renderbuffer, String> {
+        let ctx = ContextHandle::current()?;
+        let device = ctx.device();
+        let id = device.gen_renderbuffer();
+        device.renderbuffer_storage(id, internal_format, width, height);
+        ctx.created(ResourceKind::Renderbuffer, id);
+        Ok(Renderbuffer { id, ctx })
+    }
+
+    /// Renderbuffer with `samples` samples per pixel, resolved with `Framebuffer::blit`.
+    /// Fails when `samples` exceeds `GL_MAX_SAMPLES`.
+    pub fn multisample(
+        internal_format: GLenum,
+        width: u32,
+        height: u32,
+        samples: u32,
+    ) -> Result<Renderbuffer, String> {
+        let ctx = ContextHandle::current()?;
+        let device = ctx.device();
+        check_samples(device, samples)?;
+        let id = device.gen_renderbuffer();
+        device.renderbuffer_storage_multisample(id, samples, internal_format, width, height);
+        ctx.created(ResourceKind::Renderbuffer, id);
+        Ok(Renderbuffer { id, ctx })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+/// Offscreen render target with texture and renderbuffer attachments.
+pub struct Framebuffer {
+    id: u32,
+    width: u32,
+    height: u32,
+    renderbuffers: Vec<Renderbuffer>,
+    ctx: ContextHandle,
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        self.ctx.release(ResourceKind::Framebuffer, self.id);
+    }
+}
+
+#[allow(dead_code)]
+impl Framebuffer {
+    /// Framebuffer without attachments, rendered to at `width` x `height`.
+    pub fn new(width: u32, height: u32) -> Result<Framebuffer, String> {
+        let ctx = ContextHandle::current()?;
+        let id = ctx.device().gen_framebuffer();
+        ctx.created(ResourceKind::Framebuffer, id);
+        Ok(Framebuffer {
+            id,
+            width,
+            height,
+            renderbuffers: Vec::new(),
+            ctx,
+        })
+    }
+
+    /// Adds a 24 bit depth renderbuffer the size of the framebuffer.
+    pub fn with_depth(self) -> Result<Framebuffer, String> {
+        let depth = Renderbuffer::new(gl::DEPTH_COMPONENT24, self.width, self.height)?;
+        Ok(self.with_renderbuffer(gl::DEPTH_ATTACHMENT, depth))
+    }
+
+    /// Adds a 24 bit depth renderbuffer with `samples` samples per pixel, to pair with
+    /// multisampled color attachments.
+    pub fn with_multisample_depth(self, samples: u32) -> Result<Framebuffer, String> {
+        let depth =
+            Renderbuffer::multisample(gl::DEPTH_COMPONENT24, self.width, self.height, samples)?;
+        Ok(self.with_renderbuffer(gl::DEPTH_ATTACHMENT, depth))
+    }
+
+    /// Attaches `renderbuffer` at `attachment` and keeps it alive as long as the framebuffer.
+    pub fn with_renderbuffer(
+        mut self,
+        attachment: GLenum,
+        renderbuffer: Renderbuffer,
+    ) -> Framebuffer {
+        self.binding(|| {
+            self.ctx
+                .device()
+                .framebuffer_renderbuffer(attachment, renderbuffer.id())
+        });
+        self.renderbuffers.push(renderbuffer);
+        self
+    }
+
+    /// Names the object in debug messages and graphics debuggers.
+    pub fn set_label(&self, label: &str) {
+        self.ctx
+            .device()
+            .object_label(gl::FRAMEBUFFER, self.id, label);
+        resource::track_label(ResourceKind::Framebuffer, self.id, label);
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Attaches mip `level` of `texture`, e.g. at `COLOR_ATTACHMENT0`.
+    pub fn attach_texture(&self, attachment: GLenum, texture: &Texture2D, level: u32) {
+        self.binding(|| {
+            self.ctx
+                .device()
+                .framebuffer_texture(attachment, gl::TEXTURE_2D, texture.id(), level)
+        });
+    }
+
+    /// Attaches mip `level` of face `face`, indexing `TextureCube::FACES`.
+    pub fn attach_cube_face(
+        &self,
+        attachment: GLenum,
+        cube: &TextureCube,
+        face: usize,
+        level: u32,
+    ) {
+        self.binding(|| {
+            self.ctx.device().framebuffer_texture(
+                attachment,
+                TextureCube::FACES[face],
+                cube.id(),
+                level,
+            )
+        });
+    }
+
+    /// Attaches a multisampled texture, e.g. at `COLOR_ATTACHMENT0`. Every attachment of a
+    /// framebuffer needs the same sample count.
+    pub fn attach_multisample_texture(&self, attachment: GLenum, texture: &MultisampleTexture) {
+        self.binding(|| {
+            self.ctx.device().framebuffer_texture(
+                attachment,
+                gl::TEXTURE_2D_MULTISAMPLE,
+                texture.id(),
+                0,
+            )
+        });
+    }
+
+    /// Selects the color attachments draws write to; pass none for a depth-only target.
+    pub fn draw_buffers(&self, attachments: &[GLenum]) {
+        self.binding(|| self.ctx.device().draw_buffers(attachments));
+    }
+
+    /// Fails with the GL status when the attachments cannot be rendered to.
+    pub fn check(&self) -> Result<(), String> {
+        let device = self.ctx.device();
+        device.bind_framebuffer(self.id);
+        let status = device.check_framebuffer_status();
+        device.bind_framebuffer(self.ctx.screen_framebuffer());
+        if status == gl::FRAMEBUFFER_COMPLETE {
+            Ok(())
+        } else {
+            Err(format!(
+                "Framebuffer {} is incomplete: status {:#x}",
+                self.id, status
+            ))
+        }
+    }
+
+    /// Copies the `mask` buffers, e.g. `COLOR_BUFFER_BIT`, to `target`, or to the screen
+    /// framebuffer when `None`, stretching to the target's size. Multisampled buffers are
+    /// resolved on the way, which needs equal sizes; depth and stencil need `NEAREST`.
+    pub fn blit(&self, target: Option<&Framebuffer>, mask: GLenum, filter: GLenum) {
+        let (draw, width, height) = match target {
+            Some(fbo) => (fbo.id, fbo.width, fbo.height),
+            None => (self.ctx.screen_framebuffer(), self.width, self.height),
+        };
+        let rect = |width: u32, height: u32| Rect {
+            x: 0,
+            y: 0,
+            width: width as i32,
+            height: height as i32,
+        };
+        let device = self.ctx.device();
+        device.blit_framebuffer(
+            self.id,
+            draw,
+            rect(self.width, self.height),
+            rect(width, height),
+            mask,
+            filter,
+        );
+        device.bind_framebuffer(self.ctx.screen_framebuffer());
+    }
+
+    /// Resolves the color of a multisampled framebuffer into `target`, or the screen
+    /// framebuffer when `None`.
+    pub fn resolve(&self, target: Option<&Framebuffer>) {
+        self.blit(target, gl::COLOR_BUFFER_BIT, gl::NEAREST);
+    }
+
+    /// Value of the pixel at `x`, `y`, counted from the bottom-left, in the first color
+    /// attachment, which must have an unsigned integer format such as `R32UI`.
+    pub fn read_uint(&self, x: i32, y: i32) -> u32 {
+        let mut value = 0;
+        self.binding(|| value = self.ctx.device().read_pixel_uint(x, y));
+        value
+    }
+
+    /// Directs draws here until `unbind`.
+    pub fn bind(&self) {
+        self.ctx.device().bind_framebuffer(self.id);
+    }
+
+    /// Directs draws back to the window, or to the post-processing input while a frame
+    /// renders into it.
+    pub fn unbind(&self) {
+        self.ctx
+            .device()
+            .bind_framebuffer(self.ctx.screen_framebuffer());
+    }
+
+    pub fn binding<F>(&self, cb: F)
+    where
+        F: FnOnce(),
+    {
+        self.bind();
+        cb();
+        self.unbind();
+    }
+}
+
+/// Framebuffer with one color texture, and optionally a depth renderbuffer, of one size.
+pub struct RenderTarget {
+    // Declared first so the framebuffer is deleted before its attachments.
+    fbo: Framebuffer,
+    color: Texture2D,
+}
+
+#[allow(dead_code)]
+impl RenderTarget {
+    /// Linearly filtered, edge clamped color texture of `internal_format`, e.g. `RGBA16F`.
+    pub fn new(
+        width: u32,
+        height: u32,
+        internal_format: GLenum,
+        depth: bool,
+    ) -> Result<RenderTarget, String> {
+        let color = Texture2D::from_float(
+            width,
+            height,
+            internal_format,
+            None,
+            gl::LINEAR,
+            gl::CLAMP_TO_EDGE,
+        )?;
+        let mut fbo = Framebuffer::new(width, height)?;
+        if depth {
+            fbo = fbo.with_depth()?;
+        }
+        fbo.attach_texture(gl::COLOR_ATTACHMENT0, &color, 0);
+        fbo.check()?;
+        Ok(RenderTarget { fbo, color })
+    }
+
+    /// Names the framebuffer and its color texture.
+    pub fn set_label(&self, label: &str) {
+        self.fbo.set_label(label);
+        self.color.set_label(&format!("{} color", label));
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.fbo
+    }
+
+    pub fn color(&self) -> &Texture2D {
+        &self.color
+    }
+
+    pub fn width(&self) -> u32 {
+        self.fbo.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.fbo.height()
+    }
+
+    pub fn bind(&self) {
+        self.fbo.bind();
+    }
+
+    pub fn unbind(&self) {
+        self.fbo.unbind();
+    }
+}
+
+/// Framebuffer with a multisampled color renderbuffer, and optionally a multisampled
+/// depth renderbuffer, of one size. Render into it, then `resolve` it to a single-sample
+/// target or the screen.
+#[allow(dead_code)]
+pub struct MultisampleTarget {
+    fbo: Framebuffer,
+    samples: u32,
+}
+
+#[allow(dead_code)]
+impl MultisampleTarget {
+    /// Fails when `samples` exceeds `GL_MAX_SAMPLES` or the attachments are incomplete.
+    pub fn new(
+        width: u32,
+        height: u32,
+        internal_format: GLenum,
+        samples: u32,
+        depth: bool,
+    ) -> Result<MultisampleTarget, String> {
+        let color = Renderbuffer::multisample(internal_format, width, height, samples)?;
+        let mut fbo =
+            Framebuffer::new(width, height)?.with_renderbuffer(gl::COLOR_ATTACHMENT0, color);
+        if depth {
+            fbo = fbo.with_multisample_depth(samples)?;
+        }
+        fbo.check()?;
+        Ok(MultisampleTarget { fbo, samples })
+    }
+
+    pub fn set_label(&self, label: &str) {
+        self.fbo.set_label(label);
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.fbo
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    pub fn width(&self) -> u32 {
+        self.fbo.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.fbo.height()
+    }
+
+    pub fn bind(&self) {
+        self.fbo.bind();
+    }
+
+    pub fn unbind(&self) {
+        self.fbo.unbind();
+    }
+
+    /// Resolves the color into `target`, which must have the same size.
+    pub fn resolve(&self, target: &RenderTarget) {
+        self.fbo.resolve(Some(target.framebuffer()));
+    }
+
+    /// Resolves the color into the screen framebuffer.
+    pub fn resolve_to_screen(&self) {
+        self.fbo.resolve(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::device::RecordingDevice;
+    use crate::core::resource::ContextOwner;
+
+    #[test]
+    fn attaches_textures_cube_faces_and_depth() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let cube = TextureCube::new(16, gl::RGB16F, 1).unwrap();
+        let fbo = Framebuffer::new(16, 16).unwrap().with_depth().unwrap();
+        device.take();
+
+        fbo.attach_cube_face(gl::COLOR_ATTACHMENT0, &cube, 3, 0);
+        assert!(fbo.check().is_ok());
+        assert_eq!(
+            device.take(),
+            vec![
+                "BindFramebuffer(2)",
+                "FramebufferTexture(COLOR_ATTACHMENT0, TEXTURE_CUBE_MAP_NEGATIVE_Y, 1, 0)",
+                "BindFramebuffer(0)",
+                "BindFramebuffer(2)",
+                "BindFramebuffer(0)",
+            ]
+        );
+
+        drop(fbo);
+        assert_eq!(
+            device.take(),
+            vec!["Delete(Framebuffer, 2)", "Delete(Renderbuffer, 3)"]
+        );
+    }
+
+    #[test]
+    fn resolves_multisampled_color_into_a_render_target() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let msaa = MultisampleTarget::new(64, 32, gl::RGBA16F, 4, true).unwrap();
+        let resolved = RenderTarget::new(64, 32, gl::RGBA16F, false).unwrap();
+        assert_eq!(
+            device.calls_to("RenderbufferStorageMultisample"),
+            vec![
+                "RenderbufferStorageMultisample(1, 4, RGBA16F, 64x32)",
+                "RenderbufferStorageMultisample(3, 4, DEPTH_COMPONENT24, 64x32)",
+            ]
+        );
+        assert_eq!(
+            device.calls_to("FramebufferRenderbuffer"),
+            vec![
+                "FramebufferRenderbuffer(COLOR_ATTACHMENT0, 1)",
+                "FramebufferRenderbuffer(DEPTH_ATTACHMENT, 3)",
+            ]
+        );
+        device.take();
+
+        msaa.resolve(&resolved);
+        msaa.resolve_to_screen();
+        assert_eq!(
+            device.take(),
+            vec![
+                format!(
+                    "BlitFramebuffer(2 -> {}, 64x32 -> 64x32, 0x4000, NEAREST)",
+                    resolved.framebuffer().id()
+                ),
+                "BindFramebuffer(0)".to_string(),
+                "BlitFramebuffer(2 -> 0, 64x32 -> 64x32, 0x4000, NEAREST)".to_string(),
+                "BindFramebuffer(0)".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_more_samples_than_the_device_supports() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let max = RecordingDevice::MAX_SAMPLES;
+
+        assert!(Renderbuffer::multisample(gl::RGBA8, 4, 4, max).is_ok());
+        assert!(Renderbuffer::multisample(gl::RGBA8, 4, 4, max * 2).is_err());
+        assert!(MultisampleTarget::new(4, 4, gl::RGBA8, max * 2, false).is_err());
+        assert!(MultisampleTexture::new(4, 4, gl::RGBA8, max * 2).is_err());
+
+        let texture = MultisampleTexture::new(4, 4, gl::RGBA8, 2).unwrap();
+        let fbo = Framebuffer::new(4, 4)
+            .unwrap()
+            .with_multisample_depth(2)
+            .unwrap();
+        device.take();
+        fbo.attach_multisample_texture(gl::COLOR_ATTACHMENT0, &texture);
+        assert_eq!(
+            device.calls_to("FramebufferTexture"),
+            vec![format!(
+                "FramebufferTexture(COLOR_ATTACHMENT0, TEXTURE_2D_MULTISAMPLE, {}, 0)",
+                texture.id()
+            )]
+        );
+    }
+
+    #[test]
+    fn unbind_returns_to_the_screen_framebuffer() {
+        let device = RecordingDevice::new();
+        let owner = ContextOwner::with_device(Box::new(device.clone()));
+        let target = RenderTarget::new(8, 8, gl::RGBA16F, true).unwrap();
+        let fbo = Framebuffer::new(8, 8).unwrap();
+        owner
+            .handle()
+            .set_screen_framebuffer(target.framebuffer().id());
+        device.take();
+
+        fbo.binding(|| {});
+        assert_eq!(
+            device.take(),
+            vec![
+                format!("BindFramebuffer({})", fbo.id()),
+                format!("BindFramebuffer({})", target.framebuffer().id()),
+            ]
+        );
+    }
+
+    #[test]
+    fn fails_without_a_context() {
+        assert!(Framebuffer::new(4, 4).is_err());
+        assert!(Renderbuffer::new(gl::RGBA8, 4, 4).is_err());
+        assert!(RenderTarget::new(4, 4, gl::RGBA8, true).is_err());
+    }
+}
@@ -1,8 +1,21 @@
+use std::cell::{RefCell, RefMut};
 use std::default::Default;
 use std::marker::PhantomData;
+use std::path::PathBuf;
 use std::sync::mpsc::Receiver;
 
+use super::action::ActionMap;
 use super::app::App;
+use super::debug::{self, DebugFilter, Debugger};
+use super::device::GraphicsDevice;
+use super::framebuffer::check_samples;
+use super::headless::{self, Golden, Headless};
+use super::input::Input;
+use super::postprocess::PostProcess;
+use super::resource::{self, ContextOwner};
+use super::state::{Capability, Rect, RenderState, StateCache};
+use super::time::{Clock, FrameTime, SystemClock};
+use super::window::{self, DisplayMode, WindowConfig};
 use imgui_glfw_rs::glfw;
 use imgui_glfw_rs::imgui;
 use imgui_glfw_rs::ImguiGLFW;
@@ -13,20 +26,54 @@ pub struct ImGui {
 }
 
 pub struct Context {
+    // Dropped first, while the context that owns its targets is still current.
+    post: Option<RefCell<PostProcess>>,
+    // Dropped before the window so the debug callback is removed and queued
+    // deletions run while the GL context still exists.
+    debug: Debugger,
+    resources: ContextOwner,
     glfw: glfw::Glfw,
     window: glfw::Window,
     events: Receiver<(f64, glfw::WindowEvent)>,
     width: u32,
     height: u32,
     imgui: Option<ImGui>,
+    state: RefCell<StateCache>,
+    input: Input,
+    actions: ActionMap,
+    time: FrameTime,
+    headless: Option<Headless>,
 }
 
 #[allow(dead_code)]
 impl Context {
     pub fn set_viewport(&self) {
-        unsafe {
-            gl::Viewport(0, 0, self.width as i32, self.height as i32);
-        }
+        self.set_viewport_size(self.width, self.height);
+    }
+
+    /// Viewport for an offscreen target; call `set_viewport` to return to the window.
+    pub fn set_viewport_size(&self, width: u32, height: u32) {
+        self.set_viewport_rect(Rect {
+            x: 0,
+            y: 0,
+            width: width as i32,
+            height: height as i32,
+        });
+    }
+
+    /// Viewport covering part of the target, e.g. one tile of a shadow atlas.
+    pub fn set_viewport_rect(&self, rect: Rect) {
+        self.state.borrow_mut().set_viewport(self.device(), rect);
+    }
+
+    /// Applies the fixed-function state a draw needs, skipping calls for state already set.
+    pub fn apply_state(&self, state: &RenderState) {
+        self.state.borrow_mut().apply(self.device(), state);
+    }
+
+    /// Call after changing GL state without going through `apply_state`.
+    pub fn invalidate_state(&self) {
+        self.state.borrow_mut().invalidate();
     }
 
     pub fn change_view(&mut self, width: u32, height: u32) {
@@ -36,9 +83,44 @@ impl Context {
     }
 
     pub fn clear_buffer(&self, mask: gl::types::GLenum) {
-        unsafe {
-            gl::Clear(mask);
-        }
+        self.device().clear(mask);
+    }
+
+    /// Device every GL call of this context goes through.
+    pub fn device(&self) -> &dyn GraphicsDevice {
+        self.resources.handle().device()
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn input(&self) -> &Input {
+        &self.input
+    }
+
+    pub fn debugger(&self) -> &Debugger {
+        &self.debug
+    }
+
+    pub fn time(&self) -> &FrameTime {
+        &self.time
+    }
+
+    pub fn time_mut(&mut self) -> &mut FrameTime {
+        &mut self.time
+    }
+
+    pub fn actions(&self) -> &ActionMap {
+        &self.actions
+    }
+
+    pub fn actions_mut(&mut self) -> &mut ActionMap {
+        &mut self.actions
     }
 
     pub fn aspect(&self) -> f32 {
@@ -47,9 +129,38 @@ impl Context {
     }
 
     pub fn set_clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
-        unsafe {
-            gl::ClearColor(r, g, b, a);
+        self.state
+            .borrow_mut()
+            .set_clear_color(self.device(), [r, g, b, a]);
+    }
+
+    /// Reads back the current framebuffer, top row first.
+    pub fn capture(&self) -> image::RgbaImage {
+        let pixels = self.device().read_pixels(0, 0, self.width, self.height);
+        headless::from_gl_pixels(self.width, self.height, &pixels)
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.headless.is_some()
+    }
+
+    // Captures the last headless frame and checks it against the golden image.
+    fn finish_headless(&mut self) -> Result<(), String> {
+        let headless = match self.headless {
+            Some(ref headless) if self.time.frame() >= headless.frames() => headless,
+            _ => return Ok(()),
+        };
+        match headless.finish(&self.capture())? {
+            Some(Golden::Matched) => log::info!("Frame matches the golden image"),
+            Some(Golden::Blessed) | None => {}
         }
+        self.window.set_should_close(true);
+        Ok(())
+    }
+
+    /// Post-processing stack, when enabled with `FrameworkBuilder::post_process`.
+    pub fn post_process(&self) -> Option<RefMut<'_, PostProcess>> {
+        self.post.as_ref().map(|post| post.borrow_mut())
     }
 
     pub fn ui_overlay<A>(&mut self, app: &A)
@@ -57,7 +168,11 @@ impl Context {
         A: App,
     {
         if let Some(ref mut imgui) = self.imgui {
-            let ui = app.ui_overlay(imgui.glfw.frame(&mut self.window, &mut imgui.ctx));
+            let ui = imgui.glfw.frame(&mut self.window, &mut imgui.ctx);
+            if let Some(ref post) = self.post {
+                post.borrow_mut().ui(&ui);
+            }
+            let ui = app.ui_overlay(ui);
             imgui.glfw.draw(ui, &mut self.window);
         }
     }
@@ -66,23 +181,28 @@ impl Context {
     where
         A: App,
     {
+        self.input.begin_frame();
         for (_, event) in glfw::flush_messages(&self.events) {
             // Imgui Event Hangle
             if let Some(ref mut imgui) = self.imgui {
                 imgui.glfw.handle_event(&mut imgui.ctx, &event);
             }
-            match event {
-                glfw::WindowEvent::FramebufferSize(width, height) => {
-                    self.width = width as u32;
-                    self.height = height as u32;
-                    app.resized(self, width as u32, height as u32)?
-                }
-                glfw::WindowEvent::Key(glfw::Key::Escape, _, glfw::Action::Press, _) => {
-                    self.window.set_should_close(true)
+            self.input.handle_event(&event);
+            app.event(self, &event)?;
+            if let glfw::WindowEvent::FramebufferSize(width, height) = event {
+                self.width = width as u32;
+                self.height = height as u32;
+                if let Some(ref post) = self.post {
+                    post.borrow_mut().resize(self.width, self.height)?;
                 }
-                _ => {}
+                app.resized(self, width as u32, height as u32)?
             }
         }
+        self.input.update_gamepads(&self.glfw);
+        self.actions.update(&self.input);
+        if self.actions.pressed("quit") {
+            self.window.set_should_close(true)
+        }
         Ok(())
     }
 
@@ -91,26 +211,67 @@ impl Context {
         A: App,
     {
         let mut app = A::new(&self);
+        self.time.resync();
 
         // Render loop
         while !self.window.should_close() {
+            // App fixed update
+            let steps = self.time.tick();
+            if let Some(step) = self.time.fixed_step() {
+                for _ in 0..steps {
+                    app.fixed_update(self, step.as_secs_f32())?;
+                }
+            }
+
             // App update
             app.update(&self)?;
 
             // App render
-            app.render(&self)?;
+            {
+                let _group = debug::group(self.resources.handle(), "App::render");
+                if let Some(ref post) = self.post {
+                    post.borrow().begin(self);
+                }
+                app.render(self, self.time.alpha())?;
+            }
+            self.debug.check("App::render");
+            if let Some(ref post) = self.post {
+                let _group = debug::group(self.resources.handle(), "Post-processing");
+                post.borrow().end(self);
+                self.debug.check("Post-processing");
+            }
+            self.finish_headless()?;
 
             // App UI Overlay
-            self.ui_overlay(&app);
+            {
+                let _group = debug::group(self.resources.handle(), "UI overlay");
+                self.ui_overlay(&app);
+            }
+            // The imgui renderer changes blend, cull, depth and scissor state behind our back.
+            if self.imgui.is_some() {
+                self.invalidate_state();
+            }
 
             // glfw: Swap buffers and poll IO events(key pressed/released, mouse moved etc.)
             glfw::Context::swap_buffers(&mut self.window);
             self.glfw.poll_events();
             self.process_events(&mut app)?;
+
+            // Delete GL objects dropped on other threads
+            self.resources.collect();
+
+            // Frame rate cap
+            match self.headless {
+                Some(ref headless) => headless.advance(),
+                None => self.time.limit(),
+            }
         }
 
         // Destroy Application
-        app.destroy(&self)
+        let result = app.destroy(self);
+        drop(app);
+        resource::report();
+        result
     }
 }
 
@@ -125,6 +286,14 @@ pub struct FrameworkBuilder<Framework> {
     width: u32,
     height: u32,
     use_imgui: bool,
+    post_process: bool,
+    bindings: Option<PathBuf>,
+    fixed_rate: Option<f64>,
+    frame_limit: Option<f64>,
+    clock: Option<Box<dyn Clock>>,
+    config: WindowConfig,
+    debug_filter: DebugFilter,
+    headless: Option<Headless>,
     state: PhantomData<Framework>,
 }
 
@@ -136,6 +305,14 @@ impl FrameworkBuilder<Empty> {
             width: Default::default(),
             height: Default::default(),
             use_imgui: false,
+            post_process: false,
+            bindings: None,
+            fixed_rate: None,
+            frame_limit: None,
+            clock: None,
+            config: Default::default(),
+            debug_filter: Default::default(),
+            headless: None,
             state: PhantomData,
         }
     }
@@ -151,6 +328,14 @@ impl FrameworkBuilder<Empty> {
             width: width,
             height: height,
             use_imgui: self.use_imgui,
+            post_process: self.post_process,
+            bindings: self.bindings,
+            fixed_rate: self.fixed_rate,
+            frame_limit: self.frame_limit,
+            clock: self.clock,
+            config: self.config,
+            debug_filter: self.debug_filter,
+            headless: self.headless,
             state: PhantomData,
         }
     }
@@ -164,34 +349,264 @@ impl FrameworkBuilder<Fully> {
             width: self.width,
             height: self.height,
             use_imgui: true,
-            state: PhantomData,
+            ..self
+        }
+    }
+
+    /// Renders `App::render` into an HDR target and runs the default `PostProcess`
+    /// passes over it on the way to the window. Pass controls show in the imgui overlay.
+    #[allow(dead_code)]
+    pub fn post_process(self) -> FrameworkBuilder<Fully> {
+        FrameworkBuilder {
+            post_process: true,
+            ..self
+        }
+    }
+
+    /// Loads input action bindings from a TOML file instead of the defaults.
+    #[allow(dead_code)]
+    pub fn bindings<P>(self, path: P) -> FrameworkBuilder<Fully>
+    where
+        P: Into<PathBuf>,
+    {
+        FrameworkBuilder {
+            bindings: Some(path.into()),
+            ..self
+        }
+    }
+
+    /// Calls `App::fixed_update` `hz` times per second of frame time.
+    #[allow(dead_code)]
+    pub fn fixed_rate(self, hz: f64) -> FrameworkBuilder<Fully> {
+        FrameworkBuilder {
+            fixed_rate: Some(hz),
+            ..self
+        }
+    }
+
+    /// Caps the frame rate, independent of vsync.
+    #[allow(dead_code)]
+    pub fn frame_limit(self, fps: f64) -> FrameworkBuilder<Fully> {
+        FrameworkBuilder {
+            frame_limit: Some(fps),
+            ..self
+        }
+    }
+
+    /// Replaces the wall clock driving `Context::time`.
+    #[allow(dead_code)]
+    pub fn clock(self, clock: Box<dyn Clock>) -> FrameworkBuilder<Fully> {
+        FrameworkBuilder {
+            clock: Some(clock),
+            ..self
         }
     }
 
+    /// Replaces all window and context options at once.
+    #[allow(dead_code)]
+    pub fn config(mut self, config: WindowConfig) -> FrameworkBuilder<Fully> {
+        self.config = config;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn gl_version(mut self, major: u32, minor: u32) -> FrameworkBuilder<Fully> {
+        self.config.gl_version = (major, minor);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn gl_profile(mut self, profile: glfw::OpenGlProfileHint) -> FrameworkBuilder<Fully> {
+        self.config.profile = profile;
+        self
+    }
+
+    /// Requests a debug context. Defaults to on in debug builds.
+    #[allow(dead_code)]
+    pub fn debug_context(mut self, debug: bool) -> FrameworkBuilder<Fully> {
+        self.config.debug_context = debug;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn resizable(mut self, resizable: bool) -> FrameworkBuilder<Fully> {
+        self.config.resizable = resizable;
+        self
+    }
+
+    /// Exclusive fullscreen on the n-th connected monitor at the window size.
+    #[allow(dead_code)]
+    pub fn fullscreen(mut self, monitor: usize) -> FrameworkBuilder<Fully> {
+        self.config.display = DisplayMode::Fullscreen { monitor };
+        self
+    }
+
+    /// Fullscreen on the n-th connected monitor at its current video mode.
+    #[allow(dead_code)]
+    pub fn borderless(mut self, monitor: usize) -> FrameworkBuilder<Fully> {
+        self.config.display = DisplayMode::Borderless { monitor };
+        self
+    }
+
+    /// MSAA samples of the default framebuffer; 0 disables multisampling. `build` fails
+    /// when the count exceeds `GL_MAX_SAMPLES`.
+    #[allow(dead_code)]
+    pub fn samples(mut self, samples: u32) -> FrameworkBuilder<Fully> {
+        self.config.samples = samples;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn vsync(mut self, vsync: bool) -> FrameworkBuilder<Fully> {
+        self.config.swap_interval = if vsync {
+            glfw::SwapInterval::Sync(1)
+        } else {
+            glfw::SwapInterval::None
+        };
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn swap_interval(mut self, interval: glfw::SwapInterval) -> FrameworkBuilder<Fully> {
+        self.config.swap_interval = interval;
+        self
+    }
+
+    /// Requests an sRGB capable default framebuffer and enables `GL_FRAMEBUFFER_SRGB`.
+    #[allow(dead_code)]
+    pub fn srgb(mut self, srgb: bool) -> FrameworkBuilder<Fully> {
+        self.config.srgb = srgb;
+        self
+    }
+
+    /// Depth buffer bits; `None` leaves the choice to the driver.
+    #[allow(dead_code)]
+    pub fn depth_bits(mut self, bits: Option<u32>) -> FrameworkBuilder<Fully> {
+        self.config.depth_bits = bits;
+        self
+    }
+
+    /// Stencil buffer bits; `None` leaves the choice to the driver.
+    #[allow(dead_code)]
+    pub fn stencil_bits(mut self, bits: Option<u32>) -> FrameworkBuilder<Fully> {
+        self.config.stencil_bits = bits;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn icon<P>(mut self, path: P) -> FrameworkBuilder<Fully>
+    where
+        P: Into<PathBuf>,
+    {
+        self.config.icon = Some(path.into());
+        self
+    }
+
+    /// Initial position of a windowed window in screen coordinates.
+    #[allow(dead_code)]
+    pub fn position(mut self, x: i32, y: i32) -> FrameworkBuilder<Fully> {
+        self.config.position = Some((x, y));
+        self
+    }
+
+    /// Selects which KHR_debug messages are logged.
+    #[allow(dead_code)]
+    pub fn debug_filter(mut self, filter: DebugFilter) -> FrameworkBuilder<Fully> {
+        self.debug_filter = filter;
+        self
+    }
+
+    /// Renders a fixed number of frames in a hidden window with a deterministic clock,
+    /// then checks the last frame against the golden image, if any.
+    #[allow(dead_code)]
+    pub fn headless(mut self, headless: Headless) -> FrameworkBuilder<Fully> {
+        self.config.visible = false;
+        self.config.swap_interval = glfw::SwapInterval::None;
+        self.clock = Some(Box::new(headless.clock()));
+        self.headless = Some(headless);
+        self
+    }
+
     pub fn build(self) -> Result<Context, String> {
+        let actions = match self.bindings {
+            Some(ref path) => ActionMap::load(path)?,
+            None => ActionMap::with_defaults(),
+        };
+        let mut time = FrameTime::new(self.clock.unwrap_or_else(|| Box::new(SystemClock::new())));
+        time.set_fixed_rate(self.fixed_rate);
+        time.set_frame_limit(self.frame_limit);
+
+        let config = &self.config;
+        config.validate()?;
+        let icon = match config.icon {
+            Some(ref path) => Some(window::load_icon(path)?),
+            None => None,
+        };
+        let glfw_error = || {
+            window::take_error()
+                .map(|e| format!(": {}", e))
+                .unwrap_or_default()
+        };
+
         // glfw: initialize and configure
-        let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
-        glfw.window_hint(glfw::WindowHint::ContextVersion(4, 1));
-        glfw.window_hint(glfw::WindowHint::OpenGlProfile(
-            glfw::OpenGlProfileHint::Core,
-        ));
-        glfw.window_hint(glfw::WindowHint::Resizable(false));
-        #[cfg(target_os = "macos")]
-        glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(true));
-        #[cfg(debug_assertions)]
-        glfw.window_hint(glfw::WindowHint::OpenGlDebugContext(true));
+        let mut glfw = glfw::init(window::RECORD_ERRORS)
+            .map_err(|e| format!("Failed to initialize GLFW ({:?}){}", e, glfw_error()))?;
+        config.hint(&mut glfw);
 
         // glfw window creation
-        let (mut window, events) = glfw
-            .create_window(
-                self.width,
-                self.height,
-                &self.title,
-                glfw::WindowMode::Windowed,
+        let (width, height, title) = (self.width, self.height, self.title.as_str());
+        let created = match config.display {
+            DisplayMode::Windowed => {
+                glfw.create_window(width, height, title, glfw::WindowMode::Windowed)
+            }
+            DisplayMode::Fullscreen { monitor } | DisplayMode::Borderless { monitor } => glfw
+                .with_connected_monitors_mut(|glfw, monitors| {
+                    let m = monitors.get(monitor).ok_or_else(|| {
+                        format!(
+                            "Monitor {} not found ({} connected)",
+                            monitor,
+                            monitors.len()
+                        )
+                    })?;
+                    let (width, height) = match config.display {
+                        DisplayMode::Borderless { .. } => {
+                            let mode = m.get_video_mode().ok_or_else(|| {
+                                format!("Could not query the video mode of monitor {}", monitor)
+                            })?;
+                            glfw.window_hint(glfw::WindowHint::RedBits(Some(mode.red_bits)));
+                            glfw.window_hint(glfw::WindowHint::GreenBits(Some(mode.green_bits)));
+                            glfw.window_hint(glfw::WindowHint::BlueBits(Some(mode.blue_bits)));
+                            glfw.window_hint(glfw::WindowHint::RefreshRate(Some(
+                                mode.refresh_rate,
+                            )));
+                            (mode.width, mode.height)
+                        }
+                        _ => (width, height),
+                    };
+                    Ok::<_, String>(glfw.create_window(
+                        width,
+                        height,
+                        title,
+                        glfw::WindowMode::FullScreen(m),
+                    ))
+                })?,
+        };
+        let (mut window, events) = created.ok_or_else(|| {
+            format!(
+                "Failed to create a {}{}",
+                config.describe(width, height),
+                glfw_error()
             )
-            .expect("Failed to create GLFW window");
+        })?;
         glfw::Context::make_current(&mut window);
         window.set_all_polling(true);
+        glfw.set_swap_interval(config.swap_interval);
+        if let (DisplayMode::Windowed, Some((x, y))) = (config.display, config.position) {
+            window.set_pos(x, y);
+        }
+        if let Some(icon) = icon {
+            window.set_icon_from_pixels(vec![icon]);
+        }
 
         // Polling of events can be turned on and off by the specific event type
         // window.set_pos_polling(true);
@@ -213,6 +628,17 @@ impl FrameworkBuilder<Fully> {
         // window.set_content_scale_polling(true);
 
         gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
+        let resources = ContextOwner::new();
+        let debug = Debugger::new(resources.handle(), self.debug_filter);
+        let device = resources.handle().device();
+        if config.srgb {
+            device.set_enabled(Capability::FramebufferSrgb, true);
+        }
+        if config.samples > 0 {
+            check_samples(device, config.samples)?;
+            device.set_enabled(Capability::Multisample, true);
+        }
+        let (width, height) = window.get_framebuffer_size();
 
         // Imgui creation
         let imgui = if self.use_imgui {
@@ -227,13 +653,28 @@ impl FrameworkBuilder<Fully> {
             None
         };
 
+        let post = if self.post_process {
+            let post = PostProcess::with_default_passes(width as u32, height as u32)?;
+            Some(RefCell::new(post))
+        } else {
+            None
+        };
+
         Ok(Context {
+            post,
+            debug,
+            resources,
             glfw: glfw,
             window: window,
             events: events,
-            width: self.width,
-            height: self.height,
+            width: width as u32,
+            height: height as u32,
             imgui: imgui,
+            state: RefCell::new(StateCache::new()),
+            input: Input::new(),
+            actions,
+            time,
+            headless: self.headless,
         })
     }
 }
@@ -0,0 +1,221 @@
+use nalgebra_glm as glm;
+
+use super::bounds::{Aabb, BoundingSphere};
+
+/// Plane of the points `p` with `normal.dot(p) + distance == 0`; `normal` points to the
+/// positive side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: glm::Vec3,
+    pub distance: f32,
+}
+
+#[allow(dead_code)]
+impl Plane {
+    /// Plane from the coefficients of `a*x + b*y + c*z + d`, scaled so `normal` has unit
+    /// length and `signed_distance` is in world units.
+    pub fn from_coefficients(coefficients: &glm::Vec4) -> Plane {
+        let length = coefficients.xyz().norm();
+        Plane {
+            normal: coefficients.xyz() / length,
+            distance: coefficients.w / length,
+        }
+    }
+
+    pub fn signed_distance(&self, point: &glm::Vec3) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+}
+
+/// Where a volume lies relative to a frustum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Intersection {
+    Outside,
+    Intersecting,
+    Inside,
+}
+
+/// The six planes bounding what a projection sees, normals pointing inwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far.
+    pub planes: [Plane; 6],
+}
+
+#[allow(dead_code)]
+impl Frustum {
+    /// Extracts the planes of `view_projection` in the space it maps from, e.g. world space
+    /// for `Camera::view_projection` (Gribb and Hartmann). Expects OpenGL clip space, where
+    /// visible points have x, y and z within [-w, w].
+    pub fn from_matrix(view_projection: &glm::Mat4) -> Frustum {
+        let row = |i: usize| view_projection.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Frustum {
+            planes: [
+                Plane::from_coefficients(&(w + x)),
+                Plane::from_coefficients(&(w - x)),
+                Plane::from_coefficients(&(w + y)),
+                Plane::from_coefficients(&(w - y)),
+                Plane::from_coefficients(&(w + z)),
+                Plane::from_coefficients(&(w - z)),
+            ],
+        }
+    }
+
+    pub fn contains_point(&self, point: &glm::Vec3) -> bool {
+        self.planes.iter().all(|p| p.signed_distance(point) >= 0.0)
+    }
+
+    /// Conservative: boxes near a frustum corner may be reported as intersecting while
+    /// outside, never the other way around.
+    pub fn test_aabb(&self, aabb: &Aabb) -> Intersection {
+        if aabb.is_empty() {
+            return Intersection::Outside;
+        }
+        let (center, half) = (aabb.center(), aabb.half_extents());
+        let mut result = Intersection::Inside;
+        for plane in self.planes.iter() {
+            // Projected half extent, reached at the corner furthest along the normal.
+            let radius = plane.normal.abs().dot(&half);
+            let distance = plane.signed_distance(&center);
+            if distance < -radius {
+                return Intersection::Outside;
+            }
+            if distance < radius {
+                result = Intersection::Intersecting;
+            }
+        }
+        result
+    }
+
+    /// Conservative in the same way as `test_aabb`.
+    pub fn test_sphere(&self, sphere: &BoundingSphere) -> Intersection {
+        let mut result = Intersection::Inside;
+        for plane in self.planes.iter() {
+            let distance = plane.signed_distance(&sphere.center);
+            if distance < -sphere.radius {
+                return Intersection::Outside;
+            }
+            if distance < sphere.radius {
+                result = Intersection::Intersecting;
+            }
+        }
+        result
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.test_aabb(aabb) != Intersection::Outside
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.test_sphere(sphere) != Intersection::Outside
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::camera::Camera;
+
+    // 90 degree square frustum at the origin looking down -Z, from 1 to 10.
+    fn frustum() -> Frustum {
+        Frustum::from_matrix(&glm::perspective(
+            1.0,
+            std::f32::consts::FRAC_PI_2,
+            1.0,
+            10.0,
+        ))
+    }
+
+    fn cube(x: f32, y: f32, z: f32, half: f32) -> Aabb {
+        let half = glm::vec3(half, half, half);
+        let center = glm::vec3(x, y, z);
+        Aabb::new(center - half, center + half)
+    }
+
+    #[test]
+    fn extracts_normalized_inward_planes() {
+        let frustum = frustum();
+        let near = frustum.planes[4];
+        assert!((near.normal - glm::vec3(0.0, 0.0, -1.0)).norm() < 1e-5);
+        assert!((near.distance + 1.0).abs() < 1e-5);
+        let far = frustum.planes[5];
+        assert!((far.signed_distance(&glm::vec3(0.0, 0.0, -4.0)) - 6.0).abs() < 1e-4);
+        // The left plane leans 45 degrees outwards.
+        let left = frustum.planes[0];
+        let expected = glm::vec3(1.0, 0.0, -1.0).normalize();
+        assert!((left.normal - expected).norm() < 1e-5);
+
+        assert!(frustum.contains_point(&glm::vec3(0.0, 0.0, -5.0)));
+        assert!(frustum.contains_point(&glm::vec3(4.9, -4.9, -5.0)));
+        assert!(!frustum.contains_point(&glm::vec3(5.1, 0.0, -5.0)));
+        assert!(!frustum.contains_point(&glm::vec3(0.0, 0.0, -0.5)));
+        assert!(!frustum.contains_point(&glm::vec3(0.0, 0.0, -11.0)));
+        assert!(!frustum.contains_point(&glm::vec3(0.0, 0.0, 5.0)));
+    }
+
+    #[test]
+    fn classifies_boxes_and_spheres() {
+        let frustum = frustum();
+        assert_eq!(
+            frustum.test_aabb(&cube(0.0, 0.0, -5.0, 1.0)),
+            Intersection::Inside
+        );
+        assert_eq!(
+            frustum.test_aabb(&cube(5.0, 0.0, -5.0, 1.0)),
+            Intersection::Intersecting
+        );
+        assert_eq!(
+            frustum.test_aabb(&cube(0.0, 0.0, -10.0, 1.0)),
+            Intersection::Intersecting
+        );
+        assert_eq!(
+            frustum.test_aabb(&cube(8.0, 0.0, -5.0, 1.0)),
+            Intersection::Outside
+        );
+        assert_eq!(
+            frustum.test_aabb(&cube(0.0, 0.0, 3.0, 1.0)),
+            Intersection::Outside
+        );
+        assert_eq!(frustum.test_aabb(&Aabb::empty()), Intersection::Outside);
+
+        let sphere = |x, z, radius| BoundingSphere::new(glm::vec3(x, 0.0, z), radius);
+        assert_eq!(
+            frustum.test_sphere(&sphere(0.0, -5.0, 1.0)),
+            Intersection::Inside
+        );
+        assert_eq!(
+            frustum.test_sphere(&sphere(0.0, -0.5, 1.0)),
+            Intersection::Intersecting
+        );
+        assert_eq!(
+            frustum.test_sphere(&sphere(0.0, 12.0, 1.0)),
+            Intersection::Outside
+        );
+        // Just off the right plane: 7 - 5 = 2 units to the side, sqrt(2) from the plane.
+        assert!(!frustum.intersects_sphere(&sphere(7.0, -5.0, 1.4)));
+        assert!(frustum.intersects_sphere(&sphere(7.0, -5.0, 1.5)));
+    }
+
+    #[test]
+    fn follows_the_camera() {
+        let mut camera = Camera::perspective(60.0, 0.1, 100.0, 800, 600);
+        camera.look_at(
+            &glm::vec3(10.0, 0.0, 0.0),
+            &glm::vec3(20.0, 0.0, 0.0),
+            &glm::Vec3::y(),
+        );
+        let frustum = Frustum::from_matrix(&camera.view_projection());
+        assert!(frustum.intersects_aabb(&cube(30.0, 0.0, 0.0, 1.0)));
+        assert!(!frustum.intersects_aabb(&cube(0.0, 0.0, 0.0, 1.0)));
+        assert!(!frustum.intersects_aabb(&cube(30.0, 0.0, 40.0, 1.0)));
+
+        let ortho = Camera::orthographic(4.0, -1.0, 1.0, 400, 400);
+        let frustum = Frustum::from_matrix(&ortho.view_projection());
+        assert_eq!(
+            frustum.test_aabb(&cube(0.0, 0.0, 0.0, 0.5)),
+            Intersection::Inside
+        );
+        assert!(!frustum.intersects_aabb(&cube(3.0, 0.0, 0.0, 0.5)));
+    }
+}
@@ -0,0 +1,594 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use nalgebra_glm as glm;
+
+use super::bounds::{Aabb, BoundingSphere};
+use super::mesh::{Mesh, MeshBuilder};
+use super::state::RenderState;
+
+/// Triangle mesh on the CPU, in the flat layouts `MeshBuilder` consumes: three floats per
+/// position and normal, two per uv and four per tangent, with the bitangent sign in w.
+/// Triangles wind counter-clockwise seen from the side normals point to.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshData {
+    pub positions: Vec<f32>,
+    pub normals: Vec<f32>,
+    pub uvs: Vec<f32>,
+    pub tangents: Vec<f32>,
+    pub indices: Vec<i32>,
+}
+
+#[allow(dead_code)]
+impl MeshData {
+    pub fn new() -> MeshData {
+        Default::default()
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len() / 3
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn position(&self, vertex: usize) -> glm::Vec3 {
+        glm::make_vec3(&self.positions[vertex * 3..vertex * 3 + 3])
+    }
+
+    pub fn normal(&self, vertex: usize) -> glm::Vec3 {
+        glm::make_vec3(&self.normals[vertex * 3..vertex * 3 + 3])
+    }
+
+    /// Vertex indices of triangle `triangle`.
+    pub fn triangle(&self, triangle: usize) -> [usize; 3] {
+        let t = &self.indices[triangle * 3..triangle * 3 + 3];
+        [t[0] as usize, t[1] as usize, t[2] as usize]
+    }
+
+    /// Box around the positions.
+    pub fn aabb(&self) -> Aabb {
+        Aabb::from_positions(&self.positions)
+    }
+
+    /// Sphere around the positions, a few percent larger than the smallest one at most.
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere::from_positions(&self.positions)
+    }
+
+    /// Uploads every stream that has data.
+    pub fn build(&self, state: RenderState) -> Result<Mesh, String> {
+        let mut builder = MeshBuilder::new()
+            .positions(&self.positions)
+            .indices(&self.indices)
+            .render_state(state);
+        if !self.normals.is_empty() {
+            builder = builder.normals(&self.normals);
+        }
+        if !self.uvs.is_empty() {
+            builder = builder.uvs(&self.uvs);
+        }
+        if !self.tangents.is_empty() {
+            builder = builder.tangents(&self.tangents);
+        }
+        builder.build()
+    }
+
+    fn push_vertex(
+        &mut self,
+        position: glm::Vec3,
+        normal: glm::Vec3,
+        uv: glm::Vec2,
+        tangent: glm::Vec3,
+    ) -> i32 {
+        let index = self.vertex_count() as i32;
+        self.positions.extend_from_slice(position.as_slice());
+        self.normals.extend_from_slice(normal.as_slice());
+        self.uvs.extend_from_slice(uv.as_slice());
+        self.tangents
+            .extend_from_slice(&[tangent.x, tangent.y, tangent.z, 1.0]);
+        index
+    }
+
+    // Appends a `columns` x `rows` grid spanning `u` and `v` from `origin`, facing u x v,
+    // with uvs running along `u` and `v`.
+    fn push_grid(
+        &mut self,
+        origin: glm::Vec3,
+        u: glm::Vec3,
+        v: glm::Vec3,
+        columns: u32,
+        rows: u32,
+    ) {
+        let normal = u.cross(&v).normalize();
+        let tangent = u.normalize();
+        let base = self.vertex_count() as i32;
+        for j in 0..=rows {
+            for i in 0..=columns {
+                let uv = glm::vec2(i as f32 / columns as f32, j as f32 / rows as f32);
+                self.push_vertex(origin + u * uv.x + v * uv.y, normal, uv, tangent);
+            }
+        }
+        let row = columns as i32 + 1;
+        for j in 0..rows as i32 {
+            for i in 0..columns as i32 {
+                let a = base + j * row + i;
+                self.indices
+                    .extend_from_slice(&[a, a + 1, a + row + 1, a, a + row + 1, a + row]);
+            }
+        }
+    }
+
+    // Sweeps `profile` around the Y axis. Profiles run bottom to top along the outside of the
+    // surface; rings of radius 0 collapse to a point and get no degenerate triangles.
+    fn push_revolution(&mut self, profile: &[ProfilePoint], segments: u32) {
+        let base = self.vertex_count() as i32;
+        for point in profile {
+            for j in 0..=segments {
+                // The seam repeats the first column exactly.
+                let phi = 2.0 * PI * (j % segments) as f32 / segments as f32;
+                let radial = glm::vec3(phi.cos(), 0.0, -phi.sin());
+                self.push_vertex(
+                    radial * point.radius + glm::vec3(0.0, point.y, 0.0),
+                    radial * point.normal.x + glm::vec3(0.0, point.normal.y, 0.0),
+                    glm::vec2(j as f32 / segments as f32, point.v),
+                    glm::vec3(-phi.sin(), 0.0, -phi.cos()),
+                );
+            }
+        }
+        let row = segments as i32 + 1;
+        for (k, ring) in profile.windows(2).enumerate() {
+            for j in 0..segments as i32 {
+                let a = base + k as i32 * row + j;
+                let b = a + row;
+                if ring[0].radius != 0.0 {
+                    self.indices.extend_from_slice(&[a, a + 1, b]);
+                }
+                if ring[1].radius != 0.0 {
+                    self.indices.extend_from_slice(&[a + 1, b + 1, b]);
+                }
+            }
+        }
+    }
+
+    // Appends a disc of `radius` at height `y` facing up or down, with planar uvs.
+    fn push_disc(&mut self, radius: f32, y: f32, up: bool, segments: u32) {
+        let (normal, flip) = if up {
+            (glm::Vec3::y(), -1.0)
+        } else {
+            (-glm::Vec3::y(), 1.0)
+        };
+        let tangent = glm::Vec3::x();
+        let center = self.push_vertex(glm::vec3(0.0, y, 0.0), normal, glm::vec2(0.5, 0.5), tangent);
+        for j in 0..segments {
+            let phi = 2.0 * PI * j as f32 / segments as f32;
+            let (x, z) = (phi.cos(), -phi.sin());
+            self.push_vertex(
+                glm::vec3(x * radius, y, z * radius),
+                normal,
+                glm::vec2(0.5 + 0.5 * x, 0.5 + 0.5 * flip * z),
+                tangent,
+            );
+        }
+        for j in 0..segments as i32 {
+            let a = center + 1 + j;
+            let b = center + 1 + (j + 1) % segments as i32;
+            if up {
+                self.indices.extend_from_slice(&[center, a, b]);
+            } else {
+                self.indices.extend_from_slice(&[center, b, a]);
+            }
+        }
+    }
+}
+
+// A ring of a surface of revolution: its radius and height, the outward normal in the
+// (radial, y) plane and the v texture coordinate.
+struct ProfilePoint {
+    radius: f32,
+    y: f32,
+    normal: glm::Vec2,
+    v: f32,
+}
+
+/// `width` x `depth` rectangle in the XZ plane facing up, centered on the origin and split
+/// into `columns` x `rows` quads. u runs along +X, v along -Z.
+pub fn grid(width: f32, depth: f32, columns: u32, rows: u32) -> MeshData {
+    let mut mesh = MeshData::new();
+    mesh.push_grid(
+        glm::vec3(-0.5 * width, 0.0, 0.5 * depth),
+        glm::vec3(width, 0.0, 0.0),
+        glm::vec3(0.0, 0.0, -depth),
+        columns.max(1),
+        rows.max(1),
+    );
+    mesh
+}
+
+/// `size` wide square in the XZ plane facing up, as two triangles.
+pub fn plane(size: f32) -> MeshData {
+    grid(size, size, 1, 1)
+}
+
+/// Cube of edge `size` centered on the origin, each face split into `subdivisions` x
+/// `subdivisions` quads with a full 0..1 uv square.
+pub fn cube(size: f32, subdivisions: u32) -> MeshData {
+    let x = glm::Vec3::x();
+    let y = glm::Vec3::y();
+    let z = glm::Vec3::z();
+    // Outward normal and the direction u runs in; v runs along normal x u.
+    let faces = [(x, -z), (-x, z), (y, x), (-y, x), (z, x), (-z, -x)];
+    let subdivisions = subdivisions.max(1);
+    let half = 0.5 * size;
+    let mut mesh = MeshData::new();
+    for (n, u) in faces.iter() {
+        let v = n.cross(u);
+        mesh.push_grid(
+            (n - u - v) * half,
+            u * size,
+            v * size,
+            subdivisions,
+            subdivisions,
+        );
+    }
+    mesh
+}
+
+/// Sphere of `segments` meridians and `rings` bands between the poles, with u around the
+/// Y axis and v from the south to the north pole.
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> MeshData {
+    let rings = rings.max(2);
+    let profile: Vec<ProfilePoint> = (0..=rings)
+        .map(|ring| {
+            let v = ring as f32 / rings as f32;
+            let angle = (v - 0.5) * PI;
+            let normal = glm::vec2(angle.cos(), angle.sin());
+            ProfilePoint {
+                radius: if ring == 0 || ring == rings {
+                    0.0
+                } else {
+                    radius * normal.x
+                },
+                y: radius * normal.y,
+                normal,
+                v,
+            }
+        })
+        .collect();
+    let mut mesh = MeshData::new();
+    mesh.push_revolution(&profile, segments.max(3));
+    mesh
+}
+
+/// Sphere from an icosahedron whose triangles are split in four `subdivisions` times, for
+/// evenly sized triangles. Vertices are shared, so uvs wrap back to 0 across the seam at
+/// +X and textures smear over the triangles touching it.
+#[allow(dead_code)]
+pub fn icosphere(radius: f32, subdivisions: u32) -> MeshData {
+    let t = (1.0 + 5f32.sqrt()) / 2.0;
+    let mut points: Vec<glm::Vec3> = [
+        (-1.0, t, 0.0),
+        (1.0, t, 0.0),
+        (-1.0, -t, 0.0),
+        (1.0, -t, 0.0),
+        (0.0, -1.0, t),
+        (0.0, 1.0, t),
+        (0.0, -1.0, -t),
+        (0.0, 1.0, -t),
+        (t, 0.0, -1.0),
+        (t, 0.0, 1.0),
+        (-t, 0.0, -1.0),
+        (-t, 0.0, 1.0),
+    ]
+    .iter()
+    .map(|&(x, y, z)| glm::vec3(x, y, z).normalize())
+    .collect();
+    let mut triangles: Vec<[usize; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut midpoint = |a: usize, b: usize, points: &mut Vec<glm::Vec3>| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                points.push((points[a] + points[b]).normalize());
+                points.len() - 1
+            })
+        };
+        let mut split = Vec::with_capacity(triangles.len() * 4);
+        for &[a, b, c] in triangles.iter() {
+            let ab = midpoint(a, b, &mut points);
+            let bc = midpoint(b, c, &mut points);
+            let ca = midpoint(c, a, &mut points);
+            split.extend_from_slice(&[[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]);
+        }
+        triangles = split;
+    }
+
+    let mut mesh = MeshData::new();
+    for n in points.iter() {
+        let phi = (-n.z).atan2(n.x);
+        let u = (phi / (2.0 * PI)).rem_euclid(1.0);
+        let v = 0.5 + n.y.asin() / PI;
+        mesh.push_vertex(
+            n * radius,
+            *n,
+            glm::vec2(u, v),
+            glm::vec3(-phi.sin(), 0.0, -phi.cos()),
+        );
+    }
+    for triangle in triangles.iter() {
+        mesh.indices
+            .extend(triangle.iter().map(|&index| index as i32));
+    }
+    mesh
+}
+
+/// Capped cylinder along Y centered on the origin, with `stacks` bands up the side. The
+/// caps have their own vertices for hard edges and planar uvs.
+#[allow(dead_code)]
+pub fn cylinder(radius: f32, height: f32, segments: u32, stacks: u32) -> MeshData {
+    let (segments, stacks) = (segments.max(3), stacks.max(1));
+    let half = 0.5 * height;
+    let profile: Vec<ProfilePoint> = (0..=stacks)
+        .map(|stack| {
+            let v = stack as f32 / stacks as f32;
+            ProfilePoint {
+                radius,
+                y: -half + height * v,
+                normal: glm::vec2(1.0, 0.0),
+                v,
+            }
+        })
+        .collect();
+    let mut mesh = MeshData::new();
+    mesh.push_revolution(&profile, segments);
+    mesh.push_disc(radius, -half, false, segments);
+    mesh.push_disc(radius, half, true, segments);
+    mesh
+}
+
+/// Cone along Y centered on the origin, its base of `radius` at the bottom and its apex at
+/// the top, with `stacks` bands up the side.
+#[allow(dead_code)]
+pub fn cone(radius: f32, height: f32, segments: u32, stacks: u32) -> MeshData {
+    let (segments, stacks) = (segments.max(3), stacks.max(1));
+    let half = 0.5 * height;
+    let normal = glm::vec2(height, radius).normalize();
+    let profile: Vec<ProfilePoint> = (0..=stacks)
+        .map(|stack| {
+            let v = stack as f32 / stacks as f32;
+            ProfilePoint {
+                radius: radius * (1.0 - v),
+                y: -half + height * v,
+                normal,
+                v,
+            }
+        })
+        .collect();
+    let mut mesh = MeshData::new();
+    mesh.push_revolution(&profile, segments);
+    mesh.push_disc(radius, -half, false, segments);
+    mesh
+}
+
+/// Cylinder of `length` along Y between two hemispheres of `rings` bands each, centered on
+/// the origin. v runs from pole to pole in proportion to the distance along the surface.
+#[allow(dead_code)]
+pub fn capsule(radius: f32, length: f32, segments: u32, rings: u32) -> MeshData {
+    let rings = rings.max(1);
+    let half = 0.5 * length;
+    let arc = 0.5 * PI * radius;
+    let total = 2.0 * arc + length;
+    let mut profile = Vec::new();
+    let mut hemisphere = |center: f32, offset: f32, from: f32, skip_first: bool| {
+        for ring in 0..=rings {
+            if ring == 0 && skip_first {
+                continue;
+            }
+            let angle = from + 0.5 * PI * ring as f32 / rings as f32;
+            let normal = glm::vec2(angle.cos(), angle.sin());
+            let pole = (from < 0.0 && ring == 0) || (from >= 0.0 && ring == rings);
+            profile.push(ProfilePoint {
+                radius: if pole { 0.0 } else { radius * normal.x },
+                y: center + radius * normal.y,
+                normal,
+                v: (offset + arc * ring as f32 / rings as f32) / total,
+            });
+        }
+    };
+    hemisphere(-half, 0.0, -0.5 * PI, false);
+    // Without a cylinder the equators coincide and the ring is shared.
+    hemisphere(half, arc + length, 0.0, length <= 0.0);
+    let mut mesh = MeshData::new();
+    mesh.push_revolution(&profile, segments.max(3));
+    mesh
+}
+
+/// Torus around the Y axis: a tube of `minor_radius` whose center circles the origin at
+/// `major_radius`, with `segments` around the axis and `sides` around the tube.
+#[allow(dead_code)]
+pub fn torus(major_radius: f32, minor_radius: f32, segments: u32, sides: u32) -> MeshData {
+    let sides = sides.max(3);
+    let profile: Vec<ProfilePoint> = (0..=sides)
+        .map(|side| {
+            let v = side as f32 / sides as f32;
+            // Starts on the inside of the tube so the seam faces the hole.
+            let angle = PI * (2.0 * (side % sides) as f32 / sides as f32 - 1.0);
+            let normal = glm::vec2(angle.cos(), angle.sin());
+            ProfilePoint {
+                radius: major_radius + minor_radius * normal.x,
+                y: minor_radius * normal.y,
+                normal,
+                v,
+            }
+        })
+        .collect();
+    let mut mesh = MeshData::new();
+    mesh.push_revolution(&profile, segments.max(3));
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::device::RecordingDevice;
+    use crate::core::resource::ContextOwner;
+
+    fn shapes() -> Vec<(&'static str, MeshData)> {
+        vec![
+            ("cube", cube(2.0, 3)),
+            ("uv_sphere", uv_sphere(1.5, 12, 7)),
+            ("icosphere", icosphere(1.0, 2)),
+            ("cylinder", cylinder(0.5, 2.0, 10, 3)),
+            ("cone", cone(1.0, 1.5, 9, 2)),
+            ("capsule", capsule(0.5, 1.0, 8, 4)),
+            ("capsule without cylinder", capsule(0.5, 0.0, 8, 4)),
+            ("torus", torus(1.0, 0.25, 16, 8)),
+        ]
+    }
+
+    // Vertices at the same position, e.g. on seams and hard edges, share an id.
+    fn welded(mesh: &MeshData) -> Vec<usize> {
+        let mut ids = HashMap::new();
+        (0..mesh.vertex_count())
+            .map(|vertex| {
+                let p = mesh.position(vertex) * 1e4;
+                let key = (p.x.round() as i64, p.y.round() as i64, p.z.round() as i64);
+                let next = ids.len();
+                *ids.entry(key).or_insert(next)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn counts_vertices_and_triangles() {
+        let counts = [
+            (grid(4.0, 2.0, 4, 2), 15, 16),
+            (plane(1.0), 4, 2),
+            (cube(1.0, 2), 54, 48),
+            (uv_sphere(1.0, 8, 4), 45, 48),
+            (icosphere(1.0, 0), 12, 20),
+            (icosphere(1.0, 2), 162, 320),
+            (cylinder(1.0, 1.0, 8, 2), 27 + 18, 32 + 16),
+            (cone(1.0, 1.0, 8, 2), 27 + 9, 32),
+            (capsule(1.0, 1.0, 8, 3), 72, 96),
+            (torus(1.0, 0.5, 8, 6), 63, 96),
+        ];
+        for (mesh, vertices, triangles) in counts.iter() {
+            assert_eq!(
+                (mesh.vertex_count(), mesh.triangle_count()),
+                (*vertices, *triangles)
+            );
+            assert_eq!(mesh.normals.len(), mesh.positions.len());
+            assert_eq!(mesh.uvs.len(), vertices * 2);
+            assert_eq!(mesh.tangents.len(), vertices * 4);
+        }
+    }
+
+    #[test]
+    fn normals_are_unit_length_and_face_outward() {
+        for (name, mesh) in shapes()
+            .iter()
+            .chain([("grid", grid(3.0, 1.0, 3, 2))].iter())
+        {
+            for vertex in 0..mesh.vertex_count() {
+                let length = mesh.normal(vertex).norm();
+                assert!(
+                    (length - 1.0).abs() < 1e-5,
+                    "{}: normal of {}",
+                    name,
+                    length
+                );
+            }
+            for triangle in 0..mesh.triangle_count() {
+                let [a, b, c] = mesh.triangle(triangle);
+                let (pa, pb, pc) = (mesh.position(a), mesh.position(b), mesh.position(c));
+                let face = (pb - pa).cross(&(pc - pa));
+                assert!(face.norm() > 1e-6, "{}: degenerate triangle", name);
+                let smooth = mesh.normal(a) + mesh.normal(b) + mesh.normal(c);
+                assert!(face.dot(&smooth) > 0.0, "{}: triangle winds inward", name);
+            }
+        }
+    }
+
+    #[test]
+    fn closed_shapes_are_watertight() {
+        for (name, mesh) in shapes().iter() {
+            let ids = welded(mesh);
+            let mut edges: HashMap<(usize, usize), i32> = HashMap::new();
+            for triangle in 0..mesh.triangle_count() {
+                let [a, b, c] = mesh.triangle(triangle);
+                for &(from, to) in &[(a, b), (b, c), (c, a)] {
+                    *edges.entry((ids[from], ids[to])).or_insert(0) += 1;
+                }
+            }
+            // Every edge is used once in each direction by consistently wound neighbors.
+            for (&(from, to), &count) in edges.iter() {
+                assert_eq!(count, 1, "{}: edge used {} times one way", name, count);
+                assert_eq!(edges.get(&(to, from)), Some(&1), "{}: open edge", name);
+            }
+        }
+    }
+
+    #[test]
+    fn tangents_follow_increasing_u() {
+        // The icosphere's uvs wrap and pinch at the poles.
+        for (name, mesh) in shapes().iter().filter(|(name, _)| *name != "icosphere") {
+            for triangle in 0..mesh.triangle_count() {
+                let [a, b, c] = mesh.triangle(triangle);
+                let uv = |i: usize| glm::make_vec2(&mesh.uvs[i * 2..i * 2 + 2]);
+                let (e1, e2) = (
+                    mesh.position(b) - mesh.position(a),
+                    mesh.position(c) - mesh.position(a),
+                );
+                let (d1, d2) = (uv(b) - uv(a), uv(c) - uv(a));
+                let det = d1.x * d2.y - d2.x * d1.y;
+                if det.abs() < 1e-6 {
+                    continue;
+                }
+                let along_u = (e1 * d2.y - e2 * d1.y) / det;
+                let tangent = glm::make_vec3(&mesh.tangents[a * 4..a * 4 + 3]);
+                assert!(along_u.dot(&tangent) > 0.0, "{}: tangent against u", name);
+            }
+        }
+    }
+
+    #[test]
+    fn builds_a_mesh_with_every_stream() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let _mesh = plane(1.0).build(RenderState::opaque());
+
+        assert_eq!(
+            device.calls_to("BufferData"),
+            vec![
+                "BufferData(ELEMENT_ARRAY_BUFFER, 24, data, STATIC_DRAW)",
+                "BufferData(ARRAY_BUFFER, 48, data, STATIC_DRAW)",
+                "BufferData(ARRAY_BUFFER, 48, data, STATIC_DRAW)",
+                "BufferData(ARRAY_BUFFER, 32, data, STATIC_DRAW)",
+                "BufferData(ARRAY_BUFFER, 64, data, STATIC_DRAW)",
+            ]
+        );
+    }
+}
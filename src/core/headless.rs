@@ -0,0 +1,279 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use image::RgbaImage;
+
+use super::time::ManualClock;
+
+/// Options for running an app without a visible window, e.g. in CI.
+/// Time advances by exactly 1/60 s per frame so every run renders the same frames.
+#[derive(Clone)]
+pub struct Headless {
+    frames: u64,
+    golden: Option<PathBuf>,
+    tolerance: u8,
+    max_mismatch: f32,
+    clock: ManualClock,
+}
+
+/// Result of a successful golden image check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Golden {
+    Matched,
+    /// `UPDATE_GOLDEN` was set, so the capture was written as the golden image.
+    Blessed,
+}
+
+/// Pixels that differ by more than a per-channel tolerance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Difference {
+    pub mismatched: usize,
+    pub max_delta: u8,
+}
+
+#[allow(dead_code)]
+impl Headless {
+    pub const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+    /// Renders `frames` frames, then closes the window.
+    pub fn new(frames: u64) -> Headless {
+        Headless {
+            frames: frames.max(1),
+            golden: None,
+            tolerance: 2,
+            max_mismatch: 0.001,
+            clock: ManualClock::new(),
+        }
+    }
+
+    /// Compares the last frame against the PNG at `path`.
+    pub fn golden<P>(self, path: P) -> Headless
+    where
+        P: Into<PathBuf>,
+    {
+        Headless {
+            golden: Some(path.into()),
+            ..self
+        }
+    }
+
+    /// Per-channel difference ignored when comparing, and the fraction of pixels allowed to exceed it.
+    pub fn tolerance(self, tolerance: u8, max_mismatch: f32) -> Headless {
+        Headless {
+            tolerance,
+            max_mismatch,
+            ..self
+        }
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Clock to drive `Context::time` with; `advance` moves it by one frame.
+    pub fn clock(&self) -> ManualClock {
+        self.clock.clone()
+    }
+
+    pub fn advance(&self) {
+        self.clock.advance(Headless::FRAME_TIME);
+    }
+
+    /// Checks the captured last frame against the golden image, if one was requested.
+    /// With `UPDATE_GOLDEN` in the environment the capture replaces the golden image.
+    pub fn finish(&self, image: &RgbaImage) -> Result<Option<Golden>, String> {
+        let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+        match self.golden {
+            Some(ref path) => {
+                check_golden(image, path, self.tolerance, self.max_mismatch, update).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Converts `read_pixels` output, bottom row first, into an image.
+pub fn from_gl_pixels(width: u32, height: u32, pixels: &[u8]) -> RgbaImage {
+    let row = width as usize * 4;
+    let mut flipped = Vec::with_capacity(pixels.len());
+    for y in (0..height as usize).rev() {
+        flipped.extend_from_slice(&pixels[y * row..(y + 1) * row]);
+    }
+    RgbaImage::from_raw(width, height, flipped).expect("pixel buffer matches the image size")
+}
+
+/// Counts pixels where any channel differs by more than `tolerance`.
+pub fn compare(
+    actual: &RgbaImage,
+    expected: &RgbaImage,
+    tolerance: u8,
+) -> Result<Difference, String> {
+    if actual.dimensions() != expected.dimensions() {
+        return Err(format!(
+            "Image is {}x{} but the golden image is {}x{}",
+            actual.width(),
+            actual.height(),
+            expected.width(),
+            expected.height()
+        ));
+    }
+    let mut diff = Difference {
+        mismatched: 0,
+        max_delta: 0,
+    };
+    for (a, e) in actual.pixels().zip(expected.pixels()) {
+        let delta =
+            a.0.iter()
+                .zip(e.0.iter())
+                .map(|(&a, &e)| (a as i16 - e as i16).unsigned_abs() as u8)
+                .max()
+                .unwrap_or(0);
+        diff.max_delta = diff.max_delta.max(delta);
+        if delta > tolerance {
+            diff.mismatched += 1;
+        }
+    }
+    Ok(diff)
+}
+
+/// Compares `image` with the PNG at `path`, or writes it there when `update` is set. A missing
+/// golden image is an error, so a check cannot pass on a checkout without one. On a failure
+/// the capture is saved next to the golden image as `<name>.actual.png`.
+pub fn check_golden(
+    image: &RgbaImage,
+    path: &Path,
+    tolerance: u8,
+    max_mismatch: f32,
+    update: bool,
+) -> Result<Golden, String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    if update {
+        image
+            .save(path)
+            .map_err(|e| format!("Failed to write golden image {}: {}", path.display(), e))?;
+        log::info!("Wrote golden image {}", path.display());
+        return Ok(Golden::Blessed);
+    }
+
+    let actual = path.with_extension("actual.png");
+    let save_actual = || {
+        image
+            .save(&actual)
+            .map_err(|e| format!("Failed to write {}: {}", actual.display(), e))
+    };
+    if !path.exists() {
+        save_actual()?;
+        return Err(format!(
+            "Golden image {} does not exist; capture written to {}, rerun with UPDATE_GOLDEN=1 \
+             to accept it",
+            path.display(),
+            actual.display()
+        ));
+    }
+
+    let expected = image::open(path)
+        .map_err(|e| format!("Failed to load golden image {}: {}", path.display(), e))?
+        .to_rgba8();
+    let diff = compare(image, &expected, tolerance)?;
+    let allowed = (max_mismatch * (image.width() * image.height()) as f32) as usize;
+    if diff.mismatched <= allowed {
+        return Ok(Golden::Matched);
+    }
+
+    save_actual()?;
+    Err(format!(
+        "{} pixels differ from {} by more than {} (max difference {}); capture written to {}",
+        diff.mismatched,
+        path.display(),
+        tolerance,
+        diff.max_delta,
+        actual.display()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn solid(width: u32, height: u32, value: u8) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, Rgba([value, value, value, 255]))
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("headless-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn flips_gl_rows() {
+        // Bottom row red, top row blue.
+        let pixels = [255, 0, 0, 255, 0, 0, 255, 255];
+        let image = from_gl_pixels(1, 2, &pixels);
+        assert_eq!(image.get_pixel(0, 0), &Rgba([0, 0, 255, 255]));
+        assert_eq!(image.get_pixel(0, 1), &Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn compares_within_tolerance() {
+        let expected = solid(4, 4, 100);
+        let mut actual = solid(4, 4, 102);
+        actual.put_pixel(1, 1, Rgba([100, 140, 100, 255]));
+        let diff = compare(&actual, &expected, 2).unwrap();
+        assert_eq!(
+            diff,
+            Difference {
+                mismatched: 1,
+                max_delta: 40
+            }
+        );
+        assert!(compare(&solid(4, 2, 0), &expected, 2)
+            .unwrap_err()
+            .contains("4x2"));
+    }
+
+    #[test]
+    fn blesses_then_checks_golden() {
+        let dir = temp_dir("golden");
+        let path = dir.join("scene.png");
+        let image = solid(8, 8, 50);
+        let err = check_golden(&image, &path, 0, 0.0, false).unwrap_err();
+        assert!(err.contains("UPDATE_GOLDEN"), "{}", err);
+        assert!(!path.exists());
+        assert_eq!(
+            check_golden(&image, &path, 0, 0.0, true),
+            Ok(Golden::Blessed)
+        );
+        assert_eq!(
+            check_golden(&image, &path, 0, 0.0, false),
+            Ok(Golden::Matched)
+        );
+
+        let mut changed = image.clone();
+        changed.put_pixel(3, 3, Rgba([255, 0, 0, 255]));
+        // One pixel in 64 is within a 2% allowance but not a 1% one.
+        assert_eq!(
+            check_golden(&changed, &path, 0, 0.02, false),
+            Ok(Golden::Matched)
+        );
+        let err = check_golden(&changed, &path, 0, 0.01, false).unwrap_err();
+        assert!(err.contains("1 pixels differ"), "{}", err);
+        assert!(dir.join("scene.actual.png").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn advances_a_fixed_step_per_frame() {
+        use super::super::time::Clock;
+        let headless = Headless::new(3);
+        let clock = headless.clock();
+        headless.advance();
+        headless.advance();
+        assert_eq!(clock.now(), Headless::FRAME_TIME * 2);
+        assert_eq!(headless.finish(&solid(1, 1, 0)), Ok(None));
+    }
+}
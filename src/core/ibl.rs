@@ -0,0 +1,336 @@
+use std::path::Path;
+
+use nalgebra_glm as glm;
+
+use super::framebuffer::Framebuffer;
+use super::framework::Context;
+use super::geometry;
+use super::mesh::Mesh;
+use super::shader::Shader;
+use super::state::{Capability, CompareFunc, RenderState};
+use super::texture::{Texture2D, TextureCube};
+use super::vertex::VertexArray;
+
+/// Sizes and sample counts used when preprocessing an `Environment`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IblSettings {
+    /// Face size of the environment cube map converted from the equirectangular image.
+    pub environment_size: u32,
+    pub irradiance_size: u32,
+    /// Face size of the sharpest prefiltered level.
+    pub prefilter_size: u32,
+    /// Prefiltered levels, from roughness 0 at level 0 to roughness 1 at the last.
+    pub prefilter_levels: u32,
+    pub brdf_size: u32,
+    /// Importance samples per texel for the prefiltered map and the BRDF table.
+    pub sample_count: u32,
+}
+
+impl Default for IblSettings {
+    fn default() -> Self {
+        IblSettings {
+            environment_size: 512,
+            irradiance_size: 32,
+            prefilter_size: 128,
+            prefilter_levels: 5,
+            brdf_size: 512,
+            sample_count: 1024,
+        }
+    }
+}
+
+/// Image-based lighting derived from one HDR environment: the environment itself, its
+/// diffuse irradiance, GGX prefiltered radiance per roughness level and the split-sum BRDF table.
+pub struct Environment {
+    environment: TextureCube,
+    irradiance: TextureCube,
+    prefiltered: TextureCube,
+    brdf_lut: Texture2D,
+}
+
+#[allow(dead_code)]
+impl Environment {
+    /// First texture unit `bind` uses, above the units material textures take.
+    pub const FIRST_UNIT: u32 = 8;
+
+    /// Loads an equirectangular Radiance `.hdr` image and preprocesses it.
+    pub fn load<P>(ctx: &Context, path: P, settings: &IblSettings) -> Result<Environment, String>
+    where
+        P: AsRef<Path>,
+    {
+        let equirect = Texture2D::from_hdr(path)?;
+        Environment::from_equirect(ctx, &equirect, settings)
+    }
+
+    /// Renders the cube maps and BRDF table offscreen, then restores the window viewport.
+    pub fn from_equirect(
+        ctx: &Context,
+        equirect: &Texture2D,
+        settings: &IblSettings,
+    ) -> Result<Environment, String> {
+        ctx.device().set_enabled(Capability::CubeMapSeamless, true);
+        let cube = cube_mesh(RenderState::default())?;
+        let fbo = Framebuffer::new(settings.environment_size, settings.environment_size)?;
+        fbo.set_label("IBL capture");
+
+        // Equirectangular image to cube map, mipmapped for the prefilter's sampling. The cube
+        // maps are RGBA as GL does not require RGB16F to be color-renderable.
+        let environment = TextureCube::new(
+            settings.environment_size,
+            gl::RGBA16F,
+            TextureCube::full_levels(settings.environment_size),
+        )?;
+        environment.set_label("IBL environment");
+        let shader = Shader::new("res/glsl/cubemap.vs.glsl", "res/glsl/equirect.fs.glsl")?;
+        shader.bind(ctx);
+        equirect.bind(0);
+        shader.set_texture("EquirectMap", 0);
+        render_faces(ctx, &fbo, &cube, &shader, &environment, 0)?;
+        environment.generate_mipmap();
+
+        // Diffuse irradiance.
+        let irradiance = TextureCube::new(settings.irradiance_size, gl::RGBA16F, 1)?;
+        irradiance.set_label("IBL irradiance");
+        let shader = Shader::new("res/glsl/cubemap.vs.glsl", "res/glsl/irradiance.fs.glsl")?;
+        shader.bind(ctx);
+        environment.bind(0);
+        shader.set_texture("EnvironmentMap", 0);
+        render_faces(ctx, &fbo, &cube, &shader, &irradiance, 0)?;
+
+        // Specular radiance, one roughness per level.
+        let prefiltered = TextureCube::new(
+            settings.prefilter_size,
+            gl::RGBA16F,
+            settings.prefilter_levels,
+        )?;
+        prefiltered.set_label("IBL prefiltered");
+        let shader = Shader::new("res/glsl/cubemap.vs.glsl", "res/glsl/prefilter.fs.glsl")?;
+        shader.bind(ctx);
+        environment.bind(0);
+        shader.set_texture("EnvironmentMap", 0);
+        shader.set_float("EnvironmentSize", settings.environment_size as f32);
+        shader.set_int("SampleCount", settings.sample_count as i32);
+        for level in 0..prefiltered.levels() {
+            shader.set_float(
+                "Roughness",
+                prefilter_roughness(level, prefiltered.levels()),
+            );
+            render_faces(ctx, &fbo, &cube, &shader, &prefiltered, level)?;
+        }
+
+        // Split-sum BRDF table.
+        let brdf_lut = Texture2D::from_float(
+            settings.brdf_size,
+            settings.brdf_size,
+            gl::RG16F,
+            None,
+            gl::LINEAR,
+            gl::CLAMP_TO_EDGE,
+        )?;
+        brdf_lut.set_label("IBL BRDF LUT");
+        let shader = Shader::new("res/glsl/fullscreen.vs.glsl", "res/glsl/brdf.fs.glsl")?;
+        shader.bind(ctx);
+        shader.set_int("SampleCount", settings.sample_count as i32);
+        fbo.attach_texture(gl::COLOR_ATTACHMENT0, &brdf_lut, 0);
+        fbo.check()?;
+        let triangle = VertexArray::new()?;
+        ctx.set_viewport_size(settings.brdf_size, settings.brdf_size);
+        fbo.binding(|| {
+            ctx.clear_buffer(gl::COLOR_BUFFER_BIT);
+            triangle.draw_arrays(gl::TRIANGLES, 0, 3);
+        });
+
+        ctx.set_viewport();
+        Ok(Environment {
+            environment,
+            irradiance,
+            prefiltered,
+            brdf_lut,
+        })
+    }
+
+    pub fn environment(&self) -> &TextureCube {
+        &self.environment
+    }
+
+    pub fn irradiance(&self) -> &TextureCube {
+        &self.irradiance
+    }
+
+    pub fn prefiltered(&self) -> &TextureCube {
+        &self.prefiltered
+    }
+
+    pub fn brdf_lut(&self) -> &Texture2D {
+        &self.brdf_lut
+    }
+
+    /// Points the shader's `IrradianceMap`, `PrefilterMap` and `BrdfLut` samplers at the
+    /// units `bind` uses. Leaves the shader current.
+    pub fn attach(&self, shader: &Shader) {
+        shader.use_program();
+        let samplers = ["IrradianceMap", "PrefilterMap", "BrdfLut"];
+        for (unit, name) in samplers.iter().enumerate() {
+            if shader.has_uniform(name) {
+                shader.set_texture(name, (Environment::FIRST_UNIT + unit as u32) as i32);
+            }
+        }
+        if shader.has_uniform("PrefilterLevels") {
+            shader.set_float("PrefilterLevels", self.prefiltered.levels() as f32);
+        }
+    }
+
+    /// Binds the lighting textures from `FIRST_UNIT` on.
+    pub fn bind(&self) {
+        self.irradiance.bind(Environment::FIRST_UNIT);
+        self.prefiltered.bind(Environment::FIRST_UNIT + 1);
+        self.brdf_lut.bind(Environment::FIRST_UNIT + 2);
+    }
+}
+
+/// Draws an environment cube map behind everything else.
+pub struct Skybox {
+    shader: Shader,
+    cube: Mesh,
+}
+
+#[allow(dead_code)]
+impl Skybox {
+    pub fn new() -> Result<Skybox, String> {
+        let shader = Shader::new("res/glsl/skybox.vs.glsl", "res/glsl/skybox.fs.glsl")?;
+        // Depth is pinned to the far plane, so only pixels nothing else covered pass.
+        let state = RenderState::default().with_depth(Some(CompareFunc::LessEqual), false);
+        Ok(Skybox {
+            shader,
+            cube: cube_mesh(state)?,
+        })
+    }
+
+    /// Call after the opaque geometry; `exposure` should match the scene's.
+    pub fn draw(
+        &self,
+        ctx: &Context,
+        cube_map: &TextureCube,
+        view: &glm::Mat4,
+        projection: &glm::Mat4,
+        exposure: f32,
+    ) {
+        self.shader.bind(ctx);
+        cube_map.bind(0);
+        self.shader.set_texture("EnvironmentMap", 0);
+        self.shader.set_mat4("View", view);
+        self.shader.set_mat4("Projection", projection);
+        self.shader.set_float("Exposure", exposure);
+        self.cube.draw(ctx);
+    }
+}
+
+/// 90 degree projection that makes each view cover exactly one cube face.
+pub fn capture_projection() -> glm::Mat4 {
+    glm::perspective(1.0, std::f32::consts::FRAC_PI_2, 0.1, 10.0)
+}
+
+/// Views from the origin through each face of `TextureCube::FACES`, with the up vectors
+/// GL's cube map face orientation expects.
+pub fn capture_views() -> [glm::Mat4; 6] {
+    let eye = glm::vec3(0.0, 0.0, 0.0);
+    let look = |dir: glm::Vec3, up: glm::Vec3| glm::look_at(&eye, &dir, &up);
+    [
+        look(glm::vec3(1.0, 0.0, 0.0), glm::vec3(0.0, -1.0, 0.0)),
+        look(glm::vec3(-1.0, 0.0, 0.0), glm::vec3(0.0, -1.0, 0.0)),
+        look(glm::vec3(0.0, 1.0, 0.0), glm::vec3(0.0, 0.0, 1.0)),
+        look(glm::vec3(0.0, -1.0, 0.0), glm::vec3(0.0, 0.0, -1.0)),
+        look(glm::vec3(0.0, 0.0, 1.0), glm::vec3(0.0, -1.0, 0.0)),
+        look(glm::vec3(0.0, 0.0, -1.0), glm::vec3(0.0, -1.0, 0.0)),
+    ]
+}
+
+/// Roughness prefiltered into `level` of a `levels` deep map.
+pub fn prefilter_roughness(level: u32, levels: u32) -> f32 {
+    if levels <= 1 {
+        0.0
+    } else {
+        level as f32 / (levels - 1) as f32
+    }
+}
+
+// Cube from -1 to 1 on each axis; draw it without culling to see it from inside.
+fn cube_mesh(state: RenderState) -> Result<Mesh, String> {
+    geometry::cube(2.0, 1).build(state)
+}
+
+// Renders `shader` over all six faces of mip `level` of `target`.
+fn render_faces(
+    ctx: &Context,
+    fbo: &Framebuffer,
+    cube: &Mesh,
+    shader: &Shader,
+    target: &TextureCube,
+    level: u32,
+) -> Result<(), String> {
+    let size = target.level_size(level);
+    ctx.set_viewport_size(size, size);
+    shader.set_mat4("Projection", &capture_projection());
+    for (face, view) in capture_views().iter().enumerate() {
+        fbo.attach_cube_face(gl::COLOR_ATTACHMENT0, target, face, level);
+        if face == 0 {
+            fbo.check()?;
+        }
+        shader.set_mat4("View", view);
+        fbo.binding(|| {
+            ctx.clear_buffer(gl::COLOR_BUFFER_BIT);
+            cube.draw(ctx);
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Directions GL samples at the center of each face, in `TextureCube::FACES` order.
+    const FACE_DIRECTIONS: [[f32; 3]; 6] = [
+        [1.0, 0.0, 0.0],
+        [-1.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [0.0, -1.0, 0.0],
+        [0.0, 0.0, 1.0],
+        [0.0, 0.0, -1.0],
+    ];
+
+    fn view_space(view: &glm::Mat4, v: &glm::Vec3) -> glm::Vec3 {
+        (view * glm::vec4(v.x, v.y, v.z, 0.0)).xyz()
+    }
+
+    #[test]
+    fn capture_views_look_through_each_face() {
+        for (view, dir) in capture_views().iter().zip(FACE_DIRECTIONS.iter()) {
+            let forward = view_space(view, &glm::make_vec3(dir));
+            assert!(glm::distance(&forward, &glm::vec3(0.0, 0.0, -1.0)) < 1e-6);
+        }
+
+        // Faces are stored top row first but rendered bottom row first, so the side faces
+        // are rendered with -Y up.
+        let down = view_space(&capture_views()[0], &glm::vec3(0.0, -1.0, 0.0));
+        assert!(glm::distance(&down, &glm::vec3(0.0, 1.0, 0.0)) < 1e-6);
+    }
+
+    #[test]
+    fn capture_projection_covers_a_face() {
+        // The corner of a face lands on the corner of clip space.
+        let corner = capture_projection() * glm::vec4(1.0, 1.0, -1.0, 1.0);
+        assert!((corner.x / corner.w - 1.0).abs() < 1e-5);
+        assert!((corner.y / corner.w - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn spreads_roughness_over_levels() {
+        let levels = IblSettings::default().prefilter_levels;
+        let roughness: Vec<f32> = (0..levels)
+            .map(|level| prefilter_roughness(level, levels))
+            .collect();
+        assert_eq!(roughness, vec![0.0, 0.25, 0.5, 0.75, 1.0]);
+        assert_eq!(prefilter_roughness(0, 1), 0.0);
+    }
+}
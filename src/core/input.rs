@@ -0,0 +1,403 @@
+use std::collections::HashSet;
+
+use imgui_glfw_rs::glfw;
+use nalgebra_glm as glm;
+
+const JOYSTICKS: [glfw::JoystickId; 16] = [
+    glfw::JoystickId::Joystick1,
+    glfw::JoystickId::Joystick2,
+    glfw::JoystickId::Joystick3,
+    glfw::JoystickId::Joystick4,
+    glfw::JoystickId::Joystick5,
+    glfw::JoystickId::Joystick6,
+    glfw::JoystickId::Joystick7,
+    glfw::JoystickId::Joystick8,
+    glfw::JoystickId::Joystick9,
+    glfw::JoystickId::Joystick10,
+    glfw::JoystickId::Joystick11,
+    glfw::JoystickId::Joystick12,
+    glfw::JoystickId::Joystick13,
+    glfw::JoystickId::Joystick14,
+    glfw::JoystickId::Joystick15,
+    glfw::JoystickId::Joystick16,
+];
+
+/// Raw state of a connected joystick. Gamepads with a known mapping also report
+/// `GamepadButton`/`GamepadAxis` values.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Gamepad {
+    pub id: glfw::JoystickId,
+    pub name: String,
+    axes: Vec<f32>,
+    buttons: Vec<bool>,
+    prev_buttons: Vec<bool>,
+    mapped_axes: Option<Vec<f32>>,
+    mapped_buttons: Option<Vec<bool>>,
+    prev_mapped_buttons: Vec<bool>,
+}
+
+#[allow(dead_code)]
+impl Gamepad {
+    /// A gamepad with a standard mapping, indexed by `GamepadAxis` and `GamepadButton`.
+    pub fn mapped<S>(id: glfw::JoystickId, name: S, axes: Vec<f32>, buttons: Vec<bool>) -> Gamepad
+    where
+        S: Into<String>,
+    {
+        Gamepad {
+            id,
+            name: name.into(),
+            axes: axes.clone(),
+            buttons: buttons.clone(),
+            prev_buttons: Vec::new(),
+            mapped_axes: Some(axes),
+            mapped_buttons: Some(buttons),
+            prev_mapped_buttons: Vec::new(),
+        }
+    }
+
+    pub fn is_gamepad(&self) -> bool {
+        self.mapped_buttons.is_some()
+    }
+
+    pub fn axes(&self) -> &[f32] {
+        &self.axes
+    }
+
+    pub fn raw_button_down(&self, index: usize) -> bool {
+        self.buttons.get(index).cloned().unwrap_or(false)
+    }
+
+    pub fn raw_button_pressed(&self, index: usize) -> bool {
+        self.raw_button_down(index) && !self.prev_buttons.get(index).cloned().unwrap_or(false)
+    }
+
+    pub fn axis(&self, axis: glfw::GamepadAxis) -> f32 {
+        self.mapped_axes
+            .as_ref()
+            .and_then(|axes| axes.get(axis as usize).cloned())
+            .unwrap_or(0.0)
+    }
+
+    pub fn button_down(&self, button: glfw::GamepadButton) -> bool {
+        self.mapped_buttons
+            .as_ref()
+            .and_then(|buttons| buttons.get(button as usize).cloned())
+            .unwrap_or(false)
+    }
+
+    pub fn button_pressed(&self, button: glfw::GamepadButton) -> bool {
+        self.button_down(button)
+            && !self
+                .prev_mapped_buttons
+                .get(button as usize)
+                .cloned()
+                .unwrap_or(false)
+    }
+
+    pub fn button_released(&self, button: glfw::GamepadButton) -> bool {
+        !self.button_down(button)
+            && self
+                .prev_mapped_buttons
+                .get(button as usize)
+                .cloned()
+                .unwrap_or(false)
+    }
+}
+
+/// Keyboard, mouse and gamepad state gathered from GLFW events.
+/// "Pressed" and "released" are only true during the frame after the event arrived.
+#[derive(Debug, Clone)]
+pub struct Input {
+    keys_down: HashSet<glfw::Key>,
+    keys_pressed: HashSet<glfw::Key>,
+    keys_released: HashSet<glfw::Key>,
+    buttons_down: HashSet<glfw::MouseButton>,
+    buttons_pressed: HashSet<glfw::MouseButton>,
+    buttons_released: HashSet<glfw::MouseButton>,
+    modifiers: glfw::Modifiers,
+    cursor: Option<glm::Vec2>,
+    cursor_delta: glm::Vec2,
+    cursor_inside: bool,
+    scroll: glm::Vec2,
+    text: String,
+    gamepads: Vec<Gamepad>,
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Input::new()
+    }
+}
+
+#[allow(dead_code)]
+impl Input {
+    pub fn new() -> Input {
+        Input {
+            keys_down: HashSet::new(),
+            keys_pressed: HashSet::new(),
+            keys_released: HashSet::new(),
+            buttons_down: HashSet::new(),
+            buttons_pressed: HashSet::new(),
+            buttons_released: HashSet::new(),
+            modifiers: glfw::Modifiers::empty(),
+            cursor: None,
+            cursor_delta: glm::vec2(0.0, 0.0),
+            cursor_inside: false,
+            scroll: glm::vec2(0.0, 0.0),
+            text: String::new(),
+            gamepads: Vec::new(),
+        }
+    }
+
+    /// Clears per-frame state. Called before the frame's events are handled.
+    pub fn begin_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.cursor_delta = glm::vec2(0.0, 0.0);
+        self.scroll = glm::vec2(0.0, 0.0);
+        self.text.clear();
+    }
+
+    pub fn handle_event(&mut self, event: &glfw::WindowEvent) {
+        match *event {
+            glfw::WindowEvent::Key(key, _, action, mods) => {
+                self.modifiers = mods;
+                match action {
+                    glfw::Action::Press => {
+                        self.keys_down.insert(key);
+                        self.keys_pressed.insert(key);
+                    }
+                    glfw::Action::Release => {
+                        self.keys_down.remove(&key);
+                        self.keys_released.insert(key);
+                    }
+                    glfw::Action::Repeat => {}
+                }
+            }
+            glfw::WindowEvent::MouseButton(button, action, mods) => {
+                self.modifiers = mods;
+                match action {
+                    glfw::Action::Press => {
+                        self.buttons_down.insert(button);
+                        self.buttons_pressed.insert(button);
+                    }
+                    glfw::Action::Release => {
+                        self.buttons_down.remove(&button);
+                        self.buttons_released.insert(button);
+                    }
+                    glfw::Action::Repeat => {}
+                }
+            }
+            glfw::WindowEvent::CursorPos(x, y) => {
+                let pos = glm::vec2(x as f32, y as f32);
+                if let Some(prev) = self.cursor {
+                    self.cursor_delta += pos - prev;
+                }
+                self.cursor = Some(pos);
+            }
+            glfw::WindowEvent::CursorEnter(entered) => {
+                self.cursor_inside = entered;
+                if !entered {
+                    // Avoid a jump when the cursor comes back somewhere else.
+                    self.cursor = None;
+                }
+            }
+            glfw::WindowEvent::Scroll(x, y) => {
+                self.scroll += glm::vec2(x as f32, y as f32);
+            }
+            glfw::WindowEvent::Char(c) => self.text.push(c),
+            glfw::WindowEvent::Focus(false) => {
+                // Keys released while unfocused never arrive.
+                for key in self.keys_down.drain() {
+                    self.keys_released.insert(key);
+                }
+                for button in self.buttons_down.drain() {
+                    self.buttons_released.insert(button);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Polls every joystick slot.
+    pub fn update_gamepads(&mut self, glfw: &glfw::Glfw) {
+        let mut gamepads = Vec::new();
+        for &id in JOYSTICKS.iter() {
+            let joystick = glfw.get_joystick(id);
+            if !joystick.is_present() {
+                continue;
+            }
+            let state = joystick.get_gamepad_state();
+            gamepads.push(Gamepad {
+                id,
+                name: joystick
+                    .get_gamepad_name()
+                    .or_else(|| joystick.get_name())
+                    .unwrap_or_default(),
+                axes: joystick.get_axes(),
+                buttons: joystick
+                    .get_buttons()
+                    .iter()
+                    .map(|&b| b == glfw::Action::Press as i32)
+                    .collect(),
+                prev_buttons: Vec::new(),
+                mapped_axes: state.as_ref().map(|s| {
+                    (0..6)
+                        .filter_map(glfw::GamepadAxis::from_i32)
+                        .map(|a| s.get_axis(a))
+                        .collect()
+                }),
+                mapped_buttons: state.as_ref().map(|s| {
+                    (0..15)
+                        .filter_map(glfw::GamepadButton::from_i32)
+                        .map(|b| s.get_button_state(b) == glfw::Action::Press)
+                        .collect()
+                }),
+                prev_mapped_buttons: Vec::new(),
+            });
+        }
+        self.set_gamepads(gamepads);
+    }
+
+    /// Replaces the gamepad state, keeping the previous buttons for edge detection.
+    pub fn set_gamepads(&mut self, mut gamepads: Vec<Gamepad>) {
+        for gamepad in gamepads.iter_mut() {
+            if let Some(old) = self.gamepads.iter().find(|g| g.id == gamepad.id) {
+                gamepad.prev_buttons = old.buttons.clone();
+                gamepad.prev_mapped_buttons = old.mapped_buttons.clone().unwrap_or_default();
+            }
+        }
+        self.gamepads = gamepads;
+    }
+
+    pub fn key_down(&self, key: glfw::Key) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    pub fn key_pressed(&self, key: glfw::Key) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+
+    pub fn key_released(&self, key: glfw::Key) -> bool {
+        self.keys_released.contains(&key)
+    }
+
+    pub fn mouse_down(&self, button: glfw::MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    pub fn mouse_pressed(&self, button: glfw::MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    pub fn mouse_released(&self, button: glfw::MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    pub fn modifiers(&self) -> glfw::Modifiers {
+        self.modifiers
+    }
+
+    /// Cursor position in window pixels with the origin at the top-left.
+    pub fn cursor(&self) -> glm::Vec2 {
+        self.cursor.unwrap_or_else(|| glm::vec2(0.0, 0.0))
+    }
+
+    /// Cursor movement since the last frame.
+    pub fn cursor_delta(&self) -> glm::Vec2 {
+        self.cursor_delta
+    }
+
+    pub fn cursor_inside(&self) -> bool {
+        self.cursor_inside
+    }
+
+    /// Scroll offset accumulated since the last frame.
+    pub fn scroll(&self) -> glm::Vec2 {
+        self.scroll
+    }
+
+    /// Characters typed since the last frame.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn gamepads(&self) -> &[Gamepad] {
+        &self.gamepads
+    }
+
+    pub fn gamepad(&self, id: glfw::JoystickId) -> Option<&Gamepad> {
+        self.gamepads.iter().find(|g| g.id == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: glfw::Key, action: glfw::Action) -> glfw::WindowEvent {
+        glfw::WindowEvent::Key(key, 0, action, glfw::Modifiers::empty())
+    }
+
+    #[test]
+    fn tracks_key_edges() {
+        let mut input = Input::new();
+        input.begin_frame();
+        input.handle_event(&key(glfw::Key::W, glfw::Action::Press));
+        assert!(input.key_down(glfw::Key::W));
+        assert!(input.key_pressed(glfw::Key::W));
+
+        input.begin_frame();
+        input.handle_event(&key(glfw::Key::W, glfw::Action::Repeat));
+        assert!(input.key_down(glfw::Key::W));
+        assert!(!input.key_pressed(glfw::Key::W));
+
+        input.begin_frame();
+        input.handle_event(&key(glfw::Key::W, glfw::Action::Release));
+        assert!(!input.key_down(glfw::Key::W));
+        assert!(input.key_released(glfw::Key::W));
+    }
+
+    #[test]
+    fn accumulates_cursor_and_scroll() {
+        let mut input = Input::new();
+        input.begin_frame();
+        input.handle_event(&glfw::WindowEvent::CursorPos(10.0, 10.0));
+        assert_eq!(input.cursor_delta(), glm::vec2(0.0, 0.0));
+        input.handle_event(&glfw::WindowEvent::CursorPos(15.0, 8.0));
+        input.handle_event(&glfw::WindowEvent::CursorPos(20.0, 4.0));
+        input.handle_event(&glfw::WindowEvent::Scroll(0.0, 1.0));
+        input.handle_event(&glfw::WindowEvent::Scroll(0.0, 2.0));
+        assert_eq!(input.cursor(), glm::vec2(20.0, 4.0));
+        assert_eq!(input.cursor_delta(), glm::vec2(10.0, -6.0));
+        assert_eq!(input.scroll(), glm::vec2(0.0, 3.0));
+
+        input.begin_frame();
+        assert_eq!(input.cursor_delta(), glm::vec2(0.0, 0.0));
+        assert_eq!(input.scroll(), glm::vec2(0.0, 0.0));
+    }
+
+    #[test]
+    fn collects_text_and_releases_on_focus_loss() {
+        let mut input = Input::new();
+        input.begin_frame();
+        input.handle_event(&glfw::WindowEvent::Char('h'));
+        input.handle_event(&glfw::WindowEvent::Char('i'));
+        input.handle_event(&glfw::WindowEvent::MouseButton(
+            glfw::MouseButton::Button1,
+            glfw::Action::Press,
+            glfw::Modifiers::empty(),
+        ));
+        assert_eq!(input.text(), "hi");
+        assert!(input.mouse_pressed(glfw::MouseButton::Button1));
+
+        input.begin_frame();
+        input.handle_event(&glfw::WindowEvent::Focus(false));
+        assert!(!input.mouse_down(glfw::MouseButton::Button1));
+        assert!(input.mouse_released(glfw::MouseButton::Button1));
+        assert_eq!(input.text(), "");
+    }
+}
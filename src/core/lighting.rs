@@ -0,0 +1,383 @@
+use nalgebra_glm as glm;
+
+use super::buffer::Buffer;
+use super::scene::Scene;
+use super::shader::Shader;
+
+/// Lights a `LightBlock` holds; must match `MAX_LIGHTS` in the GLSL side.
+pub const MAX_LIGHTS: usize = 16;
+
+/// Uniform buffer binding point of the `Lighting` block.
+pub const LIGHTING_BINDING: u32 = 0;
+
+/// Light attached to a scene node; position and direction come from the node's world transform.
+/// Directional and spot lights shine along the node's local -Z axis.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Light {
+    Directional {
+        color: glm::Vec3,
+        intensity: f32,
+    },
+    /// Fades out completely at `range`.
+    Point {
+        color: glm::Vec3,
+        intensity: f32,
+        range: f32,
+    },
+    /// Cone angles are half angles in radians; the light fades between them.
+    Spot {
+        color: glm::Vec3,
+        intensity: f32,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+#[allow(dead_code)]
+impl Light {
+    pub fn color(&self) -> glm::Vec3 {
+        match *self {
+            Light::Directional { color, .. }
+            | Light::Point { color, .. }
+            | Light::Spot { color, .. } => color,
+        }
+    }
+
+    pub fn intensity(&self) -> f32 {
+        match *self {
+            Light::Directional { intensity, .. }
+            | Light::Point { intensity, .. }
+            | Light::Spot { intensity, .. } => intensity,
+        }
+    }
+
+    /// Distance at which the light has faded out; infinite for directional lights.
+    pub fn range(&self) -> f32 {
+        match *self {
+            Light::Directional { .. } => f32::INFINITY,
+            Light::Point { range, .. } | Light::Spot { range, .. } => range,
+        }
+    }
+
+    /// The light's `Light` struct of the `Lighting` block, placed by `world`. Also the layout
+    /// of a single light uniform such as the one `deferred_light.fs.glsl` reads.
+    pub fn std140(&self, world: &glm::Mat4) -> [glm::Vec4; 4] {
+        let position = world.column(3).xyz();
+        let direction = glm::normalize(&-world.column(2).xyz());
+        let range = self.range();
+        let (attenuation, cone) = match *self {
+            Light::Directional { .. } => (
+                Attenuation {
+                    linear: 0.0,
+                    quadratic: 0.0,
+                },
+                (1.0, 1.0),
+            ),
+            Light::Point { range, .. } => (Attenuation::from_range(range), (1.0, 1.0)),
+            Light::Spot {
+                range,
+                inner_angle,
+                outer_angle,
+                ..
+            } => (
+                Attenuation::from_range(range),
+                (inner_angle.cos(), outer_angle.cos()),
+            ),
+        };
+        let range = if range.is_finite() { range } else { 0.0 };
+        [
+            glm::vec4(position.x, position.y, position.z, self.kind()),
+            glm::vec4(direction.x, direction.y, direction.z, range),
+            glm::vec4(
+                self.color().x,
+                self.color().y,
+                self.color().z,
+                self.intensity(),
+            ),
+            glm::vec4(attenuation.linear, attenuation.quadratic, cone.0, cone.1),
+        ]
+    }
+
+    // Type tag read by the shader.
+    fn kind(&self) -> f32 {
+        match self {
+            Light::Directional { .. } => 0.0,
+            Light::Point { .. } => 1.0,
+            Light::Spot { .. } => 2.0,
+        }
+    }
+}
+
+/// Distance falloff `1 / (1 + linear * d + quadratic * d^2)`, windowed to reach zero at the range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attenuation {
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+#[allow(dead_code)]
+impl Attenuation {
+    /// Coefficients that leave about 1% of the light at `range` before windowing.
+    pub fn from_range(range: f32) -> Attenuation {
+        Attenuation {
+            linear: 4.5 / range,
+            quadratic: 75.0 / (range * range),
+        }
+    }
+
+    /// Fraction of the light left at `distance`; the shader computes the same value.
+    pub fn factor(&self, distance: f32, range: f32) -> f32 {
+        let falloff = 1.0 / (1.0 + self.linear * distance + self.quadratic * distance * distance);
+        let window = (1.0 - (distance / range).powi(4)).max(0.0);
+        falloff * window * window
+    }
+}
+
+/// Spot cone falloff for the cosine of the angle between the spot axis and the light ray.
+#[allow(dead_code)]
+pub fn spot_factor(cos_angle: f32, inner_angle: f32, outer_angle: f32) -> f32 {
+    let (cos_inner, cos_outer) = (inner_angle.cos(), outer_angle.cos());
+    let t = ((cos_angle - cos_outer) / (cos_inner - cos_outer).max(1e-4)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// CPU copy of the `Lighting` uniform block:
+///
+/// ```glsl
+/// struct Light {
+///     vec4 PositionType;   // xyz world position, w 0 directional / 1 point / 2 spot
+///     vec4 DirectionRange; // xyz direction the light shines in, w range
+///     vec4 ColorIntensity; // rgb color, a intensity
+///     vec4 Falloff;        // linear, quadratic, cos inner angle, cos outer angle
+/// };
+/// layout(std140) uniform Lighting {
+///     vec4 Ambient;
+///     vec4 CameraPosition;
+///     ivec4 LightCount;
+///     Light Lights[MAX_LIGHTS];
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightBlock {
+    ambient: glm::Vec3,
+    camera_position: glm::Vec3,
+    lights: Vec<[glm::Vec4; 4]>,
+}
+
+#[allow(dead_code)]
+impl LightBlock {
+    /// Floats in the std140 layout: three header vec4s plus four per light.
+    pub const FLOATS: usize = 4 * (3 + 4 * MAX_LIGHTS);
+
+    pub fn new(ambient: glm::Vec3) -> LightBlock {
+        LightBlock {
+            ambient,
+            camera_position: glm::vec3(0.0, 0.0, 0.0),
+            lights: Vec::new(),
+        }
+    }
+
+    /// Every visible light in `scene`, as of its last transform update.
+    pub fn from_scene(scene: &Scene, ambient: glm::Vec3) -> LightBlock {
+        let mut block = LightBlock::new(ambient);
+        for (light, world) in scene.lights() {
+            block.push(&light, &world);
+        }
+        block
+    }
+
+    pub fn set_camera_position(&mut self, position: glm::Vec3) {
+        self.camera_position = position;
+    }
+
+    pub fn clear(&mut self) {
+        self.lights.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    /// Adds a light placed by `world`. Returns false, dropping the light, once `MAX_LIGHTS` are in.
+    pub fn push(&mut self, light: &Light, world: &glm::Mat4) -> bool {
+        if self.lights.len() >= MAX_LIGHTS {
+            log::warn!("Ignoring light beyond the limit of {}", MAX_LIGHTS);
+            return false;
+        }
+        self.lights.push(light.std140(world));
+        true
+    }
+
+    /// The block in std140 layout, `FLOATS` long. The light count is stored as integer bits.
+    pub fn std140(&self) -> Vec<f32> {
+        let mut data = Vec::with_capacity(LightBlock::FLOATS);
+        data.extend_from_slice(&[self.ambient.x, self.ambient.y, self.ambient.z, 0.0]);
+        let camera = &self.camera_position;
+        data.extend_from_slice(&[camera.x, camera.y, camera.z, 1.0]);
+        data.extend_from_slice(&[f32::from_bits(self.lights.len() as u32), 0.0, 0.0, 0.0]);
+        for light in self.lights.iter() {
+            for v in light.iter() {
+                data.extend_from_slice(v.as_slice());
+            }
+        }
+        data.resize(LightBlock::FLOATS, 0.0);
+        data
+    }
+}
+
+/// Uniform buffer holding a `LightBlock` at `LIGHTING_BINDING`.
+pub struct LightingBuffer {
+    buffer: Buffer,
+}
+
+#[allow(dead_code)]
+impl LightingBuffer {
+    pub fn new() -> Result<LightingBuffer, String> {
+        let buffer = Buffer::with_size(
+            gl::UNIFORM_BUFFER,
+            LightBlock::FLOATS * std::mem::size_of::<f32>(),
+            gl::DYNAMIC_DRAW,
+        )?;
+        buffer.set_label("Lighting");
+        Ok(LightingBuffer { buffer })
+    }
+
+    /// Points the shader's `Lighting` block at this buffer's binding point.
+    pub fn attach(&self, shader: &Shader) -> Result<(), String> {
+        shader.bind_uniform_block("Lighting", LIGHTING_BINDING)
+    }
+
+    pub fn upload(&self, block: &LightBlock) {
+        self.buffer.sub_data(0, &block.std140());
+        self.buffer.bind_base(LIGHTING_BINDING);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::device::RecordingDevice;
+    use crate::core::resource::ContextOwner;
+    use crate::core::scene::Transform;
+
+    const WHITE: glm::Vec3 = glm::Vec3::new(1.0, 1.0, 1.0);
+
+    #[test]
+    fn attenuation_falls_off_to_zero_at_range() {
+        let att = Attenuation::from_range(10.0);
+        assert_eq!(att.factor(0.0, 10.0), 1.0);
+        let samples: Vec<f32> = (0..=10).map(|d| att.factor(d as f32, 10.0)).collect();
+        assert!(samples.windows(2).all(|w| w[1] < w[0]));
+        assert_eq!(att.factor(10.0, 10.0), 0.0);
+        assert_eq!(att.factor(25.0, 10.0), 0.0);
+        // Half way out most of the light is gone.
+        assert!(att.factor(5.0, 10.0) < 0.05);
+    }
+
+    #[test]
+    fn spot_cone_blends_between_angles() {
+        let (inner, outer) = (0.2f32, 0.4f32);
+        assert_eq!(spot_factor(1.0, inner, outer), 1.0);
+        assert_eq!(spot_factor(inner.cos(), inner, outer), 1.0);
+        assert_eq!(spot_factor(outer.cos(), inner, outer), 0.0);
+        assert_eq!(spot_factor(0.0, inner, outer), 0.0);
+        let mid = spot_factor(0.3f32.cos(), inner, outer);
+        assert!(mid > 0.3 && mid < 0.7, "{}", mid);
+    }
+
+    #[test]
+    fn packs_lights_in_std140_layout() {
+        let mut block = LightBlock::new(glm::vec3(0.1, 0.1, 0.1));
+        block.set_camera_position(glm::vec3(0.0, 2.0, 5.0));
+        let spot = Light::Spot {
+            color: glm::vec3(1.0, 0.5, 0.0),
+            intensity: 3.0,
+            range: 8.0,
+            inner_angle: 0.3,
+            outer_angle: 0.5,
+        };
+        let world = Transform::from_translation(glm::vec3(1.0, 4.0, 0.0))
+            .with_rotation(glm::quat_angle_axis(
+                -std::f32::consts::FRAC_PI_2,
+                &glm::Vec3::x(),
+            ))
+            .matrix();
+        assert!(block.push(&spot, &world));
+
+        let data = block.std140();
+        assert_eq!(data.len(), LightBlock::FLOATS);
+        assert_eq!(&data[4..8], &[0.0, 2.0, 5.0, 1.0]);
+        assert_eq!(data[8].to_bits(), 1);
+
+        let light = &data[12..28];
+        assert_eq!(&light[0..4], &[1.0, 4.0, 0.0, 2.0]);
+        // Pitched down, so the spot shines along -Y.
+        assert!(glm::distance(&glm::make_vec3(&light[4..7]), &glm::vec3(0.0, -1.0, 0.0)) < 1e-5);
+        assert_eq!(light[7], 8.0);
+        assert_eq!(&light[8..12], &[1.0, 0.5, 0.0, 3.0]);
+        assert_eq!(&light[12..14], &[4.5 / 8.0, 75.0 / 64.0]);
+        assert!((light[14] - 0.3f32.cos()).abs() < 1e-6);
+        // Unused slots stay zeroed.
+        assert!(data[28..].iter().all(|&f| f == 0.0));
+    }
+
+    #[test]
+    fn caps_light_count() {
+        let mut block = LightBlock::new(WHITE);
+        let sun = Light::Directional {
+            color: WHITE,
+            intensity: 1.0,
+        };
+        for _ in 0..MAX_LIGHTS {
+            assert!(block.push(&sun, &glm::Mat4::identity()));
+        }
+        assert!(!block.push(&sun, &glm::Mat4::identity()));
+        assert_eq!(block.len(), MAX_LIGHTS);
+        assert_eq!(block.std140().len(), LightBlock::FLOATS);
+    }
+
+    #[test]
+    fn collects_visible_scene_lights() {
+        let mut scene = Scene::new();
+        let lamp = scene.add("lamp");
+        let hidden = scene.add("hidden");
+        for &id in &[lamp, hidden] {
+            scene.node_mut(id).unwrap().light = Some(Light::Point {
+                color: WHITE,
+                intensity: 1.0,
+                range: 5.0,
+            });
+        }
+        scene.set_transform(lamp, Transform::from_translation(glm::vec3(0.0, 3.0, 0.0)));
+        scene.node_mut(hidden).unwrap().set_visible(false);
+        scene.update_transforms();
+
+        let block = LightBlock::from_scene(&scene, WHITE);
+        assert_eq!(block.len(), 1);
+        assert_eq!(&block.std140()[12..16], &[0.0, 3.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn uploads_to_the_binding_point() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let lighting = LightingBuffer::new().unwrap();
+        device.take();
+        lighting.upload(&LightBlock::new(WHITE));
+        assert_eq!(
+            device.take(),
+            vec![
+                "BindBuffer(UNIFORM_BUFFER, 1)",
+                "BufferSubData(UNIFORM_BUFFER, 0, 1072)",
+                "BindBuffer(UNIFORM_BUFFER, 0)",
+                "BindBufferBase(UNIFORM_BUFFER, 0, 1)",
+            ]
+        );
+    }
+}
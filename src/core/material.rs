@@ -0,0 +1,687 @@
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use indexmap::IndexMap;
+use nalgebra_glm as glm;
+use serde::Deserialize;
+
+use super::framework::Context;
+use super::shader::Shader;
+use super::state::{Blend, CompareFunc, Face, RenderState};
+use super::texture::{Texture2D, TextureBuilder};
+
+/// Typed value of a shader uniform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Param {
+    Int(i32),
+    Float(f32),
+    Vec2(glm::Vec2),
+    Vec3(glm::Vec3),
+    Vec4(glm::Vec4),
+    Mat4(glm::Mat4),
+}
+
+impl From<i32> for Param {
+    fn from(v: i32) -> Param {
+        Param::Int(v)
+    }
+}
+
+impl From<f32> for Param {
+    fn from(v: f32) -> Param {
+        Param::Float(v)
+    }
+}
+
+impl From<glm::Vec2> for Param {
+    fn from(v: glm::Vec2) -> Param {
+        Param::Vec2(v)
+    }
+}
+
+impl From<glm::Vec3> for Param {
+    fn from(v: glm::Vec3) -> Param {
+        Param::Vec3(v)
+    }
+}
+
+impl From<glm::Vec4> for Param {
+    fn from(v: glm::Vec4) -> Param {
+        Param::Vec4(v)
+    }
+}
+
+impl From<glm::Mat4> for Param {
+    fn from(v: glm::Mat4) -> Param {
+        Param::Mat4(v)
+    }
+}
+
+#[allow(dead_code)]
+impl Param {
+    /// Integers become `Int`, floats `Float`, and arrays of 2, 3, 4 or 16 numbers
+    /// vectors or a column-major matrix. `Material::from_desc` turns integers meant for
+    /// float uniforms into `Float`.
+    pub fn from_toml(value: &toml::Value) -> Result<Param, String> {
+        let number = |v: &toml::Value| match v {
+            toml::Value::Float(f) => Ok(*f as f32),
+            toml::Value::Integer(i) => Ok(*i as f32),
+            _ => Err(format!("Expected a number, got {}", v)),
+        };
+        match value {
+            toml::Value::Integer(i) => Ok(Param::Int(*i as i32)),
+            toml::Value::Float(f) => Ok(Param::Float(*f as f32)),
+            toml::Value::Array(values) => {
+                let v = values.iter().map(number).collect::<Result<Vec<_>, _>>()?;
+                match v.len() {
+                    2 => Ok(Param::Vec2(glm::make_vec2(&v))),
+                    3 => Ok(Param::Vec3(glm::make_vec3(&v))),
+                    4 => Ok(Param::Vec4(glm::make_vec4(&v))),
+                    16 => Ok(Param::Mat4(glm::make_mat4(&v))),
+                    n => Err(format!(
+                        "Arrays must have 2, 3, 4 or 16 elements, got {}",
+                        n
+                    )),
+                }
+            }
+            _ => Err(format!("Unsupported parameter value {}", value)),
+        }
+    }
+
+    /// Sets the uniform `name` of the current program.
+    pub fn upload(&self, shader: &Shader, name: &str) {
+        match self {
+            Param::Int(v) => shader.set_int(name, *v),
+            Param::Float(v) => shader.set_float(name, *v),
+            Param::Vec2(v) => shader.set_vec2(name, v),
+            Param::Vec3(v) => shader.set_vec3(name, v),
+            Param::Vec4(v) => shader.set_vec4(name, v),
+            Param::Mat4(v) => shader.set_mat4(name, v),
+        }
+    }
+}
+
+/// How a material file describes a texture slot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureDesc {
+    pub path: String,
+    pub filter: u32,
+    pub wrap: u32,
+    pub mipmap: bool,
+}
+
+/// Parsed material file; `Material::from_desc` turns it into GL objects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialDesc {
+    pub vertex: String,
+    pub fragment: String,
+    pub textures: IndexMap<String, TextureDesc>,
+    pub params: IndexMap<String, Param>,
+    pub state: RenderState,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialFile {
+    vertex: String,
+    fragment: String,
+    #[serde(default)]
+    textures: IndexMap<String, TextureEntry>,
+    #[serde(default)]
+    params: IndexMap<String, toml::Value>,
+    #[serde(default)]
+    state: StateEntry,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextureEntry {
+    Path(String),
+    Full {
+        path: String,
+        #[serde(default = "TextureEntry::default_filter")]
+        filter: String,
+        #[serde(default = "TextureEntry::default_wrap")]
+        wrap: String,
+        #[serde(default)]
+        mipmap: bool,
+    },
+}
+
+impl TextureEntry {
+    fn default_filter() -> String {
+        "linear".to_string()
+    }
+
+    fn default_wrap() -> String {
+        "repeat".to_string()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StateEntry {
+    #[serde(default)]
+    blend: Option<String>,
+    #[serde(default)]
+    depth_test: Option<String>,
+    #[serde(default = "StateEntry::default_depth_write")]
+    depth_write: bool,
+    #[serde(default)]
+    cull: Option<String>,
+}
+
+impl StateEntry {
+    fn default_depth_write() -> bool {
+        true
+    }
+
+    fn to_state(&self) -> Result<RenderState, String> {
+        let blend = match self.blend.as_deref() {
+            None | Some("none") => None,
+            Some("alpha") => Some(Blend::ALPHA),
+            Some("premultiplied") => Some(Blend::PREMULTIPLIED),
+            Some("additive") => Some(Blend::ADDITIVE),
+            Some("multiply") => Some(Blend::MULTIPLY),
+            Some(other) => return Err(format!("Unknown blend mode \"{}\"", other)),
+        };
+        let depth_test = match self.depth_test.as_deref() {
+            None | Some("none") => None,
+            Some("less") => Some(CompareFunc::Less),
+            Some("lequal") => Some(CompareFunc::LessEqual),
+            Some("equal") => Some(CompareFunc::Equal),
+            Some("greater") => Some(CompareFunc::Greater),
+            Some("always") => Some(CompareFunc::Always),
+            Some(other) => return Err(format!("Unknown depth test \"{}\"", other)),
+        };
+        let cull = match self.cull.as_deref() {
+            None | Some("none") => None,
+            Some("back") => Some(Face::Back),
+            Some("front") => Some(Face::Front),
+            Some(other) => return Err(format!("Unknown cull mode \"{}\"", other)),
+        };
+        Ok(RenderState::default()
+            .with_blend(blend)
+            .with_depth(depth_test, self.depth_write)
+            .with_cull(cull))
+    }
+}
+
+impl Default for StateEntry {
+    fn default() -> Self {
+        StateEntry {
+            blend: None,
+            depth_test: None,
+            depth_write: true,
+            cull: None,
+        }
+    }
+}
+
+fn parse_filter(name: &str) -> Result<u32, String> {
+    match name {
+        "nearest" => Ok(gl::NEAREST),
+        "linear" => Ok(gl::LINEAR),
+        "linear_mipmap_linear" | "trilinear" => Ok(gl::LINEAR_MIPMAP_LINEAR),
+        "nearest_mipmap_nearest" => Ok(gl::NEAREST_MIPMAP_NEAREST),
+        _ => Err(format!("Unknown texture filter \"{}\"", name)),
+    }
+}
+
+fn parse_wrap(name: &str) -> Result<u32, String> {
+    match name {
+        "repeat" => Ok(gl::REPEAT),
+        "clamp" => Ok(gl::CLAMP_TO_EDGE),
+        "mirror" => Ok(gl::MIRRORED_REPEAT),
+        _ => Err(format!("Unknown texture wrap mode \"{}\"", name)),
+    }
+}
+
+#[allow(dead_code)]
+impl MaterialDesc {
+    /// Parses a material file such as
+    ///
+    /// ```toml
+    /// vertex = "res/glsl/texture.vs.glsl"
+    /// fragment = "res/glsl/texture.fs.glsl"
+    ///
+    /// [textures]
+    /// Tex0 = { path = "res/textures/sailboat-on-lake.png", filter = "linear", wrap = "repeat" }
+    ///
+    /// [params]
+    /// Tint = [1.0, 1.0, 1.0, 1.0]
+    ///
+    /// [state]
+    /// blend = "alpha"
+    /// ```
+    ///
+    /// Paths are relative to the working directory, like everywhere else in the crate.
+    pub fn from_toml(text: &str) -> Result<MaterialDesc, String> {
+        let file: MaterialFile =
+            toml::from_str(text).map_err(|e| format!("Could not parse material: {}", e))?;
+        let mut textures = IndexMap::new();
+        for (slot, entry) in file.textures {
+            let desc = match entry {
+                TextureEntry::Path(path) => TextureDesc {
+                    path,
+                    filter: gl::LINEAR,
+                    wrap: gl::REPEAT,
+                    mipmap: false,
+                },
+                TextureEntry::Full {
+                    path,
+                    filter,
+                    wrap,
+                    mipmap,
+                } => TextureDesc {
+                    path,
+                    filter: parse_filter(&filter)?,
+                    wrap: parse_wrap(&wrap)?,
+                    mipmap,
+                },
+            };
+            textures.insert(slot, desc);
+        }
+        let mut params = IndexMap::new();
+        for (name, value) in file.params.iter() {
+            let param =
+                Param::from_toml(value).map_err(|e| format!("Parameter \"{}\": {}", name, e))?;
+            params.insert(name.clone(), param);
+        }
+        Ok(MaterialDesc {
+            vertex: file.vertex,
+            fragment: file.fragment,
+            textures,
+            params,
+            state: file.state.to_state()?,
+        })
+    }
+
+    pub fn load<P>(path: P) -> Result<MaterialDesc, String>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("failed to open file: {}: {}", path.display(), e))?;
+        MaterialDesc::from_toml(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// A shader with the textures, uniform values and render state it is drawn with.
+///
+/// Texture slots are bound to consecutive units in insertion order, and the sampler
+/// uniform named after the slot is pointed at its unit.
+pub struct Material {
+    shader: Rc<Shader>,
+    textures: IndexMap<String, Rc<Texture2D>>,
+    params: IndexMap<String, Param>,
+    state: RenderState,
+}
+
+#[allow(dead_code)]
+impl Material {
+    /// Starts with the shader's own render state.
+    pub fn new(shader: Rc<Shader>) -> Material {
+        let state = *shader.render_state();
+        Material {
+            shader,
+            textures: IndexMap::new(),
+            params: IndexMap::new(),
+            state,
+        }
+    }
+
+    /// Compiles the shader and loads the textures a material file describes.
+    pub fn from_desc(desc: &MaterialDesc) -> Result<Material, String> {
+        let shader = Shader::new(&desc.vertex, &desc.fragment)?;
+        let mut material = Material::new(Rc::new(shader));
+        for (slot, tex) in desc.textures.iter() {
+            let builder = TextureBuilder::new().filter(tex.filter).wrap(tex.wrap);
+            let builder = if tex.mipmap {
+                builder.generate_mipmap()
+            } else {
+                builder
+            };
+            let texture = builder.build2d(&tex.path)?;
+            texture.set_label(&tex.path);
+            material.set_texture(slot, Rc::new(texture));
+        }
+        // `Shininess = 32` parses as an integer, which a float uniform would reject.
+        for (name, param) in desc.params.iter() {
+            let param = match *param {
+                Param::Int(v) if material.shader.uniform_type(name) == Some(gl::FLOAT) => {
+                    Param::Float(v as f32)
+                }
+                param => param,
+            };
+            material.params.insert(name.clone(), param);
+        }
+        material.state = desc.state;
+        Ok(material)
+    }
+
+    pub fn load<P>(path: P) -> Result<Material, String>
+    where
+        P: AsRef<Path>,
+    {
+        Material::from_desc(&MaterialDesc::load(path)?)
+    }
+
+    pub fn shader(&self) -> &Rc<Shader> {
+        &self.shader
+    }
+
+    pub fn set_texture(&mut self, slot: &str, texture: Rc<Texture2D>) {
+        self.textures.insert(slot.to_string(), texture);
+    }
+
+    pub fn texture(&self, slot: &str) -> Option<&Rc<Texture2D>> {
+        self.textures.get(slot)
+    }
+
+    pub fn set_param<T>(&mut self, name: &str, value: T)
+    where
+        T: Into<Param>,
+    {
+        self.params.insert(name.to_string(), value.into());
+    }
+
+    pub fn param(&self, name: &str) -> Option<&Param> {
+        self.params.get(name)
+    }
+
+    pub fn render_state(&self) -> &RenderState {
+        &self.state
+    }
+
+    pub fn set_render_state(&mut self, state: RenderState) {
+        self.state = state;
+    }
+
+    /// Applies the render state, then uploads everything with `upload`.
+    pub fn apply(&self, ctx: &Context) {
+        ctx.apply_state(&self.state);
+        self.upload();
+    }
+
+    /// Makes the program current and binds its textures and parameters.
+    pub fn upload(&self) {
+        self.upload_with(&IndexMap::new(), &IndexMap::new());
+    }
+
+    fn upload_with(
+        &self,
+        textures: &IndexMap<String, Rc<Texture2D>>,
+        params: &IndexMap<String, Param>,
+    ) {
+        let shader = &self.shader;
+        shader.use_program();
+
+        // Overrides replace a slot in place; new slots follow the material's own.
+        let slots = self
+            .textures
+            .iter()
+            .map(|(slot, tex)| (slot, textures.get(slot).unwrap_or(tex)));
+        let extra = textures
+            .iter()
+            .filter(|(slot, _)| !self.textures.contains_key(*slot));
+        for (unit, (slot, tex)) in slots.chain(extra).enumerate() {
+            tex.bind(unit as u32);
+            if shader.has_uniform(slot) {
+                shader.set_texture(slot, unit as i32);
+            }
+        }
+
+        let values = self
+            .params
+            .iter()
+            .map(|(name, value)| (name, params.get(name).unwrap_or(value)));
+        let extra = params
+            .iter()
+            .filter(|(name, _)| !self.params.contains_key(*name));
+        for (name, value) in values.chain(extra) {
+            // Uniforms the compiler optimized away are skipped rather than fatal.
+            if shader.has_uniform(name) {
+                value.upload(shader, name);
+            } else {
+                log::debug!("Material parameter \"{}\" is not used by the shader", name);
+            }
+        }
+    }
+}
+
+/// A shared `Material` with per-instance texture and parameter overrides.
+#[derive(Clone)]
+pub struct MaterialInstance {
+    material: Rc<Material>,
+    textures: IndexMap<String, Rc<Texture2D>>,
+    params: IndexMap<String, Param>,
+}
+
+#[allow(dead_code)]
+impl MaterialInstance {
+    pub fn new(material: Rc<Material>) -> MaterialInstance {
+        MaterialInstance {
+            material,
+            textures: IndexMap::new(),
+            params: IndexMap::new(),
+        }
+    }
+
+    pub fn material(&self) -> &Rc<Material> {
+        &self.material
+    }
+
+    pub fn shader(&self) -> &Rc<Shader> {
+        self.material.shader()
+    }
+
+    pub fn set_texture(&mut self, slot: &str, texture: Rc<Texture2D>) {
+        self.textures.insert(slot.to_string(), texture);
+    }
+
+    pub fn set_param<T>(&mut self, name: &str, value: T)
+    where
+        T: Into<Param>,
+    {
+        self.params.insert(name.to_string(), value.into());
+    }
+
+    /// Drops the override so the material's value applies again.
+    pub fn reset_param(&mut self, name: &str) {
+        self.params.shift_remove(name);
+    }
+
+    /// The override if set, otherwise the material's value.
+    pub fn param(&self, name: &str) -> Option<&Param> {
+        self.params.get(name).or_else(|| self.material.param(name))
+    }
+
+    pub fn render_state(&self) -> &RenderState {
+        &self.material.state
+    }
+
+    pub fn apply(&self, ctx: &Context) {
+        ctx.apply_state(&self.material.state);
+        self.upload();
+    }
+
+    pub fn upload(&self) {
+        self.material.upload_with(&self.textures, &self.params);
+    }
+}
+
+impl From<Rc<Material>> for MaterialInstance {
+    fn from(material: Rc<Material>) -> MaterialInstance {
+        MaterialInstance::new(material)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::device::RecordingDevice;
+    use crate::core::resource::ContextOwner;
+    use image::DynamicImage;
+
+    #[test]
+    fn parses_material_file() {
+        let desc = MaterialDesc::from_toml(
+            r#"
+            vertex = "a.vs.glsl"
+            fragment = "a.fs.glsl"
+
+            [textures]
+            Albedo = "albedo.png"
+            Normal = { path = "normal.png", filter = "nearest", wrap = "clamp", mipmap = true }
+
+            [params]
+            Tint = [1.0, 0.5, 0.25, 1]
+            Roughness = 0.5
+            Layers = 3
+            Offset = [0, 1]
+
+            [state]
+            blend = "alpha"
+            depth_test = "lequal"
+            depth_write = false
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(desc.vertex, "a.vs.glsl");
+        assert_eq!(
+            desc.textures.keys().collect::<Vec<_>>(),
+            vec!["Albedo", "Normal"]
+        );
+        assert_eq!(desc.textures["Albedo"].filter, gl::LINEAR);
+        assert_eq!(
+            desc.textures["Normal"],
+            TextureDesc {
+                path: "normal.png".to_string(),
+                filter: gl::NEAREST,
+                wrap: gl::CLAMP_TO_EDGE,
+                mipmap: true,
+            }
+        );
+        assert_eq!(
+            desc.params["Tint"],
+            Param::Vec4(glm::vec4(1.0, 0.5, 0.25, 1.0))
+        );
+        assert_eq!(desc.params["Roughness"], Param::Float(0.5));
+        assert_eq!(desc.params["Layers"], Param::Int(3));
+        assert_eq!(desc.params["Offset"], Param::Vec2(glm::vec2(0.0, 1.0)));
+        assert_eq!(
+            desc.state,
+            RenderState::default()
+                .with_blend(Some(Blend::ALPHA))
+                .with_depth(Some(CompareFunc::LessEqual), false)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_material_files() {
+        let base = "vertex = \"a\"\nfragment = \"b\"\n";
+        assert!(MaterialDesc::from_toml(base).is_ok());
+        assert!(MaterialDesc::from_toml("vertex = \"a\"").is_err());
+        let err = MaterialDesc::from_toml(&format!("{}[params]\nColor = [1, 2, 3, 4, 5]", base))
+            .unwrap_err();
+        assert!(err.contains("Color") && err.contains("5"), "{}", err);
+        assert!(MaterialDesc::from_toml(&format!("{}[params]\nName = \"x\"", base)).is_err());
+        assert!(MaterialDesc::from_toml(&format!(
+            "{}[textures]\nA = {{ path = \"a.png\", wrap = \"bounce\" }}",
+            base
+        ))
+        .is_err());
+        assert!(MaterialDesc::from_toml(&format!("{}[state]\nblend = \"screen\"", base)).is_err());
+    }
+
+    fn texture() -> Rc<Texture2D> {
+        let tex = TextureBuilder::new()
+            .filter(gl::LINEAR)
+            .wrap(gl::REPEAT)
+            .build2d_from_image(DynamicImage::new_rgba8(1, 1))
+            .unwrap();
+        Rc::new(tex)
+    }
+
+    #[test]
+    fn uploads_textures_and_params_with_overrides() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let shader = Shader::new("res/glsl/texture.vs.glsl", "res/glsl/texture.fs.glsl").unwrap();
+        let (albedo, detail, replacement) = (texture(), texture(), texture());
+
+        let mut material = Material::new(Rc::new(shader));
+        material.set_texture("Albedo", albedo.clone());
+        material.set_texture("Detail", detail.clone());
+        material.set_param("Tint", glm::vec4(1.0, 1.0, 1.0, 1.0));
+        material.set_param("Strength", 0.5);
+        let material = Rc::new(material);
+
+        let mut instance = MaterialInstance::new(material.clone());
+        instance.set_texture("Albedo", replacement.clone());
+        instance.set_param("Strength", 2.0);
+        instance.set_param("Layer", 1);
+        assert_eq!(instance.param("Strength"), Some(&Param::Float(2.0)));
+        assert_eq!(instance.param("Tint"), material.param("Tint"));
+
+        device.take();
+        instance.upload();
+        let binds = device.calls_to("BindTexture");
+        assert_eq!(
+            binds,
+            vec![
+                format!("BindTexture(TEXTURE_2D, {})", replacement.id()),
+                format!("BindTexture(TEXTURE_2D, {})", detail.id()),
+            ]
+        );
+        assert_eq!(
+            device.calls_to("Uniform1i"),
+            vec!["Uniform1i(0, 0)", "Uniform1i(0, 1)", "Uniform1i(0, 1)"]
+        );
+        assert_eq!(
+            device.calls_to("Uniform4fv"),
+            vec!["Uniform4fv(0, [1.0, 1.0, 1.0, 1.0])"]
+        );
+        assert_eq!(device.calls_to("Uniform1f"), vec!["Uniform1f(0, 2.0)"]);
+
+        // The shared material is unaffected by the instance's overrides.
+        device.take();
+        material.upload();
+        assert_eq!(device.calls_to("Uniform1f"), vec!["Uniform1f(0, 0.5)"]);
+        assert_eq!(
+            device.calls_to("BindTexture")[0],
+            format!("BindTexture(TEXTURE_2D, {})", albedo.id())
+        );
+
+        instance.reset_param("Strength");
+        assert_eq!(instance.param("Strength"), Some(&Param::Float(0.5)));
+    }
+
+    #[test]
+    fn integers_for_float_uniforms_load_as_floats() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        device.set_uniform_type("Shininess", gl::FLOAT);
+        device.set_uniform_type("ShadowMap", gl::SAMPLER_2D);
+        let desc = MaterialDesc::from_toml(
+            r#"
+            vertex = "res/glsl/basic.vs.glsl"
+            fragment = "res/glsl/basic.fs.glsl"
+
+            [params]
+            Shininess = 32
+            ShadowMap = 6
+            Unused = 1
+            "#,
+        )
+        .unwrap();
+        let material = Material::from_desc(&desc).unwrap();
+        assert_eq!(material.param("Shininess"), Some(&Param::Float(32.0)));
+        assert_eq!(material.param("ShadowMap"), Some(&Param::Int(6)));
+        assert_eq!(material.param("Unused"), Some(&Param::Int(1)));
+    }
+}
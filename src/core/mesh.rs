@@ -2,14 +2,31 @@ use std::default::Default;
 use std::marker::PhantomData;
 use std::vec::Vec;
 
+use nalgebra_glm as glm;
+
+use super::bounds::{Aabb, Ray};
 use super::buffer::Buffer;
+use super::framework::Context;
+use super::state::RenderState;
 use super::vertex::VertexArray;
 
+/// Attribute locations `MeshBuilder` binds vertex streams to.
+pub const POSITION_LOCATION: u32 = 0;
+pub const NORMAL_LOCATION: u32 = 1;
+pub const UV_LOCATION: u32 = 2;
+pub const COLOR_LOCATION: u32 = 3;
+pub const TANGENT_LOCATION: u32 = 4;
+
 #[allow(dead_code)]
 pub struct Mesh {
     vao: VertexArray,
     vbo: Vec<Buffer>,
     verts_num: i32,
+    render_state: RenderState,
+    bounds: Aabb,
+    // Kept on the CPU for picking.
+    positions: Vec<f32>,
+    indices: Vec<i32>,
 }
 
 #[allow(dead_code)]
@@ -22,6 +39,48 @@ impl Mesh {
         self.vao
             .draw_elements(gl::TRIANGLES, self.verts_num, gl::UNSIGNED_INT, 0);
     }
+
+    /// Applies the mesh's render state through `ctx`, then draws its indexed triangles.
+    pub fn draw(&self, ctx: &Context) {
+        ctx.apply_state(&self.render_state);
+        unsafe {
+            self.draw_elements();
+        }
+    }
+
+    pub fn render_state(&self) -> &RenderState {
+        &self.render_state
+    }
+
+    pub fn set_render_state(&mut self, state: RenderState) {
+        self.render_state = state;
+    }
+
+    /// Box around the positions, in model space.
+    pub fn bounds(&self) -> &Aabb {
+        &self.bounds
+    }
+
+    /// `t` of the nearest triangle `ray` hits, with `ray` in model space.
+    pub fn raycast(&self, ray: &Ray) -> Option<f32> {
+        ray.intersect_aabb(&self.bounds)?;
+        let position = |index: i32| {
+            let i = index as usize * 3;
+            glm::vec3(
+                self.positions[i],
+                self.positions[i + 1],
+                self.positions[i + 2],
+            )
+        };
+        self.indices
+            .chunks_exact(3)
+            .filter_map(|t| {
+                ray.intersect_triangle(&[position(t[0]), position(t[1]), position(t[2])])
+            })
+            .fold(None, |nearest: Option<f32>, t| {
+                Some(nearest.map_or(t, |n| n.min(t)))
+            })
+    }
 }
 
 #[allow(dead_code)]
@@ -37,6 +96,7 @@ pub struct MeshBuilder<Indices, Positions> {
     col: Option<Vec<f32>>,
     uv: Option<Vec<f32>>,
     tan: Option<Vec<f32>>,
+    render_state: RenderState,
     state: (PhantomData<Indices>, PhantomData<Positions>),
 }
 
@@ -50,6 +110,7 @@ impl MeshBuilder<Empty, Empty> {
             col: Default::default(),
             uv: Default::default(),
             tan: Default::default(),
+            render_state: Default::default(),
             state: (PhantomData, PhantomData),
         }
     }
@@ -57,39 +118,57 @@ impl MeshBuilder<Empty, Empty> {
 
 #[allow(dead_code)]
 impl MeshBuilder<Fully, Fully> {
-    pub fn build(self) -> Mesh {
-        let vao = VertexArray::new();
+    pub fn build(self) -> Result<Mesh, String> {
+        let vao = VertexArray::new()?;
         let mut vbo = Vec::new();
 
-        unsafe {
-            let index = Buffer::new(gl::ELEMENT_ARRAY_BUFFER, &self.indices, gl::STATIC_DRAW);
-            vbo.push(index);
+        let index = Buffer::new(gl::ELEMENT_ARRAY_BUFFER, &self.indices, gl::STATIC_DRAW)?;
+        vbo.push(index);
 
-            let pos = Buffer::new(gl::ARRAY_BUFFER, &self.pos, gl::STATIC_DRAW);
+        let pos = Buffer::new(gl::ARRAY_BUFFER, &self.pos, gl::STATIC_DRAW)?;
+        let streams: Vec<(u32, i32, Buffer)> = [
+            (NORMAL_LOCATION, 3, &self.norm),
+            (UV_LOCATION, 2, &self.uv),
+            (COLOR_LOCATION, 4, &self.col),
+            (TANGENT_LOCATION, 4, &self.tan),
+        ]
+        .iter()
+        .filter_map(|(location, size, data)| {
+            data.as_ref().map(|data| {
+                let buf = Buffer::new(gl::ARRAY_BUFFER, data, gl::STATIC_DRAW)?;
+                Ok((*location, *size, buf))
+            })
+        })
+        .collect::<Result<_, String>>()?;
 
-            // TODO: norm, col, uv, tan, ...
+        // Relationship with VAO ans VBO;
+        vao.binding(|| {
+            // Bind positions
+            pos.vertex_input_attrib(POSITION_LOCATION, 3, 0, 0);
 
-            // Relationship with VAO ans VBO;
-            vao.binding(|| {
-                // Bind positions
-                pos.vertex_input_attrib(0, 3, 0, 0);
-                vbo.push(pos);
+            // Bind normals, uvs, colors and tangents
+            for (location, size, buf) in streams {
+                buf.vertex_input_attrib(location, size, 0, 0);
+                vbo.push(buf);
+            }
 
-                // TODO: Bind norm, col, uv, tan, ...
+            // Unbind VBO
+            pos.unbind();
+            vbo.push(pos);
 
-                // Unbind VBO
-                gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            // REMEMBER: do NOT unbind the IBO while a VAO is active as the bound index buffer object IS stored in the VAO; keep the IBO bound.
+            vbo[0].bind();
+        });
 
-                // REMEMBER: do NOT unbind the IBO while a VAO is active as the bound index buffer object IS stored in the VAO; keep the IBO bound.
-                //gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
-            });
-        }
-
-        Mesh {
+        Ok(Mesh {
             vao: vao,
             vbo: vbo,
             verts_num: self.indices.len() as i32,
-        }
+            render_state: self.render_state,
+            bounds: Aabb::from_positions(&self.pos),
+            positions: self.pos,
+            indices: self.indices,
+        })
     }
 }
 
@@ -105,6 +184,7 @@ impl<Positions> MeshBuilder<Empty, Positions> {
             col: self.col,
             uv: self.uv,
             tan: self.tan,
+            render_state: self.render_state,
             state: (PhantomData, self.state.1),
         }
     }
@@ -122,7 +202,139 @@ impl<Indices> MeshBuilder<Indices, Empty> {
             col: self.col,
             uv: self.uv,
             tan: self.tan,
+            render_state: self.render_state,
             state: (self.state.0, PhantomData),
         }
     }
 }
+
+#[allow(dead_code)]
+impl<Indices, Positions> MeshBuilder<Indices, Positions> {
+    /// Three floats per vertex.
+    pub fn normals(mut self, norm: &[f32]) -> Self {
+        self.norm = Some(norm.to_vec());
+        self
+    }
+
+    /// Two floats per vertex.
+    pub fn uvs(mut self, uv: &[f32]) -> Self {
+        self.uv = Some(uv.to_vec());
+        self
+    }
+
+    /// RGBA, four floats per vertex.
+    pub fn colors(mut self, col: &[f32]) -> Self {
+        self.col = Some(col.to_vec());
+        self
+    }
+
+    /// Four floats per vertex: the tangent and the bitangent sign in w.
+    pub fn tangents(mut self, tan: &[f32]) -> Self {
+        self.tan = Some(tan.to_vec());
+        self
+    }
+
+    /// State applied by `Mesh::draw`.
+    pub fn render_state(mut self, state: RenderState) -> Self {
+        self.render_state = state;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::device::RecordingDevice;
+    use crate::core::resource::ContextOwner;
+    use crate::core::state::Blend;
+
+    #[test]
+    fn builds_vertex_array_from_positions_and_indices() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let state = RenderState::opaque().with_blend(Some(Blend::ALPHA));
+        let mesh = MeshBuilder::new()
+            .positions(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0])
+            .indices(&[0, 1, 2])
+            .render_state(state)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            device.take(),
+            vec![
+                "GenVertexArray -> 1",
+                "GenBuffer -> 2",
+                "BindBuffer(ELEMENT_ARRAY_BUFFER, 2)",
+                "BufferData(ELEMENT_ARRAY_BUFFER, 12, data, STATIC_DRAW)",
+                "BindBuffer(ELEMENT_ARRAY_BUFFER, 0)",
+                "GenBuffer -> 3",
+                "BindBuffer(ARRAY_BUFFER, 3)",
+                "BufferData(ARRAY_BUFFER, 36, data, STATIC_DRAW)",
+                "BindBuffer(ARRAY_BUFFER, 0)",
+                "BindVertexArray(1)",
+                "BindBuffer(ARRAY_BUFFER, 3)",
+                "VertexAttrib(0, 3, 0, 0)",
+                "BindBuffer(ARRAY_BUFFER, 0)",
+                "BindBuffer(ARRAY_BUFFER, 0)",
+                // The index buffer stays bound in the vertex array.
+                "BindBuffer(ELEMENT_ARRAY_BUFFER, 2)",
+                "BindVertexArray(0)",
+            ]
+        );
+        assert_eq!(mesh.render_state(), &state);
+        assert_eq!(mesh.bounds().max, glm::vec3(1.0, 1.0, 0.0));
+        let down = |x, y| Ray::new(glm::vec3(x, y, 2.0), glm::vec3(0.0, 0.0, -1.0));
+        assert_eq!(mesh.raycast(&down(0.25, 0.25)), Some(2.0));
+        assert_eq!(mesh.raycast(&down(0.75, 0.75)), None);
+
+        unsafe {
+            mesh.draw_elements();
+        }
+        assert_eq!(
+            device.take(),
+            vec![
+                "BindVertexArray(1)",
+                "DrawElements(TRIANGLES, 3, UNSIGNED_INT, 0)",
+                "BindVertexArray(0)",
+            ]
+        );
+
+        drop(mesh);
+        assert_eq!(
+            device.take(),
+            vec![
+                "Delete(VertexArray, 1)",
+                "Delete(Buffer, 2)",
+                "Delete(Buffer, 3)"
+            ]
+        );
+    }
+
+    #[test]
+    fn binds_optional_streams_to_their_locations() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let _mesh = MeshBuilder::new()
+            .positions(&[0.0; 9])
+            .normals(&[0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0])
+            .uvs(&[0.0; 6])
+            .indices(&[0, 1, 2])
+            .build()
+            .unwrap();
+
+        let attribs: Vec<String> = device
+            .take()
+            .into_iter()
+            .filter(|call| call.starts_with("VertexAttrib"))
+            .collect();
+        assert_eq!(
+            attribs,
+            vec![
+                format!("VertexAttrib({}, 3, 0, 0)", POSITION_LOCATION),
+                format!("VertexAttrib({}, 3, 0, 0)", NORMAL_LOCATION),
+                format!("VertexAttrib({}, 2, 0, 0)", UV_LOCATION),
+            ]
+        );
+    }
+}
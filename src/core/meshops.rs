@@ -0,0 +1,647 @@
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+
+use nalgebra_glm as glm;
+
+use super::geometry::MeshData;
+
+/// Entries of the LRU vertex cache `optimize_vertex_cache` scores against.
+pub const CACHE_SIZE: usize = 32;
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+/// Replaces the normals with the average of the face normals around each vertex, weighted
+/// by the corner angles so how faces are split into triangles does not matter. Only
+/// triangles sharing a vertex index are averaged; `weld` split copies first.
+#[allow(dead_code)]
+pub fn smooth_normals(mesh: &mut MeshData) {
+    let mut sums = vec![glm::Vec3::zeros(); mesh.vertex_count()];
+    for triangle in 0..mesh.triangle_count() {
+        let corners = mesh.triangle(triangle);
+        let p = corner_positions(mesh, &corners);
+        let face = (p[1] - p[0]).cross(&(p[2] - p[0]));
+        if face.norm() == 0.0 {
+            continue;
+        }
+        let face = face.normalize();
+        for k in 0..3 {
+            sums[corners[k]] += face * corner_angle(&p, k);
+        }
+    }
+    mesh.normals = sums
+        .iter()
+        .flat_map(|n| {
+            let n = if n.norm() > 0.0 {
+                n.normalize()
+            } else {
+                glm::Vec3::y()
+            };
+            vec![n.x, n.y, n.z]
+        })
+        .collect();
+}
+
+/// Gives every triangle three vertices of its own carrying the face normal, for faceted
+/// shading. The other streams are copied per corner.
+#[allow(dead_code)]
+pub fn flat_normals(mesh: &mut MeshData) {
+    let corners: Vec<usize> = mesh.indices.iter().map(|&v| v as usize).collect();
+    let mut flat = gather(mesh, &corners);
+    flat.indices = (0..corners.len() as i32).collect();
+    flat.normals = Vec::with_capacity(corners.len() * 3);
+    for triangle in 0..flat.triangle_count() {
+        let p = corner_positions(&flat, &flat.triangle(triangle));
+        let face = (p[1] - p[0]).cross(&(p[2] - p[0]));
+        let n = if face.norm() > 0.0 {
+            face.normalize()
+        } else {
+            glm::Vec3::y()
+        };
+        for _ in 0..3 {
+            flat.normals.extend_from_slice(n.as_slice());
+        }
+    }
+    *mesh = flat;
+}
+
+/// Computes tangents from the normals and uvs the way MikkTSpace does, so normal maps baked
+/// against it shade without seams: each corner's uv tangent is projected onto the plane of
+/// the vertex normal and weighted by the corner angle, and w holds the sign that makes
+/// `w * cross(n, t)` the bitangent. Vertices whose corners disagree on that sign, as along
+/// mirrored uv seams, are split in two.
+#[allow(dead_code)]
+pub fn tangents(mesh: &mut MeshData) -> Result<(), String> {
+    let count = mesh.vertex_count();
+    if mesh.normals.len() != count * 3 || mesh.uvs.len() != count * 2 {
+        return Err("Tangents need a normal and a uv for every vertex".to_string());
+    }
+    let uv = |v: usize| glm::make_vec2(&mesh.uvs[v * 2..v * 2 + 2]);
+
+    // Per vertex, the sums for right-handed and mirrored corners, and per corner which one
+    // it added to. Corners of triangles without a uv gradient follow their vertex.
+    let mut sums = vec![[glm::Vec3::zeros(); 2]; count];
+    let mut sides = vec![None; mesh.indices.len()];
+    for triangle in 0..mesh.triangle_count() {
+        let corners = mesh.triangle(triangle);
+        let p = corner_positions(mesh, &corners);
+        let (e1, e2) = (p[1] - p[0], p[2] - p[0]);
+        let (d1, d2) = (
+            uv(corners[1]) - uv(corners[0]),
+            uv(corners[2]) - uv(corners[0]),
+        );
+        let det = d1.x * d2.y - d2.x * d1.y;
+        if det == 0.0 {
+            continue;
+        }
+        let tangent = (e1 * d2.y - e2 * d1.y) / det;
+        let bitangent = (e2 * d1.x - e1 * d2.x) / det;
+        for k in 0..3 {
+            let n = mesh.normal(corners[k]);
+            let projected = tangent - n * n.dot(&tangent);
+            if projected.norm() == 0.0 {
+                continue;
+            }
+            let side = if n.cross(&tangent).dot(&bitangent) < 0.0 {
+                1
+            } else {
+                0
+            };
+            sums[corners[k]][side] += projected.normalize() * corner_angle(&p, k);
+            sides[triangle * 3 + k] = Some(side);
+        }
+    }
+
+    let with_sign = |t: glm::Vec3, w: f32| [t.x, t.y, t.z, w];
+    let mut sources: Vec<usize> = (0..count).collect();
+    let mut tangents = Vec::with_capacity(count);
+    let mut mirrored = vec![None; count];
+    let mut extra = Vec::new();
+    for (v, [right, left]) in sums.iter().enumerate() {
+        let (has_right, has_left) = (right.norm() > 0.0, left.norm() > 0.0);
+        let tangent = if has_right || !has_left {
+            if has_left {
+                mirrored[v] = Some(sources.len());
+                sources.push(v);
+                extra.push(with_sign(left.normalize(), -1.0));
+            }
+            if has_right {
+                with_sign(right.normalize(), 1.0)
+            } else {
+                with_sign(perpendicular(&mesh.normal(v)), 1.0)
+            }
+        } else {
+            with_sign(left.normalize(), -1.0)
+        };
+        tangents.push(tangent);
+    }
+    tangents.extend(extra);
+
+    let mut split = gather(mesh, &sources);
+    split.tangents = tangents.concat();
+    split.indices = mesh
+        .indices
+        .iter()
+        .zip(sides.iter())
+        .map(|(&v, side)| match (side, mirrored[v as usize]) {
+            (Some(1), Some(copy)) => copy as i32,
+            _ => v,
+        })
+        .collect();
+    *mesh = split;
+    Ok(())
+}
+
+/// Merges vertices whose streams all round to the same multiples of `epsilon`, keeping the
+/// first of each group, and returns how many were removed. Equal vertices always merge;
+/// nearly equal ones may fall on either side of a rounding step. `epsilon` must be positive.
+#[allow(dead_code)]
+pub fn weld(mesh: &mut MeshData, epsilon: f32) -> usize {
+    let count = mesh.vertex_count();
+    let streams: Vec<(&[f32], usize)> = streams(mesh)
+        .iter()
+        .cloned()
+        .filter(|(stream, _)| !stream.is_empty())
+        .collect();
+    let mut groups: HashMap<Vec<i64>, usize> = HashMap::new();
+    let mut kept = Vec::new();
+    let mut remap = Vec::with_capacity(count);
+    for v in 0..count {
+        let key: Vec<i64> = streams
+            .iter()
+            .flat_map(|(stream, width)| stream[v * width..(v + 1) * width].iter())
+            .map(|x| (x / epsilon).round() as i64)
+            .collect();
+        let next = kept.len();
+        let group = *groups.entry(key).or_insert(next);
+        if group == next {
+            kept.push(v);
+        }
+        remap.push(group as i32);
+    }
+
+    let mut welded = gather(mesh, &kept);
+    welded.indices = mesh.indices.iter().map(|&v| remap[v as usize]).collect();
+    *mesh = welded;
+    count - kept.len()
+}
+
+/// Average vertices transformed per triangle by a FIFO post-transform cache of
+/// `cache_size` entries: 3 without reuse, approaching 0.5 on large regular grids.
+#[allow(dead_code)]
+pub fn cache_miss_ratio(indices: &[i32], cache_size: usize) -> f32 {
+    if indices.len() < 3 {
+        return 0.0;
+    }
+    let mut cache = VecDeque::with_capacity(cache_size + 1);
+    let mut misses = 0;
+    for v in indices {
+        if !cache.contains(v) {
+            misses += 1;
+            cache.push_back(*v);
+            if cache.len() > cache_size {
+                cache.pop_front();
+            }
+        }
+    }
+    misses as f32 / (indices.len() / 3) as f32
+}
+
+/// Reorders triangles so consecutive ones reuse recently transformed vertices, with Tom
+/// Forsyth's linear-speed vertex cache optimization. Vertices keep their indices.
+#[allow(dead_code)]
+pub fn optimize_vertex_cache(mesh: &mut MeshData) {
+    let indices: Vec<usize> = mesh.indices.iter().map(|&v| v as usize).collect();
+    let triangle_count = indices.len() / 3;
+    let mut triangles_of = vec![Vec::new(); mesh.vertex_count()];
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        for &v in corners {
+            triangles_of[v].push(triangle);
+        }
+    }
+    let mut remaining: Vec<usize> = triangles_of.iter().map(Vec::len).collect();
+    let mut scores: Vec<f32> = remaining
+        .iter()
+        .map(|&count| vertex_score(None, count))
+        .collect();
+    let triangle_score = |scores: &[f32], t: usize| -> f32 {
+        indices[t * 3..t * 3 + 3].iter().map(|&v| scores[v]).sum()
+    };
+
+    let mut emitted = vec![false; triangle_count];
+    let mut cache: Vec<usize> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut order = Vec::with_capacity(indices.len());
+    let mut next_unemitted = 0;
+    let mut best = (0..triangle_count).max_by(|&a, &b| {
+        triangle_score(&scores, a)
+            .partial_cmp(&triangle_score(&scores, b))
+            .unwrap()
+    });
+    while let Some(triangle) = best {
+        emitted[triangle] = true;
+        let corners = &indices[triangle * 3..triangle * 3 + 3];
+        order.extend(corners.iter().map(|&v| v as i32));
+        for &v in corners {
+            remaining[v] -= 1;
+        }
+
+        // The triangle's vertices move to the front; the ones pushed off the end leave.
+        let mut updated: Vec<usize> = Vec::with_capacity(CACHE_SIZE + 3);
+        for &v in corners.iter().chain(cache.iter()) {
+            if !updated.contains(&v) {
+                updated.push(v);
+            }
+        }
+        for (position, &v) in updated.iter().enumerate() {
+            let position = if position < CACHE_SIZE {
+                Some(position)
+            } else {
+                None
+            };
+            scores[v] = vertex_score(position, remaining[v]);
+        }
+
+        // Only triangles touching a rescored vertex changed score.
+        best = None;
+        let mut best_score = f32::NEG_INFINITY;
+        for &v in updated.iter() {
+            for &t in triangles_of[v].iter().filter(|&&t| !emitted[t]) {
+                let score = triangle_score(&scores, t);
+                if score > best_score {
+                    best = Some(t);
+                    best_score = score;
+                }
+            }
+        }
+        updated.truncate(CACHE_SIZE);
+        cache = updated;
+
+        if best.is_none() {
+            while next_unemitted < triangle_count && emitted[next_unemitted] {
+                next_unemitted += 1;
+            }
+            if next_unemitted < triangle_count {
+                best = Some(next_unemitted);
+            }
+        }
+    }
+    mesh.indices = order;
+}
+
+/// Reorders clusters of triangles so the ones facing away from the mesh's center draw
+/// first and hide what is behind them. Run it after `optimize_vertex_cache`: clusters end
+/// where a triangle misses the cache on all three vertices, so they keep their reuse.
+#[allow(dead_code)]
+pub fn optimize_overdraw(mesh: &mut MeshData) {
+    let triangle_count = mesh.triangle_count();
+    let mut clusters: Vec<Range<usize>> = Vec::new();
+    let mut cache = VecDeque::with_capacity(CACHE_SIZE + 3);
+    let mut start = 0;
+    for triangle in 0..triangle_count {
+        let corners = &mesh.indices[triangle * 3..triangle * 3 + 3];
+        let misses: Vec<i32> = corners
+            .iter()
+            .cloned()
+            .filter(|v| !cache.contains(v))
+            .collect();
+        if misses.len() == 3 && triangle > start {
+            clusters.push(start..triangle);
+            start = triangle;
+        }
+        for v in misses {
+            cache.push_back(v);
+            if cache.len() > CACHE_SIZE {
+                cache.pop_front();
+            }
+        }
+    }
+    if start < triangle_count {
+        clusters.push(start..triangle_count);
+    }
+
+    let center = mesh.aabb().center();
+    let facing = |range: &Range<usize>| {
+        let (mut normal, mut centroid, mut area) = (glm::Vec3::zeros(), glm::Vec3::zeros(), 0.0);
+        for triangle in range.clone() {
+            let p = corner_positions(mesh, &mesh.triangle(triangle));
+            let face = (p[1] - p[0]).cross(&(p[2] - p[0]));
+            normal += face;
+            centroid += (p[0] + p[1] + p[2]) * (face.norm() / 3.0);
+            area += face.norm();
+        }
+        if area == 0.0 || normal.norm() == 0.0 {
+            return 0.0;
+        }
+        (centroid / area - center).dot(&normal.normalize())
+    };
+    let mut keyed: Vec<(f32, Range<usize>)> = clusters
+        .into_iter()
+        .map(|range| (facing(&range), range))
+        .collect();
+    keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    mesh.indices = keyed
+        .iter()
+        .flat_map(|(_, range)| mesh.indices[range.start * 3..range.end * 3].iter().cloned())
+        .collect();
+}
+
+/// Renumbers vertices in the order triangles first use them, so vertex fetches walk
+/// memory forward, dropping vertices no triangle uses. Run it once the triangle order is
+/// final.
+#[allow(dead_code)]
+pub fn optimize_vertex_fetch(mesh: &mut MeshData) {
+    let mut remap: Vec<Option<usize>> = vec![None; mesh.vertex_count()];
+    let mut order = Vec::new();
+    let indices: Vec<i32> = mesh
+        .indices
+        .iter()
+        .map(|&v| {
+            *remap[v as usize].get_or_insert_with(|| {
+                order.push(v as usize);
+                order.len() - 1
+            }) as i32
+        })
+        .collect();
+    let mut fetched = gather(mesh, &order);
+    fetched.indices = indices;
+    *mesh = fetched;
+}
+
+/// Runs the vertex cache, overdraw and vertex fetch optimizations in that order.
+#[allow(dead_code)]
+pub fn optimize(mesh: &mut MeshData) {
+    optimize_vertex_cache(mesh);
+    optimize_overdraw(mesh);
+    optimize_vertex_fetch(mesh);
+}
+
+// Forsyth's score: recently used vertices and vertices with few triangles left come first.
+fn vertex_score(cache_position: Option<usize>, remaining: usize) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache = match cache_position {
+        // The last triangle's vertices score lower so strips do not turn back on themselves.
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            (1.0 - (position - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(CACHE_DECAY_POWER)
+        }
+        None => 0.0,
+    };
+    cache + VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+fn streams(mesh: &MeshData) -> [(&[f32], usize); 4] {
+    [
+        (mesh.positions.as_slice(), 3),
+        (mesh.normals.as_slice(), 3),
+        (mesh.uvs.as_slice(), 2),
+        (mesh.tangents.as_slice(), 4),
+    ]
+}
+
+// Vertices `order` names, in that order, without indices.
+fn gather(mesh: &MeshData, order: &[usize]) -> MeshData {
+    let pick = |(stream, width): (&[f32], usize)| -> Vec<f32> {
+        if stream.is_empty() {
+            return Vec::new();
+        }
+        order
+            .iter()
+            .flat_map(|&v| stream[v * width..(v + 1) * width].iter().cloned())
+            .collect()
+    };
+    let [positions, normals, uvs, tangents] = streams(mesh);
+    MeshData {
+        positions: pick(positions),
+        normals: pick(normals),
+        uvs: pick(uvs),
+        tangents: pick(tangents),
+        indices: Vec::new(),
+    }
+}
+
+fn corner_positions(mesh: &MeshData, corners: &[usize; 3]) -> [glm::Vec3; 3] {
+    [
+        mesh.position(corners[0]),
+        mesh.position(corners[1]),
+        mesh.position(corners[2]),
+    ]
+}
+
+// Angle of the triangle `p` at corner `k`.
+fn corner_angle(p: &[glm::Vec3; 3], k: usize) -> f32 {
+    glm::angle(&(p[(k + 1) % 3] - p[k]), &(p[(k + 2) % 3] - p[k]))
+}
+
+// Some unit vector perpendicular to `n`.
+fn perpendicular(n: &glm::Vec3) -> glm::Vec3 {
+    let axis = if n.x.abs() < 0.9 {
+        glm::Vec3::x()
+    } else {
+        glm::Vec3::y()
+    };
+    n.cross(&axis).normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::geometry;
+
+    // Each triangle's positions, rotated to start at its smallest, in a sorted list: equal
+    // for meshes that draw the same triangles with the same winding.
+    fn triangle_set(mesh: &MeshData) -> Vec<Vec<i64>> {
+        let mut set: Vec<Vec<i64>> = (0..mesh.triangle_count())
+            .map(|triangle| {
+                let mut corners: Vec<Vec<i64>> = mesh
+                    .triangle(triangle)
+                    .iter()
+                    .map(|&v| {
+                        mesh.position(v)
+                            .iter()
+                            .map(|x| (x * 1e4).round() as i64)
+                            .collect()
+                    })
+                    .collect();
+                let first = (0..3).min_by_key(|&k| corners[k].clone()).unwrap();
+                corners.rotate_left(first);
+                corners.concat()
+            })
+            .collect();
+        set.sort();
+        set
+    }
+
+    fn positions_only(mut mesh: MeshData) -> MeshData {
+        mesh.normals.clear();
+        mesh.uvs.clear();
+        mesh.tangents.clear();
+        mesh
+    }
+
+    #[test]
+    fn smooth_normals_ignore_how_faces_are_split() {
+        let mut sphere = geometry::icosphere(1.0, 2);
+        let expected = sphere.normals.clone();
+        smooth_normals(&mut sphere);
+        for (n, e) in sphere.normals.chunks(3).zip(expected.chunks(3)) {
+            assert!(glm::make_vec3(n).dot(&glm::make_vec3(e)) > 0.995);
+        }
+
+        // Corners of a welded cube point along its diagonals.
+        let mut cube = positions_only(geometry::cube(2.0, 1));
+        weld(&mut cube, 1e-4);
+        smooth_normals(&mut cube);
+        for v in 0..cube.vertex_count() {
+            let diagonal = cube.position(v).normalize();
+            assert!((cube.normal(v) - diagonal).norm() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn flat_normals_split_every_corner() {
+        let mut cube = geometry::cube(1.0, 1);
+        let before = triangle_set(&cube);
+        smooth_normals(&mut cube);
+        flat_normals(&mut cube);
+
+        assert_eq!(cube.vertex_count(), 36);
+        assert_eq!(cube.uvs.len(), 72);
+        assert_eq!(triangle_set(&cube), before);
+        for v in 0..cube.vertex_count() {
+            let n = cube.normal(v);
+            // Each normal is a unit axis.
+            assert_eq!(n.iter().filter(|x| x.abs() == 1.0).count(), 1);
+        }
+    }
+
+    #[test]
+    fn tangents_match_the_analytic_sphere_tangents() {
+        let sphere = geometry::uv_sphere(1.0, 64, 16);
+        let mut computed = sphere.clone();
+        computed.tangents.clear();
+        tangents(&mut computed).unwrap();
+
+        assert_eq!(computed.vertex_count(), sphere.vertex_count());
+        for v in 0..sphere.vertex_count() {
+            if sphere.normal(v).y.abs() > 0.99 {
+                continue;
+            }
+            let t = glm::make_vec4(&computed.tangents[v * 4..v * 4 + 4]);
+            let e = glm::make_vec4(&sphere.tangents[v * 4..v * 4 + 4]);
+            assert!(t.xyz().dot(&e.xyz()) > 0.99);
+            assert_eq!(t.w, 1.0);
+        }
+
+        assert!(tangents(&mut positions_only(sphere)).is_err());
+    }
+
+    #[test]
+    fn tangents_split_vertices_on_mirrored_seams() {
+        // Two quads in the XY plane sharing the edge at x = 0, their uvs mirrored across it.
+        let mut mesh = MeshData {
+            positions: vec![
+                -1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, //
+                -1.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0,
+            ],
+            normals: [0.0, 0.0, 1.0].repeat(6),
+            uvs: vec![1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 1.0, 1.0],
+            tangents: Vec::new(),
+            indices: vec![0, 1, 2, 0, 2, 3, 1, 4, 5, 1, 5, 2],
+        };
+        tangents(&mut mesh).unwrap();
+
+        assert_eq!(mesh.vertex_count(), 8);
+        for triangle in 0..mesh.triangle_count() {
+            let mirrored = triangle < 2;
+            for &v in mesh.triangle(triangle).iter() {
+                let t = &mesh.tangents[v * 4..v * 4 + 4];
+                let expected = if mirrored {
+                    [-1.0, 0.0, 0.0, -1.0]
+                } else {
+                    [1.0, 0.0, 0.0, 1.0]
+                };
+                assert_eq!(t, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn bounds_enclose_the_mesh() {
+        let cube = geometry::cube(2.0, 2);
+        let aabb = cube.aabb();
+        assert_eq!(aabb.min, glm::vec3(-1.0, -1.0, -1.0));
+        assert_eq!(aabb.max, glm::vec3(1.0, 1.0, 1.0));
+
+        let sphere = geometry::uv_sphere(3.0, 12, 6).bounding_sphere();
+        assert!(sphere.center.norm() < 1e-4);
+        assert!((sphere.radius - 3.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn welds_equal_vertices() {
+        let mut cube = geometry::cube(1.0, 1);
+        assert_eq!(weld(&mut cube, 1e-5), 0);
+
+        let mut cube = positions_only(cube);
+        let before = triangle_set(&cube);
+        assert_eq!(weld(&mut cube, 1e-5), 16);
+        assert_eq!(cube.vertex_count(), 8);
+        assert_eq!(triangle_set(&cube), before);
+    }
+
+    #[test]
+    fn optimizations_keep_the_triangles_and_improve_cache_reuse() {
+        let mut grid = geometry::grid(1.0, 1.0, 24, 24);
+        // Scatter the triangles so nothing is reused.
+        let count = grid.triangle_count();
+        let scattered: Vec<i32> = (0..count)
+            .flat_map(|t| {
+                let from = (t * 97) % count;
+                grid.indices[from * 3..from * 3 + 3].to_vec()
+            })
+            .collect();
+        grid.indices = scattered;
+        let before = triangle_set(&grid);
+        let scattered_ratio = cache_miss_ratio(&grid.indices, CACHE_SIZE);
+
+        optimize_vertex_cache(&mut grid);
+        let optimized_ratio = cache_miss_ratio(&grid.indices, CACHE_SIZE);
+        assert!(scattered_ratio > 2.0, "{}", scattered_ratio);
+        assert!(optimized_ratio < 1.0, "{}", optimized_ratio);
+        assert_eq!(triangle_set(&grid), before);
+
+        optimize_overdraw(&mut grid);
+        assert_eq!(triangle_set(&grid), before);
+
+        optimize_vertex_fetch(&mut grid);
+        assert_eq!(triangle_set(&grid), before);
+        let mut next = 0;
+        for &v in grid.indices.iter() {
+            assert!(v <= next);
+            next = next.max(v + 1);
+        }
+        assert_eq!(next as usize, grid.vertex_count());
+    }
+
+    #[test]
+    fn overdraw_draws_outward_facing_clusters_first() {
+        // Two parallel planes facing +Y, far apart: the upper one faces away from the
+        // center and should draw first.
+        let mut mesh = geometry::grid(1.0, 1.0, 1, 1);
+        let upper = geometry::grid(1.0, 1.0, 1, 1);
+        for y in mesh.positions.iter_mut().skip(1).step_by(3) {
+            *y = -1.0;
+        }
+        let offset = mesh.vertex_count() as i32;
+        mesh.positions.extend(upper.positions.iter().cloned());
+        mesh.indices
+            .extend(upper.indices.iter().map(|&v| v + offset));
+        let mesh = &mut positions_only(mesh);
+
+        optimize_overdraw(mesh);
+        assert!(mesh.indices[..6].iter().all(|&v| v >= offset));
+    }
+}
@@ -0,0 +1,86 @@
+use std::path::Path;
+use std::rc::Rc;
+
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use super::material::Material;
+use super::texture::{Texture2D, TextureBuilder};
+
+/// Texture slots of `res/glsl/pbr.fs.glsl`, each with the texel used when a material
+/// leaves it out. Neutral texels make the material's factors apply unchanged.
+pub const TEXTURE_SLOTS: [(&str, [u8; 4]); 5] = [
+    ("AlbedoMap", [255, 255, 255, 255]),
+    ("NormalMap", [128, 128, 255, 255]),
+    // Roughness in green, metalness in blue, as in glTF.
+    ("MetallicRoughnessMap", [255, 255, 255, 255]),
+    ("OcclusionMap", [255, 255, 255, 255]),
+    ("EmissiveMap", [255, 255, 255, 255]),
+];
+
+/// Path of the default metallic-roughness material.
+pub const DEFAULT_MATERIAL: &str = "res/materials/pbr.toml";
+
+/// 1x1 texture holding `texel`.
+pub fn solid_texture(texel: [u8; 4]) -> Result<Texture2D, String> {
+    let image = RgbaImage::from_pixel(1, 1, Rgba(texel));
+    TextureBuilder::new()
+        .filter(gl::NEAREST)
+        .wrap(gl::REPEAT)
+        .build2d_from_image(DynamicImage::ImageRgba8(image))
+}
+
+/// Gives every PBR texture slot the material does not set its neutral texture.
+pub fn fill_default_textures(material: &mut Material) -> Result<(), String> {
+    for (slot, texel) in TEXTURE_SLOTS.iter() {
+        if material.texture(slot).is_none() {
+            let texture = solid_texture(*texel)?;
+            texture.set_label(&format!("default {}", slot));
+            material.set_texture(slot, Rc::new(texture));
+        }
+    }
+    Ok(())
+}
+
+/// Loads a metallic-roughness material file, filling the texture slots it leaves out.
+pub fn load_material<P>(path: P) -> Result<Material, String>
+where
+    P: AsRef<Path>,
+{
+    let mut material = Material::load(path)?;
+    fill_default_textures(&mut material)?;
+    Ok(material)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::device::RecordingDevice;
+    use crate::core::material::{MaterialDesc, Param};
+    use crate::core::resource::ContextOwner;
+
+    #[test]
+    fn default_material_uses_neutral_factors() {
+        let desc = MaterialDesc::load(DEFAULT_MATERIAL).unwrap();
+        assert!(desc.textures.is_empty());
+        assert_eq!(desc.params.get("Metallic"), Some(&Param::Float(0.0)));
+        assert_eq!(desc.params.get("Roughness"), Some(&Param::Float(0.5)));
+        assert!(desc.state.depth_test.is_some());
+    }
+
+    #[test]
+    fn fills_only_missing_slots() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let mut material = Material::load(DEFAULT_MATERIAL).unwrap();
+        let albedo = Rc::new(solid_texture([10, 20, 30, 255]).unwrap());
+        material.set_texture("AlbedoMap", albedo.clone());
+        fill_default_textures(&mut material).unwrap();
+
+        for (slot, _) in TEXTURE_SLOTS.iter() {
+            assert!(material.texture(slot).is_some(), "{}", slot);
+        }
+        assert!(Rc::ptr_eq(material.texture("AlbedoMap").unwrap(), &albedo));
+        // One upload for the explicit albedo, four for the defaults.
+        assert_eq!(device.calls_to("TexImage2D").len(), 5);
+    }
+}
@@ -0,0 +1,312 @@
+use imgui_glfw_rs::glfw;
+use nalgebra_glm as glm;
+
+use super::camera::Camera;
+use super::framebuffer::{Framebuffer, Renderbuffer};
+use super::framework::Context;
+use super::frustum::Frustum;
+use super::input::Input;
+use super::scene::{NodeId, Pick, Scene};
+use super::shader::Shader;
+use super::state::RenderState;
+
+/// Offscreen target holding, per pixel, which scene node is nearest, for picking on the
+/// GPU. Pixel-exact where the ray cast of `Scene::raycast` only sees triangles, but reading
+/// it back waits for the render to finish.
+pub struct IdBuffer {
+    fbo: Framebuffer,
+    shader: Shader,
+    /// Nodes of the last `render`; a pixel holds its node's index plus one, 0 for none.
+    nodes: Vec<NodeId>,
+}
+
+#[allow(dead_code)]
+impl IdBuffer {
+    pub fn new(width: u32, height: u32) -> Result<IdBuffer, String> {
+        let shader = Shader::new(
+            "res/glsl/shadow_depth.vs.glsl",
+            "res/glsl/object_id.fs.glsl",
+        )?;
+        Ok(IdBuffer {
+            fbo: IdBuffer::target(width, height)?,
+            shader,
+            nodes: Vec::new(),
+        })
+    }
+
+    fn target(width: u32, height: u32) -> Result<Framebuffer, String> {
+        let ids = Renderbuffer::new(gl::R32UI, width, height)?;
+        let fbo = Framebuffer::new(width, height)?
+            .with_renderbuffer(gl::COLOR_ATTACHMENT0, ids)
+            .with_depth()?;
+        fbo.set_label("object ids");
+        fbo.check()?;
+        Ok(fbo)
+    }
+
+    /// Recreates the target for a new frame size, forgetting the last `render`. Ignores a
+    /// minimized window.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        if width == 0 || height == 0 || (width, height) == (self.width(), self.height()) {
+            return Ok(());
+        }
+        self.fbo = IdBuffer::target(width, height)?;
+        self.nodes.clear();
+        Ok(())
+    }
+
+    pub fn width(&self) -> u32 {
+        self.fbo.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.fbo.height()
+    }
+
+    /// Renders the ids of the visible meshes of `scene` as seen through `view_projection`,
+    /// all opaque and without culling whatever their render states, so blended ids are not
+    /// mixed and every face seen can be picked. Leaves the window framebuffer and viewport
+    /// current. Returns the number of draws.
+    pub fn render(&mut self, ctx: &Context, scene: &Scene, view_projection: &glm::Mat4) -> usize {
+        self.nodes = scene.visible_in(&Frustum::from_matrix(view_projection));
+        self.fbo.bind();
+        ctx.set_viewport_size(self.width(), self.height());
+        // Also lets the clears write depth.
+        ctx.apply_state(&RenderState::opaque().with_cull(None));
+        ctx.device().clear_color_uint(0, 0);
+        ctx.clear_buffer(gl::DEPTH_BUFFER_BIT);
+        self.shader.bind(ctx);
+        let mut draws = 0;
+        for (i, &id) in self.nodes.iter().enumerate() {
+            let node = match scene.node(id) {
+                Some(node) => node,
+                None => continue,
+            };
+            let mesh = match node.mesh {
+                Some(ref mesh) => mesh,
+                None => continue,
+            };
+            self.shader
+                .set_mat4("MVP", &(view_projection * node.world()));
+            self.shader.set_int("Id", i as i32 + 1);
+            unsafe {
+                mesh.draw_elements();
+            }
+            draws += 1;
+        }
+        self.fbo.unbind();
+        ctx.set_viewport();
+        draws
+    }
+
+    /// Node drawn under `cursor`, in pixels with the origin at the top-left of a `viewport`
+    /// sized window, in the last `render`.
+    pub fn pick(&self, cursor: &glm::Vec2, viewport: &glm::Vec2) -> Option<NodeId> {
+        let (width, height) = (self.width() as f32, self.height() as f32);
+        let x = (cursor.x * width / viewport.x.max(1.0)).floor();
+        let y = (cursor.y * height / viewport.y.max(1.0)).floor();
+        if x < 0.0 || y < 0.0 || x >= width || y >= height {
+            return None;
+        }
+        // Framebuffer rows start at the bottom.
+        let id = self.fbo.read_uint(x as i32, height as i32 - 1 - y as i32);
+        if id == 0 {
+            return None;
+        }
+        self.nodes.get(id as usize - 1).copied()
+    }
+}
+
+/// Selects scene nodes by clicking them, either by casting a ray from the camera or by
+/// reading an `IdBuffer`.
+#[derive(Debug, Clone)]
+pub struct Picker {
+    pub button: glfw::MouseButton,
+    selected: Option<NodeId>,
+}
+
+impl Default for Picker {
+    fn default() -> Self {
+        Picker::new()
+    }
+}
+
+#[allow(dead_code)]
+impl Picker {
+    /// Picks with the left mouse button.
+    pub fn new() -> Picker {
+        Picker {
+            button: glfw::MouseButton::Button1,
+            selected: None,
+        }
+    }
+
+    /// Node of the last click, `None` after a click on nothing.
+    pub fn selected(&self) -> Option<NodeId> {
+        self.selected
+    }
+
+    pub fn clear(&mut self) {
+        self.selected = None;
+    }
+
+    /// Cursor position when `button` was pressed this frame.
+    pub fn clicked(&self, input: &Input) -> Option<glm::Vec2> {
+        if input.mouse_pressed(self.button) {
+            Some(input.cursor())
+        } else {
+            None
+        }
+    }
+
+    /// On a click, casts a ray from `camera` through the cursor against the triangles of
+    /// `scene` and selects the nearest node hit, or nothing on a miss. `viewport` is the
+    /// size the cursor position is relative to. Returns the pick made this frame.
+    pub fn update(
+        &mut self,
+        input: &Input,
+        camera: &Camera,
+        viewport: &glm::Vec2,
+        scene: &Scene,
+    ) -> Option<Pick> {
+        let cursor = self.clicked(input)?;
+        let pick = scene.raycast(&camera.ray(&cursor, viewport));
+        self.selected = pick.map(|p| p.node);
+        pick
+    }
+
+    /// Like `update`, reading the node under the cursor from the last render of `ids`.
+    pub fn update_with_ids(
+        &mut self,
+        input: &Input,
+        ids: &IdBuffer,
+        viewport: &glm::Vec2,
+    ) -> Option<NodeId> {
+        let cursor = self.clicked(input)?;
+        self.selected = ids.pick(&cursor, viewport);
+        self.selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::device::RecordingDevice;
+    use crate::core::geometry;
+    use crate::core::resource::ContextOwner;
+    use crate::core::scene::Transform;
+    use std::rc::Rc;
+
+    fn click(x: f64, y: f64) -> Input {
+        let mut input = Input::new();
+        input.begin_frame();
+        input.handle_event(&glfw::WindowEvent::CursorPos(x, y));
+        input.handle_event(&glfw::WindowEvent::MouseButton(
+            glfw::MouseButton::Button1,
+            glfw::Action::Press,
+            glfw::Modifiers::empty(),
+        ));
+        input
+    }
+
+    #[test]
+    fn id_buffer_maps_pixels_to_nodes() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let mut ids = IdBuffer::new(200, 100).unwrap();
+        let storage = device.calls_to("RenderbufferStorage");
+        assert!(storage[0].ends_with("R32UI, 200x100)"), "{:?}", storage);
+        assert!(device
+            .calls()
+            .iter()
+            .any(|c| c.starts_with("FramebufferRenderbuffer(COLOR_ATTACHMENT0")));
+
+        let mut scene = Scene::new();
+        let (a, b) = (scene.add("a"), scene.add("b"));
+        ids.nodes = vec![a, b];
+        let viewport = glm::vec2(400.0, 200.0);
+        device.take();
+        device.set_pixel_uint(2);
+        assert_eq!(ids.pick(&glm::vec2(10.0, 10.0), &viewport), Some(b));
+        // Half resolution, flipped to count rows from the bottom.
+        assert_eq!(
+            device.calls_to("ReadPixelUint"),
+            vec!["ReadPixelUint(5, 94)"]
+        );
+
+        device.set_pixel_uint(0);
+        assert_eq!(ids.pick(&glm::vec2(10.0, 10.0), &viewport), None);
+        device.set_pixel_uint(3);
+        assert_eq!(ids.pick(&glm::vec2(10.0, 10.0), &viewport), None);
+        device.take();
+        assert_eq!(ids.pick(&glm::vec2(400.0, 10.0), &viewport), None);
+        assert!(device.calls_to("ReadPixelUint").is_empty());
+    }
+
+    #[test]
+    fn resize_recreates_the_target() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let mut ids = IdBuffer::new(200, 100).unwrap();
+        let mut scene = Scene::new();
+        ids.nodes = vec![scene.add("a")];
+
+        device.take();
+        ids.resize(200, 100).unwrap();
+        ids.resize(0, 0).unwrap();
+        assert!(device.calls().is_empty());
+        assert_eq!(ids.nodes.len(), 1);
+
+        ids.resize(400, 300).unwrap();
+        let storage = device.calls_to("RenderbufferStorage");
+        assert!(storage[0].ends_with("R32UI, 400x300)"), "{:?}", storage);
+        assert_eq!((ids.width(), ids.height()), (400, 300));
+        // Ids of the old size no longer match any pixel.
+        assert!(ids.nodes.is_empty());
+    }
+
+    #[test]
+    fn picker_selects_the_clicked_mesh() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let cube = Rc::new(
+            geometry::cube(1.0, 1)
+                .build(RenderState::default())
+                .unwrap(),
+        );
+        let mut scene = Scene::new();
+        let near = scene.add("near");
+        scene.set_transform(near, Transform::from_translation(glm::vec3(0.0, 0.0, -3.0)));
+        scene.node_mut(near).unwrap().mesh = Some(cube.clone());
+        let far = scene.add("far");
+        scene.set_transform(far, Transform::from_translation(glm::vec3(0.0, 0.0, -6.0)));
+        scene.node_mut(far).unwrap().mesh = Some(cube);
+        scene.update_transforms();
+
+        let camera = Camera::perspective(90.0, 0.1, 100.0, 800, 600);
+        let viewport = glm::vec2(800.0, 600.0);
+        let mut picker = Picker::new();
+        assert_eq!(
+            picker.update(&Input::new(), &camera, &viewport, &scene),
+            None
+        );
+
+        let pick = picker
+            .update(&click(400.0, 300.0), &camera, &viewport, &scene)
+            .unwrap();
+        assert_eq!(pick.node, near);
+        assert!((pick.point.z + 2.5).abs() < 1e-4, "{:?}", pick.point);
+        assert_eq!(picker.selected(), Some(near));
+
+        scene.node_mut(near).unwrap().set_visible(false);
+        picker.update(&click(400.0, 300.0), &camera, &viewport, &scene);
+        assert_eq!(picker.selected(), Some(far));
+
+        // Clicking empty space clears the selection.
+        assert_eq!(
+            picker.update(&click(10.0, 10.0), &camera, &viewport, &scene),
+            None
+        );
+        assert_eq!(picker.selected(), None);
+    }
+}
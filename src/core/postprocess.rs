@@ -0,0 +1,851 @@
+use std::path::Path;
+use std::rc::Rc;
+
+use gl::types::GLenum;
+use image::{DynamicImage, Rgba, RgbaImage};
+use imgui_glfw_rs::imgui::{self, im_str, ImStr, ImString};
+use nalgebra_glm as glm;
+
+use super::debug;
+use super::framebuffer::{Framebuffer, RenderTarget};
+use super::framework::Context;
+use super::resource::ContextHandle;
+use super::shader::Shader;
+use super::texture::{Texture2D, TextureBuilder};
+use super::vertex::VertexArray;
+
+const FULLSCREEN_VS: &str = "res/glsl/fullscreen.vs.glsl";
+
+/// Operators of `ShaderPass::tonemap`, in `Operator` uniform order.
+pub const TONEMAP_OPERATORS: &[&str] = &["Clamp", "Reinhard", "ACES", "Uncharted 2"];
+
+/// A pass parameter the overlay can edit, uploaded as the uniform it is named after.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Control {
+    /// `float` set with a slider between `min` and `max`.
+    Float { value: f32, min: f32, max: f32 },
+    /// `int` set with a slider between `min` and `max`.
+    Int { value: i32, min: i32, max: i32 },
+    /// `vec3` set with a color editor.
+    Color(glm::Vec3),
+    /// `int` index into `options`, picked from a list.
+    Choice {
+        value: usize,
+        options: &'static [&'static str],
+    },
+    /// `int` 0 or 1.
+    Toggle(bool),
+}
+
+impl Control {
+    pub fn float(value: f32, min: f32, max: f32) -> Control {
+        Control::Float { value, min, max }
+    }
+
+    pub fn int(value: i32, min: i32, max: i32) -> Control {
+        Control::Int { value, min, max }
+    }
+
+    fn upload(&self, shader: &Shader, name: &str) {
+        match *self {
+            Control::Float { value, .. } => shader.set_float(name, value),
+            Control::Int { value, .. } => shader.set_int(name, value),
+            Control::Color(ref color) => shader.set_vec3(name, color),
+            Control::Choice { value, .. } => shader.set_int(name, value as i32),
+            Control::Toggle(on) => shader.set_int(name, on as i32),
+        }
+    }
+
+    fn ui(&mut self, ui: &imgui::Ui, label: &ImStr) -> bool {
+        match self {
+            Control::Float { value, min, max } => ui.slider_float(label, value, *min, *max).build(),
+            Control::Int { value, min, max } => ui.slider_int(label, value, *min, *max).build(),
+            Control::Color(color) => {
+                let mut rgb = [color.x, color.y, color.z];
+                let changed = ui.color_edit(label, &mut rgb).build();
+                *color = glm::make_vec3(&rgb);
+                changed
+            }
+            Control::Choice { value, options } => {
+                let items: Vec<ImString> = options.iter().map(|o| ImString::new(*o)).collect();
+                let items: Vec<&ImString> = items.iter().collect();
+                let mut current = *value as i32;
+                let changed = ui.combo(label, &mut current, &items, -1);
+                *value = current.max(0) as usize;
+                changed
+            }
+            Control::Toggle(on) => ui.checkbox(label, on),
+        }
+    }
+}
+
+/// Named controls of one pass, in overlay order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Controls {
+    entries: Vec<(String, Control)>,
+}
+
+#[allow(dead_code)]
+impl Controls {
+    pub fn new() -> Controls {
+        Controls::default()
+    }
+
+    pub fn with(mut self, name: &str, control: Control) -> Controls {
+        self.set(name, control);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Control> {
+        self.entries.iter().find(|(n, _)| n == name).map(|(_, c)| c)
+    }
+
+    /// Replaces the control called `name`, or appends it.
+    pub fn set(&mut self, name: &str, control: Control) {
+        match self.entries.iter_mut().find(|(n, _)| n == name) {
+            Some(entry) => entry.1 = control,
+            None => self.entries.push((name.to_string(), control)),
+        }
+    }
+
+    /// Numeric value of a `Float`, `Int`, `Choice` or `Toggle` control.
+    pub fn value(&self, name: &str) -> Option<f32> {
+        match *self.get(name)? {
+            Control::Float { value, .. } => Some(value),
+            Control::Int { value, .. } => Some(value as f32),
+            Control::Choice { value, .. } => Some(value as f32),
+            Control::Toggle(on) => Some(on as i32 as f32),
+            Control::Color(_) => None,
+        }
+    }
+
+    /// Sets the value of a `Float` or `Int` control, clamped to its range. Returns whether
+    /// such a control exists.
+    pub fn set_value(&mut self, name: &str, v: f32) -> bool {
+        match self.entries.iter_mut().find(|(n, _)| n == name) {
+            Some((_, Control::Float { value, min, max })) => *value = v.clamp(*min, *max),
+            Some((_, Control::Int { value, min, max })) => {
+                *value = (v.round() as i32).clamp(*min, *max)
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.entries.iter().map(|(n, _)| n.as_str()).collect()
+    }
+
+    /// Uploads every control the program uses.
+    pub fn upload(&self, shader: &Shader) {
+        for (name, control) in self.entries.iter() {
+            if shader.has_uniform(name) {
+                control.upload(shader, name);
+            }
+        }
+    }
+
+    /// Draws a widget per control. Returns whether any value changed.
+    pub fn ui(&mut self, ui: &imgui::Ui) -> bool {
+        let mut changed = false;
+        for (name, control) in self.entries.iter_mut() {
+            changed |= control.ui(ui, &ImString::new(name.as_str()));
+        }
+        changed
+    }
+}
+
+/// Input and output of one pass.
+pub struct PassFrame<'a> {
+    ctx: &'a Context,
+    input: &'a Texture2D,
+    output: Option<&'a Framebuffer>,
+    width: u32,
+    height: u32,
+    triangle: &'a VertexArray,
+}
+
+#[allow(dead_code)]
+impl<'a> PassFrame<'a> {
+    pub fn ctx(&self) -> &Context {
+        self.ctx
+    }
+
+    /// Output of the previous pass, or the rendered frame for the first.
+    pub fn input(&self) -> &Texture2D {
+        self.input
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Binds the pass output at full size: an HDR target, or the window for the last pass.
+    pub fn bind_output(&self) {
+        match self.output {
+            Some(fbo) => fbo.bind(),
+            None => self.ctx.device().bind_framebuffer(0),
+        }
+        self.ctx.set_viewport_size(self.width, self.height);
+    }
+
+    /// Draws one triangle covering the viewport, for programs using
+    /// `res/glsl/fullscreen.vs.glsl`.
+    pub fn draw(&self) {
+        self.triangle.draw_arrays(gl::TRIANGLES, 0, 3);
+    }
+}
+
+/// One full-screen step of a `PostProcess` stack.
+#[allow(unused_variables)]
+pub trait PostPass {
+    /// Shown in the overlay and used to look the pass up.
+    fn name(&self) -> &str;
+
+    fn controls(&self) -> &Controls;
+
+    fn controls_mut(&mut self) -> &mut Controls;
+
+    /// Draws `frame.input()` processed into the output `frame.bind_output` binds.
+    fn run(&self, frame: &PassFrame);
+
+    /// Called with the frame size when the window resizes.
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Pass drawing one fragment shader over the frame. The shader reads the frame from
+/// `Input`, gets `TexelSize` if it uses it, and each control as a uniform.
+pub struct ShaderPass {
+    name: String,
+    shader: Shader,
+    controls: Controls,
+    textures: Vec<(String, Rc<Texture2D>)>,
+}
+
+#[allow(dead_code)]
+impl ShaderPass {
+    pub fn new(name: &str, fragment: &str) -> Result<ShaderPass, String> {
+        let shader = Shader::new(FULLSCREEN_VS, fragment)?;
+        shader.set_label(name);
+        Ok(ShaderPass {
+            name: name.to_string(),
+            shader,
+            controls: Controls::new(),
+            textures: Vec::new(),
+        })
+    }
+
+    pub fn with(mut self, uniform: &str, control: Control) -> ShaderPass {
+        self.controls.set(uniform, control);
+        self
+    }
+
+    /// Binds `texture` for the sampler `uniform`, on the units after the input's.
+    pub fn with_texture(mut self, uniform: &str, texture: Rc<Texture2D>) -> ShaderPass {
+        self.textures.push((uniform.to_string(), texture));
+        self
+    }
+
+    pub fn shader(&self) -> &Shader {
+        &self.shader
+    }
+
+    /// Maps HDR to displayable range with one of `TONEMAP_OPERATORS` after scaling by
+    /// `Exposure`.
+    pub fn tonemap() -> Result<ShaderPass, String> {
+        Ok(ShaderPass::new("Tonemap", "res/glsl/post_tonemap.fs.glsl")?
+            .with(
+                "Operator",
+                Control::Choice {
+                    value: 2,
+                    options: TONEMAP_OPERATORS,
+                },
+            )
+            .with("Exposure", Control::float(1.0, 0.0, 8.0)))
+    }
+
+    /// Encodes linear color for the display. Leave it out when the window framebuffer is
+    /// sRGB, which encodes on write.
+    pub fn gamma() -> Result<ShaderPass, String> {
+        Ok(ShaderPass::new("Gamma", "res/glsl/post_gamma.fs.glsl")?
+            .with("Gamma", Control::float(2.2, 1.0, 3.0)))
+    }
+
+    /// Fast approximate anti-aliasing; runs best on gamma encoded color.
+    pub fn fxaa() -> Result<ShaderPass, String> {
+        Ok(ShaderPass::new("FXAA", "res/glsl/post_fxaa.fs.glsl")?
+            .with("SpanMax", Control::float(8.0, 1.0, 16.0))
+            .with("ReduceMul", Control::float(0.125, 0.0, 0.5))
+            .with("ReduceMin", Control::float(1.0 / 128.0, 0.0, 0.1)))
+    }
+
+    /// Darkens the frame towards its corners, in an ellipse following the frame, or a
+    /// circle with `Round`.
+    pub fn vignette() -> Result<ShaderPass, String> {
+        Ok(
+            ShaderPass::new("Vignette", "res/glsl/post_vignette.fs.glsl")?
+                .with("Strength", Control::float(0.5, 0.0, 1.0))
+                .with("Radius", Control::float(0.75, 0.0, 1.5))
+                .with("Softness", Control::float(0.45, 0.01, 1.0))
+                .with("Color", Control::Color(glm::vec3(0.0, 0.0, 0.0)))
+                .with("Round", Control::Toggle(false)),
+        )
+    }
+
+    /// Looks colors up in a table laid out like `identity_lut`, blended in by `Strength`.
+    pub fn color_grading(lut: Rc<Texture2D>) -> Result<ShaderPass, String> {
+        Ok(
+            ShaderPass::new("Color grading", "res/glsl/post_grading.fs.glsl")?
+                .with("Strength", Control::float(1.0, 0.0, 1.0))
+                .with_texture("Lut", lut),
+        )
+    }
+}
+
+impl PostPass for ShaderPass {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn controls(&self) -> &Controls {
+        &self.controls
+    }
+
+    fn controls_mut(&mut self) -> &mut Controls {
+        &mut self.controls
+    }
+
+    fn run(&self, frame: &PassFrame) {
+        frame.bind_output();
+        self.shader.bind(frame.ctx());
+        frame.input().bind(0);
+        self.shader.set_texture("Input", 0);
+        for (i, (uniform, texture)) in self.textures.iter().enumerate() {
+            let unit = i as u32 + 1;
+            texture.bind(unit);
+            self.shader.set_texture(uniform, unit as i32);
+        }
+        if self.shader.has_uniform("TexelSize") {
+            let texel = glm::vec2(1.0 / frame.width() as f32, 1.0 / frame.height() as f32);
+            self.shader.set_vec2("TexelSize", &texel);
+        }
+        self.controls.upload(&self.shader);
+        frame.draw();
+    }
+}
+
+/// Glow around bright areas: pixels over `Threshold` are blurred at half size and added
+/// back scaled by `Intensity`.
+pub struct Bloom {
+    controls: Controls,
+    extract: Shader,
+    blur: Shader,
+    composite: Shader,
+    targets: [RenderTarget; 2],
+}
+
+#[allow(dead_code)]
+impl Bloom {
+    pub fn new(width: u32, height: u32) -> Result<Bloom, String> {
+        let controls = Controls::new()
+            .with("Threshold", Control::float(1.0, 0.0, 8.0))
+            .with("Knee", Control::float(0.5, 0.0, 1.0))
+            .with("Intensity", Control::float(0.3, 0.0, 2.0))
+            .with("Iterations", Control::int(4, 1, 10));
+        Ok(Bloom {
+            controls,
+            extract: Shader::new(FULLSCREEN_VS, "res/glsl/post_bloom_extract.fs.glsl")?,
+            blur: Shader::new(FULLSCREEN_VS, "res/glsl/post_blur.fs.glsl")?,
+            composite: Shader::new(FULLSCREEN_VS, "res/glsl/post_bloom_composite.fs.glsl")?,
+            targets: Bloom::targets(width, height)?,
+        })
+    }
+
+    fn targets(width: u32, height: u32) -> Result<[RenderTarget; 2], String> {
+        let (width, height) = ((width / 2).max(1), (height / 2).max(1));
+        let a = RenderTarget::new(width, height, PostProcess::FORMAT, false)?;
+        let b = RenderTarget::new(width, height, PostProcess::FORMAT, false)?;
+        a.set_label("bloom a");
+        b.set_label("bloom b");
+        Ok([a, b])
+    }
+}
+
+impl PostPass for Bloom {
+    fn name(&self) -> &str {
+        "Bloom"
+    }
+
+    fn controls(&self) -> &Controls {
+        &self.controls
+    }
+
+    fn controls_mut(&mut self) -> &mut Controls {
+        &mut self.controls
+    }
+
+    fn run(&self, frame: &PassFrame) {
+        let ctx = frame.ctx();
+        let (a, b) = (&self.targets[0], &self.targets[1]);
+        ctx.set_viewport_size(a.width(), a.height());
+
+        a.bind();
+        self.extract.bind(ctx);
+        frame.input().bind(0);
+        self.extract.set_texture("Input", 0);
+        self.controls.upload(&self.extract);
+        frame.draw();
+
+        // Separable blur, ping-ponging between the half size targets.
+        self.blur.bind(ctx);
+        self.blur.set_texture("Input", 0);
+        let texel = glm::vec2(1.0 / a.width() as f32, 1.0 / a.height() as f32);
+        let iterations = self.controls.value("Iterations").unwrap_or(4.0) as u32;
+        for _ in 0..iterations {
+            b.bind();
+            a.color().bind(0);
+            self.blur.set_vec2("Direction", &glm::vec2(texel.x, 0.0));
+            frame.draw();
+            a.bind();
+            b.color().bind(0);
+            self.blur.set_vec2("Direction", &glm::vec2(0.0, texel.y));
+            frame.draw();
+        }
+
+        frame.bind_output();
+        self.composite.bind(ctx);
+        frame.input().bind(0);
+        a.color().bind(1);
+        self.composite.set_texture("Input", 0);
+        self.composite.set_texture("Bloom", 1);
+        self.controls.upload(&self.composite);
+        frame.draw();
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        self.targets = Bloom::targets(width, height)?;
+        Ok(())
+    }
+}
+
+struct Entry {
+    pass: Box<dyn PostPass>,
+    enabled: bool,
+}
+
+/// Ordered full-screen passes over the frame. While enabled through
+/// `FrameworkBuilder::post_process`, `App::render` draws into an HDR target, then each
+/// enabled pass reads the previous one's output from one of two ping-ponged targets and
+/// the last one draws to the window.
+pub struct PostProcess {
+    width: u32,
+    height: u32,
+    scene: RenderTarget,
+    targets: [RenderTarget; 2],
+    copy: Shader,
+    triangle: VertexArray,
+    passes: Vec<Entry>,
+    ctx: ContextHandle,
+}
+
+#[allow(dead_code)]
+impl PostProcess {
+    /// Color format of the frame and intermediate targets.
+    pub const FORMAT: GLenum = gl::RGBA16F;
+
+    /// Stack without passes; frames reach the window unchanged.
+    pub fn new(width: u32, height: u32) -> Result<PostProcess, String> {
+        let ctx = ContextHandle::current()?;
+        let (scene, targets) = PostProcess::targets(width, height)?;
+        let triangle = VertexArray::new()?;
+        triangle.set_label("post-processing triangle");
+        Ok(PostProcess {
+            width,
+            height,
+            scene,
+            targets,
+            copy: Shader::new(FULLSCREEN_VS, "res/glsl/post_copy.fs.glsl")?,
+            triangle,
+            passes: Vec::new(),
+            ctx,
+        })
+    }
+
+    /// Bloom, ACES tonemapping, gamma and FXAA, plus an identity color grade and a vignette
+    /// that start disabled.
+    pub fn with_default_passes(width: u32, height: u32) -> Result<PostProcess, String> {
+        let mut post = PostProcess::new(width, height)?;
+        let lut = Rc::new(lut_texture(&identity_lut(16))?);
+        post.push(Box::new(Bloom::new(width, height)?));
+        post.push(Box::new(ShaderPass::tonemap()?));
+        post.push(Box::new(ShaderPass::color_grading(lut)?));
+        post.push(Box::new(ShaderPass::gamma()?));
+        post.push(Box::new(ShaderPass::fxaa()?));
+        post.push(Box::new(ShaderPass::vignette()?));
+        post.set_enabled("Color grading", false);
+        post.set_enabled("Vignette", false);
+        Ok(post)
+    }
+
+    fn targets(width: u32, height: u32) -> Result<(RenderTarget, [RenderTarget; 2]), String> {
+        let scene = RenderTarget::new(width, height, PostProcess::FORMAT, true)?;
+        scene.set_label("post-processing input");
+        let a = RenderTarget::new(width, height, PostProcess::FORMAT, false)?;
+        let b = RenderTarget::new(width, height, PostProcess::FORMAT, false)?;
+        a.set_label("post-processing ping");
+        b.set_label("post-processing pong");
+        Ok((scene, [a, b]))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Target `App::render` draws into, with a depth buffer.
+    pub fn input(&self) -> &RenderTarget {
+        &self.scene
+    }
+
+    pub fn len(&self) -> usize {
+        self.passes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    /// Appends an enabled pass.
+    pub fn push(&mut self, pass: Box<dyn PostPass>) {
+        let index = self.passes.len();
+        self.insert(index, pass);
+    }
+
+    /// Inserts an enabled pass at `index`, shifting the passes after it.
+    ///
+    /// # Panics
+    /// Panics if `index > len()`.
+    pub fn insert(&mut self, index: usize, pass: Box<dyn PostPass>) {
+        self.passes.insert(
+            index,
+            Entry {
+                pass,
+                enabled: true,
+            },
+        );
+    }
+
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> Box<dyn PostPass> {
+        self.passes.remove(index).pass
+    }
+
+    /// Moves the pass at `from` to `to`, shifting the passes between.
+    ///
+    /// # Panics
+    /// Panics if either index is out of bounds.
+    pub fn move_pass(&mut self, from: usize, to: usize) {
+        let entry = self.passes.remove(from);
+        self.passes.insert(to, entry);
+    }
+
+    /// Pass names in run order.
+    pub fn names(&self) -> Vec<&str> {
+        self.passes.iter().map(|e| e.pass.name()).collect()
+    }
+
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.passes.iter().position(|e| e.pass.name() == name)
+    }
+
+    pub fn pass(&self, name: &str) -> Option<&dyn PostPass> {
+        let index = self.position(name)?;
+        Some(self.passes[index].pass.as_ref())
+    }
+
+    pub fn pass_mut(&mut self, name: &str) -> Option<&mut dyn PostPass> {
+        let index = self.position(name)?;
+        Some(self.passes[index].pass.as_mut())
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.position(name)
+            .is_some_and(|index| self.passes[index].enabled)
+    }
+
+    /// Returns whether a pass called `name` exists.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.position(name) {
+            Some(index) => {
+                self.passes[index].enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Recreates the targets for a new frame size. Ignores a minimized window.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        if width == 0 || height == 0 || (width, height) == (self.width, self.height) {
+            return Ok(());
+        }
+        let (scene, targets) = PostProcess::targets(width, height)?;
+        self.scene = scene;
+        self.targets = targets;
+        self.width = width;
+        self.height = height;
+        for entry in self.passes.iter_mut() {
+            entry.pass.resize(width, height)?;
+        }
+        Ok(())
+    }
+
+    /// Directs the frame into the HDR input, including from `Framebuffer::unbind`.
+    pub fn begin(&self, ctx: &Context) {
+        self.ctx
+            .set_screen_framebuffer(self.scene.framebuffer().id());
+        self.scene.bind();
+        ctx.set_viewport();
+    }
+
+    /// Runs the enabled passes in order, the last one drawing to the window.
+    pub fn end(&self, ctx: &Context) {
+        self.ctx.set_screen_framebuffer(0);
+        let passes: Vec<&dyn PostPass> = self
+            .passes
+            .iter()
+            .filter(|e| e.enabled)
+            .map(|e| e.pass.as_ref())
+            .collect();
+        let mut input = self.scene.color();
+        if passes.is_empty() {
+            let frame = self.frame(ctx, input, None);
+            frame.bind_output();
+            self.copy.bind(ctx);
+            input.bind(0);
+            self.copy.set_texture("Input", 0);
+            frame.draw();
+        }
+        for (i, pass) in passes.iter().enumerate() {
+            let output = if i + 1 == passes.len() {
+                None
+            } else {
+                Some(&self.targets[i % 2])
+            };
+            let _group = debug::group(&self.ctx, pass.name());
+            pass.run(&self.frame(ctx, input, output.map(|t| t.framebuffer())));
+            if let Some(target) = output {
+                input = target.color();
+            }
+        }
+        ctx.set_viewport();
+    }
+
+    fn frame<'a>(
+        &'a self,
+        ctx: &'a Context,
+        input: &'a Texture2D,
+        output: Option<&'a Framebuffer>,
+    ) -> PassFrame<'a> {
+        PassFrame {
+            ctx,
+            input,
+            output,
+            width: self.width,
+            height: self.height,
+            triangle: &self.triangle,
+        }
+    }
+
+    /// Window listing the passes in run order, with toggles, reordering arrows and the
+    /// controls of each enabled pass that has any.
+    pub fn ui(&mut self, ui: &imgui::Ui) {
+        let count = self.passes.len();
+        let passes = &mut self.passes;
+        let mut moved = None;
+        ui.window(im_str!("Post-processing"))
+            .always_auto_resize(true)
+            .build(|| {
+                for (i, entry) in passes.iter_mut().enumerate() {
+                    ui.with_id(i as i32, || {
+                        ui.checkbox(&ImString::new(entry.pass.name()), &mut entry.enabled);
+                        ui.same_line(0.0);
+                        if ui.arrow_button(im_str!("up"), imgui::Direction::Up) && i > 0 {
+                            moved = Some((i, i - 1));
+                        }
+                        ui.same_line(0.0);
+                        if ui.arrow_button(im_str!("down"), imgui::Direction::Down) && i + 1 < count
+                        {
+                            moved = Some((i, i + 1));
+                        }
+                        if entry.enabled && !entry.pass.controls().names().is_empty() {
+                            ui.indent();
+                            entry.pass.controls_mut().ui(ui);
+                            ui.unindent();
+                        }
+                    });
+                }
+            });
+        if let Some((from, to)) = moved {
+            self.move_pass(from, to);
+        }
+    }
+}
+
+/// Color grading table that leaves colors unchanged: `size` slices of `size` x `size`
+/// texels side by side, red increasing across each slice, green down the rows and blue
+/// from slice to slice. Edit it in an image editor along with a screenshot to make a grade.
+pub fn identity_lut(size: u32) -> RgbaImage {
+    let scale = 255.0 / (size.max(2) - 1) as f32;
+    let level = |i: u32| (i as f32 * scale).round() as u8;
+    RgbaImage::from_fn(size * size, size, |x, y| {
+        Rgba([level(x % size), level(y), level(x / size), 255])
+    })
+}
+
+/// Uploads a table laid out like `identity_lut` for `ShaderPass::color_grading`.
+pub fn lut_texture(image: &RgbaImage) -> Result<Texture2D, String> {
+    let texture = TextureBuilder::new()
+        .filter(gl::LINEAR)
+        .wrap(gl::CLAMP_TO_EDGE)
+        .build2d_from_image(DynamicImage::ImageRgba8(image.clone()))?;
+    texture.set_label("color grading LUT");
+    Ok(texture)
+}
+
+/// Loads a table laid out like `identity_lut` from an image file.
+#[allow(dead_code)]
+pub fn load_lut<P>(path: P) -> Result<Texture2D, String>
+where
+    P: AsRef<Path>,
+{
+    let image = image::open(path.as_ref())
+        .map_err(|e| format!("Failed to load LUT {}: {}", path.as_ref().display(), e))?
+        .into_rgba8();
+    if image.width() != image.height() * image.height() {
+        return Err(format!(
+            "LUT {} is {}x{}, expected {}x{} slices side by side",
+            path.as_ref().display(),
+            image.width(),
+            image.height(),
+            image.height() * image.height(),
+            image.height()
+        ));
+    }
+    lut_texture(&image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::device::RecordingDevice;
+    use crate::core::resource::ContextOwner;
+
+    fn pass(name: &str) -> Box<dyn PostPass> {
+        Box::new(ShaderPass::new(name, "res/glsl/post_copy.fs.glsl").unwrap())
+    }
+
+    #[test]
+    fn identity_lut_maps_texels_to_their_coordinates() {
+        let lut = identity_lut(4);
+        assert_eq!(lut.dimensions(), (16, 4));
+        assert_eq!(lut.get_pixel(0, 0), &Rgba([0, 0, 0, 255]));
+        // Red 1 of slice 2 (blue 2), row 3 (green 3).
+        assert_eq!(lut.get_pixel(9, 3), &Rgba([85, 255, 170, 255]));
+        assert_eq!(lut.get_pixel(15, 3), &Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn controls_clamp_and_replace_values() {
+        let mut controls = Controls::new()
+            .with("Exposure", Control::float(1.0, 0.0, 8.0))
+            .with("Iterations", Control::int(4, 1, 10))
+            .with("Color", Control::Color(glm::vec3(1.0, 0.0, 0.0)));
+        assert!(controls.set_value("Exposure", 12.0));
+        assert!(controls.set_value("Iterations", 2.6));
+        assert!(!controls.set_value("Color", 1.0));
+        assert!(!controls.set_value("Missing", 1.0));
+        assert_eq!(controls.value("Exposure"), Some(8.0));
+        assert_eq!(controls.value("Iterations"), Some(3.0));
+
+        controls.set("Exposure", Control::float(0.5, 0.0, 1.0));
+        assert_eq!(controls.names(), vec!["Exposure", "Iterations", "Color"]);
+        assert_eq!(controls.value("Exposure"), Some(0.5));
+    }
+
+    #[test]
+    fn passes_can_be_reordered_and_toggled() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let mut post = PostProcess::new(64, 32).unwrap();
+        post.push(pass("a"));
+        post.push(pass("b"));
+        post.push(pass("c"));
+        post.insert(0, pass("first"));
+        assert_eq!(post.names(), vec!["first", "a", "b", "c"]);
+
+        post.move_pass(3, 1);
+        assert_eq!(post.names(), vec!["first", "c", "a", "b"]);
+        assert_eq!(post.remove(0).name(), "first");
+        assert_eq!(post.position("b"), Some(2));
+
+        assert!(post.is_enabled("a"));
+        assert!(post.set_enabled("a", false));
+        assert!(!post.is_enabled("a"));
+        assert!(!post.set_enabled("missing", false));
+    }
+
+    #[test]
+    fn default_stack_and_resize() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let mut post = PostProcess::with_default_passes(64, 32).unwrap();
+        assert_eq!(
+            post.names(),
+            vec![
+                "Bloom",
+                "Tonemap",
+                "Color grading",
+                "Gamma",
+                "FXAA",
+                "Vignette"
+            ]
+        );
+        assert!(!post.is_enabled("Vignette"));
+        assert_eq!(
+            post.pass("Vignette").unwrap().controls().get("Round"),
+            Some(&Control::Toggle(false))
+        );
+        let tonemap = post.pass_mut("Tonemap").unwrap();
+        assert!(tonemap.controls_mut().set_value("Exposure", 2.0));
+        assert_eq!(
+            post.pass("Tonemap").unwrap().controls().value("Exposure"),
+            Some(2.0)
+        );
+
+        device.take();
+        post.resize(128, 64).unwrap();
+        assert_eq!(
+            device.calls_to("TexImageFloat"),
+            vec![
+                "TexImageFloat(TEXTURE_2D, 0, RGBA16F, 128x64, null)",
+                "TexImageFloat(TEXTURE_2D, 0, RGBA16F, 128x64, null)",
+                "TexImageFloat(TEXTURE_2D, 0, RGBA16F, 128x64, null)",
+                "TexImageFloat(TEXTURE_2D, 0, RGBA16F, 64x32, null)",
+                "TexImageFloat(TEXTURE_2D, 0, RGBA16F, 64x32, null)",
+            ]
+        );
+        post.resize(0, 0).unwrap();
+        assert_eq!(post.width(), 128);
+    }
+}
@@ -0,0 +1,395 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
+
+use super::device::{GlDevice, GraphicsDevice};
+
+/// Kinds of GL objects owned by the crate's resource types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ResourceKind {
+    Buffer,
+    Shader,
+    Texture,
+    VertexArray,
+    Framebuffer,
+    Renderbuffer,
+}
+
+impl ResourceKind {
+    pub const ALL: [ResourceKind; 6] = [
+        ResourceKind::Buffer,
+        ResourceKind::Shader,
+        ResourceKind::Texture,
+        ResourceKind::VertexArray,
+        ResourceKind::Framebuffer,
+        ResourceKind::Renderbuffer,
+    ];
+}
+
+/// A GL object that was created and not yet deleted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveResource {
+    pub kind: ResourceKind,
+    pub id: u32,
+    pub label: Option<String>,
+}
+
+impl fmt::Display for LiveResource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} {}", self.kind, self.id)?;
+        if let Some(ref label) = self.label {
+            write!(f, " \"{}\"", label)?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts GL object creation and deletion per kind.
+#[derive(Debug, Default)]
+pub struct Tracker {
+    live: BTreeMap<(ResourceKind, u32), Option<String>>,
+    created: BTreeMap<ResourceKind, usize>,
+    deleted: BTreeMap<ResourceKind, usize>,
+}
+
+#[allow(dead_code)]
+impl Tracker {
+    pub const fn new() -> Tracker {
+        Tracker {
+            live: BTreeMap::new(),
+            created: BTreeMap::new(),
+            deleted: BTreeMap::new(),
+        }
+    }
+
+    pub fn created(&mut self, kind: ResourceKind, id: u32) {
+        self.live.insert((kind, id), None);
+        *self.created.entry(kind).or_default() += 1;
+    }
+
+    pub fn deleted(&mut self, kind: ResourceKind, id: u32) {
+        if self.live.remove(&(kind, id)).is_none() {
+            log::warn!("{:?} {} deleted but never tracked", kind, id);
+        }
+        *self.deleted.entry(kind).or_default() += 1;
+    }
+
+    pub fn labeled(&mut self, kind: ResourceKind, id: u32, label: &str) {
+        if let Some(l) = self.live.get_mut(&(kind, id)) {
+            *l = Some(label.to_string());
+        }
+    }
+
+    pub fn live(&self) -> Vec<LiveResource> {
+        self.live
+            .iter()
+            .map(|(&(kind, id), label)| LiveResource {
+                kind,
+                id,
+                label: label.clone(),
+            })
+            .collect()
+    }
+
+    /// One line per kind: `Buffer: 12 created, 10 deleted, 2 alive`.
+    pub fn summary(&self) -> Vec<String> {
+        ResourceKind::ALL
+            .iter()
+            .filter_map(|kind| {
+                let created = self.created.get(kind).cloned().unwrap_or(0);
+                let deleted = self.deleted.get(kind).cloned().unwrap_or(0);
+                if created == 0 && deleted == 0 {
+                    return None;
+                }
+                let alive = self.live.keys().filter(|(k, _)| k == kind).count();
+                Some(format!(
+                    "{:?}: {} created, {} deleted, {} alive",
+                    kind, created, deleted, alive
+                ))
+            })
+            .collect()
+    }
+}
+
+static TRACKER: Mutex<Tracker> = Mutex::new(Tracker::new());
+
+fn with_tracker<F, T>(f: F) -> T
+where
+    F: FnOnce(&mut Tracker) -> T,
+{
+    // A panic while holding the lock leaves the counts usable.
+    let mut tracker = TRACKER.lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut tracker)
+}
+
+pub fn track_created(kind: ResourceKind, id: u32) {
+    log::trace!("Create {:?}: {}", kind, id);
+    with_tracker(|t| t.created(kind, id));
+}
+
+pub fn track_deleted(kind: ResourceKind, id: u32) {
+    log::trace!("Delete {:?}: {}", kind, id);
+    with_tracker(|t| t.deleted(kind, id));
+}
+
+pub fn track_label(kind: ResourceKind, id: u32, label: &str) {
+    with_tracker(|t| t.labeled(kind, id, label));
+}
+
+/// GL objects currently alive across the process.
+#[allow(dead_code)]
+pub fn live_resources() -> Vec<LiveResource> {
+    with_tracker(|t| t.live())
+}
+
+/// Logs creation counts and every GL object still alive, e.g. at shutdown.
+pub fn report() {
+    let (summary, live) = with_tracker(|t| (t.summary(), t.live()));
+    for line in summary {
+        log::info!("{}", line);
+    }
+    for resource in live.iter() {
+        log::warn!("Still alive: {}", resource);
+    }
+}
+
+struct Shared {
+    alive: AtomicBool,
+    thread: ThreadId,
+    pending: Mutex<Vec<(ResourceKind, u32)>>,
+    screen: AtomicU32,
+    device: Box<dyn GraphicsDevice>,
+}
+
+/// Reference to the GL context a resource was created in.
+///
+/// Dropping a resource on the context's thread deletes it immediately, on any
+/// other thread it is queued until `ContextOwner::collect`, and after the
+/// context is gone it is skipped since the driver already freed it.
+#[derive(Clone)]
+pub struct ContextHandle {
+    shared: Arc<Shared>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<ContextHandle>> = const { RefCell::new(None) };
+}
+
+#[allow(dead_code)]
+impl ContextHandle {
+    /// Handle to the context current on this thread.
+    pub fn current() -> Result<ContextHandle, String> {
+        CURRENT
+            .with(|c| c.borrow().clone())
+            .filter(|c| c.is_alive())
+            .ok_or_else(|| {
+                "No current GL context: build a Context with FrameworkBuilder before creating GL resources"
+                    .to_string()
+            })
+    }
+
+    /// Device that issues the context's GL calls.
+    pub fn device(&self) -> &dyn GraphicsDevice {
+        &*self.shared.device
+    }
+
+    /// Framebuffer that stands in for the window: 0, or the post-processing input while a
+    /// frame renders into it. `Framebuffer::unbind` returns here.
+    pub fn screen_framebuffer(&self) -> u32 {
+        self.shared.screen.load(Ordering::Relaxed)
+    }
+
+    pub fn set_screen_framebuffer(&self, id: u32) {
+        self.shared.screen.store(id, Ordering::Relaxed);
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.shared.alive.load(Ordering::Acquire)
+    }
+
+    /// Registers a freshly created object.
+    pub fn created(&self, kind: ResourceKind, id: u32) {
+        track_created(kind, id);
+    }
+
+    /// Deletes an object now or later, depending on the calling thread.
+    pub fn release(&self, kind: ResourceKind, id: u32) {
+        if !self.is_alive() {
+            log::trace!("{:?} {} outlived its context", kind, id);
+            track_deleted(kind, id);
+        } else if thread::current().id() == self.shared.thread {
+            self.shared.device.delete(kind, id);
+            track_deleted(kind, id);
+        } else {
+            log::trace!("Queue {:?} {} for deletion on the context thread", kind, id);
+            self.shared
+                .pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push((kind, id));
+        }
+    }
+
+    pub fn pending(&self) -> usize {
+        self.shared
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+}
+
+/// Owns the deferred deletion queue; lives exactly as long as the GL context.
+pub struct ContextOwner {
+    handle: ContextHandle,
+}
+
+impl Drop for ContextOwner {
+    fn drop(&mut self) {
+        self.collect();
+        self.handle.shared.alive.store(false, Ordering::Release);
+        CURRENT.with(|c| {
+            let mut c = c.borrow_mut();
+            if c.as_ref()
+                .is_some_and(|c| Arc::ptr_eq(&c.shared, &self.handle.shared))
+            {
+                *c = None;
+            }
+        });
+    }
+}
+
+#[allow(dead_code)]
+impl ContextOwner {
+    /// Makes a context current on this thread; call once its GL functions are loaded.
+    pub fn new() -> ContextOwner {
+        ContextOwner::with_device(Box::new(GlDevice))
+    }
+
+    /// Like `new`, issuing GL calls through `device`, e.g. a `RecordingDevice` in tests.
+    pub fn with_device(device: Box<dyn GraphicsDevice>) -> ContextOwner {
+        let handle = ContextHandle {
+            shared: Arc::new(Shared {
+                alive: AtomicBool::new(true),
+                thread: thread::current().id(),
+                pending: Mutex::new(Vec::new()),
+                screen: AtomicU32::new(0),
+                device,
+            }),
+        };
+        CURRENT.with(|c| *c.borrow_mut() = Some(handle.clone()));
+        ContextOwner { handle }
+    }
+
+    pub fn handle(&self) -> &ContextHandle {
+        &self.handle
+    }
+
+    /// Deletes objects dropped on other threads since the last call.
+    pub fn collect(&self) {
+        let pending: Vec<_> = self
+            .handle
+            .shared
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .drain(..)
+            .collect();
+        for (kind, id) in pending {
+            self.handle.shared.device.delete(kind, id);
+            track_deleted(kind, id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_live_objects_and_summary() {
+        let mut tracker = Tracker::new();
+        tracker.created(ResourceKind::Buffer, 1);
+        tracker.created(ResourceKind::Buffer, 2);
+        tracker.created(ResourceKind::Texture, 1);
+        tracker.labeled(ResourceKind::Buffer, 2, "quad vertices");
+        tracker.deleted(ResourceKind::Buffer, 1);
+
+        assert_eq!(
+            tracker.live(),
+            vec![
+                LiveResource {
+                    kind: ResourceKind::Buffer,
+                    id: 2,
+                    label: Some("quad vertices".to_string())
+                },
+                LiveResource {
+                    kind: ResourceKind::Texture,
+                    id: 1,
+                    label: None
+                },
+            ]
+        );
+        assert_eq!(
+            tracker.summary(),
+            vec![
+                "Buffer: 2 created, 1 deleted, 1 alive".to_string(),
+                "Texture: 1 created, 0 deleted, 1 alive".to_string(),
+            ]
+        );
+        assert_eq!(tracker.live()[0].to_string(), "Buffer 2 \"quad vertices\"");
+    }
+
+    #[test]
+    fn reused_ids_are_tracked_again() {
+        let mut tracker = Tracker::new();
+        tracker.created(ResourceKind::Shader, 3);
+        tracker.deleted(ResourceKind::Shader, 3);
+        tracker.created(ResourceKind::Shader, 3);
+        assert_eq!(tracker.live().len(), 1);
+        assert_eq!(
+            tracker.summary(),
+            vec!["Shader: 2 created, 1 deleted, 1 alive"]
+        );
+    }
+
+    use crate::core::device::RecordingDevice;
+
+    // Each test runs on its own thread, so the current context never leaks between them.
+    #[test]
+    fn creation_requires_a_current_context() {
+        assert!(ContextHandle::current().is_err());
+        let owner = ContextOwner::with_device(Box::new(RecordingDevice::new()));
+        assert!(ContextHandle::current().is_ok());
+        drop(owner);
+        assert!(ContextHandle::current().is_err());
+    }
+
+    #[test]
+    fn defers_deletion_across_threads_and_contexts() {
+        let device = RecordingDevice::new();
+        let owner = ContextOwner::with_device(Box::new(device.clone()));
+
+        // Dropped on another thread: queued until the owner collects.
+        let handle = ContextHandle::current().unwrap();
+        thread::spawn(move || handle.release(ResourceKind::Buffer, 1000))
+            .join()
+            .unwrap();
+        assert_eq!(owner.handle().pending(), 1);
+        assert!(device.calls().is_empty());
+        owner.collect();
+        assert_eq!(owner.handle().pending(), 0);
+        assert_eq!(device.take(), vec!["Delete(Buffer, 1000)"]);
+
+        // Dropped after the context is gone: skipped.
+        let handle = owner.handle().clone();
+        drop(owner);
+        assert!(!handle.is_alive());
+        handle.release(ResourceKind::Texture, 1001);
+        assert_eq!(handle.pending(), 0);
+        assert!(device.calls().is_empty());
+    }
+}
@@ -0,0 +1,895 @@
+use std::rc::Rc;
+
+use nalgebra_glm as glm;
+
+use super::bounds::{Aabb, Ray};
+use super::bvh::Bvh;
+use super::camera::Camera;
+use super::framework::Context;
+use super::frustum::Frustum;
+use super::lighting::Light;
+use super::material::MaterialInstance;
+use super::mesh::Mesh;
+use super::shader::Shader;
+use super::state::RenderState;
+
+/// Local translation, rotation and scale of a node, applied in scale-rotate-translate order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: glm::Vec3,
+    pub rotation: glm::Quat,
+    pub scale: glm::Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            translation: glm::vec3(0.0, 0.0, 0.0),
+            rotation: glm::quat_identity(),
+            scale: glm::vec3(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Transform {
+    pub fn from_translation(translation: glm::Vec3) -> Transform {
+        Transform {
+            translation,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_rotation(self, rotation: glm::Quat) -> Transform {
+        Transform { rotation, ..self }
+    }
+
+    pub fn with_scale(self, scale: glm::Vec3) -> Transform {
+        Transform { scale, ..self }
+    }
+
+    pub fn matrix(&self) -> glm::Mat4 {
+        glm::translation(&self.translation)
+            * glm::quat_to_mat4(&self.rotation)
+            * glm::scaling(&self.scale)
+    }
+}
+
+/// Handle to a node; stays invalid after the node is removed, even if its slot is reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: usize,
+    generation: u32,
+}
+
+/// Nearest mesh a ray hits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pick {
+    pub node: NodeId,
+    /// `t` along the ray; the world-space distance for a unit direction.
+    pub distance: f32,
+    /// World-space point hit.
+    pub point: glm::Vec3,
+}
+
+/// A scene node: a transform in its parent's space plus optional components.
+pub struct Node {
+    name: String,
+    local: Transform,
+    world: glm::Mat4,
+    dirty: bool,
+    visible: bool,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    pub mesh: Option<Rc<Mesh>>,
+    /// Material the mesh is drawn with instead of the shader passed to `Scene::render`.
+    pub material: Option<MaterialInstance>,
+    pub light: Option<Light>,
+    /// Follows the node's world position and orientation after `Scene::update_transforms`.
+    pub camera: Option<Camera>,
+}
+
+#[allow(dead_code)]
+impl Node {
+    fn new(name: &str, parent: Option<NodeId>) -> Node {
+        Node {
+            name: name.to_string(),
+            local: Transform::default(),
+            world: glm::Mat4::identity(),
+            dirty: true,
+            visible: true,
+            parent,
+            children: Vec::new(),
+            mesh: None,
+            material: None,
+            light: None,
+            camera: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn transform(&self) -> &Transform {
+        &self.local
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.local = transform;
+        self.dirty = true;
+    }
+
+    /// World matrix as of the last `Scene::update_transforms`.
+    pub fn world(&self) -> &glm::Mat4 {
+        &self.world
+    }
+
+    pub fn world_position(&self) -> glm::Vec3 {
+        self.world.column(3).xyz()
+    }
+
+    /// Hiding a node also hides its descendants.
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+struct Slot {
+    generation: u32,
+    node: Option<Node>,
+}
+
+/// Node hierarchy with cached world matrices.
+///
+/// Changing a local transform only marks that node dirty; `update_transforms`
+/// recomputes it and everything below it on the next call.
+#[derive(Default)]
+pub struct Scene {
+    slots: Vec<Slot>,
+    free: Vec<usize>,
+    roots: Vec<NodeId>,
+    active_camera: Option<NodeId>,
+}
+
+#[allow(dead_code)]
+impl Scene {
+    pub fn new() -> Scene {
+        Default::default()
+    }
+
+    /// Adds a node at the top of the hierarchy.
+    pub fn add(&mut self, name: &str) -> NodeId {
+        let id = self.alloc(Node::new(name, None));
+        self.roots.push(id);
+        id
+    }
+
+    /// Adds a node under `parent`.
+    ///
+    /// # Panics
+    /// Panics if `parent` is not in the scene.
+    pub fn add_child(&mut self, parent: NodeId, name: &str) -> NodeId {
+        assert!(
+            self.contains(parent),
+            "Parent node {:?} does not exist",
+            parent
+        );
+        let id = self.alloc(Node::new(name, Some(parent)));
+        self.get_mut(parent).children.push(id);
+        id
+    }
+
+    /// Removes a node together with its descendants.
+    pub fn remove(&mut self, id: NodeId) {
+        if !self.contains(id) {
+            return;
+        }
+        self.detach(id);
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let slot = &mut self.slots[id.index];
+            if let Some(node) = slot.node.take() {
+                stack.extend(node.children);
+                slot.generation += 1;
+                self.free.push(id.index);
+            }
+            if self.active_camera == Some(id) {
+                self.active_camera = None;
+            }
+        }
+    }
+
+    /// Moves `id` under `parent`, or to the top level with `None`, keeping its local transform.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<(), String> {
+        if !self.contains(id) {
+            return Err(format!("Node {:?} does not exist", id));
+        }
+        if let Some(parent) = parent {
+            if !self.contains(parent) {
+                return Err(format!("Parent node {:?} does not exist", parent));
+            }
+            if self.ancestors(parent).any(|a| a == id) {
+                return Err(format!(
+                    "Cannot parent \"{}\" to its own descendant \"{}\"",
+                    self.get(id).name,
+                    self.get(parent).name
+                ));
+            }
+        }
+        self.detach(id);
+        match parent {
+            Some(parent) => self.get_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+        let node = self.get_mut(id);
+        node.parent = parent;
+        node.dirty = true;
+        Ok(())
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.node(id).is_some()
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&Node> {
+        self.slots
+            .get(id.index)
+            .filter(|s| s.generation == id.generation)
+            .and_then(|s| s.node.as_ref())
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.slots
+            .get_mut(id.index)
+            .filter(|s| s.generation == id.generation)
+            .and_then(|s| s.node.as_mut())
+    }
+
+    pub fn set_transform(&mut self, id: NodeId, transform: Transform) {
+        if let Some(node) = self.node_mut(id) {
+            node.set_transform(transform);
+        }
+    }
+
+    /// First node named `name`, in depth-first order.
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.depth_first()
+            .into_iter()
+            .find(|&id| self.get(id).name == name)
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.iter().filter(|s| s.node.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    /// Camera node used by `render`.
+    pub fn set_active_camera(&mut self, id: Option<NodeId>) {
+        self.active_camera = id;
+    }
+
+    pub fn active_camera(&self) -> Option<&Camera> {
+        self.active_camera
+            .and_then(|id| self.node(id))
+            .and_then(|n| n.camera.as_ref())
+    }
+
+    /// Recomputes world matrices of dirty nodes and their descendants.
+    /// Returns how many nodes were recomputed.
+    pub fn update_transforms(&mut self) -> usize {
+        let mut updated = 0;
+        let mut stack: Vec<(NodeId, glm::Mat4, bool)> = self
+            .roots
+            .iter()
+            .rev()
+            .map(|&id| (id, glm::Mat4::identity(), false))
+            .collect();
+        while let Some((id, parent_world, parent_changed)) = stack.pop() {
+            let node = self.get_mut(id);
+            let changed = node.dirty || parent_changed;
+            if changed {
+                node.world = parent_world * node.local.matrix();
+                node.dirty = false;
+                if let Some(camera) = node.camera.as_mut() {
+                    sync_camera(camera, &node.world);
+                }
+                updated += 1;
+            }
+            let world = node.world;
+            stack.extend(node.children.iter().rev().map(|&c| (c, world, changed)));
+        }
+        updated
+    }
+
+    /// All nodes in depth-first order, parents before children.
+    pub fn depth_first(&self) -> Vec<NodeId> {
+        self.walk(false)
+    }
+
+    /// Nodes that are visible along with all their ancestors, in depth-first order.
+    pub fn visible_nodes(&self) -> Vec<NodeId> {
+        self.walk(true)
+    }
+
+    /// World-space box around the node's mesh as of the last `update_transforms`, or `None`
+    /// when it has no mesh.
+    pub fn world_bounds(&self, id: NodeId) -> Option<Aabb> {
+        let node = self.node(id)?;
+        let mesh = node.mesh.as_ref()?;
+        Some(mesh.bounds().transformed(&node.world))
+    }
+
+    /// Visible nodes with a mesh whose world bounds `frustum` may see, in depth-first order.
+    pub fn visible_in(&self, frustum: &Frustum) -> Vec<NodeId> {
+        self.visible_nodes()
+            .into_iter()
+            .filter(|&id| {
+                self.world_bounds(id)
+                    .is_some_and(|b| frustum.intersects_aabb(&b))
+            })
+            .collect()
+    }
+
+    /// Hierarchy over the world bounds of the visible nodes with a mesh, for culling and
+    /// picking large scenes faster than `visible_in`. Call after `update_transforms`, and
+    /// build it again once nodes move, appear or change visibility.
+    pub fn build_bvh(&self) -> Bvh<NodeId> {
+        Bvh::new(
+            self.visible_nodes()
+                .into_iter()
+                .filter_map(|id| self.world_bounds(id).map(|b| (id, b)))
+                .collect(),
+        )
+    }
+
+    /// Nearest triangle of a visible mesh `ray`, in world space, hits as of the last
+    /// `update_transforms`. Tests every node's world bounds first.
+    pub fn raycast(&self, ray: &Ray) -> Option<Pick> {
+        let mut nearest: Option<(NodeId, f32)> = None;
+        for id in self.visible_nodes() {
+            let entry = match self.world_bounds(id).and_then(|b| ray.intersect_aabb(&b)) {
+                Some(t) => t,
+                None => continue,
+            };
+            if nearest.is_some_and(|(_, best)| entry >= best) {
+                continue;
+            }
+            if let Some(t) = self.raycast_node(id, ray) {
+                if nearest.map_or(true, |(_, best)| t < best) {
+                    nearest = Some((id, t));
+                }
+            }
+        }
+        nearest.map(|(node, t)| Pick {
+            node,
+            distance: t,
+            point: ray.at(t),
+        })
+    }
+
+    /// Like `raycast`, visiting only the nodes of `bvh` from `build_bvh` whose bounds the
+    /// ray enters, nearest first.
+    pub fn raycast_bvh(&self, bvh: &Bvh<NodeId>, ray: &Ray) -> Option<Pick> {
+        bvh.raycast(ray, |id| self.raycast_node(id, ray))
+            .map(|(node, t)| Pick {
+                node,
+                distance: t,
+                point: ray.at(t),
+            })
+    }
+
+    /// Lights of visible nodes with their world matrices.
+    pub fn lights(&self) -> Vec<(Light, glm::Mat4)> {
+        self.visible_nodes()
+            .into_iter()
+            .filter_map(|id| {
+                let node = self.get(id);
+                node.light.map(|light| (light, node.world))
+            })
+            .collect()
+    }
+
+    /// Draws every visible mesh in view of the active camera. Returns the number of draws.
+    ///
+    /// Each draw applies the node's material, or binds `shader` when it has none, and sets
+    /// `MVP` plus `Model` when the program uses it. A material's render state replaces the
+    /// mesh's; without one the mesh's applies.
+    pub fn render(&mut self, ctx: &Context, shader: &Shader) -> usize {
+        self.update_transforms();
+        let view_projection = match self.active_camera() {
+            Some(camera) => camera.view_projection(),
+            None => glm::Mat4::identity(),
+        };
+        self.render_with(ctx, shader, &view_projection)
+    }
+
+    /// Like `render`, with an explicit view-projection matrix. Meshes outside its frustum
+    /// are skipped.
+    pub fn render_with(
+        &self,
+        ctx: &Context,
+        shader: &Shader,
+        view_projection: &glm::Mat4,
+    ) -> usize {
+        let frustum = Frustum::from_matrix(view_projection);
+        self.render_nodes(ctx, shader, view_projection, &self.visible_in(&frustum))
+    }
+
+    /// Like `render_with`, for `nodes` in the order given, e.g. sorted back to front.
+    /// Removed nodes and nodes without a mesh are skipped.
+    pub fn render_nodes(
+        &self,
+        ctx: &Context,
+        shader: &Shader,
+        view_projection: &glm::Mat4,
+        nodes: &[NodeId],
+    ) -> usize {
+        self.draw_nodes(
+            &mut |state| ctx.apply_state(state),
+            shader,
+            view_projection,
+            nodes,
+        )
+    }
+
+    /// Draws every visible mesh inside the frustum of `view_projection` with `shader`,
+    /// ignoring materials, for passes that only need geometry such as shadow maps. Sets
+    /// `MVP` plus `Model` when the program uses it.
+    pub fn render_depth(
+        &self,
+        ctx: &Context,
+        shader: &Shader,
+        view_projection: &glm::Mat4,
+    ) -> usize {
+        shader.bind(ctx);
+        let with_model = shader.has_uniform("Model");
+        let mut draws = 0;
+        for id in self.visible_in(&Frustum::from_matrix(view_projection)) {
+            let node = self.get(id);
+            let mesh = match node.mesh {
+                Some(ref mesh) => mesh,
+                None => continue,
+            };
+            shader.set_mat4("MVP", &(view_projection * node.world));
+            if with_model {
+                shader.set_mat4("Model", &node.world);
+            }
+            mesh.draw(ctx);
+            draws += 1;
+        }
+        draws
+    }
+
+    // `render_nodes` with the state application passed in, so tests can run it without a
+    // window.
+    fn draw_nodes(
+        &self,
+        apply_state: &mut dyn FnMut(&RenderState),
+        shader: &Shader,
+        view_projection: &glm::Mat4,
+        nodes: &[NodeId],
+    ) -> usize {
+        let mut draws = 0;
+        for node in nodes.iter().filter_map(|&id| self.node(id)) {
+            let mesh = match node.mesh {
+                Some(ref mesh) => mesh,
+                None => continue,
+            };
+            let (program, state) = match node.material {
+                Some(ref material) => {
+                    material.upload();
+                    (&**material.shader(), material.render_state())
+                }
+                None => {
+                    shader.use_program();
+                    (shader, mesh.render_state())
+                }
+            };
+            apply_state(state);
+            program.set_mat4("MVP", &(view_projection * node.world));
+            if program.has_uniform("Model") {
+                program.set_mat4("Model", &node.world);
+            }
+            unsafe {
+                mesh.draw_elements();
+            }
+            draws += 1;
+        }
+        draws
+    }
+
+    fn alloc(&mut self, node: Node) -> NodeId {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index];
+                slot.node = Some(node);
+                NodeId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    node: Some(node),
+                });
+                NodeId {
+                    index: self.slots.len() - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    // Tests the node's mesh in model space; `t` carries over since the ray's direction is
+    // transformed without renormalizing.
+    fn raycast_node(&self, id: NodeId, ray: &Ray) -> Option<f32> {
+        let node = self.node(id)?;
+        let mesh = node.mesh.as_ref()?;
+        mesh.raycast(&ray.transformed(&node.world.try_inverse()?))
+    }
+
+    fn get(&self, id: NodeId) -> &Node {
+        self.node(id).expect("stale NodeId")
+    }
+
+    fn get_mut(&mut self, id: NodeId) -> &mut Node {
+        self.node_mut(id).expect("stale NodeId")
+    }
+
+    fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(Some(id), move |&id| self.get(id).parent)
+    }
+
+    // Unlinks `id` from its parent or the root list.
+    fn detach(&mut self, id: NodeId) {
+        match self.get(id).parent {
+            Some(parent) => self.get_mut(parent).children.retain(|&c| c != id),
+            None => self.roots.retain(|&r| r != id),
+        }
+    }
+
+    fn walk(&self, visible_only: bool) -> Vec<NodeId> {
+        let mut order = Vec::new();
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().cloned().collect();
+        while let Some(id) = stack.pop() {
+            let node = self.get(id);
+            if visible_only && !node.visible {
+                continue;
+            }
+            order.push(id);
+            stack.extend(node.children.iter().rev());
+        }
+        order
+    }
+}
+
+// Places the camera at the node's origin, looking down the node's -Z axis. Scale is ignored.
+fn sync_camera(camera: &mut Camera, world: &glm::Mat4) {
+    let axes = glm::Mat3::from_columns(&[
+        glm::normalize(&world.column(0).xyz()),
+        glm::normalize(&world.column(1).xyz()),
+        glm::normalize(&world.column(2).xyz()),
+    ]);
+    camera.set_position(world.column(3).xyz());
+    camera.set_orientation(glm::mat3_to_quat(&axes));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::bounds::Ray;
+    use crate::core::device::RecordingDevice;
+    use crate::core::geometry;
+    use crate::core::material::Material;
+    use crate::core::resource::ContextOwner;
+    use crate::core::state::StateCache;
+
+    fn assert_near(a: &glm::Vec3, b: &glm::Vec3) {
+        assert!(glm::distance(a, b) < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    fn translated(x: f32, y: f32, z: f32) -> Transform {
+        Transform::from_translation(glm::vec3(x, y, z))
+    }
+
+    #[test]
+    fn composes_trs() {
+        let t = translated(1.0, 2.0, 3.0)
+            .with_rotation(glm::quat_angle_axis(
+                std::f32::consts::FRAC_PI_2,
+                &glm::Vec3::z(),
+            ))
+            .with_scale(glm::vec3(2.0, 2.0, 2.0));
+        let p = t.matrix() * glm::vec4(1.0, 0.0, 0.0, 1.0);
+        // Scaled to (2, 0, 0), rotated to (0, 2, 0), then translated.
+        assert_near(&p.xyz(), &glm::vec3(1.0, 4.0, 3.0));
+    }
+
+    #[test]
+    fn propagates_world_matrices_down_the_hierarchy() {
+        let mut scene = Scene::new();
+        let root = scene.add("root");
+        let arm = scene.add_child(root, "arm");
+        let hand = scene.add_child(arm, "hand");
+        scene.set_transform(
+            root,
+            translated(10.0, 0.0, 0.0).with_scale(glm::vec3(2.0, 2.0, 2.0)),
+        );
+        scene.set_transform(
+            arm,
+            translated(0.0, 1.0, 0.0)
+                .with_rotation(glm::quat_angle_axis(std::f32::consts::PI, &glm::Vec3::y())),
+        );
+        scene.set_transform(hand, translated(1.0, 0.0, 0.0));
+
+        assert_eq!(scene.update_transforms(), 3);
+        let pos = |id| scene.node(id).unwrap().world_position();
+        assert_near(&pos(root), &glm::vec3(10.0, 0.0, 0.0));
+        assert_near(&pos(arm), &glm::vec3(10.0, 2.0, 0.0));
+        // The arm faces backwards, so the hand ends up on the root's -X side.
+        assert_near(&pos(hand), &glm::vec3(8.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn recomputes_only_dirty_subtrees() {
+        let mut scene = Scene::new();
+        let a = scene.add("a");
+        let a1 = scene.add_child(a, "a1");
+        let a2 = scene.add_child(a1, "a2");
+        let b = scene.add("b");
+        scene.update_transforms();
+        assert_eq!(scene.update_transforms(), 0);
+
+        scene.set_transform(a1, translated(0.0, 5.0, 0.0));
+        assert_eq!(scene.update_transforms(), 2);
+        assert_near(
+            &scene.node(a2).unwrap().world_position(),
+            &glm::vec3(0.0, 5.0, 0.0),
+        );
+
+        scene.set_transform(b, translated(1.0, 0.0, 0.0));
+        assert_eq!(scene.update_transforms(), 1);
+        scene.set_transform(a, translated(0.0, 0.0, 1.0));
+        assert_eq!(scene.update_transforms(), 3);
+        assert_near(
+            &scene.node(a2).unwrap().world_position(),
+            &glm::vec3(0.0, 5.0, 1.0),
+        );
+    }
+
+    #[test]
+    fn reparenting_moves_into_the_new_parent_space() {
+        let mut scene = Scene::new();
+        let a = scene.add("a");
+        let b = scene.add("b");
+        let child = scene.add_child(a, "child");
+        scene.set_transform(a, translated(1.0, 0.0, 0.0));
+        scene.set_transform(b, translated(0.0, 0.0, -4.0));
+        scene.set_transform(child, translated(0.0, 1.0, 0.0));
+        scene.update_transforms();
+
+        scene.set_parent(child, Some(b)).unwrap();
+        assert_eq!(scene.node(a).unwrap().children(), &[] as &[NodeId]);
+        assert_eq!(scene.node(child).unwrap().parent(), Some(b));
+        assert_eq!(scene.update_transforms(), 1);
+        assert_near(
+            &scene.node(child).unwrap().world_position(),
+            &glm::vec3(0.0, 1.0, -4.0),
+        );
+
+        scene.set_parent(child, None).unwrap();
+        assert_eq!(scene.roots(), &[a, b, child]);
+        scene.update_transforms();
+        assert_near(
+            &scene.node(child).unwrap().world_position(),
+            &glm::vec3(0.0, 1.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn rejects_cycles_and_stale_ids() {
+        let mut scene = Scene::new();
+        let a = scene.add("a");
+        let b = scene.add_child(a, "b");
+        let c = scene.add_child(b, "c");
+        assert!(scene.set_parent(a, Some(c)).is_err());
+        assert!(scene.set_parent(a, Some(a)).is_err());
+
+        scene.remove(b);
+        assert!(!scene.contains(b) && !scene.contains(c));
+        assert_eq!(scene.len(), 1);
+        assert!(scene.node(a).unwrap().children().is_empty());
+
+        // The freed slot is reused without reviving the old handle.
+        let d = scene.add("d");
+        assert!(scene.contains(d));
+        assert!(!scene.contains(b) && !scene.contains(c));
+        assert!(scene.set_parent(d, Some(c)).is_err());
+    }
+
+    #[test]
+    fn hidden_nodes_hide_their_subtree() {
+        let mut scene = Scene::new();
+        let a = scene.add("a");
+        let b = scene.add_child(a, "b");
+        let c = scene.add_child(b, "c");
+        let d = scene.add_child(a, "d");
+        assert_eq!(scene.visible_nodes(), vec![a, b, c, d]);
+        assert_eq!(scene.find("d"), Some(d));
+
+        scene.node_mut(b).unwrap().set_visible(false);
+        assert_eq!(scene.visible_nodes(), vec![a, d]);
+        assert_eq!(scene.depth_first(), vec![a, b, c, d]);
+    }
+
+    #[test]
+    fn cameras_and_lights_follow_their_nodes() {
+        let mut scene = Scene::new();
+        let rig = scene.add("rig");
+        let eye = scene.add_child(rig, "eye");
+        scene.node_mut(eye).unwrap().camera = Some(Camera::perspective(60.0, 0.1, 100.0, 800, 600));
+        scene.set_active_camera(Some(eye));
+        scene.set_transform(
+            rig,
+            translated(0.0, 0.0, 5.0)
+                .with_rotation(glm::quat_angle_axis(
+                    std::f32::consts::FRAC_PI_2,
+                    &glm::Vec3::y(),
+                ))
+                .with_scale(glm::vec3(3.0, 3.0, 3.0)),
+        );
+        scene.update_transforms();
+
+        let camera = scene.active_camera().unwrap();
+        assert_near(&camera.position(), &glm::vec3(0.0, 0.0, 5.0));
+        // Turned left by 90 degrees, so -Z now points down -X.
+        assert_near(&camera.forward(), &glm::vec3(-1.0, 0.0, 0.0));
+
+        let sun = scene.add_child(rig, "sun");
+        scene.node_mut(sun).unwrap().light = Some(Light::Directional {
+            color: glm::vec3(1.0, 1.0, 1.0),
+            intensity: 2.0,
+        });
+        scene.update_transforms();
+        assert_eq!(scene.lights().len(), 1);
+        scene.node_mut(rig).unwrap().set_visible(false);
+        assert!(scene.lights().is_empty());
+    }
+
+    #[test]
+    fn culls_and_picks_meshes_by_their_world_bounds() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let cube = Rc::new(
+            geometry::cube(1.0, 1)
+                .build(RenderState::default())
+                .unwrap(),
+        );
+
+        // A row of unit cubes along X, five units in front of a camera looking down -Z.
+        let mut scene = Scene::new();
+        let row = scene.add("row");
+        scene.set_transform(row, translated(0.0, 0.0, -5.0));
+        let cubes: Vec<NodeId> = (0..10)
+            .map(|i| {
+                let id = scene.add_child(row, &format!("cube {}", i));
+                scene.set_transform(
+                    id,
+                    translated(i as f32 * 3.0, 0.0, 0.0).with_scale(glm::vec3(2.0, 2.0, 2.0)),
+                );
+                scene.node_mut(id).unwrap().mesh = Some(cube.clone());
+                id
+            })
+            .collect();
+        scene.node_mut(cubes[1]).unwrap().set_visible(false);
+        scene.update_transforms();
+
+        let bounds = scene.world_bounds(cubes[2]).unwrap();
+        assert_near(&bounds.min, &glm::vec3(5.0, -1.0, -6.0));
+        assert_near(&bounds.max, &glm::vec3(7.0, 1.0, -4.0));
+        assert_eq!(scene.world_bounds(row), None);
+
+        // A 90 degree frustum sees five units to either side at that distance.
+        let projection = glm::perspective(1.0, std::f32::consts::FRAC_PI_2, 0.1, 100.0);
+        let frustum = Frustum::from_matrix(&projection);
+        assert_eq!(scene.visible_in(&frustum), vec![cubes[0], cubes[2]]);
+        let bvh = scene.build_bvh();
+        assert_eq!(bvh.len(), 9);
+        let mut culled = bvh.cull(&frustum);
+        culled.sort_by_key(|id| id.index);
+        assert_eq!(culled, vec![cubes[0], cubes[2]]);
+
+        // Looking along the row hits the nearest cube first, skipping the hidden one.
+        let ray = Ray::new(glm::vec3(-10.0, 0.0, -5.0), glm::vec3(1.0, 0.0, 0.0));
+        let hit = bvh.raycast(&ray, |id| ray.intersect_aabb(&scene.world_bounds(id)?));
+        assert_eq!(hit.map(|h| h.0), Some(cubes[0]));
+        let ray = Ray::new(glm::vec3(2.0, 0.0, -5.0), glm::vec3(1.0, 0.0, 0.0));
+        let hit = bvh.raycast(&ray, |id| ray.intersect_aabb(&scene.world_bounds(id)?));
+        assert_eq!(hit, Some((cubes[2], 3.0)));
+
+        // Against the triangles, with and without the hierarchy.
+        let ray = Ray::new(glm::vec3(6.5, 0.5, 0.0), glm::vec3(0.0, 0.0, -1.0));
+        let pick = scene.raycast(&ray).unwrap();
+        assert_eq!(pick.node, cubes[2]);
+        assert!((pick.distance - 4.0).abs() < 1e-5);
+        assert_near(&pick.point, &glm::vec3(6.5, 0.5, -4.0));
+        assert_eq!(scene.raycast_bvh(&bvh, &ray), Some(pick));
+        let between = Ray::new(glm::vec3(7.5, 0.0, 0.0), glm::vec3(0.0, 0.0, -1.0));
+        assert_eq!(scene.raycast(&between), None);
+        assert_eq!(scene.raycast_bvh(&bvh, &between), None);
+    }
+
+    #[test]
+    fn material_state_replaces_the_mesh_state() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let shader =
+            Rc::new(Shader::new("res/glsl/basic.vs.glsl", "res/glsl/basic.fs.glsl").unwrap());
+        let mut glass = Material::new(shader.clone());
+        glass.set_render_state(RenderState::transparent());
+        let cube = Rc::new(geometry::cube(1.0, 1).build(RenderState::opaque()).unwrap());
+
+        let mut scene = Scene::new();
+        let plain = scene.add("plain");
+        scene.node_mut(plain).unwrap().mesh = Some(cube.clone());
+        let blended = scene.add("blended");
+        let node = scene.node_mut(blended).unwrap();
+        node.mesh = Some(cube);
+        node.material = Some(MaterialInstance::new(Rc::new(glass)));
+        scene.update_transforms();
+
+        let mut cache = StateCache::new();
+        let mut apply = |state: &RenderState| cache.apply(&device, state);
+        let identity = glm::Mat4::identity();
+        device.take();
+        assert_eq!(
+            scene.draw_nodes(&mut apply, &shader, &identity, &[plain]),
+            1
+        );
+        let calls = device.take();
+        assert!(calls.contains(&"Disable(Blend)".to_string()), "{:?}", calls);
+        assert!(
+            calls.contains(&"Enable(CullFace)".to_string()),
+            "{:?}",
+            calls
+        );
+
+        assert_eq!(
+            scene.draw_nodes(&mut apply, &shader, &identity, &[blended]),
+            1
+        );
+        let calls = device.take();
+        assert!(calls.contains(&"Enable(Blend)".to_string()), "{:?}", calls);
+        assert!(
+            calls.contains(&"DepthMask(false)".to_string()),
+            "{:?}",
+            calls
+        );
+        assert!(
+            calls.contains(&"Disable(CullFace)".to_string()),
+            "{:?}",
+            calls
+        );
+    }
+}
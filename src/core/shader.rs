@@ -1,29 +1,35 @@
 extern crate nalgebra_glm as glm;
 
-use std::ffi::CString;
 use std::fs::File;
 use std::io::Read;
-use std::str;
 
 use gl::types::*;
 
+use super::framework::Context;
+use super::resource::{self, ContextHandle, ResourceKind};
+use super::state::RenderState;
+
 pub struct Shader {
     id: u32,
+    ctx: ContextHandle,
+    render_state: RenderState,
 }
 
 impl Drop for Shader {
     // Deletes the Shader program.
     fn drop(&mut self) {
-        unsafe {
-            gl::DeleteProgram(self.id);
+        // Programs that failed to compile were never created.
+        if self.id != 0 {
+            self.ctx.release(ResourceKind::Shader, self.id);
         }
-        println!("Delete Shader Program: {}", self.id);
     }
 }
 
 #[allow(dead_code)]
 impl Shader {
     pub fn new(vert_path: &str, frag_path: &str) -> Result<Shader, String> {
+        let ctx = ContextHandle::current()?;
+
         // Vertex
         let mut vert_file =
             File::open(vert_path).unwrap_or_else(|_| panic!("failed to open file: {}", vert_path));
@@ -40,111 +46,78 @@ impl Shader {
             .read_to_string(&mut frag_code)
             .expect("failed to read fragment shader file");
 
-        let successful: bool;
-        let mut shader = Shader { id: 0 };
-        unsafe {
-            // Compile
-            let vert = shader.compile(vert_code.as_str(), gl::VERTEX_SHADER)?;
-            let frag = shader.compile(frag_code.as_str(), gl::FRAGMENT_SHADER)?;
-
-            // Link
-            let id = gl::CreateProgram();
-            gl::AttachShader(id, vert);
-            gl::AttachShader(id, frag);
-            gl::LinkProgram(id);
-            successful = {
-                let mut res: GLint = 0;
-                gl::GetProgramiv(id, gl::LINK_STATUS, &mut res);
-                res != 0
-            };
-
-            gl::DeleteShader(vert);
-            gl::DeleteShader(frag);
-
-            shader.id = id;
-        }
+        let mut shader = Shader {
+            id: 0,
+            ctx,
+            render_state: Default::default(),
+        };
+        let device = shader.ctx.device();
+
+        // Compile
+        let vert = device.compile_shader(gl::VERTEX_SHADER, &vert_code)?;
+        let frag = match device.compile_shader(gl::FRAGMENT_SHADER, &frag_code) {
+            Ok(frag) => frag,
+            Err(log) => {
+                device.delete_shader(vert);
+                return Err(log);
+            }
+        };
 
-        if successful {
-            Ok(shader)
-        } else {
-            Err(shader.program_log())
-        }
-    }
+        // Link
+        let id = device.create_program();
+        shader.ctx.created(ResourceKind::Shader, id);
+        device.attach_shader(id, vert);
+        device.attach_shader(id, frag);
+        let linked = device.link_program(id);
 
-    fn compile(&self, source: &str, shader_type: GLuint) -> Result<u32, String> {
-        let id = unsafe { gl::CreateShader(shader_type) };
-        unsafe {
-            let ptr: *const u8 = source.as_bytes().as_ptr();
-            let ptr_i8: *const i8 = std::mem::transmute(ptr);
-            let len = source.len() as GLint;
-            gl::ShaderSource(id, 1, &ptr_i8, &len);
-            gl::CompileShader(id);
-        }
+        device.delete_shader(vert);
+        device.delete_shader(frag);
 
-        let successful = unsafe {
-            let mut res: GLint = 0;
-            gl::GetShaderiv(id, gl::COMPILE_STATUS, &mut res);
-            res != 0
-        };
-        if successful {
-            Ok(id)
-        } else {
-            Err(self.shader_log())
-        }
+        shader.id = id;
+        linked?;
+        shader.set_label(&format!("{} + {}", vert_path, frag_path));
+        Ok(shader)
     }
 
-    fn shader_log(&self) -> String {
-        let mut len = 0;
-        unsafe {
-            gl::GetShaderiv(self.id, gl::INFO_LOG_LENGTH, &mut len);
-        }
-        assert!(len > 0);
+    /// Names the object in debug messages and graphics debuggers.
+    pub fn set_label(&self, label: &str) {
+        self.ctx.device().object_label(gl::PROGRAM, self.id, label);
+        resource::track_label(ResourceKind::Shader, self.id, label);
+    }
 
-        let mut buf = Vec::with_capacity(len as usize);
-        let buf_ptr = buf.as_mut_ptr() as *mut gl::types::GLchar;
-        unsafe {
-            gl::GetShaderInfoLog(self.id, len, std::ptr::null_mut(), buf_ptr);
-            buf.set_len(len as usize);
-        };
+    pub fn use_program(&self) {
+        self.ctx.device().use_program(self.id);
+    }
 
-        match String::from_utf8(buf) {
-            Ok(log) => log,
-            Err(vec) => panic!("Could not convert log from buffer: {}", vec),
-        }
+    /// Applies the state this program expects through `ctx` and makes it current.
+    pub fn bind(&self, ctx: &Context) {
+        ctx.apply_state(&self.render_state);
+        self.use_program();
     }
 
-    fn program_log(&self) -> String {
-        let mut len = 0;
-        unsafe {
-            gl::GetProgramiv(self.id, gl::INFO_LOG_LENGTH, &mut len);
-        }
-        assert!(len > 0);
+    pub fn render_state(&self) -> &RenderState {
+        &self.render_state
+    }
 
-        let mut buf = Vec::with_capacity(len as usize);
-        let buf_ptr = buf.as_mut_ptr() as *mut gl::types::GLchar;
-        unsafe {
-            gl::GetProgramInfoLog(self.id, len, std::ptr::null_mut(), buf_ptr);
-            buf.set_len(len as usize);
-        };
+    pub fn set_render_state(&mut self, state: RenderState) {
+        self.render_state = state;
+    }
 
-        match String::from_utf8(buf) {
-            Ok(log) => log,
-            Err(vec) => panic!("Could not convert log from buffer: {}", vec),
-        }
+    fn get_uniform_location_option(&self, name: &str) -> Option<Uniform> {
+        self.ctx
+            .device()
+            .uniform_location(self.id, name)
+            .map(|loc| Uniform { id: loc })
     }
 
-    pub fn use_program(&self) {
-        unsafe {
-            gl::UseProgram(self.id);
-        }
+    /// GL type of the active uniform `name`, e.g. `gl::FLOAT`.
+    pub fn uniform_type(&self, name: &str) -> Option<GLenum> {
+        self.ctx.device().uniform_type(self.id, name)
     }
 
-    fn get_uniform_location_option(&self, name: &str) -> Option<Uniform> {
-        let c_name = CString::new(name).unwrap();
-        match unsafe { gl::GetUniformLocation(self.id, c_name.as_ptr()) } {
-            -1 => None,
-            loc => Some(Uniform { id: loc }),
-        }
+    /// Whether the program has an active uniform called `name`.
+    pub fn has_uniform(&self, name: &str) -> bool {
+        self.get_uniform_location_option(name).is_some()
     }
 
     fn get_uniform_location(&self, name: &str) -> Uniform {
@@ -154,25 +127,49 @@ impl Shader {
         }
     }
 
+    /// Reads the uniform block `name` from buffer binding point `binding`.
+    pub fn bind_uniform_block(&self, name: &str, binding: u32) -> Result<(), String> {
+        if self.ctx.device().uniform_block_binding(self.id, name, binding) {
+            Ok(())
+        } else {
+            Err(format!("Could not find uniform block \"{}\"", name))
+        }
+    }
+
+    pub fn set_float(&self, name: &str, f: f32) {
+        let uni = self.get_uniform_location(name);
+        self.ctx.device().uniform_float(uni.id, f);
+    }
+
+    pub fn set_vec2(&self, name: &str, vec2: &glm::Vec2) {
+        let uni = self.get_uniform_location(name);
+        self.ctx.device().uniform_vec2(uni.id, &[vec2.x, vec2.y]);
+    }
+
     pub fn set_vec3(&self, name: &str, vec3: &glm::Vec3) {
         let uni = self.get_uniform_location(name);
-        unsafe {
-            gl::Uniform3fv(uni.id, 1, vec3.as_ptr());
-        }
+        self.ctx
+            .device()
+            .uniform_vec3(uni.id, &[vec3.x, vec3.y, vec3.z]);
+    }
+
+    pub fn set_vec4(&self, name: &str, vec4: &glm::Vec4) {
+        let uni = self.get_uniform_location(name);
+        self.ctx
+            .device()
+            .uniform_vec4(uni.id, &[vec4.x, vec4.y, vec4.z, vec4.w]);
     }
 
     pub fn set_mat4(&self, name: &str, mat4: &glm::Mat4) {
         let uni = self.get_uniform_location(name);
-        unsafe {
-            gl::UniformMatrix4fv(uni.id, 1, gl::FALSE, mat4.as_ptr());
-        }
+        let mut m = [0.0; 16];
+        m.copy_from_slice(mat4.as_slice());
+        self.ctx.device().uniform_mat4(uni.id, &m);
     }
 
     pub fn set_int(&self, name: &str, i: i32) {
         let uni = self.get_uniform_location(name);
-        unsafe {
-            gl::Uniform1i(uni.id, i);
-        }
+        self.ctx.device().uniform_int(uni.id, i);
     }
 
     pub fn set_texture(&self, name: &str, tex: i32) {
@@ -0,0 +1,575 @@
+use std::f32::consts::FRAC_PI_2;
+
+use nalgebra_glm as glm;
+
+use super::camera::{Camera, Projection};
+use super::framebuffer::Framebuffer;
+use super::framework::Context;
+use super::ibl;
+use super::scene::Scene;
+use super::shader::Shader;
+use super::state::{Rect, RenderState};
+use super::texture::{Texture2D, TextureCube};
+
+/// Most cascades `res/glsl/phong.fs.glsl` samples.
+pub const MAX_CASCADES: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CascadeSettings {
+    /// Number of cascades, clamped to `1..=MAX_CASCADES`.
+    pub count: usize,
+    /// Blend between uniform (0) and logarithmic (1) split distances.
+    pub lambda: f32,
+    /// Shadows end here or at the camera's far plane, whichever is nearer.
+    pub max_distance: f32,
+    /// Pixels along each side of one cascade.
+    pub resolution: u32,
+    /// How far towards the light casters outside a cascade still cast into it.
+    pub caster_distance: f32,
+}
+
+impl Default for CascadeSettings {
+    fn default() -> Self {
+        CascadeSettings {
+            count: 4,
+            lambda: 0.75,
+            max_distance: 40.0,
+            resolution: 1024,
+            caster_distance: 20.0,
+        }
+    }
+}
+
+/// One slice of the camera frustum and the light space it is shadowed in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cascade {
+    /// Camera distance the cascade ends at.
+    pub split: f32,
+    /// World to light clip space.
+    pub view_projection: glm::Mat4,
+    /// World size of one shadow map texel, for normal offset biasing.
+    pub texel_size: f32,
+}
+
+/// Far distance of each of `count` cascades between `near` and `far`, using the practical
+/// split scheme: `lambda` blends logarithmic splits, which keep texel density even, with
+/// uniform ones, which keep near cascades from becoming tiny.
+pub fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+    (1..=count)
+        .map(|i| {
+            if i == count {
+                return far;
+            }
+            let p = i as f32 / count as f32;
+            let log = near * (far / near).powf(p);
+            let uniform = near + (far - near) * p;
+            lambda * log + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+/// World space corners of the camera frustum between distances `near` and `far` along its
+/// view direction, near corners first.
+pub fn frustum_slice(camera: &Camera, near: f32, far: f32) -> [glm::Vec3; 8] {
+    let (position, forward) = (camera.position(), camera.forward());
+    let (right, up) = (camera.right(), camera.up());
+    let half_height = |distance: f32| match camera.projection_params() {
+        Projection::Perspective { fovy, .. } => distance * (fovy * 0.5).tan(),
+        Projection::Orthographic { height, .. } => height * 0.5,
+    };
+    let mut corners = [glm::Vec3::zeros(); 8];
+    for (i, &distance) in [near, far].iter().enumerate() {
+        let hh = half_height(distance);
+        let hw = hh * camera.aspect();
+        let center = position + forward * distance;
+        for (j, (sx, sy)) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+            .iter()
+            .enumerate()
+        {
+            corners[i * 4 + j] = center + right * (hw * sx) + up * (hh * sy);
+        }
+    }
+    corners
+}
+
+/// Orthographic light view projection covering `corners` for a light shining along
+/// `direction`, and the world size of one of `resolution` texels.
+///
+/// The projection bounds the corners' sphere instead of the corners themselves, so its size
+/// does not change as the camera turns, and moves in whole texels, so shadow edges do not
+/// shimmer as the camera moves.
+pub fn fit_cascade(
+    corners: &[glm::Vec3; 8],
+    direction: &glm::Vec3,
+    resolution: u32,
+    caster_distance: f32,
+) -> (glm::Mat4, f32) {
+    let center = corners.iter().fold(glm::Vec3::zeros(), |sum, c| sum + c) / 8.0;
+    let radius = corners
+        .iter()
+        .map(|c| glm::distance(c, &center))
+        .fold(0.0f32, f32::max);
+    // Rounding keeps float noise in the radius from resizing the texels.
+    let radius = (radius * 16.0).ceil() / 16.0;
+    // A texel of border on each side absorbs the snapping below.
+    let texel_size = 2.0 * radius / (resolution.max(3) - 2) as f32;
+    let half = radius + texel_size;
+
+    let direction = glm::normalize(direction);
+    let up = if direction.y.abs() > 0.99 {
+        glm::Vec3::z()
+    } else {
+        glm::Vec3::y()
+    };
+    // A view through the origin only rotates, so snapping in its space snaps the world.
+    let view = glm::look_at(&glm::Vec3::zeros(), &direction, &up);
+    let light_center = (view * glm::vec4(center.x, center.y, center.z, 1.0)).xyz();
+    let x = (light_center.x / texel_size).floor() * texel_size;
+    let y = (light_center.y / texel_size).floor() * texel_size;
+    let projection = glm::ortho(
+        x - half,
+        x + half,
+        y - half,
+        y + half,
+        -light_center.z - radius - caster_distance,
+        -light_center.z + radius,
+    );
+    (projection * view, texel_size)
+}
+
+/// Cascades covering `camera`'s view out to `settings.max_distance`, shadowed by a
+/// directional light shining along `direction`.
+pub fn compute_cascades(
+    camera: &Camera,
+    direction: &glm::Vec3,
+    settings: &CascadeSettings,
+) -> Vec<Cascade> {
+    let (near, far) = match camera.projection_params() {
+        Projection::Perspective { near, far, .. } => (near, far),
+        Projection::Orthographic { near, far, .. } => (near, far),
+    };
+    let far = far.min(settings.max_distance);
+    let count = settings.count.clamp(1, MAX_CASCADES);
+    let mut start = near;
+    cascade_splits(near, far, count, settings.lambda)
+        .into_iter()
+        .map(|split| {
+            let corners = frustum_slice(camera, start, split);
+            let (view_projection, texel_size) = fit_cascade(
+                &corners,
+                direction,
+                settings.resolution,
+                settings.caster_distance,
+            );
+            start = split;
+            Cascade {
+                split,
+                view_projection,
+                texel_size,
+            }
+        })
+        .collect()
+}
+
+/// Maps light clip space to texture coordinates and depth in tile `index` of an atlas of
+/// `count` tiles laid out left to right.
+pub fn atlas_matrix(index: usize, count: usize) -> glm::Mat4 {
+    let tile = 1.0 / count as f32;
+    glm::translation(&glm::vec3((index as f32 + 0.5) * tile, 0.5, 0.5))
+        * glm::scaling(&glm::vec3(0.5 * tile, 0.5, 0.5))
+}
+
+/// 90 degree projection from a point light onto one cube face.
+pub fn point_light_projection(near: f32, far: f32) -> glm::Mat4 {
+    glm::perspective(1.0, FRAC_PI_2, near, far)
+}
+
+/// Views from `position` through each face of `TextureCube::FACES`.
+pub fn point_light_views(position: &glm::Vec3) -> [glm::Mat4; 6] {
+    let translation = glm::translation(&-position);
+    let mut views = ibl::capture_views();
+    for view in views.iter_mut() {
+        *view *= translation;
+    }
+    views
+}
+
+/// Cascaded shadow maps for one directional light, rendered side by side into a depth atlas.
+pub struct CascadedShadowMap {
+    settings: CascadeSettings,
+    depth: Texture2D,
+    fbo: Framebuffer,
+    shader: Shader,
+    cascades: Vec<Cascade>,
+}
+
+#[allow(dead_code)]
+impl CascadedShadowMap {
+    /// Texture unit `bind` uses.
+    pub const UNIT: u32 = 6;
+
+    pub fn new(settings: CascadeSettings) -> Result<CascadedShadowMap, String> {
+        let settings = CascadeSettings {
+            count: settings.count.clamp(1, MAX_CASCADES),
+            ..settings
+        };
+        let width = settings.resolution * settings.count as u32;
+        let depth = Texture2D::depth(width, settings.resolution)?;
+        depth.set_label("cascaded shadow map");
+        let fbo = Framebuffer::new(width, settings.resolution)?;
+        fbo.attach_texture(gl::DEPTH_ATTACHMENT, &depth, 0);
+        fbo.draw_buffers(&[]);
+        fbo.check()?;
+        let shader = Shader::new(
+            "res/glsl/shadow_depth.vs.glsl",
+            "res/glsl/shadow_depth.fs.glsl",
+        )?;
+        Ok(CascadedShadowMap {
+            settings,
+            depth,
+            fbo,
+            shader,
+            cascades: Vec::new(),
+        })
+    }
+
+    pub fn settings(&self) -> &CascadeSettings {
+        &self.settings
+    }
+
+    /// Cascades of the last `fit`.
+    pub fn cascades(&self) -> &[Cascade] {
+        &self.cascades
+    }
+
+    pub fn depth(&self) -> &Texture2D {
+        &self.depth
+    }
+
+    /// Fits the cascades to `camera` for a light shining along `direction`. Call whenever
+    /// either moves, before `render`.
+    pub fn fit(&mut self, camera: &Camera, direction: &glm::Vec3) {
+        self.cascades = compute_cascades(camera, direction, &self.settings);
+    }
+
+    /// Renders `scene` into the cascades of the last `fit`. Leaves the window framebuffer
+    /// and viewport current.
+    pub fn render(&self, ctx: &Context, scene: &Scene) {
+        let resolution = self.settings.resolution;
+        self.fbo.bind();
+        // Clears honor the depth mask.
+        ctx.apply_state(&RenderState::opaque());
+        ctx.clear_buffer(gl::DEPTH_BUFFER_BIT);
+        for (i, cascade) in self.cascades.iter().enumerate() {
+            ctx.set_viewport_rect(Rect {
+                x: (i as u32 * resolution) as i32,
+                y: 0,
+                width: resolution as i32,
+                height: resolution as i32,
+            });
+            scene.render_depth(ctx, &self.shader, &cascade.view_projection);
+        }
+        self.fbo.unbind();
+        ctx.set_viewport();
+    }
+
+    /// Points the shader's `ShadowMap` sampler at `UNIT`. Leaves the shader current.
+    pub fn attach(&self, shader: &Shader) {
+        shader.use_program();
+        shader.set_texture("ShadowMap", CascadedShadowMap::UNIT as i32);
+    }
+
+    /// Uploads the cascades of the last `fit` for the light at `light_index` of the
+    /// `Lighting` block, as seen from a camera with `view`. Leaves the shader current.
+    pub fn upload(&self, shader: &Shader, view: &glm::Mat4, light_index: i32) {
+        shader.use_program();
+        let count = self.cascades.len();
+        let mut splits = [0.0; MAX_CASCADES];
+        let mut texels = [0.0; MAX_CASCADES];
+        for (i, cascade) in self.cascades.iter().enumerate() {
+            splits[i] = cascade.split;
+            texels[i] = cascade.texel_size;
+            let matrix = atlas_matrix(i, count) * cascade.view_projection;
+            shader.set_mat4(&format!("ShadowMatrices[{}]", i), &matrix);
+        }
+        shader.set_vec4("CascadeSplits", &glm::make_vec4(&splits));
+        shader.set_vec4("CascadeTexels", &glm::make_vec4(&texels));
+        shader.set_int("CascadeCount", count as i32);
+        shader.set_int("CascadeLight", light_index);
+        shader.set_mat4("CameraView", view);
+    }
+
+    pub fn bind(&self) {
+        self.depth.bind(CascadedShadowMap::UNIT);
+    }
+}
+
+/// Omnidirectional shadow map for one point light. Each face stores the distance to the
+/// light divided by the far plane.
+pub struct PointShadowMap {
+    cube: TextureCube,
+    fbo: Framebuffer,
+    shader: Shader,
+    near: f32,
+    far: f32,
+    position: glm::Vec3,
+}
+
+#[allow(dead_code)]
+impl PointShadowMap {
+    /// Texture unit `bind` uses.
+    pub const UNIT: u32 = 7;
+
+    /// `size` pixel faces covering casters up to `far` from the light.
+    pub fn new(size: u32, far: f32) -> Result<PointShadowMap, String> {
+        let cube = TextureCube::new(size, gl::DEPTH_COMPONENT24, 1)?;
+        cube.set_label("point shadow map");
+        let fbo = Framebuffer::new(size, size)?;
+        fbo.attach_cube_face(gl::DEPTH_ATTACHMENT, &cube, 0, 0);
+        fbo.draw_buffers(&[]);
+        fbo.check()?;
+        let shader = Shader::new(
+            "res/glsl/point_shadow.vs.glsl",
+            "res/glsl/point_shadow.fs.glsl",
+        )?;
+        Ok(PointShadowMap {
+            cube,
+            fbo,
+            shader,
+            near: 0.05,
+            far,
+            position: glm::Vec3::zeros(),
+        })
+    }
+
+    pub fn cube(&self) -> &TextureCube {
+        &self.cube
+    }
+
+    pub fn far(&self) -> f32 {
+        self.far
+    }
+
+    pub fn position(&self) -> glm::Vec3 {
+        self.position
+    }
+
+    /// Moves the light the map is rendered from.
+    pub fn set_position(&mut self, position: glm::Vec3) {
+        self.position = position;
+    }
+
+    /// Renders `scene` into every face as seen from the light. Leaves the window framebuffer
+    /// and viewport current.
+    pub fn render(&self, ctx: &Context, scene: &Scene) {
+        self.shader.use_program();
+        self.shader.set_vec3("LightPosition", &self.position);
+        self.shader.set_float("FarPlane", self.far);
+
+        let size = self.cube.size();
+        ctx.set_viewport_size(size, size);
+        ctx.apply_state(&RenderState::opaque());
+        let projection = point_light_projection(self.near, self.far);
+        for (face, view) in point_light_views(&self.position).iter().enumerate() {
+            self.fbo
+                .attach_cube_face(gl::DEPTH_ATTACHMENT, &self.cube, face, 0);
+            self.fbo.bind();
+            ctx.clear_buffer(gl::DEPTH_BUFFER_BIT);
+            scene.render_depth(ctx, &self.shader, &(projection * view));
+        }
+        self.fbo.unbind();
+        ctx.set_viewport();
+    }
+
+    /// Points the shader's `PointShadowMap` sampler at `UNIT`. Leaves the shader current.
+    pub fn attach(&self, shader: &Shader) {
+        shader.use_program();
+        shader.set_texture("PointShadowMap", PointShadowMap::UNIT as i32);
+    }
+
+    /// Uploads the light position, at `light_index` of the `Lighting` block.
+    /// Leaves the shader current.
+    pub fn upload(&self, shader: &Shader, light_index: i32) {
+        shader.use_program();
+        shader.set_vec3("PointShadowPosition", &self.position);
+        shader.set_float("PointShadowFar", self.far);
+        shader.set_int("PointShadowLight", light_index);
+    }
+
+    pub fn bind(&self) {
+        self.cube.bind(PointShadowMap::UNIT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::device::RecordingDevice;
+    use crate::core::resource::ContextOwner;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn camera() -> Camera {
+        let mut camera = Camera::perspective(60.0, 0.1, 100.0, 1600, 900);
+        camera.look_at(
+            &glm::vec3(3.0, 4.0, 10.0),
+            &glm::vec3(0.0, 0.0, 0.0),
+            &glm::Vec3::y(),
+        );
+        camera
+    }
+
+    fn sun() -> glm::Vec3 {
+        glm::normalize(&glm::vec3(-0.4, -1.0, -0.3))
+    }
+
+    fn clip(m: &glm::Mat4, p: &glm::Vec3) -> glm::Vec3 {
+        let v = m * glm::vec4(p.x, p.y, p.z, 1.0);
+        v.xyz() / v.w
+    }
+
+    #[test]
+    fn splits_blend_uniform_and_logarithmic() {
+        let uniform = cascade_splits(1.0, 100.0, 4, 0.0);
+        let log = cascade_splits(1.0, 100.0, 4, 1.0);
+        let expected_uniform = [25.75, 50.5, 75.25, 100.0];
+        let expected_log = [100f32.powf(0.25), 10.0, 100f32.powf(0.75), 100.0];
+        for i in 0..4 {
+            assert!((uniform[i] - expected_uniform[i]).abs() < EPSILON);
+            assert!((log[i] - expected_log[i]).abs() < EPSILON);
+        }
+
+        let practical = cascade_splits(0.1, 40.0, 4, 0.75);
+        assert_eq!(practical.len(), 4);
+        assert!(practical[0] > 0.1);
+        assert!(practical.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(practical[3], 40.0);
+        for i in 0..3 {
+            let (low, high) = (log[i].min(uniform[i]), log[i].max(uniform[i]));
+            let blended = cascade_splits(1.0, 100.0, 4, 0.5)[i];
+            assert!(blended > low && blended < high);
+        }
+    }
+
+    #[test]
+    fn slices_tile_the_view_frustum() {
+        let camera = camera();
+        let corners = frustum_slice(&camera, 0.1, 100.0);
+        let view_projection = camera.view_projection();
+        for corner in corners.iter() {
+            let ndc = clip(&view_projection, corner);
+            assert!((ndc.x.abs() - 1.0).abs() < 1e-3, "{:?}", ndc);
+            assert!((ndc.y.abs() - 1.0).abs() < 1e-3, "{:?}", ndc);
+        }
+        assert!((clip(&view_projection, &corners[0]).z + 1.0).abs() < 1e-3);
+        assert!((clip(&view_projection, &corners[4]).z - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn cascades_contain_their_slices() {
+        let camera = camera();
+        let settings = CascadeSettings::default();
+        let cascades = compute_cascades(&camera, &sun(), &settings);
+        assert_eq!(cascades.len(), 4);
+        assert_eq!(cascades[3].split, settings.max_distance);
+
+        let mut start = 0.1;
+        for cascade in cascades.iter() {
+            for corner in frustum_slice(&camera, start, cascade.split).iter() {
+                let p = clip(&cascade.view_projection, corner);
+                assert!(p.x.abs() <= 1.0 && p.y.abs() <= 1.0 && p.z.abs() <= 1.0);
+            }
+            start = cascade.split;
+        }
+        assert!(cascades
+            .windows(2)
+            .all(|w| w[0].texel_size < w[1].texel_size));
+    }
+
+    #[test]
+    fn cascades_are_stable_under_camera_motion() {
+        let resolution = 1024;
+        let corners = frustum_slice(&camera(), 0.1, 10.0);
+        let (first, texel) = fit_cascade(&corners, &sun(), resolution, 20.0);
+
+        // Turning keeps the bounding sphere, so the texel size must not change.
+        let mut turned = camera();
+        turned.set_orientation(glm::quat_angle_axis(0.3, &glm::Vec3::y()) * camera().orientation());
+        let (_, turned_texel) =
+            fit_cascade(&frustum_slice(&turned, 0.1, 10.0), &sun(), resolution, 20.0);
+        assert!((texel - turned_texel).abs() < EPSILON);
+
+        // Moving shifts the projection by whole texels only.
+        let mut moved = camera();
+        moved.set_position(camera().position() + glm::vec3(0.123, 0.0, 0.0457));
+        let (second, _) = fit_cascade(&frustum_slice(&moved, 0.1, 10.0), &sun(), resolution, 20.0);
+        let origin = glm::Vec3::zeros();
+        let shift = (clip(&second, &origin) - clip(&first, &origin)).xy() * 0.5 * resolution as f32;
+        assert!(shift.norm() > 0.5);
+        assert!((shift.x - shift.x.round()).abs() < 1e-2, "{:?}", shift);
+        assert!((shift.y - shift.y.round()).abs() < 1e-2, "{:?}", shift);
+    }
+
+    #[test]
+    fn atlas_tiles_sit_side_by_side() {
+        let m = atlas_matrix(2, 4);
+        let low = clip(&m, &glm::vec3(-1.0, -1.0, -1.0));
+        let high = clip(&m, &glm::vec3(1.0, 1.0, 1.0));
+        assert!((low - glm::vec3(0.5, 0.0, 0.0)).norm() < EPSILON);
+        assert!((high - glm::vec3(0.75, 1.0, 1.0)).norm() < EPSILON);
+    }
+
+    #[test]
+    fn point_light_faces_look_out_from_the_light() {
+        let position = glm::vec3(1.0, 2.0, 3.0);
+        let directions = [
+            glm::Vec3::x(),
+            -glm::Vec3::x(),
+            glm::Vec3::y(),
+            -glm::Vec3::y(),
+            glm::Vec3::z(),
+            -glm::Vec3::z(),
+        ];
+        let projection = point_light_projection(0.05, 10.0);
+        for (view, direction) in point_light_views(&position).iter().zip(directions.iter()) {
+            let p = clip(&(projection * view), &(position + direction * 2.0));
+            assert!(p.x.abs() < EPSILON && p.y.abs() < EPSILON, "{:?}", p);
+            assert!(p.z.abs() < 1.0);
+        }
+    }
+
+    #[test]
+    fn shadow_targets_are_depth_only() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let csm = CascadedShadowMap::new(CascadeSettings {
+            count: 3,
+            resolution: 256,
+            ..CascadeSettings::default()
+        })
+        .unwrap();
+        let _point = PointShadowMap::new(128, 10.0).unwrap();
+
+        assert_eq!(
+            device.calls_to("TexImageFloat"),
+            vec![
+                "TexImageFloat(TEXTURE_2D, 0, DEPTH_COMPONENT24, 768x256, null)",
+                "TexImageFloat(TEXTURE_CUBE_MAP_POSITIVE_X, 0, DEPTH_COMPONENT24, 128x128, null)",
+                "TexImageFloat(TEXTURE_CUBE_MAP_NEGATIVE_X, 0, DEPTH_COMPONENT24, 128x128, null)",
+                "TexImageFloat(TEXTURE_CUBE_MAP_POSITIVE_Y, 0, DEPTH_COMPONENT24, 128x128, null)",
+                "TexImageFloat(TEXTURE_CUBE_MAP_NEGATIVE_Y, 0, DEPTH_COMPONENT24, 128x128, null)",
+                "TexImageFloat(TEXTURE_CUBE_MAP_POSITIVE_Z, 0, DEPTH_COMPONENT24, 128x128, null)",
+                "TexImageFloat(TEXTURE_CUBE_MAP_NEGATIVE_Z, 0, DEPTH_COMPONENT24, 128x128, null)",
+            ]
+        );
+        let texture = csm.depth().id();
+        assert!(device.calls().contains(&format!(
+            "FramebufferTexture(DEPTH_ATTACHMENT, TEXTURE_2D, {}, 0)",
+            texture
+        )));
+        assert!(device.calls().contains(
+            &"TexParameter(TEXTURE_2D, TEXTURE_COMPARE_MODE, COMPARE_REF_TO_TEXTURE)".to_string()
+        ));
+        assert_eq!(device.calls_to("DrawBuffers"), vec!["DrawBuffers(NONE)"; 2]);
+    }
+}
@@ -0,0 +1,583 @@
+use gl::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    Blend,
+    DepthTest,
+    CullFace,
+    ScissorTest,
+    StencilTest,
+    FramebufferSrgb,
+    Multisample,
+    /// Filters across cube map faces instead of clamping at their edges.
+    CubeMapSeamless,
+}
+
+impl Capability {
+    pub fn to_gl(self) -> GLenum {
+        match self {
+            Capability::Blend => gl::BLEND,
+            Capability::DepthTest => gl::DEPTH_TEST,
+            Capability::CullFace => gl::CULL_FACE,
+            Capability::ScissorTest => gl::SCISSOR_TEST,
+            Capability::StencilTest => gl::STENCIL_TEST,
+            Capability::FramebufferSrgb => gl::FRAMEBUFFER_SRGB,
+            Capability::Multisample => gl::MULTISAMPLE,
+            Capability::CubeMapSeamless => gl::TEXTURE_CUBE_MAP_SEAMLESS,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlendFactor {
+    Zero,
+    One,
+    SrcColor,
+    OneMinusSrcColor,
+    DstColor,
+    OneMinusDstColor,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    DstAlpha,
+    OneMinusDstAlpha,
+}
+
+impl BlendFactor {
+    pub fn to_gl(self) -> GLenum {
+        match self {
+            BlendFactor::Zero => gl::ZERO,
+            BlendFactor::One => gl::ONE,
+            BlendFactor::SrcColor => gl::SRC_COLOR,
+            BlendFactor::OneMinusSrcColor => gl::ONE_MINUS_SRC_COLOR,
+            BlendFactor::DstColor => gl::DST_COLOR,
+            BlendFactor::OneMinusDstColor => gl::ONE_MINUS_DST_COLOR,
+            BlendFactor::SrcAlpha => gl::SRC_ALPHA,
+            BlendFactor::OneMinusSrcAlpha => gl::ONE_MINUS_SRC_ALPHA,
+            BlendFactor::DstAlpha => gl::DST_ALPHA,
+            BlendFactor::OneMinusDstAlpha => gl::ONE_MINUS_DST_ALPHA,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlendOp {
+    Add,
+    Subtract,
+    ReverseSubtract,
+    Min,
+    Max,
+}
+
+impl BlendOp {
+    pub fn to_gl(self) -> GLenum {
+        match self {
+            BlendOp::Add => gl::FUNC_ADD,
+            BlendOp::Subtract => gl::FUNC_SUBTRACT,
+            BlendOp::ReverseSubtract => gl::FUNC_REVERSE_SUBTRACT,
+            BlendOp::Min => gl::MIN,
+            BlendOp::Max => gl::MAX,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Blend {
+    pub src_color: BlendFactor,
+    pub dst_color: BlendFactor,
+    pub color_op: BlendOp,
+    pub src_alpha: BlendFactor,
+    pub dst_alpha: BlendFactor,
+    pub alpha_op: BlendOp,
+}
+
+#[allow(dead_code)]
+impl Blend {
+    /// Classic straight alpha blending.
+    pub const ALPHA: Blend = Blend::uniform(BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha);
+    pub const PREMULTIPLIED: Blend =
+        Blend::uniform(BlendFactor::One, BlendFactor::OneMinusSrcAlpha);
+    pub const ADDITIVE: Blend = Blend::uniform(BlendFactor::SrcAlpha, BlendFactor::One);
+    pub const MULTIPLY: Blend = Blend::uniform(BlendFactor::DstColor, BlendFactor::Zero);
+
+    /// Same factors for color and alpha, added together.
+    pub const fn uniform(src: BlendFactor, dst: BlendFactor) -> Blend {
+        Blend {
+            src_color: src,
+            dst_color: dst,
+            color_op: BlendOp::Add,
+            src_alpha: src,
+            dst_alpha: dst,
+            alpha_op: BlendOp::Add,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompareFunc {
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl CompareFunc {
+    pub fn to_gl(self) -> GLenum {
+        match self {
+            CompareFunc::Never => gl::NEVER,
+            CompareFunc::Less => gl::LESS,
+            CompareFunc::Equal => gl::EQUAL,
+            CompareFunc::LessEqual => gl::LEQUAL,
+            CompareFunc::Greater => gl::GREATER,
+            CompareFunc::NotEqual => gl::NOTEQUAL,
+            CompareFunc::GreaterEqual => gl::GEQUAL,
+            CompareFunc::Always => gl::ALWAYS,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Face {
+    Front,
+    Back,
+    FrontAndBack,
+}
+
+impl Face {
+    pub fn to_gl(self) -> GLenum {
+        match self {
+            Face::Front => gl::FRONT,
+            Face::Back => gl::BACK,
+            Face::FrontAndBack => gl::FRONT_AND_BACK,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrontFace {
+    CounterClockwise,
+    Clockwise,
+}
+
+impl FrontFace {
+    pub fn to_gl(self) -> GLenum {
+        match self {
+            FrontFace::CounterClockwise => gl::CCW,
+            FrontFace::Clockwise => gl::CW,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    Replace,
+    Increment,
+    IncrementWrap,
+    Decrement,
+    DecrementWrap,
+    Invert,
+}
+
+impl StencilOp {
+    pub fn to_gl(self) -> GLenum {
+        match self {
+            StencilOp::Keep => gl::KEEP,
+            StencilOp::Zero => gl::ZERO,
+            StencilOp::Replace => gl::REPLACE,
+            StencilOp::Increment => gl::INCR,
+            StencilOp::IncrementWrap => gl::INCR_WRAP,
+            StencilOp::Decrement => gl::DECR,
+            StencilOp::DecrementWrap => gl::DECR_WRAP,
+            StencilOp::Invert => gl::INVERT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Stencil {
+    pub func: CompareFunc,
+    pub reference: i32,
+    pub read_mask: u32,
+    pub write_mask: u32,
+    pub fail: StencilOp,
+    pub depth_fail: StencilOp,
+    pub pass: StencilOp,
+}
+
+impl Default for Stencil {
+    fn default() -> Self {
+        Stencil {
+            func: CompareFunc::Always,
+            reference: 0,
+            read_mask: !0,
+            write_mask: !0,
+            fail: StencilOp::Keep,
+            depth_fail: StencilOp::Keep,
+            pass: StencilOp::Keep,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PolygonMode {
+    Fill,
+    Line,
+    Point,
+}
+
+impl PolygonMode {
+    pub fn to_gl(self) -> GLenum {
+        match self {
+            PolygonMode::Fill => gl::FILL,
+            PolygonMode::Line => gl::LINE,
+            PolygonMode::Point => gl::POINT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Fixed-function state a draw call needs. `Default` matches a fresh GL context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RenderState {
+    pub blend: Option<Blend>,
+    /// Depth test function; `None` disables the test.
+    pub depth_test: Option<CompareFunc>,
+    pub depth_write: bool,
+    /// Faces to cull; `None` disables culling.
+    pub cull: Option<Face>,
+    pub front_face: FrontFace,
+    pub scissor: Option<Rect>,
+    pub color_mask: [bool; 4],
+    pub stencil: Option<Stencil>,
+    pub polygon_mode: PolygonMode,
+}
+
+impl Default for RenderState {
+    fn default() -> Self {
+        RenderState {
+            blend: None,
+            depth_test: None,
+            depth_write: true,
+            cull: None,
+            front_face: FrontFace::CounterClockwise,
+            scissor: None,
+            color_mask: [true; 4],
+            stencil: None,
+            polygon_mode: PolygonMode::Fill,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl RenderState {
+    /// Depth tested and written, back faces culled: the usual state for opaque 3D geometry.
+    pub fn opaque() -> RenderState {
+        RenderState {
+            depth_test: Some(CompareFunc::Less),
+            cull: Some(Face::Back),
+            ..Default::default()
+        }
+    }
+
+    /// Alpha blended, depth tested but not written.
+    pub fn transparent() -> RenderState {
+        RenderState {
+            blend: Some(Blend::ALPHA),
+            depth_test: Some(CompareFunc::Less),
+            depth_write: false,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_blend(self, blend: Option<Blend>) -> RenderState {
+        RenderState { blend, ..self }
+    }
+
+    pub fn with_depth(self, test: Option<CompareFunc>, write: bool) -> RenderState {
+        RenderState {
+            depth_test: test,
+            depth_write: write,
+            ..self
+        }
+    }
+
+    pub fn with_cull(self, cull: Option<Face>) -> RenderState {
+        RenderState { cull, ..self }
+    }
+
+    pub fn with_scissor(self, scissor: Option<Rect>) -> RenderState {
+        RenderState { scissor, ..self }
+    }
+
+    pub fn with_color_mask(self, color_mask: [bool; 4]) -> RenderState {
+        RenderState { color_mask, ..self }
+    }
+
+    pub fn with_stencil(self, stencil: Option<Stencil>) -> RenderState {
+        RenderState { stencil, ..self }
+    }
+
+    pub fn with_polygon_mode(self, polygon_mode: PolygonMode) -> RenderState {
+        RenderState {
+            polygon_mode,
+            ..self
+        }
+    }
+}
+
+/// The GL calls a `StateCache` issues.
+pub trait StateBackend {
+    fn set_enabled(&self, cap: Capability, enabled: bool);
+    fn blend_func(&self, blend: &Blend);
+    fn depth_func(&self, func: CompareFunc);
+    fn depth_mask(&self, write: bool);
+    fn cull_face(&self, face: Face);
+    fn front_face(&self, front_face: FrontFace);
+    fn scissor(&self, rect: Rect);
+    fn color_mask(&self, mask: [bool; 4]);
+    fn stencil_func(&self, func: CompareFunc, reference: i32, mask: u32);
+    fn stencil_op(&self, fail: StencilOp, depth_fail: StencilOp, pass: StencilOp);
+    fn stencil_mask(&self, mask: u32);
+    fn polygon_mode(&self, mode: PolygonMode);
+    fn clear_color(&self, color: [f32; 4]);
+    fn viewport(&self, rect: Rect);
+}
+
+/// Last value sent to the backend for each piece of state; `None` when unknown.
+#[derive(Debug, Clone, Default)]
+pub struct StateCache {
+    blend_enabled: Option<bool>,
+    blend: Option<Blend>,
+    depth_enabled: Option<bool>,
+    depth_func: Option<CompareFunc>,
+    depth_write: Option<bool>,
+    cull_enabled: Option<bool>,
+    cull_face: Option<Face>,
+    front_face: Option<FrontFace>,
+    scissor_enabled: Option<bool>,
+    scissor: Option<Rect>,
+    color_mask: Option<[bool; 4]>,
+    stencil_enabled: Option<bool>,
+    stencil_func: Option<(CompareFunc, i32, u32)>,
+    stencil_op: Option<(StencilOp, StencilOp, StencilOp)>,
+    stencil_mask: Option<u32>,
+    polygon_mode: Option<PolygonMode>,
+    clear_color: Option<[f32; 4]>,
+    viewport: Option<Rect>,
+}
+
+fn update<T, F>(slot: &mut Option<T>, value: T, set: F)
+where
+    T: PartialEq + Copy,
+    F: FnOnce(T),
+{
+    if *slot != Some(value) {
+        set(value);
+        *slot = Some(value);
+    }
+}
+
+#[allow(dead_code)]
+impl StateCache {
+    pub fn new() -> StateCache {
+        Default::default()
+    }
+
+    /// Forgets everything, e.g. after code outside the cache touched GL state.
+    pub fn invalidate(&mut self) {
+        *self = StateCache::new();
+    }
+
+    /// Issues only the calls needed to get from the cached state to `state`.
+    pub fn apply(&mut self, backend: &dyn StateBackend, state: &RenderState) {
+        update(&mut self.blend_enabled, state.blend.is_some(), |on| {
+            backend.set_enabled(Capability::Blend, on)
+        });
+        if let Some(blend) = state.blend {
+            update(&mut self.blend, blend, |b| backend.blend_func(&b));
+        }
+
+        update(&mut self.depth_enabled, state.depth_test.is_some(), |on| {
+            backend.set_enabled(Capability::DepthTest, on)
+        });
+        if let Some(func) = state.depth_test {
+            update(&mut self.depth_func, func, |f| backend.depth_func(f));
+        }
+        // The depth mask also applies to clears, so it is tracked even with the test off.
+        update(&mut self.depth_write, state.depth_write, |w| {
+            backend.depth_mask(w)
+        });
+
+        update(&mut self.cull_enabled, state.cull.is_some(), |on| {
+            backend.set_enabled(Capability::CullFace, on)
+        });
+        if let Some(face) = state.cull {
+            update(&mut self.cull_face, face, |f| backend.cull_face(f));
+        }
+        update(&mut self.front_face, state.front_face, |f| {
+            backend.front_face(f)
+        });
+
+        update(&mut self.scissor_enabled, state.scissor.is_some(), |on| {
+            backend.set_enabled(Capability::ScissorTest, on)
+        });
+        if let Some(rect) = state.scissor {
+            update(&mut self.scissor, rect, |r| backend.scissor(r));
+        }
+
+        update(&mut self.color_mask, state.color_mask, |m| {
+            backend.color_mask(m)
+        });
+
+        update(&mut self.stencil_enabled, state.stencil.is_some(), |on| {
+            backend.set_enabled(Capability::StencilTest, on)
+        });
+        if let Some(s) = state.stencil {
+            update(
+                &mut self.stencil_func,
+                (s.func, s.reference, s.read_mask),
+                |(f, r, m)| backend.stencil_func(f, r, m),
+            );
+            update(
+                &mut self.stencil_op,
+                (s.fail, s.depth_fail, s.pass),
+                |(f, z, p)| backend.stencil_op(f, z, p),
+            );
+        }
+        // Like the depth mask, the stencil mask applies to clears; without a stencil state
+        // every bit is writable.
+        let write_mask = state.stencil.map_or(!0, |s| s.write_mask);
+        update(&mut self.stencil_mask, write_mask, |m| {
+            backend.stencil_mask(m)
+        });
+
+        update(&mut self.polygon_mode, state.polygon_mode, |m| {
+            backend.polygon_mode(m)
+        });
+    }
+
+    pub fn set_clear_color(&mut self, backend: &dyn StateBackend, color: [f32; 4]) {
+        update(&mut self.clear_color, color, |c| backend.clear_color(c));
+    }
+
+    pub fn set_viewport(&mut self, backend: &dyn StateBackend, rect: Rect) {
+        update(&mut self.viewport, rect, |r| backend.viewport(r));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::device::RecordingDevice;
+
+    #[test]
+    fn first_apply_sets_everything() {
+        let mut cache = StateCache::new();
+        let gl = RecordingDevice::new();
+        cache.apply(&gl, &RenderState::default());
+        assert_eq!(
+            gl.take(),
+            vec![
+                "Disable(Blend)",
+                "Disable(DepthTest)",
+                "DepthMask(true)",
+                "Disable(CullFace)",
+                "FrontFace(CounterClockwise)",
+                "Disable(ScissorTest)",
+                "ColorMask([true, true, true, true])",
+                "Disable(StencilTest)",
+                "StencilMask(0xffffffff)",
+                "PolygonMode(Fill)",
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_redundant_state() {
+        let mut cache = StateCache::new();
+        let gl = RecordingDevice::new();
+        cache.apply(&gl, &RenderState::opaque());
+        gl.take();
+
+        cache.apply(&gl, &RenderState::opaque());
+        assert!(gl.take().is_empty());
+
+        cache.apply(&gl, &RenderState::transparent());
+        assert_eq!(
+            gl.take(),
+            vec![
+                "Enable(Blend)",
+                "BlendFunc(SrcAlpha, OneMinusSrcAlpha)",
+                "DepthMask(false)",
+                "Disable(CullFace)",
+            ]
+        );
+
+        // Re-enabling culling keeps the cached cull face and blend func.
+        cache.apply(&gl, &RenderState::opaque().with_blend(Some(Blend::ALPHA)));
+        assert_eq!(gl.take(), vec!["DepthMask(true)", "Enable(CullFace)"]);
+    }
+
+    #[test]
+    fn tracks_stencil_scissor_and_globals() {
+        let mut cache = StateCache::new();
+        let gl = RecordingDevice::new();
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            width: 64,
+            height: 32,
+        };
+        let outline = RenderState::default()
+            .with_scissor(Some(rect))
+            .with_stencil(Some(Stencil {
+                func: CompareFunc::NotEqual,
+                reference: 1,
+                write_mask: 0,
+                ..Default::default()
+            }));
+        cache.apply(&gl, &outline);
+        let calls = gl.take();
+        assert!(calls.contains(&"Scissor(64, 32)".to_string()));
+        assert!(calls.contains(&"StencilFunc(NotEqual, 1)".to_string()));
+        assert!(calls.contains(&"StencilMask(0x0)".to_string()));
+
+        // Clears write every stencil bit again once the stencil state is gone.
+        cache.apply(&gl, &outline.with_stencil(None));
+        assert_eq!(
+            gl.take(),
+            vec!["Disable(StencilTest)", "StencilMask(0xffffffff)"]
+        );
+        cache.apply(&gl, &outline.with_stencil(None));
+        assert!(gl.take().is_empty());
+
+        cache.set_clear_color(&gl, [0.1, 0.2, 0.3, 1.0]);
+        cache.set_clear_color(&gl, [0.1, 0.2, 0.3, 1.0]);
+        cache.set_viewport(&gl, rect);
+        cache.set_viewport(&gl, rect);
+        assert_eq!(gl.take().len(), 2);
+
+        cache.invalidate();
+        cache.set_viewport(&gl, rect);
+        assert_eq!(gl.take(), vec!["Viewport(64, 32)"]);
+    }
+}
@@ -1,19 +1,24 @@
+use std::fs::File;
+use std::io::BufReader;
 use std::marker::PhantomData;
 use std::path::Path;
 
+use gl::types::*;
+use image::codecs::hdr::HdrDecoder;
 use image::{DynamicImage, GenericImageView};
 
+use super::framebuffer::check_samples;
+use super::resource::{self, ContextHandle, ResourceKind};
+
 pub struct Texture2D {
     id: u32,
+    ctx: ContextHandle,
 }
 
 impl Drop for Texture2D {
     // Delete the texture
     fn drop(&mut self) {
-        unsafe {
-            gl::DeleteTextures(1, &self.id);
-        }
-        println!("Delete Texture: {}", self.id);
+        self.ctx.release(ResourceKind::Texture, self.id);
     }
 }
 
@@ -32,6 +37,24 @@ impl Texture2D {
     {
         // Load Image
         let img: DynamicImage = image::open(path).expect("failed to load image");
+        Texture2D::from_image(img, min_filter, mag_filter, wrap_s, wrap_t, generate_mipmap)
+    }
+
+    /// Names the object in debug messages and graphics debuggers.
+    pub fn set_label(&self, label: &str) {
+        self.ctx.device().object_label(gl::TEXTURE, self.id, label);
+        resource::track_label(ResourceKind::Texture, self.id, label);
+    }
+
+    pub fn from_image(
+        img: DynamicImage,
+        min_filter: u32,
+        mag_filter: u32,
+        wrap_s: u32,
+        wrap_t: u32,
+        generate_mipmap: bool,
+    ) -> Result<Texture2D, String> {
+        let ctx = ContextHandle::current()?;
         let (w, h) = img.dimensions();
         let format = match img {
             DynamicImage::ImageLuma8(_) => gl::RED,
@@ -45,54 +68,305 @@ impl Texture2D {
         let data = img.into_bytes();
 
         // Generate Texture
-        let mut tex = 0;
-        unsafe {
-            gl::GenTextures(1, &mut tex);
-            gl::BindTexture(gl::TEXTURE_2D, tex);
-
-            // Set texture filtering parameters
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mag_filter as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter as i32);
-
-            // Set the texture wrapping parameters
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap_s as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap_t as i32);
-
-            // Generate mipmap
-            gl::TexImage2D(
-                gl::TEXTURE_2D,
-                0,
-                format as i32,
-                w as i32,
-                h as i32,
-                0,
-                format,
-                gl::UNSIGNED_BYTE,
-                std::mem::transmute(data.as_ptr()),
-            );
-            if generate_mipmap {
-                gl::GenerateMipmap(gl::TEXTURE_2D);
-            }
+        let device = ctx.device();
+        let tex = device.gen_texture();
+        device.bind_texture(gl::TEXTURE_2D, tex);
 
-            gl::BindTexture(gl::TEXTURE_2D, 0);
+        // Set texture filtering parameters
+        device.tex_parameter(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mag_filter);
+        device.tex_parameter(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter);
+
+        // Set the texture wrapping parameters
+        device.tex_parameter(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap_s);
+        device.tex_parameter(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap_t);
+
+        // Generate mipmap
+        device.tex_image_2d(gl::TEXTURE_2D, format, w, h, &data);
+        if generate_mipmap {
+            device.generate_mipmap(gl::TEXTURE_2D);
         }
 
-        Ok(Texture2D { id: tex })
+        device.bind_texture(gl::TEXTURE_2D, 0);
+
+        ctx.created(ResourceKind::Texture, tex);
+        Ok(Texture2D { id: tex, ctx })
+    }
+
+    /// Float texture of `internal_format`, e.g. `RG16F` for a render target, initialized
+    /// from tightly packed rows, bottom row first, when `data` is given.
+    pub fn from_float(
+        width: u32,
+        height: u32,
+        internal_format: GLenum,
+        data: Option<&[f32]>,
+        filter: u32,
+        wrap: u32,
+    ) -> Result<Texture2D, String> {
+        let ctx = ContextHandle::current()?;
+        let device = ctx.device();
+        let tex = device.gen_texture();
+        device.bind_texture(gl::TEXTURE_2D, tex);
+        device.tex_parameter(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter);
+        device.tex_parameter(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter);
+        device.tex_parameter(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap);
+        device.tex_parameter(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap);
+        device.tex_image_float(gl::TEXTURE_2D, 0, internal_format, width, height, data);
+        device.bind_texture(gl::TEXTURE_2D, 0);
+
+        ctx.created(ResourceKind::Texture, tex);
+        Ok(Texture2D { id: tex, ctx })
+    }
+
+    /// 24 bit depth texture to render shadow maps into. Sampled through a `sampler2DShadow`,
+    /// lookups return how much of the filter footprint passes a less-or-equal comparison.
+    pub fn depth(width: u32, height: u32) -> Result<Texture2D, String> {
+        let texture = Texture2D::from_float(
+            width,
+            height,
+            gl::DEPTH_COMPONENT24,
+            None,
+            gl::LINEAR,
+            gl::CLAMP_TO_EDGE,
+        )?;
+        let device = texture.ctx.device();
+        device.bind_texture(gl::TEXTURE_2D, texture.id);
+        device.tex_parameter(
+            gl::TEXTURE_2D,
+            gl::TEXTURE_COMPARE_MODE,
+            gl::COMPARE_REF_TO_TEXTURE,
+        );
+        device.tex_parameter(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL);
+        device.bind_texture(gl::TEXTURE_2D, 0);
+        Ok(texture)
+    }
+
+    /// Loads a Radiance `.hdr` image into a linearly filtered `RGB16F` texture.
+    pub fn from_hdr<P>(path: P) -> Result<Texture2D, String>
+    where
+        P: AsRef<Path>,
+    {
+        let (width, height, data) = load_hdr(path.as_ref())?;
+        let texture = Texture2D::from_float(
+            width,
+            height,
+            gl::RGB16F,
+            Some(&data),
+            gl::LINEAR,
+            gl::CLAMP_TO_EDGE,
+        )?;
+        texture.set_label(&path.as_ref().display().to_string());
+        Ok(texture)
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn bind(&self, unit: u32) {
+        let device = self.ctx.device();
+        device.active_texture(unit);
+        device.bind_texture(gl::TEXTURE_2D, self.id);
     }
 
     pub fn binding<F>(&self, cb: F)
     where
         F: FnOnce(),
     {
-        unsafe {
-            gl::BindTexture(gl::TEXTURE_2D, self.id);
+        self.ctx.device().bind_texture(gl::TEXTURE_2D, self.id);
+        cb();
+        self.ctx.device().bind_texture(gl::TEXTURE_2D, 0);
+    }
+}
+
+/// Reads a Radiance `.hdr` image as linear RGB floats, bottom row first like GL textures.
+pub fn load_hdr(path: &Path) -> Result<(u32, u32, Vec<f32>), String> {
+    let file =
+        File::open(path).map_err(|e| format!("failed to open file: {}: {}", path.display(), e))?;
+    let decoder =
+        HdrDecoder::new(BufReader::new(file)).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let meta = decoder.metadata();
+    let pixels = decoder
+        .read_image_hdr()
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let row = meta.width as usize;
+    let mut data = Vec::with_capacity(pixels.len() * 3);
+    for y in (0..meta.height as usize).rev() {
+        for pixel in pixels[y * row..(y + 1) * row].iter() {
+            data.extend_from_slice(&pixel.0);
         }
+    }
+    Ok((meta.width, meta.height, data))
+}
 
-        cb();
+/// Six square faces sharing a size, with `levels` mips each.
+pub struct TextureCube {
+    id: u32,
+    size: u32,
+    levels: u32,
+    ctx: ContextHandle,
+}
 
-        unsafe {
-            gl::BindTexture(gl::TEXTURE_2D, 0);
+impl Drop for TextureCube {
+    fn drop(&mut self) {
+        self.ctx.release(ResourceKind::Texture, self.id);
+    }
+}
+
+#[allow(dead_code)]
+impl TextureCube {
+    /// Face targets in GL order: +X, -X, +Y, -Y, +Z, -Z.
+    pub const FACES: [GLenum; 6] = [
+        gl::TEXTURE_CUBE_MAP_POSITIVE_X,
+        gl::TEXTURE_CUBE_MAP_NEGATIVE_X,
+        gl::TEXTURE_CUBE_MAP_POSITIVE_Y,
+        gl::TEXTURE_CUBE_MAP_NEGATIVE_Y,
+        gl::TEXTURE_CUBE_MAP_POSITIVE_Z,
+        gl::TEXTURE_CUBE_MAP_NEGATIVE_Z,
+    ];
+
+    /// Allocates uninitialized `size` pixel faces of `internal_format`, e.g. to render into.
+    /// More than one level selects trilinear filtering.
+    pub fn new(size: u32, internal_format: GLenum, levels: u32) -> Result<TextureCube, String> {
+        let ctx = ContextHandle::current()?;
+        let levels = levels.clamp(1, TextureCube::full_levels(size));
+        let device = ctx.device();
+        let tex = device.gen_texture();
+        device.bind_texture(gl::TEXTURE_CUBE_MAP, tex);
+
+        let min_filter = if levels > 1 {
+            gl::LINEAR_MIPMAP_LINEAR
+        } else {
+            gl::LINEAR
+        };
+        device.tex_parameter(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR);
+        device.tex_parameter(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, min_filter);
+        for &wrap in &[gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R] {
+            device.tex_parameter(gl::TEXTURE_CUBE_MAP, wrap, gl::CLAMP_TO_EDGE);
         }
+        // A shorter chain than `full_levels` is only mipmap complete, for sampling and for
+        // rendering into levels above 0, when the last level is declared.
+        device.tex_parameter(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAX_LEVEL, levels - 1);
+
+        for level in 0..levels {
+            let face_size = (size >> level).max(1);
+            for &face in TextureCube::FACES.iter() {
+                device.tex_image_float(face, level, internal_format, face_size, face_size, None);
+            }
+        }
+        device.bind_texture(gl::TEXTURE_CUBE_MAP, 0);
+
+        ctx.created(ResourceKind::Texture, tex);
+        Ok(TextureCube {
+            id: tex,
+            size,
+            levels,
+            ctx,
+        })
+    }
+
+    /// Levels of a complete mip chain for `size` pixel faces.
+    pub fn full_levels(size: u32) -> u32 {
+        32 - size.max(1).leading_zeros()
+    }
+
+    /// Names the object in debug messages and graphics debuggers.
+    pub fn set_label(&self, label: &str) {
+        self.ctx.device().object_label(gl::TEXTURE, self.id, label);
+        resource::track_label(ResourceKind::Texture, self.id, label);
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn levels(&self) -> u32 {
+        self.levels
+    }
+
+    /// Face size of mip `level`.
+    pub fn level_size(&self, level: u32) -> u32 {
+        (self.size >> level).max(1)
+    }
+
+    pub fn bind(&self, unit: u32) {
+        let device = self.ctx.device();
+        device.active_texture(unit);
+        device.bind_texture(gl::TEXTURE_CUBE_MAP, self.id);
+    }
+
+    /// Fills the lower levels from level 0.
+    pub fn generate_mipmap(&self) {
+        let device = self.ctx.device();
+        device.bind_texture(gl::TEXTURE_CUBE_MAP, self.id);
+        device.generate_mipmap(gl::TEXTURE_CUBE_MAP);
+        device.bind_texture(gl::TEXTURE_CUBE_MAP, 0);
+    }
+}
+
+/// Multisampled 2D texture to render into, sampled per sample with `texelFetch` on a
+/// `sampler2DMS` or resolved into a single-sample target with `Framebuffer::blit`.
+#[allow(dead_code)]
+pub struct MultisampleTexture {
+    id: u32,
+    samples: u32,
+    ctx: ContextHandle,
+}
+
+impl Drop for MultisampleTexture {
+    fn drop(&mut self) {
+        self.ctx.release(ResourceKind::Texture, self.id);
+    }
+}
+
+#[allow(dead_code)]
+impl MultisampleTexture {
+    /// Allocates `samples` samples per pixel of `internal_format`, e.g. `RGBA16F`. Fails when
+    /// `samples` exceeds `GL_MAX_SAMPLES`.
+    pub fn new(
+        width: u32,
+        height: u32,
+        internal_format: GLenum,
+        samples: u32,
+    ) -> Result<MultisampleTexture, String> {
+        let ctx = ContextHandle::current()?;
+        let device = ctx.device();
+        check_samples(device, samples)?;
+        let tex = device.gen_texture();
+        device.bind_texture(gl::TEXTURE_2D_MULTISAMPLE, tex);
+        device.tex_image_multisample(samples, internal_format, width, height);
+        device.bind_texture(gl::TEXTURE_2D_MULTISAMPLE, 0);
+
+        ctx.created(ResourceKind::Texture, tex);
+        Ok(MultisampleTexture {
+            id: tex,
+            samples,
+            ctx,
+        })
+    }
+
+    /// Names the object in debug messages and graphics debuggers.
+    pub fn set_label(&self, label: &str) {
+        self.ctx.device().object_label(gl::TEXTURE, self.id, label);
+        resource::track_label(ResourceKind::Texture, self.id, label);
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    pub fn bind(&self, unit: u32) {
+        let device = self.ctx.device();
+        device.active_texture(unit);
+        device.bind_texture(gl::TEXTURE_2D_MULTISAMPLE, self.id);
     }
 }
 
@@ -184,4 +458,138 @@ impl TextureBuilder<Fully, Fully, Fully, Fully> {
             self.generate_mipmap,
         )
     }
+
+    pub fn build2d_from_image(&self, img: DynamicImage) -> Result<Texture2D, String> {
+        Texture2D::from_image(
+            img,
+            self.min_filter,
+            self.mag_filter,
+            self.wrap_s,
+            self.wrap_t,
+            self.generate_mipmap,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::device::RecordingDevice;
+    use crate::core::resource::ContextOwner;
+
+    #[test]
+    fn uploads_image_with_builder_parameters() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let texture = TextureBuilder::new()
+            .filter(gl::NEAREST)
+            .wrap(gl::REPEAT)
+            .generate_mipmap()
+            .build2d_from_image(DynamicImage::new_rgb8(4, 2))
+            .unwrap();
+
+        assert_eq!(texture.id(), 1);
+        assert_eq!(
+            device.take(),
+            vec![
+                "GenTexture -> 1",
+                "BindTexture(TEXTURE_2D, 1)",
+                "TexParameter(TEXTURE_2D, TEXTURE_MAG_FILTER, NEAREST)",
+                "TexParameter(TEXTURE_2D, TEXTURE_MIN_FILTER, NEAREST)",
+                "TexParameter(TEXTURE_2D, TEXTURE_WRAP_S, REPEAT)",
+                "TexParameter(TEXTURE_2D, TEXTURE_WRAP_T, REPEAT)",
+                "TexImage2D(TEXTURE_2D, RGB, 4x2, 24 bytes)",
+                "GenerateMipmap(TEXTURE_2D)",
+                "BindTexture(TEXTURE_2D, 0)",
+            ]
+        );
+
+        texture.bind(2);
+        assert_eq!(
+            device.take(),
+            vec!["ActiveTexture(2)", "BindTexture(TEXTURE_2D, 1)"]
+        );
+    }
+
+    #[test]
+    fn picks_format_from_image_and_skips_mipmaps() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let builder = TextureBuilder::new()
+            .filter(gl::LINEAR)
+            .wrap(gl::CLAMP_TO_EDGE);
+
+        builder
+            .build2d_from_image(DynamicImage::new_luma8(3, 3))
+            .unwrap();
+        builder
+            .build2d_from_image(DynamicImage::new_rgba8(1, 1))
+            .unwrap();
+
+        assert_eq!(
+            device.calls_to("TexImage2D"),
+            vec![
+                "TexImage2D(TEXTURE_2D, RED, 3x3, 9 bytes)",
+                "TexImage2D(TEXTURE_2D, RGBA, 1x1, 4 bytes)",
+            ]
+        );
+        assert!(device.calls_to("GenerateMipmap").is_empty());
+    }
+
+    #[test]
+    fn allocates_cube_faces_per_level() {
+        let device = RecordingDevice::new();
+        let _owner = ContextOwner::with_device(Box::new(device.clone()));
+        let cube = TextureCube::new(8, gl::RGB16F, 2).unwrap();
+        assert_eq!((cube.levels(), cube.level_size(1)), (2, 4));
+
+        let faces = device.calls_to("TexImageFloat");
+        assert_eq!(faces.len(), 12);
+        assert_eq!(
+            faces[0],
+            "TexImageFloat(TEXTURE_CUBE_MAP_POSITIVE_X, 0, RGB16F, 8x8, null)"
+        );
+        assert_eq!(
+            faces[11],
+            "TexImageFloat(TEXTURE_CUBE_MAP_NEGATIVE_Z, 1, RGB16F, 4x4, null)"
+        );
+        assert!(device.calls().contains(
+            &"TexParameter(TEXTURE_CUBE_MAP, TEXTURE_MIN_FILTER, LINEAR_MIPMAP_LINEAR)".to_string()
+        ));
+        assert!(device
+            .calls()
+            .contains(&"TexParameter(TEXTURE_CUBE_MAP, TEXTURE_MAX_LEVEL, 1)".to_string()));
+
+        // Levels are capped at a complete chain.
+        assert_eq!(TextureCube::full_levels(8), 4);
+        assert_eq!(TextureCube::new(8, gl::RGB16F, 10).unwrap().levels(), 4);
+    }
+
+    #[test]
+    fn loads_hdr_bottom_row_first() {
+        use image::codecs::hdr::HdrEncoder;
+        use image::Rgb;
+
+        let path = std::env::temp_dir().join(format!("texture-{}.hdr", std::process::id()));
+        // Top row bright, bottom row dim.
+        let pixels = [Rgb([4.0, 2.0, 1.0]), Rgb([0.5, 0.25, 0.125])];
+        HdrEncoder::new(File::create(&path).unwrap())
+            .encode(&pixels, 1, 2)
+            .unwrap();
+        let (width, height, data) = load_hdr(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!((width, height), (1, 2));
+        assert_eq!(data, vec![0.5, 0.25, 0.125, 4.0, 2.0, 1.0]);
+        assert!(load_hdr(Path::new("missing.hdr")).is_err());
+    }
+
+    #[test]
+    fn fails_without_a_context() {
+        let result = TextureBuilder::new()
+            .filter(gl::LINEAR)
+            .wrap(gl::REPEAT)
+            .build2d_from_image(DynamicImage::new_rgb8(1, 1));
+        assert!(result.is_err());
+    }
 }
@@ -0,0 +1,276 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Source of time for the frame loop.
+pub trait Clock {
+    /// Time since the clock was created.
+    fn now(&self) -> Duration;
+
+    /// Blocks for `duration`; used by the frame-rate cap.
+    fn sleep(&self, duration: Duration);
+}
+
+/// Wall clock backed by `Instant`.
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration)
+    }
+}
+
+/// Clock that only moves when told to. Clones share the same time.
+#[derive(Clone, Default)]
+pub struct ManualClock {
+    now: Rc<Cell<Duration>>,
+}
+
+#[allow(dead_code)]
+impl ManualClock {
+    pub fn new() -> ManualClock {
+        Default::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+
+    pub fn set(&self, now: Duration) {
+        self.now.set(now);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration)
+    }
+}
+
+/// Per-frame timing state kept by `Context`.
+pub struct FrameTime {
+    clock: Box<dyn Clock>,
+    frame_start: Duration,
+    delta: Duration,
+    elapsed: Duration,
+    frame: u64,
+    fixed_step: Option<Duration>,
+    accumulator: Duration,
+    max_delta: Duration,
+    frame_limit: Option<Duration>,
+}
+
+#[allow(dead_code)]
+impl FrameTime {
+    /// Longest delta accepted per frame, so a stall does not trigger a burst of fixed updates.
+    pub const DEFAULT_MAX_DELTA: Duration = Duration::from_millis(250);
+
+    pub fn new(clock: Box<dyn Clock>) -> FrameTime {
+        let now = clock.now();
+        FrameTime {
+            clock,
+            frame_start: now,
+            delta: Duration::default(),
+            elapsed: Duration::default(),
+            frame: 0,
+            fixed_step: None,
+            accumulator: Duration::default(),
+            max_delta: FrameTime::DEFAULT_MAX_DELTA,
+            frame_limit: None,
+        }
+    }
+
+    /// Runs `App::fixed_update` at `hz` updates per second.
+    pub fn set_fixed_rate(&mut self, hz: Option<f64>) {
+        self.fixed_step = hz
+            .filter(|&hz| hz > 0.0)
+            .map(|hz| Duration::from_secs_f64(1.0 / hz));
+        self.accumulator = Duration::default();
+    }
+
+    /// Caps the frame rate by sleeping at the end of each frame.
+    pub fn set_frame_limit(&mut self, fps: Option<f64>) {
+        self.frame_limit = fps
+            .filter(|&fps| fps > 0.0)
+            .map(|fps| Duration::from_secs_f64(1.0 / fps));
+    }
+
+    pub fn set_max_delta(&mut self, max_delta: Duration) {
+        self.max_delta = max_delta;
+    }
+
+    /// Discards time passed since the last frame, e.g. after a long load.
+    pub fn resync(&mut self) {
+        self.frame_start = self.clock.now();
+    }
+
+    /// Starts a new frame and returns how many fixed updates to run.
+    pub fn tick(&mut self) -> u32 {
+        let now = self.clock.now();
+        self.delta = now.saturating_sub(self.frame_start).min(self.max_delta);
+        self.frame_start = now;
+        self.elapsed += self.delta;
+        self.frame += 1;
+
+        match self.fixed_step {
+            Some(step) => {
+                self.accumulator += self.delta;
+                let mut steps = 0;
+                while self.accumulator >= step {
+                    self.accumulator -= step;
+                    steps += 1;
+                }
+                steps
+            }
+            None => 0,
+        }
+    }
+
+    /// Sleeps away the rest of the frame when a frame limit is set.
+    pub fn limit(&self) {
+        if let Some(limit) = self.frame_limit {
+            let spent = self.clock.now().saturating_sub(self.frame_start);
+            if spent < limit {
+                self.clock.sleep(limit - spent);
+            }
+        }
+    }
+
+    /// Time between the last two frames, clamped to the max delta.
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn delta_secs(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    /// Sum of all frame deltas.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn elapsed_secs(&self) -> f32 {
+        self.elapsed.as_secs_f32()
+    }
+
+    /// Number of frames started so far.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn fixed_step(&self) -> Option<Duration> {
+        self.fixed_step
+    }
+
+    /// How far the render lies between the last and the next fixed update, in [0, 1).
+    pub fn alpha(&self) -> f32 {
+        match self.fixed_step {
+            Some(step) => (self.accumulator.as_secs_f64() / step.as_secs_f64()) as f32,
+            None => 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn tracks_delta_elapsed_and_frames() {
+        let clock = ManualClock::new();
+        let mut time = FrameTime::new(Box::new(clock.clone()));
+
+        clock.advance(ms(16));
+        assert_eq!(time.tick(), 0);
+        assert_eq!(time.delta(), ms(16));
+
+        clock.advance(ms(20));
+        time.tick();
+        assert_eq!(time.delta(), ms(20));
+        assert_eq!(time.elapsed(), ms(36));
+        assert_eq!(time.frame(), 2);
+        assert_eq!(time.alpha(), 1.0);
+    }
+
+    #[test]
+    fn clamps_long_frames() {
+        let clock = ManualClock::new();
+        let mut time = FrameTime::new(Box::new(clock.clone()));
+        time.set_max_delta(ms(100));
+
+        clock.advance(ms(5000));
+        time.tick();
+        assert_eq!(time.delta(), ms(100));
+        assert_eq!(time.elapsed(), ms(100));
+    }
+
+    #[test]
+    fn runs_fixed_steps_with_interpolation() {
+        let clock = ManualClock::new();
+        let mut time = FrameTime::new(Box::new(clock.clone()));
+        time.set_fixed_rate(Some(100.0));
+
+        clock.advance(ms(25));
+        assert_eq!(time.tick(), 2);
+        assert!((time.alpha() - 0.5).abs() < 1e-4);
+
+        clock.advance(ms(5));
+        assert_eq!(time.tick(), 1);
+        assert!(time.alpha().abs() < 1e-4);
+
+        clock.advance(ms(3));
+        assert_eq!(time.tick(), 0);
+        assert!((time.alpha() - 0.3).abs() < 1e-4);
+    }
+
+    #[test]
+    fn frame_limit_sleeps_remaining_time() {
+        let clock = ManualClock::new();
+        let mut time = FrameTime::new(Box::new(clock.clone()));
+        time.set_frame_limit(Some(50.0));
+
+        time.tick();
+        clock.advance(ms(5));
+        time.limit();
+        assert_eq!(clock.now(), ms(20));
+
+        // A frame that already overran is not delayed further.
+        time.tick();
+        clock.advance(ms(30));
+        time.limit();
+        assert_eq!(clock.now(), ms(50));
+
+        time.tick();
+        assert_eq!(time.delta(), ms(30));
+    }
+}
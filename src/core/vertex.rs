@@ -1,62 +1,61 @@
 use gl::types::*;
-use std::os::raw::c_void;
+
+use super::resource::{self, ContextHandle, ResourceKind};
 
 pub struct VertexArray {
     id: u32,
+    ctx: ContextHandle,
 }
 
 impl Drop for VertexArray {
     fn drop(&mut self) {
-        unsafe {
-            gl::DeleteVertexArrays(1, &self.id);
-        }
-        println!("Delete Vertex Array: {}", self.id);
+        self.ctx.release(ResourceKind::VertexArray, self.id);
     }
 }
 
 #[allow(dead_code)]
 impl VertexArray {
-    pub fn new() -> Self {
-        let mut vao = 0;
-        unsafe {
-            gl::GenVertexArrays(1, &mut vao);
-        }
-        VertexArray { id: vao }
+    pub fn new() -> Result<VertexArray, String> {
+        let ctx = ContextHandle::current()?;
+        let vao = ctx.device().gen_vertex_array();
+        ctx.created(ResourceKind::VertexArray, vao);
+        Ok(VertexArray { id: vao, ctx })
+    }
+
+    /// Names the object in debug messages and graphics debuggers.
+    pub fn set_label(&self, label: &str) {
+        self.ctx
+            .device()
+            .object_label(gl::VERTEX_ARRAY, self.id, label);
+        resource::track_label(ResourceKind::VertexArray, self.id, label);
     }
 
     pub fn draw_arrays(&self, mode: GLenum, first: GLint, count: GLsizei) {
-        unsafe {
-            gl::BindVertexArray(self.id);
-            gl::DrawArrays(mode, first, count);
-            gl::BindVertexArray(0);
-        }
+        let device = self.ctx.device();
+        device.bind_vertex_array(self.id);
+        device.draw_arrays(mode, first, count);
+        device.bind_vertex_array(0);
     }
 
+    /// Draws `count` indices, starting `offset` indices into the bound index buffer.
     pub fn draw_elements(&self, mode: GLenum, count: GLsizei, indices_type: GLenum, offset: usize) {
-        unsafe {
-            gl::BindVertexArray(self.id);
-            gl::DrawElements(
-                mode,
-                count,
-                indices_type,
-                (offset * std::mem::size_of::<GLfloat>()) as *const c_void,
-            );
-            gl::BindVertexArray(0);
-        }
+        let device = self.ctx.device();
+        device.bind_vertex_array(self.id);
+        device.draw_elements(
+            mode,
+            count,
+            indices_type,
+            offset * std::mem::size_of::<GLfloat>(),
+        );
+        device.bind_vertex_array(0);
     }
 
     pub fn binding<F>(&self, cb: F)
     where
         F: FnOnce(),
     {
-        unsafe {
-            gl::BindVertexArray(self.id);
-        }
-
+        self.ctx.device().bind_vertex_array(self.id);
         cb();
-
-        unsafe {
-            gl::BindVertexArray(0);
-        }
+        self.ctx.device().bind_vertex_array(0);
     }
 }
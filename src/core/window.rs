@@ -0,0 +1,248 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+use imgui_glfw_rs::glfw;
+
+/// Where the window is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayMode {
+    Windowed,
+    /// Exclusive fullscreen at the requested window size on the n-th connected monitor.
+    Fullscreen {
+        monitor: usize,
+    },
+    /// Fullscreen at the monitor's current video mode, without a mode switch.
+    Borderless {
+        monitor: usize,
+    },
+}
+
+/// Window and OpenGL context options used by `FrameworkBuilder::build`.
+#[derive(Debug, Clone)]
+pub struct WindowConfig {
+    pub gl_version: (u32, u32),
+    pub profile: glfw::OpenGlProfileHint,
+    pub debug_context: bool,
+    pub resizable: bool,
+    pub display: DisplayMode,
+    /// MSAA samples of the default framebuffer; 0 disables multisampling. Must be a power
+    /// of two no larger than the device's `GL_MAX_SAMPLES`.
+    pub samples: u32,
+    pub swap_interval: glfw::SwapInterval,
+    pub srgb: bool,
+    pub depth_bits: Option<u32>,
+    pub stencil_bits: Option<u32>,
+    pub icon: Option<PathBuf>,
+    pub position: Option<(i32, i32)>,
+    /// Hidden windows still render, e.g. for headless runs.
+    pub visible: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            gl_version: (4, 1),
+            profile: glfw::OpenGlProfileHint::Core,
+            debug_context: cfg!(debug_assertions),
+            resizable: false,
+            display: DisplayMode::Windowed,
+            samples: 0,
+            swap_interval: glfw::SwapInterval::Sync(1),
+            srgb: false,
+            depth_bits: Some(24),
+            stencil_bits: Some(8),
+            icon: None,
+            position: None,
+            visible: true,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl WindowConfig {
+    /// Rejects option combinations no OpenGL context can satisfy.
+    pub fn validate(&self) -> Result<(), String> {
+        let (major, minor) = self.gl_version;
+        let known = match major {
+            1 => minor <= 5,
+            2 => minor <= 1,
+            3 => minor <= 3,
+            4 => minor <= 6,
+            _ => false,
+        };
+        if !known {
+            return Err(format!("Unknown OpenGL version {}.{}", major, minor));
+        }
+        if self.profile != glfw::OpenGlProfileHint::Any && (major, minor) < (3, 2) {
+            return Err(format!(
+                "OpenGL {}.{} does not support the {:?} profile (requires 3.2 or later)",
+                major, minor, self.profile
+            ));
+        }
+        if cfg!(target_os = "macos")
+            && (major, minor) >= (3, 2)
+            && self.profile != glfw::OpenGlProfileHint::Core
+        {
+            return Err(format!(
+                "OpenGL {}.{} is only available with the Core profile on macOS",
+                major, minor
+            ));
+        }
+        if self.samples > 0 && !self.samples.is_power_of_two() {
+            return Err(format!(
+                "MSAA sample count must be a power of two, got {}",
+                self.samples
+            ));
+        }
+        for (name, bits) in &[("Depth", self.depth_bits), ("Stencil", self.stencil_bits)] {
+            if let Some(bits) = bits {
+                if *bits > 32 {
+                    return Err(format!("{} buffer of {} bits is not supported", name, bits));
+                }
+            }
+        }
+        if let glfw::SwapInterval::Sync(n) = self.swap_interval {
+            if n > 4 {
+                return Err(format!("Swap interval {} is out of range (0-4)", n));
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the context and framebuffer hints.
+    pub fn hint(&self, glfw: &mut glfw::Glfw) {
+        glfw.default_window_hints();
+        glfw.window_hint(glfw::WindowHint::ContextVersion(
+            self.gl_version.0,
+            self.gl_version.1,
+        ));
+        glfw.window_hint(glfw::WindowHint::OpenGlProfile(self.profile));
+        #[cfg(target_os = "macos")]
+        glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(true));
+        glfw.window_hint(glfw::WindowHint::OpenGlDebugContext(self.debug_context));
+        glfw.window_hint(glfw::WindowHint::Resizable(self.resizable));
+        glfw.window_hint(glfw::WindowHint::Samples(if self.samples > 0 {
+            Some(self.samples)
+        } else {
+            None
+        }));
+        glfw.window_hint(glfw::WindowHint::SRgbCapable(self.srgb));
+        glfw.window_hint(glfw::WindowHint::DepthBits(self.depth_bits));
+        glfw.window_hint(glfw::WindowHint::StencilBits(self.stencil_bits));
+        glfw.window_hint(glfw::WindowHint::Visible(self.visible));
+    }
+
+    /// Describes the requested context, for error messages.
+    pub fn describe(&self, width: u32, height: u32) -> String {
+        let mut s = format!(
+            "{}x{} {:?} window with an OpenGL {}.{} {:?} profile context",
+            width, height, self.display, self.gl_version.0, self.gl_version.1, self.profile
+        );
+        if self.samples > 0 {
+            s += &format!(", {}x MSAA", self.samples);
+        }
+        if self.srgb {
+            s += ", sRGB";
+        }
+        s += &format!(
+            ", depth {} / stencil {} bits",
+            self.depth_bits.map_or("any".to_string(), |b| b.to_string()),
+            self.stencil_bits
+                .map_or("any".to_string(), |b| b.to_string())
+        );
+        s
+    }
+}
+
+/// Decodes an image file into a GLFW window icon.
+pub fn load_icon<P>(path: P) -> Result<glfw::PixelImage, String>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let img = image::open(path)
+        .map_err(|e| format!("Failed to load window icon {}: {}", path.display(), e))?
+        .into_rgba8();
+    Ok(glfw::PixelImage {
+        width: img.width(),
+        height: img.height(),
+        pixels: img.pixels().map(|p| u32::from_ne_bytes(p.0)).collect(),
+    })
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+fn record_error(error: glfw::Error, description: String, _: &()) {
+    log::error!("GLFW Error: {}", description);
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(format!("{}: {}", error, description)));
+}
+
+/// GLFW error callback that keeps the last message for `take_error`.
+pub static RECORD_ERRORS: Option<glfw::ErrorCallback<()>> = Some(glfw::Callback {
+    f: record_error as fn(glfw::Error, String, &()),
+    data: (),
+});
+
+/// Takes the last GLFW error reported on this thread.
+pub fn take_error() -> Option<String> {
+    LAST_ERROR.with(|e| e.borrow_mut().take())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_valid() {
+        assert_eq!(WindowConfig::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_bad_versions() {
+        let mut config = WindowConfig {
+            gl_version: (3, 7),
+            ..Default::default()
+        };
+        assert!(config.validate().unwrap_err().contains("3.7"));
+        config.gl_version = (5, 0);
+        assert!(config.validate().is_err());
+        config.gl_version = (3, 1);
+        assert!(config.validate().unwrap_err().contains("profile"));
+        config.profile = glfw::OpenGlProfileHint::Any;
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_bad_framebuffer_options() {
+        let mut config = WindowConfig {
+            samples: 6,
+            ..Default::default()
+        };
+        assert!(config.validate().unwrap_err().contains("power of two"));
+        // The upper limit is GL_MAX_SAMPLES, checked against the device by `build`.
+        config.samples = 64;
+        assert_eq!(config.validate(), Ok(()));
+        config.samples = 8;
+        config.depth_bits = Some(48);
+        assert!(config.validate().unwrap_err().contains("Depth"));
+        config.depth_bits = None;
+        config.swap_interval = glfw::SwapInterval::Sync(10);
+        assert!(config.validate().is_err());
+        config.swap_interval = glfw::SwapInterval::Adaptive;
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn describes_request() {
+        let config = WindowConfig {
+            samples: 4,
+            srgb: true,
+            ..Default::default()
+        };
+        let s = config.describe(800, 600);
+        assert!(s.starts_with("800x600 Windowed window with an OpenGL 4.1 Core"));
+        assert!(s.contains("4x MSAA") && s.contains("sRGB"));
+    }
+}
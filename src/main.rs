@@ -1,35 +1,160 @@
 mod apps;
 mod core;
+mod utils;
 
+use crate::apps::deferred::DeferredApp;
 use crate::apps::hello_triangle::HelloTriangleApp;
 use crate::apps::imgui::ImGuiApp;
+use crate::apps::lighting::LightingApp;
 use crate::apps::minimal::MinimalApp;
+use crate::apps::pbr::PbrApp;
+use crate::apps::sprites::SpritesApp;
 use crate::apps::texture_mapping::TextureMappingApp;
-use crate::core::framework::FrameworkBuilder;
+use crate::core::framework::{FrameworkBuilder, Fully};
+use crate::core::headless::Headless;
 
 const WINDOW_WIDTH: u32 = 1280;
 const WINDOW_HEIGHT: u32 = 720;
 
+/// Log filter from `--log <spec>`, `-v`/`-vv` or `-q`, falling back to `RUST_LOG`.
+/// Specs use env_logger syntax, e.g. `info,gl=warn,rust_opengl_graphics::core::buffer=trace`.
+fn init_logging(args: &mut Vec<String>) -> Result<(), String> {
+    let mut spec = None;
+    let mut i = 1;
+    while i < args.len() {
+        let arg = args[i].clone();
+        let value = match arg.as_str() {
+            "--log" => {
+                if i + 1 >= args.len() {
+                    return Err("--log requires a filter, e.g. --log debug".to_string());
+                }
+                Some(args.remove(i + 1))
+            }
+            "-q" => Some("warn".to_string()),
+            "-v" => Some("debug".to_string()),
+            "-vv" => Some("trace".to_string()),
+            _ => arg.strip_prefix("--log=").map(|s| s.to_string()),
+        };
+        match value {
+            Some(value) => {
+                spec = Some(value);
+                args.remove(i);
+            }
+            None => i += 1,
+        }
+    }
+
+    let mut builder =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
+    if let Some(spec) = spec {
+        builder.parse_filters(&spec);
+    }
+    builder.init();
+    Ok(())
+}
+
+/// Headless options from `--headless`, `--frames <n>` and `--golden <png>`.
+/// `--frames` and `--golden` imply `--headless`.
+fn parse_headless(args: &mut Vec<String>) -> Result<Option<Headless>, String> {
+    let mut headless = false;
+    let mut frames = 60;
+    let mut golden = None;
+    let mut i = 1;
+    while i < args.len() {
+        let arg = args[i].clone();
+        match arg.as_str() {
+            "--headless" => {
+                headless = true;
+                args.remove(i);
+            }
+            flag @ "--frames" | flag @ "--golden" => {
+                if i + 1 >= args.len() {
+                    return Err(format!("{} requires a value", flag));
+                }
+                let value = args.remove(i + 1);
+                if flag == "--frames" {
+                    frames = value
+                        .parse()
+                        .map_err(|_| format!("Invalid frame count \"{}\"", value))?;
+                } else {
+                    golden = Some(value);
+                }
+                headless = true;
+                args.remove(i);
+            }
+            _ => i += 1,
+        }
+    }
+    if !headless {
+        return Ok(None);
+    }
+    let options = Headless::new(frames);
+    Ok(Some(match golden {
+        Some(path) => options.golden(path),
+        None => options,
+    }))
+}
+
+/// Removes `flag` from the arguments, returning whether it was there.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    match args.iter().position(|arg| arg == flag) {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
 fn main() -> Result<(), String> {
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    init_logging(&mut args)?;
+    let headless = parse_headless(&mut args)?;
+    // Apps that tonemap and gamma encode themselves, like `pbr`, want those passes off.
+    let post = take_flag(&mut args, "--post");
+    if args.len() < 2 {
+        return Err(format!(
+            "Usage: {} [--log <filter> | -v | -vv | -q] [--headless] [--frames <n>] [--golden <png>] [--post] <app>",
+            args[0]
+        ));
+    }
+    let window = |title: &str| -> FrameworkBuilder<Fully> {
+        let mut builder = FrameworkBuilder::new().window(title, WINDOW_WIDTH, WINDOW_HEIGHT);
+        if post {
+            builder = builder.use_imgui().post_process();
+        }
+        match headless.clone() {
+            Some(headless) => builder.headless(headless),
+            None => builder,
+        }
+    };
     match args[1].as_str() {
-        "triangle" => FrameworkBuilder::new()
-            .window("Hello Triangle", WINDOW_WIDTH, WINDOW_HEIGHT)
+        "triangle" => window("Hello Triangle")
             .build()?
             .run::<HelloTriangleApp>(),
-        "minimal" => FrameworkBuilder::new()
-            .window("Minimal Application", WINDOW_WIDTH, WINDOW_HEIGHT)
+        "minimal" => window("Minimal Application")
             .build()?
             .run::<MinimalApp>(),
-        "texture_mapping" => FrameworkBuilder::new()
-            .window("Texture Mapping", WINDOW_WIDTH, WINDOW_HEIGHT)
+        "texture_mapping" => window("Texture Mapping")
             .build()?
             .run::<TextureMappingApp>(),
-        "imgui" => FrameworkBuilder::new()
-            .window("ImGui Demo App", WINDOW_WIDTH, WINDOW_HEIGHT)
+        "imgui" => window("ImGui Demo App")
             .use_imgui()
             .build()?
             .run::<ImGuiApp>(),
+        "sprites" => window("Sprite Batch")
+            .build()?
+            .run::<SpritesApp>(),
+        "lighting" => window("Blinn-Phong Lighting")
+            .build()?
+            .run::<LightingApp>(),
+        "pbr" => window("Physically Based Rendering")
+            .build()?
+            .run::<PbrApp>(),
+        "deferred" => window("Deferred Shading")
+            .use_imgui()
+            .build()?
+            .run::<DeferredApp>(),
         _ => panic!("Could not support app for {}", args[1]),
     }
 }
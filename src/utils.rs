@@ -1,2 +1,5 @@
 pub mod anim2d;
+pub mod atlas;
+pub mod packer;
 pub mod sprite;
+pub mod sprite_batch;
@@ -1,36 +1,377 @@
+use std::collections::HashMap;
+
+use super::atlas::{AtlasDesc, AtlasLayout, FramesDesc};
+
+/// Frame duration used when the atlas does not specify one.
+pub const DEFAULT_FRAME_DURATION: u32 = 100;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayMode {
+    Loop,
+    Once,
+    PingPong,
+}
+
+/// Animation clip: frame keys with per-frame durations in milliseconds.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Anim2DDesc {
-    durations: Vec<u32>,
-    keys: Vec<u32>,
+    pub durations: Vec<u32>,
+    pub keys: Vec<u32>,
+    pub mode: PlayMode,
+}
+
+#[allow(dead_code)]
+impl Anim2DDesc {
+    pub fn new(keys: Vec<u32>, durations: Vec<u32>, mode: PlayMode) -> Anim2DDesc {
+        Anim2DDesc {
+            durations,
+            keys,
+            mode,
+        }
+    }
+
+    /// Every key shown for the same duration.
+    pub fn uniform(keys: Vec<u32>, duration: u32, mode: PlayMode) -> Anim2DDesc {
+        let durations = vec![duration; keys.len()];
+        Anim2DDesc::new(keys, durations, mode)
+    }
+
+    /// Loads clips from Aseprite-style `meta.frameTags`. Keys index into `AtlasLayout::frames`.
+    pub fn clips(desc: &AtlasDesc) -> Result<HashMap<String, Anim2DDesc>, String> {
+        let durations: Vec<u32> = match desc.frames {
+            FramesDesc::Hash(ref frames) => frames.values().map(|f| f.duration).collect(),
+            FramesDesc::Array(ref frames) => frames.iter().map(|f| f.duration).collect::<Vec<_>>(),
+        }
+        .into_iter()
+        .map(|d| d.unwrap_or(DEFAULT_FRAME_DURATION))
+        .collect();
+
+        let mut clips = HashMap::with_capacity(desc.meta.frame_tags.len());
+        for tag in desc.meta.frame_tags.iter() {
+            if tag.from > tag.to || tag.to >= durations.len() {
+                return Err(format!(
+                    "Frame tag \"{}\" has invalid range {}..={}",
+                    tag.name, tag.from, tag.to
+                ));
+            }
+            let mut keys: Vec<u32> = (tag.from as u32..=tag.to as u32).collect();
+            let mode = match tag.direction.as_str() {
+                "forward" => PlayMode::Loop,
+                "reverse" => {
+                    keys.reverse();
+                    PlayMode::Loop
+                }
+                "pingpong" => PlayMode::PingPong,
+                dir => {
+                    return Err(format!(
+                        "Frame tag \"{}\" has unknown direction \"{}\"",
+                        tag.name, dir
+                    ))
+                }
+            };
+            let clip_durations = keys.iter().map(|&k| durations[k as usize]).collect();
+            clips.insert(
+                tag.name.clone(),
+                Anim2DDesc::new(keys, clip_durations, mode),
+            );
+        }
+        Ok(clips)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimEvent {
+    /// The displayed frame changed; `frame` is the position in the clip.
+    FrameChanged { frame: usize, key: u32 },
+    /// A looping or ping-pong clip started another cycle.
+    Looped,
+    /// A play-once clip reached its last frame.
+    Finished,
 }
 
+/// Plays an `Anim2DDesc` against a millisecond clock.
 pub struct Anim2D {
     time: u32,
     started: u32,
+    elapsed: f32,
     durations: Vec<u32>,
     keys: Vec<u32>,
-    index: u32,
+    mode: PlayMode,
+    speed: f32,
+    frame: usize,
+    cycle: u32,
+    finished: bool,
 }
 
 #[allow(dead_code)]
 impl Anim2D {
     pub fn new(time: u32, started: u32, anim: &Anim2DDesc) -> Anim2D {
-        assert!(anim.duration != 0);
-        Anim2D {
-            time: time,
-            started: started,
-            durations: anim.durations,
-            keys: anim.keys,
-            index: 0,
+        assert!(!anim.keys.is_empty());
+        assert!(anim.keys.len() == anim.durations.len());
+        assert!(anim.durations.iter().all(|&d| d != 0));
+        let mut anim = Anim2D {
+            time: started,
+            started,
+            elapsed: 0.0,
+            durations: anim.durations.clone(),
+            keys: anim.keys.clone(),
+            mode: anim.mode,
+            speed: 1.0,
+            frame: 0,
+            cycle: 0,
+            finished: false,
+        };
+        anim.update(time);
+        anim
+    }
+
+    /// Advances to `time`. Times before `started` show the first frame.
+    pub fn update(&mut self, time: u32) -> Vec<AnimEvent> {
+        let from = self.time.max(self.started);
+        let to = time.max(self.started);
+        self.time = self.time.max(time);
+        if to > from {
+            self.advance((to - from) as f32)
+        } else {
+            Vec::new()
         }
     }
 
-    pub fn update(&mut self, time: u32) {
+    /// Advances by `dt` milliseconds of wall time, scaled by the playback speed.
+    pub fn advance(&mut self, dt: f32) -> Vec<AnimEvent> {
+        let mut events = Vec::new();
+        if self.finished || dt <= 0.0 {
+            return events;
+        }
+        self.elapsed += dt * self.speed;
+
+        let (frame, cycle, finished) = self.frame_at(self.elapsed);
+        if frame != self.frame {
+            events.push(AnimEvent::FrameChanged {
+                frame,
+                key: self.keys[frame],
+            });
+        }
+        if cycle > self.cycle {
+            events.push(AnimEvent::Looped);
+        }
+        if finished {
+            events.push(AnimEvent::Finished);
+        }
+        self.frame = frame;
+        self.cycle = cycle;
+        self.finished = finished;
+        events
+    }
+
+    pub fn restart(&mut self, time: u32) {
         self.time = time;
-        self.index = self.index()
+        self.started = time;
+        self.elapsed = 0.0;
+        self.frame = 0;
+        self.cycle = 0;
+        self.finished = false;
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        assert!(speed >= 0.0);
+        self.speed = speed;
     }
 
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Position of the displayed frame within the clip.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Key of the displayed frame.
     pub fn index(&self) -> u32 {
-        let keyframe = (self.time - self.started) / self.durations[self.index];
-        self.keys[keyframe % self.keys.len()]
+        self.keys[self.frame]
+    }
+
+    /// Atlas region name of the displayed frame, for clips loaded with `Anim2DDesc::clips`.
+    pub fn region<'a>(&self, layout: &'a AtlasLayout) -> Option<&'a str> {
+        layout
+            .frames
+            .get(self.index() as usize)
+            .map(|name| name.as_str())
+    }
+
+    // Returns (frame, completed cycles, finished) for the scaled elapsed time.
+    fn frame_at(&self, elapsed: f32) -> (usize, u32, bool) {
+        let n = self.keys.len();
+        let total: u32 = self.durations.iter().sum();
+        match self.mode {
+            PlayMode::Once => {
+                if elapsed >= total as f32 {
+                    (n - 1, 0, true)
+                } else {
+                    (self.find(elapsed), 0, false)
+                }
+            }
+            PlayMode::Loop => {
+                let cycle = (elapsed / total as f32) as u32;
+                let t = elapsed - (cycle * total) as f32;
+                (self.find(t), cycle, false)
+            }
+            PlayMode::PingPong => {
+                if n == 1 {
+                    return (0, (elapsed / total as f32) as u32, false);
+                }
+                // Forward over every frame, then back without repeating either end.
+                let back: u32 = self.durations[1..n - 1].iter().sum();
+                let period = total + back;
+                let cycle = (elapsed / period as f32) as u32;
+                let t = elapsed - (cycle * period) as f32;
+                if t < total as f32 {
+                    (self.find(t), cycle, false)
+                } else {
+                    let mut t = t - total as f32;
+                    for i in (1..n - 1).rev() {
+                        if t < self.durations[i] as f32 {
+                            return (i, cycle, false);
+                        }
+                        t -= self.durations[i] as f32;
+                    }
+                    (0, cycle + 1, false)
+                }
+            }
+        }
+    }
+
+    fn find(&self, mut t: f32) -> usize {
+        for (i, &d) in self.durations.iter().enumerate() {
+            if t < d as f32 {
+                return i;
+            }
+            t -= d as f32;
+        }
+        self.durations.len() - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys_over_time(anim: &mut Anim2D, times: &[u32]) -> Vec<u32> {
+        times
+            .iter()
+            .map(|&t| {
+                anim.update(t);
+                anim.index()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loop_uses_per_frame_durations() {
+        let desc = Anim2DDesc::new(vec![10, 11, 12], vec![100, 50, 200], PlayMode::Loop);
+        let mut anim = Anim2D::new(1000, 1000, &desc);
+        let keys = keys_over_time(&mut anim, &[1000, 1099, 1100, 1149, 1150, 1349, 1350, 1460]);
+        assert_eq!(keys, vec![10, 10, 11, 11, 12, 12, 10, 11]);
+    }
+
+    #[test]
+    fn time_before_start_shows_first_frame() {
+        let desc = Anim2DDesc::uniform(vec![3, 4], 10, PlayMode::Loop);
+        let mut anim = Anim2D::new(0, 500, &desc);
+        assert_eq!(anim.index(), 3);
+        assert_eq!(keys_over_time(&mut anim, &[100, 499, 510]), vec![3, 3, 4]);
+    }
+
+    #[test]
+    fn once_stops_on_last_frame() {
+        let desc = Anim2DDesc::uniform(vec![0, 1, 2], 10, PlayMode::Once);
+        let mut anim = Anim2D::new(0, 0, &desc);
+        assert_eq!(keys_over_time(&mut anim, &[15, 25]), vec![1, 2]);
+        assert_eq!(anim.update(30), vec![AnimEvent::Finished]);
+        assert!(anim.is_finished());
+        assert_eq!(keys_over_time(&mut anim, &[1000]), vec![2]);
+    }
+
+    #[test]
+    fn ping_pong_does_not_repeat_ends() {
+        let desc = Anim2DDesc::uniform(vec![0, 1, 2, 3], 10, PlayMode::PingPong);
+        let mut anim = Anim2D::new(0, 0, &desc);
+        let times: Vec<u32> = (0..12).map(|i| i * 10 + 5).collect();
+        assert_eq!(
+            keys_over_time(&mut anim, &times),
+            vec![0, 1, 2, 3, 2, 1, 0, 1, 2, 3, 2, 1]
+        );
+    }
+
+    #[test]
+    fn speed_scales_playback() {
+        let desc = Anim2DDesc::uniform(vec![0, 1, 2, 3], 100, PlayMode::Loop);
+        let mut anim = Anim2D::new(0, 0, &desc);
+        anim.set_speed(2.0);
+        assert_eq!(keys_over_time(&mut anim, &[50, 100, 150]), vec![1, 2, 3]);
+        anim.set_speed(0.5);
+        assert_eq!(keys_over_time(&mut anim, &[250, 350]), vec![3, 0]);
+    }
+
+    #[test]
+    fn reports_frame_changes_and_loops() {
+        let desc = Anim2DDesc::uniform(vec![7, 8], 10, PlayMode::Loop);
+        let mut anim = Anim2D::new(0, 0, &desc);
+        assert!(anim.update(5).is_empty());
+        assert_eq!(
+            anim.update(12),
+            vec![AnimEvent::FrameChanged { frame: 1, key: 8 }]
+        );
+        assert_eq!(
+            anim.update(21),
+            vec![
+                AnimEvent::FrameChanged { frame: 0, key: 7 },
+                AnimEvent::Looped
+            ]
+        );
+    }
+
+    #[test]
+    fn loads_clips_from_frame_tags() {
+        let desc = AtlasDesc::from_json(
+            r#"{
+                "frames": {
+                    "hero 0": {"frame": {"x": 0, "y": 0, "w": 8, "h": 8}, "duration": 80},
+                    "hero 1": {"frame": {"x": 8, "y": 0, "w": 8, "h": 8}, "duration": 120},
+                    "hero 2": {"frame": {"x": 16, "y": 0, "w": 8, "h": 8}},
+                    "hero 10": {"frame": {"x": 24, "y": 0, "w": 8, "h": 8}, "duration": 60}
+                },
+                "meta": {
+                    "image": "hero.png",
+                    "size": {"w": 32, "h": 8},
+                    "frameTags": [
+                        {"name": "walk", "from": 0, "to": 2, "direction": "forward"},
+                        {"name": "back", "from": 1, "to": 3, "direction": "reverse"},
+                        {"name": "idle", "from": 2, "to": 3, "direction": "pingpong"}
+                    ]
+                }
+            }"#,
+        )
+        .unwrap();
+        let clips = Anim2DDesc::clips(&desc).unwrap();
+
+        let walk = &clips["walk"];
+        assert_eq!(walk.keys, vec![0, 1, 2]);
+        assert_eq!(walk.durations, vec![80, 120, DEFAULT_FRAME_DURATION]);
+        assert_eq!(walk.mode, PlayMode::Loop);
+        assert_eq!(clips["back"].keys, vec![3, 2, 1]);
+        assert_eq!(clips["idle"].mode, PlayMode::PingPong);
+
+        let layout = desc.layout().unwrap();
+        let mut anim = Anim2D::new(0, 0, &clips["back"]);
+        assert_eq!(anim.region(&layout), Some("hero 10"));
+        anim.update(70);
+        assert_eq!(anim.region(&layout), Some("hero 2"));
     }
 }
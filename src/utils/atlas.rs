@@ -0,0 +1,582 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use image::{DynamicImage, GenericImageView, RgbaImage};
+use indexmap::IndexMap;
+use nalgebra_glm as glm;
+use serde::{Deserialize, Serialize};
+
+use super::packer::{AtlasPacker, Rect};
+use crate::core::texture::{Texture2D, TextureBuilder};
+
+// TexturePacker "JSON (Hash)" / "JSON (Array)" description format.
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct RectDesc {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SizeDesc {
+    pub w: u32,
+    pub h: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PointDesc {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameDesc {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    pub frame: RectDesc,
+    #[serde(default)]
+    pub rotated: bool,
+    #[serde(default)]
+    pub trimmed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sprite_source_size: Option<RectDesc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_size: Option<SizeDesc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pivot: Option<PointDesc>,
+    /// Frame duration in milliseconds (Aseprite).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FramesDesc {
+    Hash(IndexMap<String, FrameDesc>),
+    Array(Vec<FrameDesc>),
+}
+
+/// Animation tag (Aseprite). `from` and `to` are inclusive frame indices.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameTagDesc {
+    pub name: String,
+    pub from: usize,
+    pub to: usize,
+    #[serde(default = "FrameTagDesc::default_direction")]
+    pub direction: String,
+}
+
+impl FrameTagDesc {
+    fn default_direction() -> String {
+        "forward".to_string()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetaDesc {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    pub image: String,
+    pub size: SizeDesc,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub frame_tags: Vec<FrameTagDesc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AtlasDesc {
+    pub frames: FramesDesc,
+    pub meta: MetaDesc,
+}
+
+#[allow(dead_code)]
+impl AtlasDesc {
+    pub fn from_json(json: &str) -> Result<AtlasDesc, String> {
+        serde_json::from_str(json).map_err(|e| format!("Could not parse atlas description: {}", e))
+    }
+
+    pub fn open<P>(path: P) -> Result<AtlasDesc, String>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|e| format!("failed to open file: {}: {}", path.display(), e))?;
+        serde_json::from_reader(BufReader::new(file)).map_err(|e| {
+            format!(
+                "Could not parse atlas description {}: {}",
+                path.display(),
+                e
+            )
+        })
+    }
+
+    pub fn save<P>(&self, path: P) -> Result<(), String>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .map_err(|e| format!("failed to create file: {}: {}", path.display(), e))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self).map_err(|e| {
+            format!(
+                "Could not write atlas description {}: {}",
+                path.display(),
+                e
+            )
+        })
+    }
+
+    pub fn layout(&self) -> Result<AtlasLayout, String> {
+        let frames: Vec<(String, &FrameDesc)> = match self.frames {
+            FramesDesc::Hash(ref frames) => {
+                frames.iter().map(|(name, f)| (name.clone(), f)).collect()
+            }
+            FramesDesc::Array(ref frames) => frames
+                .iter()
+                .map(|f| match f.filename {
+                    Some(ref name) => Ok((name.clone(), f)),
+                    None => Err("Atlas frame without \"filename\"".to_string()),
+                })
+                .collect::<Result<_, _>>()?,
+        };
+
+        let mut regions = HashMap::with_capacity(frames.len());
+        let mut names = Vec::with_capacity(frames.len());
+        for (name, f) in frames {
+            let rect = Rect::new(f.frame.x, f.frame.y, f.frame.w, f.frame.h);
+            let (sheet_w, sheet_h) = if f.rotated {
+                (rect.h, rect.w)
+            } else {
+                (rect.w, rect.h)
+            };
+            if rect.x + sheet_w > self.meta.size.w || rect.y + sheet_h > self.meta.size.h {
+                return Err(format!("Atlas frame \"{}\" is out of bounds", name));
+            }
+
+            let trim = f.sprite_source_size.unwrap_or(RectDesc {
+                x: 0,
+                y: 0,
+                w: rect.w,
+                h: rect.h,
+            });
+            let source = f.source_size.unwrap_or(SizeDesc {
+                w: rect.w,
+                h: rect.h,
+            });
+            let pivot = f.pivot.unwrap_or(PointDesc { x: 0.5, y: 0.5 });
+            let region = Region {
+                name: name.clone(),
+                rect,
+                rotated: f.rotated,
+                offset: (trim.x, trim.y),
+                source_size: (source.w, source.h),
+                pivot: glm::vec2(pivot.x, pivot.y),
+            };
+            names.push(name.clone());
+            regions.insert(name, region);
+        }
+
+        Ok(AtlasLayout {
+            width: self.meta.size.w,
+            height: self.meta.size.h,
+            regions,
+            frames: names,
+        })
+    }
+}
+
+/// A sub-image of the atlas.
+/// Like TexturePacker, `rect` keeps the unrotated sprite size even if the sprite is stored rotated
+/// 90 degrees clockwise in the sheet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub name: String,
+    pub rect: Rect,
+    pub rotated: bool,
+    pub offset: (u32, u32),
+    pub source_size: (u32, u32),
+    pub pivot: glm::Vec2,
+}
+
+#[allow(dead_code)]
+impl Region {
+    /// Texture coordinates of the sprite's top-left, top-right, bottom-right and bottom-left corners.
+    /// v = 0 is the first row of the image.
+    pub fn uvs(&self, atlas_width: u32, atlas_height: u32) -> [glm::Vec2; 4] {
+        let (w, h) = if self.rotated {
+            (self.rect.h, self.rect.w)
+        } else {
+            (self.rect.w, self.rect.h)
+        };
+        let u0 = self.rect.x as f32 / atlas_width as f32;
+        let v0 = self.rect.y as f32 / atlas_height as f32;
+        let u1 = (self.rect.x + w) as f32 / atlas_width as f32;
+        let v1 = (self.rect.y + h) as f32 / atlas_height as f32;
+
+        if self.rotated {
+            [
+                glm::vec2(u1, v0),
+                glm::vec2(u1, v1),
+                glm::vec2(u0, v1),
+                glm::vec2(u0, v0),
+            ]
+        } else {
+            [
+                glm::vec2(u0, v0),
+                glm::vec2(u1, v0),
+                glm::vec2(u1, v1),
+                glm::vec2(u0, v1),
+            ]
+        }
+    }
+}
+
+/// CPU side of an atlas: its size and named regions.
+#[derive(Debug, Clone)]
+pub struct AtlasLayout {
+    pub width: u32,
+    pub height: u32,
+    pub regions: HashMap<String, Region>,
+    /// Region names in description order; animation keys index into this.
+    pub frames: Vec<String>,
+}
+
+#[allow(dead_code)]
+impl AtlasLayout {
+    pub fn region(&self, name: &str) -> Result<&Region, String> {
+        self.regions
+            .get(name)
+            .ok_or_else(|| format!("Could not find atlas region \"{}\"", name))
+    }
+}
+
+#[allow(dead_code)]
+pub struct Atlas {
+    pub texture: Texture2D,
+    pub layout: AtlasLayout,
+}
+
+#[allow(dead_code)]
+impl Atlas {
+    /// Loads a TexturePacker JSON description. The image path is relative to the description.
+    pub fn open<P>(path: P) -> Result<Atlas, String>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let desc = AtlasDesc::open(path)?;
+        let image_path = path
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(&desc.meta.image);
+        let img = image::open(&image_path)
+            .map_err(|e| format!("failed to load image: {}: {}", image_path.display(), e))?;
+        Atlas::new(&desc, img)
+    }
+
+    pub fn new(desc: &AtlasDesc, img: DynamicImage) -> Result<Atlas, String> {
+        let layout = desc.layout()?;
+        if img.dimensions() != (layout.width, layout.height) {
+            return Err(format!(
+                "Atlas image is {}x{} but description says {}x{}",
+                img.width(),
+                img.height(),
+                layout.width,
+                layout.height
+            ));
+        }
+        let texture = TextureBuilder::new()
+            .filter(gl::NEAREST)
+            .wrap(gl::CLAMP_TO_EDGE)
+            .build2d_from_image(img)?;
+        Ok(Atlas { texture, layout })
+    }
+
+    pub fn from_packed(packed: PackedAtlas) -> Result<Atlas, String> {
+        Atlas::new(&packed.desc, DynamicImage::ImageRgba8(packed.image))
+    }
+}
+
+pub struct PackedAtlas {
+    pub image: RgbaImage,
+    pub desc: AtlasDesc,
+}
+
+#[allow(dead_code)]
+impl PackedAtlas {
+    /// Writes the description to `json_path` and the image next to it.
+    pub fn save<P>(&self, json_path: P) -> Result<(), String>
+    where
+        P: AsRef<Path>,
+    {
+        let json_path = json_path.as_ref();
+        let image_path = json_path
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(&self.desc.meta.image);
+        self.image
+            .save(&image_path)
+            .map_err(|e| format!("failed to save image: {}: {}", image_path.display(), e))?;
+        self.desc.save(json_path)
+    }
+}
+
+/// Packs many images into one atlas, growing in powers of two up to the maximum size.
+pub struct AtlasBuilder {
+    max_width: u32,
+    max_height: u32,
+    padding: u32,
+    image_name: String,
+    images: Vec<(String, RgbaImage)>,
+}
+
+#[allow(dead_code)]
+impl AtlasBuilder {
+    pub fn new(max_width: u32, max_height: u32) -> AtlasBuilder {
+        AtlasBuilder {
+            max_width,
+            max_height,
+            padding: 1,
+            image_name: "atlas.png".to_string(),
+            images: Vec::new(),
+        }
+    }
+
+    pub fn padding(mut self, padding: u32) -> AtlasBuilder {
+        self.padding = padding;
+        self
+    }
+
+    pub fn image_name<S>(mut self, name: S) -> AtlasBuilder
+    where
+        S: Into<String>,
+    {
+        self.image_name = name.into();
+        self
+    }
+
+    pub fn image<S>(mut self, name: S, img: DynamicImage) -> AtlasBuilder
+    where
+        S: Into<String>,
+    {
+        self.images.push((name.into(), img.into_rgba8()));
+        self
+    }
+
+    /// Adds every image file in `dir`, named by file name.
+    pub fn directory<P>(mut self, dir: P) -> Result<AtlasBuilder, String>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .map_err(|e| format!("failed to read directory: {}: {}", dir.display(), e))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| image::ImageFormat::from_path(path).is_ok())
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let img = image::open(&path)
+                .map_err(|e| format!("failed to load image: {}: {}", path.display(), e))?;
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            self = self.image(name, img);
+        }
+        Ok(self)
+    }
+
+    pub fn build(self) -> Result<PackedAtlas, String> {
+        let sizes: Vec<(u32, u32)> = self
+            .images
+            .iter()
+            .map(|(_, img)| img.dimensions())
+            .collect();
+
+        let (mut w, mut h) = (64.min(self.max_width), 64.min(self.max_height));
+        let rects = loop {
+            let mut packer = AtlasPacker::new(w, h, self.padding);
+            match packer.pack(&sizes) {
+                Ok(rects) => break rects,
+                Err(e) if w >= self.max_width && h >= self.max_height => return Err(e),
+                Err(_) => {
+                    if w <= h && w < self.max_width {
+                        w = (w * 2).min(self.max_width);
+                    } else {
+                        h = (h * 2).min(self.max_height);
+                    }
+                }
+            }
+        };
+
+        let mut image = RgbaImage::new(w, h);
+        let mut frames = IndexMap::new();
+        for ((name, img), rect) in self.images.iter().zip(rects.iter()) {
+            image::imageops::replace(&mut image, img, rect.x, rect.y);
+            frames.insert(
+                name.clone(),
+                FrameDesc {
+                    filename: None,
+                    frame: RectDesc {
+                        x: rect.x,
+                        y: rect.y,
+                        w: rect.w,
+                        h: rect.h,
+                    },
+                    rotated: false,
+                    trimmed: false,
+                    sprite_source_size: Some(RectDesc {
+                        x: 0,
+                        y: 0,
+                        w: rect.w,
+                        h: rect.h,
+                    }),
+                    source_size: Some(SizeDesc {
+                        w: rect.w,
+                        h: rect.h,
+                    }),
+                    pivot: None,
+                    duration: None,
+                },
+            );
+        }
+
+        Ok(PackedAtlas {
+            image,
+            desc: AtlasDesc {
+                frames: FramesDesc::Hash(frames),
+                meta: MetaDesc {
+                    app: Some(env!("CARGO_PKG_NAME").to_string()),
+                    image: self.image_name,
+                    size: SizeDesc { w, h },
+                    scale: Some("1".to_string()),
+                    frame_tags: Vec::new(),
+                },
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH_JSON: &str = r#"{
+        "frames": {
+            "hero.png": {
+                "frame": {"x": 2, "y": 4, "w": 16, "h": 32},
+                "rotated": false,
+                "trimmed": true,
+                "spriteSourceSize": {"x": 8, "y": 0, "w": 16, "h": 32},
+                "sourceSize": {"w": 32, "h": 32},
+                "pivot": {"x": 0.5, "y": 1.0}
+            },
+            "coin.png": {
+                "frame": {"x": 20, "y": 4, "w": 8, "h": 4},
+                "rotated": true,
+                "trimmed": false,
+                "spriteSourceSize": {"x": 0, "y": 0, "w": 8, "h": 4},
+                "sourceSize": {"w": 8, "h": 4}
+            }
+        },
+        "meta": {"app": "https://www.codeandweb.com/texturepacker", "image": "sheet.png", "size": {"w": 64, "h": 64}, "scale": "1"}
+    }"#;
+
+    #[test]
+    fn parses_hash_format() {
+        let layout = AtlasDesc::from_json(HASH_JSON).unwrap().layout().unwrap();
+        assert_eq!((layout.width, layout.height), (64, 64));
+        assert_eq!(layout.frames, vec!["hero.png", "coin.png"]);
+
+        let hero = layout.region("hero.png").unwrap();
+        assert_eq!(hero.rect, Rect::new(2, 4, 16, 32));
+        assert_eq!(hero.offset, (8, 0));
+        assert_eq!(hero.source_size, (32, 32));
+        assert_eq!(hero.pivot, glm::vec2(0.5, 1.0));
+        assert!(layout.region("coin.png").unwrap().rotated);
+        assert!(layout.region("missing.png").is_err());
+    }
+
+    #[test]
+    fn parses_array_format() {
+        let json = r#"{
+            "frames": [
+                {"filename": "a", "frame": {"x": 0, "y": 0, "w": 4, "h": 4}},
+                {"filename": "b", "frame": {"x": 4, "y": 0, "w": 4, "h": 4}}
+            ],
+            "meta": {"image": "a.png", "size": {"w": 8, "h": 4}}
+        }"#;
+        let layout = AtlasDesc::from_json(json).unwrap().layout().unwrap();
+        let b = layout.region("b").unwrap();
+        assert_eq!(b.rect, Rect::new(4, 0, 4, 4));
+        assert_eq!(b.source_size, (4, 4));
+        assert_eq!(b.pivot, glm::vec2(0.5, 0.5));
+    }
+
+    #[test]
+    fn rejects_out_of_bounds_frames() {
+        let json = r#"{
+            "frames": {"a": {"frame": {"x": 6, "y": 0, "w": 4, "h": 4}}},
+            "meta": {"image": "a.png", "size": {"w": 8, "h": 4}}
+        }"#;
+        assert!(AtlasDesc::from_json(json).unwrap().layout().is_err());
+    }
+
+    #[test]
+    fn computes_uvs() {
+        let layout = AtlasDesc::from_json(HASH_JSON).unwrap().layout().unwrap();
+        let uvs = layout.region("hero.png").unwrap().uvs(64, 64);
+        assert_eq!(uvs[0], glm::vec2(2.0 / 64.0, 4.0 / 64.0));
+        assert_eq!(uvs[1], glm::vec2(18.0 / 64.0, 4.0 / 64.0));
+        assert_eq!(uvs[2], glm::vec2(18.0 / 64.0, 36.0 / 64.0));
+        assert_eq!(uvs[3], glm::vec2(2.0 / 64.0, 36.0 / 64.0));
+    }
+
+    #[test]
+    fn computes_rotated_uvs() {
+        // 8x4 sprite stored as 4x8 in the sheet, rotated clockwise.
+        let layout = AtlasDesc::from_json(HASH_JSON).unwrap().layout().unwrap();
+        let uvs = layout.region("coin.png").unwrap().uvs(64, 64);
+        let (u0, u1) = (20.0 / 64.0, 24.0 / 64.0);
+        let (v0, v1) = (4.0 / 64.0, 12.0 / 64.0);
+        assert_eq!(uvs[0], glm::vec2(u1, v0));
+        assert_eq!(uvs[1], glm::vec2(u1, v1));
+        assert_eq!(uvs[2], glm::vec2(u0, v1));
+        assert_eq!(uvs[3], glm::vec2(u0, v0));
+    }
+
+    #[test]
+    fn builds_and_round_trips() {
+        let packed = AtlasBuilder::new(256, 256)
+            .image("red", DynamicImage::new_rgba8(40, 30))
+            .image("green", DynamicImage::new_rgba8(50, 20))
+            .image("blue", DynamicImage::new_rgba8(10, 60))
+            .build()
+            .unwrap();
+
+        let json = serde_json::to_string(&packed.desc).unwrap();
+        let layout = AtlasDesc::from_json(&json).unwrap().layout().unwrap();
+        assert_eq!(layout.width, packed.image.width());
+        assert_eq!(layout.height, packed.image.height());
+        assert_eq!(layout.region("green").unwrap().rect.w, 50);
+        assert_eq!(layout.region("blue").unwrap().rect.h, 60);
+    }
+
+    #[test]
+    fn build_fails_when_too_large() {
+        let res = AtlasBuilder::new(32, 32)
+            .image("big", DynamicImage::new_rgba8(64, 64))
+            .build();
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,223 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+#[allow(dead_code)]
+impl Rect {
+    pub fn new(x: u32, y: u32, w: u32, h: u32) -> Rect {
+        Rect { x, y, w, h }
+    }
+
+    pub fn right(&self) -> u32 {
+        self.x + self.w
+    }
+
+    pub fn bottom(&self) -> u32 {
+        self.y + self.h
+    }
+
+    pub fn contains(&self, other: &Rect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.right() <= self.right()
+            && other.bottom() <= self.bottom()
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.right()
+            && other.x < self.right()
+            && self.y < other.bottom()
+            && other.y < self.bottom()
+    }
+}
+
+/// MaxRects bin packer (best short side fit).
+pub struct AtlasPacker {
+    width: u32,
+    height: u32,
+    padding: u32,
+    free: Vec<Rect>,
+}
+
+#[allow(dead_code)]
+impl AtlasPacker {
+    pub fn new(width: u32, height: u32, padding: u32) -> AtlasPacker {
+        AtlasPacker {
+            width,
+            height,
+            padding,
+            free: vec![Rect::new(0, 0, width, height)],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Finds a place for a `w` x `h` rectangle. The returned rectangle excludes the padding,
+    /// which goes to its right and bottom except where it meets the atlas edge.
+    pub fn insert(&mut self, w: u32, h: u32) -> Option<Rect> {
+        if w == 0 || h == 0 {
+            return Some(Rect::new(0, 0, w, h));
+        }
+
+        let mut best: Option<(Rect, u32, u32)> = None;
+        for free in self.free.iter() {
+            if free.w < w || free.h < h {
+                continue;
+            }
+            // Nothing follows at the atlas edge, so the padding there is cut off.
+            let pw = (w + self.padding).min(self.width - free.x);
+            let ph = (h + self.padding).min(self.height - free.y);
+            if free.w < pw || free.h < ph {
+                continue;
+            }
+            let short = (free.w - pw).min(free.h - ph);
+            let long = (free.w - pw).max(free.h - ph);
+            let better = match best {
+                Some((_, s, l)) => short < s || (short == s && long < l),
+                None => true,
+            };
+            if better {
+                best = Some((Rect::new(free.x, free.y, pw, ph), short, long));
+            }
+        }
+
+        let (placed, _, _) = best?;
+        self.split(&placed);
+        self.prune();
+        Some(Rect::new(placed.x, placed.y, w, h))
+    }
+
+    /// Packs every size at once, larger rectangles first. Results are in input order.
+    pub fn pack(&mut self, sizes: &[(u32, u32)]) -> Result<Vec<Rect>, String> {
+        let mut order: Vec<usize> = (0..sizes.len()).collect();
+        order.sort_by_key(|&i| {
+            let (w, h) = sizes[i];
+            std::cmp::Reverse((w.max(h), w * h))
+        });
+
+        let mut rects = vec![Rect::new(0, 0, 0, 0); sizes.len()];
+        for i in order {
+            let (w, h) = sizes[i];
+            rects[i] = self.insert(w, h).ok_or_else(|| {
+                format!(
+                    "Could not fit {}x{} rectangle into {}x{} atlas",
+                    w, h, self.width, self.height
+                )
+            })?;
+        }
+        Ok(rects)
+    }
+
+    fn split(&mut self, used: &Rect) {
+        let mut next = Vec::with_capacity(self.free.len() + 4);
+        for free in self.free.iter() {
+            if !free.intersects(used) {
+                next.push(*free);
+                continue;
+            }
+            if used.x > free.x {
+                next.push(Rect::new(free.x, free.y, used.x - free.x, free.h));
+            }
+            if used.right() < free.right() {
+                next.push(Rect::new(
+                    used.right(),
+                    free.y,
+                    free.right() - used.right(),
+                    free.h,
+                ));
+            }
+            if used.y > free.y {
+                next.push(Rect::new(free.x, free.y, free.w, used.y - free.y));
+            }
+            if used.bottom() < free.bottom() {
+                next.push(Rect::new(
+                    free.x,
+                    used.bottom(),
+                    free.w,
+                    free.bottom() - used.bottom(),
+                ));
+            }
+        }
+        self.free = next;
+    }
+
+    // Drop free rectangles fully contained in another one.
+    fn prune(&mut self) {
+        let mut i = 0;
+        while i < self.free.len() {
+            let mut removed = false;
+            let mut j = i + 1;
+            while j < self.free.len() {
+                if self.free[j].contains(&self.free[i]) {
+                    self.free.swap_remove(i);
+                    removed = true;
+                    break;
+                }
+                if self.free[i].contains(&self.free[j]) {
+                    self.free.swap_remove(j);
+                } else {
+                    j += 1;
+                }
+            }
+            if !removed {
+                i += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_without_overlap() {
+        let sizes = [(32, 32), (64, 16), (16, 64), (8, 8), (40, 20), (20, 40)];
+        let mut packer = AtlasPacker::new(128, 128, 1);
+        let rects = packer.pack(&sizes).unwrap();
+
+        let bounds = Rect::new(0, 0, 128, 128);
+        for (i, r) in rects.iter().enumerate() {
+            assert_eq!((r.w, r.h), sizes[i]);
+            assert!(bounds.contains(r));
+            for other in rects.iter().skip(i + 1) {
+                assert!(!r.intersects(other), "{:?} overlaps {:?}", r, other);
+            }
+        }
+    }
+
+    #[test]
+    fn fills_exactly() {
+        let mut packer = AtlasPacker::new(64, 64, 0);
+        let rects = packer.pack(&[(32, 32); 4]).unwrap();
+        assert_eq!(rects.len(), 4);
+        assert!(packer.insert(1, 1).is_none());
+    }
+
+    #[test]
+    fn skips_padding_at_the_atlas_edge() {
+        let mut packer = AtlasPacker::new(32, 32, 2);
+        assert_eq!(packer.insert(32, 32), Some(Rect::new(0, 0, 32, 32)));
+
+        let mut packer = AtlasPacker::new(32, 32, 2);
+        let rects = packer.pack(&[(15, 32), (15, 32)]).unwrap();
+        assert_eq!(rects[1].x - rects[0].right(), 2);
+        assert!(packer.insert(1, 1).is_none());
+    }
+
+    #[test]
+    fn reports_overflow() {
+        let mut packer = AtlasPacker::new(32, 32, 0);
+        assert!(packer.pack(&[(16, 16), (64, 8)]).is_err());
+    }
+}
@@ -1,3 +1,152 @@
+use nalgebra_glm as glm;
+
+use super::atlas::{AtlasLayout, Region};
+
+/// A drawable instance of an atlas region.
+/// Positions are in pixels with the y axis pointing down, like the atlas image.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
 pub struct Sprite {
-    
-}
\ No newline at end of file
+    region: Region,
+    uvs: [glm::Vec2; 4],
+    pub position: glm::Vec2,
+    /// Normalized pivot within the untrimmed source image; (0, 0) is the top-left corner.
+    pub origin: glm::Vec2,
+    pub scale: glm::Vec2,
+    /// Clockwise rotation in radians.
+    pub rotation: f32,
+    pub tint: glm::Vec4,
+}
+
+#[allow(dead_code)]
+impl Sprite {
+    pub fn new(layout: &AtlasLayout, name: &str) -> Result<Sprite, String> {
+        let region = layout.region(name)?;
+        Ok(Sprite::from_region(region, layout.width, layout.height))
+    }
+
+    pub fn from_region(region: &Region, atlas_width: u32, atlas_height: u32) -> Sprite {
+        Sprite {
+            region: region.clone(),
+            uvs: region.uvs(atlas_width, atlas_height),
+            position: glm::vec2(0.0, 0.0),
+            origin: region.pivot,
+            scale: glm::vec2(1.0, 1.0),
+            rotation: 0.0,
+            tint: glm::vec4(1.0, 1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn region(&self) -> &Region {
+        &self.region
+    }
+
+    /// Switches to another region of the same atlas, keeping the transform.
+    pub fn set_region(&mut self, layout: &AtlasLayout, name: &str) -> Result<(), String> {
+        let region = layout.region(name)?;
+        self.uvs = region.uvs(layout.width, layout.height);
+        self.region = region.clone();
+        Ok(())
+    }
+
+    /// Texture coordinates of the top-left, top-right, bottom-right and bottom-left corners.
+    pub fn uvs(&self) -> &[glm::Vec2; 4] {
+        &self.uvs
+    }
+
+    /// Untrimmed size in pixels, before scaling.
+    pub fn size(&self) -> glm::Vec2 {
+        glm::vec2(
+            self.region.source_size.0 as f32,
+            self.region.source_size.1 as f32,
+        )
+    }
+
+    /// Corners of the trimmed quad in the same order as `uvs`.
+    pub fn corners(&self) -> [glm::Vec2; 4] {
+        let pivot = self.origin.component_mul(&self.size());
+        let x0 = self.region.offset.0 as f32 - pivot.x;
+        let y0 = self.region.offset.1 as f32 - pivot.y;
+        let x1 = x0 + self.region.rect.w as f32;
+        let y1 = y0 + self.region.rect.h as f32;
+
+        let (sin, cos) = self.rotation.sin_cos();
+        let transform = |x: f32, y: f32| {
+            let p = glm::vec2(x * self.scale.x, y * self.scale.y);
+            glm::vec2(p.x * cos - p.y * sin, p.x * sin + p.y * cos) + self.position
+        };
+        [
+            transform(x0, y0),
+            transform(x1, y0),
+            transform(x1, y1),
+            transform(x0, y1),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::atlas::AtlasDesc;
+
+    fn layout() -> AtlasLayout {
+        AtlasDesc::from_json(
+            r#"{
+                "frames": {
+                    "idle": {
+                        "frame": {"x": 0, "y": 0, "w": 16, "h": 16},
+                        "spriteSourceSize": {"x": 8, "y": 16, "w": 16, "h": 16},
+                        "sourceSize": {"w": 32, "h": 32},
+                        "pivot": {"x": 0.5, "y": 1.0}
+                    },
+                    "walk": {"frame": {"x": 16, "y": 0, "w": 32, "h": 32}}
+                },
+                "meta": {"image": "hero.png", "size": {"w": 64, "h": 32}}
+            }"#,
+        )
+        .unwrap()
+        .layout()
+        .unwrap()
+    }
+
+    fn assert_near(a: glm::Vec2, b: glm::Vec2) {
+        assert!(glm::distance(&a, &b) < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn uvs_follow_region() {
+        let layout = layout();
+        let mut sprite = Sprite::new(&layout, "idle").unwrap();
+        assert_eq!(sprite.uvs()[0], glm::vec2(0.0, 0.0));
+        assert_eq!(sprite.uvs()[2], glm::vec2(0.25, 0.5));
+
+        sprite.set_region(&layout, "walk").unwrap();
+        assert_eq!(sprite.uvs()[0], glm::vec2(0.25, 0.0));
+        assert_eq!(sprite.uvs()[2], glm::vec2(0.75, 1.0));
+        assert!(sprite.set_region(&layout, "jump").is_err());
+    }
+
+    #[test]
+    fn corners_respect_trim_and_origin() {
+        let mut sprite = Sprite::new(&layout(), "idle").unwrap();
+        sprite.position = glm::vec2(100.0, 50.0);
+
+        // Pivot at bottom-center of the 32x32 source, trimmed quad at (8, 16)..(24, 32).
+        let corners = sprite.corners();
+        assert_near(corners[0], glm::vec2(92.0, 34.0));
+        assert_near(corners[2], glm::vec2(108.0, 50.0));
+    }
+
+    #[test]
+    fn corners_apply_scale_and_rotation() {
+        let mut sprite = Sprite::new(&layout(), "walk").unwrap();
+        sprite.origin = glm::vec2(0.0, 0.0);
+        sprite.scale = glm::vec2(2.0, 1.0);
+        sprite.rotation = std::f32::consts::FRAC_PI_2;
+
+        let corners = sprite.corners();
+        assert_near(corners[0], glm::vec2(0.0, 0.0));
+        assert_near(corners[1], glm::vec2(0.0, 64.0));
+        assert_near(corners[2], glm::vec2(-32.0, 64.0));
+    }
+}
@@ -0,0 +1,251 @@
+use std::ops::Range;
+
+use nalgebra_glm as glm;
+
+use super::sprite::Sprite;
+use crate::core::buffer::Buffer;
+use crate::core::framework::Context;
+use crate::core::shader::Shader;
+use crate::core::state::{Blend, RenderState};
+use crate::core::texture::Texture2D;
+use crate::core::vertex::VertexArray;
+
+const FLOATS_PER_VERTEX: usize = 8;
+const VERTICES_PER_QUAD: usize = 4;
+const INDICES_PER_QUAD: usize = 6;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct SpriteVertex {
+    pos: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+#[derive(Debug, Clone)]
+struct Quad {
+    texture: u32,
+    layer: i32,
+    corners: [glm::Vec2; 4],
+    uvs: [glm::Vec2; 4],
+    tint: glm::Vec4,
+}
+
+/// Accumulates textured quads between `begin` and `end`, then draws them sorted by layer and
+/// texture with as few draw calls as possible.
+/// Lower layers are drawn first; quads on the same layer and texture keep submission order.
+pub struct SpriteBatch {
+    shader: Shader,
+    vao: VertexArray,
+    vbo: Buffer,
+    _ibo: Buffer,
+    capacity: usize,
+    quads: Vec<Quad>,
+    vertices: Vec<SpriteVertex>,
+    projection: glm::Mat4,
+    virtual_height: Option<f32>,
+    state: RenderState,
+    draw_calls: usize,
+}
+
+#[allow(dead_code)]
+impl SpriteBatch {
+    /// `capacity` is the number of quads uploaded per draw call.
+    pub fn new(capacity: usize) -> Result<SpriteBatch, String> {
+        assert!(capacity > 0 && capacity * VERTICES_PER_QUAD <= u32::MAX as usize);
+        let shader = Shader::new("res/glsl/sprite.vs.glsl", "res/glsl/sprite.fs.glsl")?;
+
+        let indices: Vec<u32> = (0..capacity as u32)
+            .flat_map(|q| {
+                let v = q * VERTICES_PER_QUAD as u32;
+                vec![v, v + 1, v + 2, v + 2, v + 3, v]
+            })
+            .collect();
+
+        let vao = VertexArray::new()?;
+        let vbo = Buffer::with_size(
+            gl::ARRAY_BUFFER,
+            capacity * VERTICES_PER_QUAD * std::mem::size_of::<SpriteVertex>(),
+            gl::STREAM_DRAW,
+        )?;
+        let ibo = Buffer::new(gl::ELEMENT_ARRAY_BUFFER, &indices, gl::STATIC_DRAW)?;
+        vao.binding(|| {
+            vbo.vertex_input_attrib(0, 2, FLOATS_PER_VERTEX, 0);
+            vbo.vertex_input_attrib(1, 2, FLOATS_PER_VERTEX, 2);
+            vbo.vertex_input_attrib(2, 4, FLOATS_PER_VERTEX, 4);
+            ibo.bind();
+        });
+
+        Ok(SpriteBatch {
+            shader,
+            vao,
+            vbo,
+            _ibo: ibo,
+            capacity,
+            quads: Vec::with_capacity(capacity),
+            vertices: Vec::with_capacity(capacity * VERTICES_PER_QUAD),
+            projection: glm::Mat4::identity(),
+            virtual_height: None,
+            state: RenderState::default().with_blend(Some(Blend::ALPHA)),
+            draw_calls: 0,
+        })
+    }
+
+    /// State used by `end`; alpha blended without depth test by default.
+    pub fn set_render_state(&mut self, state: RenderState) {
+        self.state = state;
+    }
+
+    /// Uses a fixed logical height instead of window pixels; the width follows `Context::aspect`.
+    pub fn set_virtual_height(&mut self, height: Option<f32>) {
+        self.virtual_height = height;
+    }
+
+    /// Starts a new batch with a y-down orthographic projection covering the window.
+    pub fn begin(&mut self, ctx: &Context) {
+        let (w, h) = match self.virtual_height {
+            Some(h) => (h * ctx.aspect(), h),
+            None => (ctx.width() as f32, ctx.height() as f32),
+        };
+        self.begin_with(glm::ortho(0.0, w, h, 0.0, -1.0, 1.0));
+    }
+
+    pub fn begin_with(&mut self, projection: glm::Mat4) {
+        self.projection = projection;
+        self.quads.clear();
+        self.draw_calls = 0;
+    }
+
+    pub fn draw(&mut self, texture: &Texture2D, sprite: &Sprite, layer: i32) {
+        self.draw_quad(texture, sprite.corners(), *sprite.uvs(), sprite.tint, layer);
+    }
+
+    /// Corners and uvs are in top-left, top-right, bottom-right, bottom-left order.
+    pub fn draw_quad(
+        &mut self,
+        texture: &Texture2D,
+        corners: [glm::Vec2; 4],
+        uvs: [glm::Vec2; 4],
+        tint: glm::Vec4,
+        layer: i32,
+    ) {
+        self.quads.push(Quad {
+            texture: texture.id(),
+            layer,
+            corners,
+            uvs,
+            tint,
+        });
+    }
+
+    /// Sorts and draws everything submitted since `begin`.
+    pub fn end(&mut self, ctx: &Context) {
+        if self.quads.is_empty() {
+            return;
+        }
+        let runs = sort_into_runs(&mut self.quads);
+
+        ctx.apply_state(&self.state);
+        ctx.device().active_texture(0);
+        self.shader.use_program();
+        self.shader.set_mat4("Projection", &self.projection);
+        self.shader.set_texture("Tex0", 0);
+
+        for (texture, range) in runs {
+            ctx.device().bind_texture(gl::TEXTURE_2D, texture);
+            let mut start = range.start;
+            while start < range.end {
+                let end = range.end.min(start + self.capacity);
+                self.flush(start..end);
+                start = end;
+            }
+        }
+
+        ctx.device().bind_texture(gl::TEXTURE_2D, 0);
+        self.quads.clear();
+    }
+
+    /// Number of draw calls issued by the last `end`.
+    pub fn draw_calls(&self) -> usize {
+        self.draw_calls
+    }
+
+    fn flush(&mut self, range: Range<usize>) {
+        self.vertices.clear();
+        for quad in self.quads[range.clone()].iter() {
+            let color = [quad.tint.x, quad.tint.y, quad.tint.z, quad.tint.w];
+            for (pos, uv) in quad.corners.iter().zip(quad.uvs.iter()) {
+                self.vertices.push(SpriteVertex {
+                    pos: [pos.x, pos.y],
+                    uv: [uv.x, uv.y],
+                    color,
+                });
+            }
+        }
+
+        self.vbo.orphan(
+            self.capacity * VERTICES_PER_QUAD * std::mem::size_of::<SpriteVertex>(),
+            gl::STREAM_DRAW,
+        );
+        self.vbo.sub_data(0, &self.vertices);
+        self.vao.draw_elements(
+            gl::TRIANGLES,
+            (range.len() * INDICES_PER_QUAD) as i32,
+            gl::UNSIGNED_INT,
+            0,
+        );
+        self.draw_calls += 1;
+    }
+}
+
+// Sorts quads by layer, then texture, and returns the ranges sharing a texture.
+fn sort_into_runs(quads: &mut [Quad]) -> Vec<(u32, Range<usize>)> {
+    quads.sort_by_key(|q| (q.layer, q.texture));
+
+    let mut runs: Vec<(u32, Range<usize>)> = Vec::new();
+    for (i, quad) in quads.iter().enumerate() {
+        match runs.last_mut() {
+            Some((texture, range)) if *texture == quad.texture => range.end = i + 1,
+            _ => runs.push((quad.texture, i..i + 1)),
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad(texture: u32, layer: i32, tag: f32) -> Quad {
+        Quad {
+            texture,
+            layer,
+            corners: [glm::vec2(tag, 0.0); 4],
+            uvs: [glm::vec2(0.0, 0.0); 4],
+            tint: glm::vec4(1.0, 1.0, 1.0, 1.0),
+        }
+    }
+
+    #[test]
+    fn groups_by_layer_then_texture() {
+        let mut quads = vec![
+            quad(2, 1, 0.0),
+            quad(1, 0, 1.0),
+            quad(2, 0, 2.0),
+            quad(1, 0, 3.0),
+            quad(1, 1, 4.0),
+            quad(2, 1, 5.0),
+        ];
+        let runs = sort_into_runs(&mut quads);
+
+        assert_eq!(runs, vec![(1, 0..2), (2, 2..3), (1, 3..4), (2, 4..6)]);
+        let order: Vec<f32> = quads.iter().map(|q| q.corners[0].x).collect();
+        assert_eq!(order, vec![1.0, 3.0, 2.0, 4.0, 0.0, 5.0]);
+    }
+
+    #[test]
+    fn single_texture_is_one_run() {
+        let mut quads: Vec<Quad> = (0..1000).map(|i| quad(7, 0, i as f32)).collect();
+        assert_eq!(sort_into_runs(&mut quads), vec![(7, 0..1000)]);
+    }
+}